@@ -0,0 +1,158 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::sync::Arc;
+
+use crate::browser::BrowserManager;
+use crate::command::{CommandRunner, SystemRunner};
+use crate::config::DetectionAction;
+use crate::state::{AppState, Restriction};
+use chrono::{DateTime, Utc};
+
+/// The steps taken on a detection (`monitoring.on_detection`), run in order.
+/// A failing step is logged and the remaining steps still run.
+pub struct ActionSequence {
+    actions: Vec<DetectionAction>,
+    lock_command: Vec<String>,
+    runner: Arc<dyn CommandRunner>,
+}
+
+impl ActionSequence {
+    pub fn new(actions: Vec<DetectionAction>, lock_command: Vec<String>) -> Self {
+        ActionSequence { actions, lock_command, runner: Arc::new(SystemRunner) }
+    }
+
+    /// Runs the lock command through `runner` instead of spawning it directly.
+    #[allow(dead_code)]
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    pub fn includes(&self, action: DetectionAction) -> bool {
+        self.actions.contains(&action)
+    }
+
+    /// Runs every action. `force` skips the SIGTERM grace period when killing, and
+    /// `notify` sends the detection notification. Returns the number of failed steps.
+    pub fn run(&self, browser: &BrowserManager, force: bool, notify: impl Fn()) -> usize {
+        let mut failures = 0;
+        for action in &self.actions {
+            let result = match action {
+                DetectionAction::Kill => kill(browser, force),
+                DetectionAction::Lock => self.lock_screen(),
+                DetectionAction::Notify => {
+                    notify();
+                    Ok(())
+                }
+            };
+            if let Err(e) = result {
+                warn!("Detection action {:?} failed: {}", action, e);
+                failures += 1;
+            }
+        }
+        failures
+    }
+
+    fn lock_screen(&self) -> Result<()> {
+        let (program, args) = self.lock_command.split_first()
+            .ok_or_else(|| anyhow!("monitoring.lock_command is empty"))?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        info!("Locking the screen with '{}'", self.lock_command.join(" "));
+        let output = self.runner.run(program, &args)?;
+        if !output.status.success() {
+            return Err(anyhow!("'{}' exited with {}", program, output.status));
+        }
+        Ok(())
+    }
+}
+
+/// Kills any running browser while `state` is blocked or on a break at `now`, and
+/// returns the restriction. This also catches a browser started by some other
+/// means than `--start-browser`.
+pub fn enforce_restriction(state: &AppState, now: DateTime<Utc>, browser: &BrowserManager) -> Result<Option<Restriction>> {
+    let Some(restriction) = state.restriction(now) else {
+        return Ok(None);
+    };
+    info!("Browser not allowed while {}", restriction);
+    kill(browser, false)?;
+    Ok(Some(restriction))
+}
+
+fn kill(browser: &BrowserManager, force: bool) -> Result<()> {
+    let report = if force {
+        browser.force_kill_browser_processes()?
+    } else {
+        browser.kill_browser_processes()?
+    };
+    if !report.is_empty() {
+        info!("Kill report: {}", report);
+    }
+    if !report.failed.is_empty() {
+        warn!("Could not signal browser pid(s) {:?}", report.failed);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::browser::PidBackend;
+    use crate::command::testing::RecordingRunner;
+    use std::cell::Cell;
+
+    fn lock_command() -> Vec<String> {
+        vec!["loginctl".to_string(), "lock-session".to_string()]
+    }
+
+    fn browser(runner: &Arc<RecordingRunner>) -> BrowserManager {
+        BrowserManager::new("google-chrome-stable".to_string(), "chrome".to_string())
+            .with_runner(runner.clone())
+            .with_pid_backend(PidBackend::Pgrep)
+    }
+
+    #[test]
+    fn test_kill_then_lock_runs_in_order() {
+        let runner = Arc::new(RecordingRunner::answering(1, ""));
+        let sequence = ActionSequence::new(vec![DetectionAction::Kill, DetectionAction::Lock], lock_command())
+            .with_runner(runner.clone());
+
+        let notified = Cell::new(false);
+        let failures = sequence.run(&browser(&runner), false, || notified.set(true));
+
+        // pgrep exiting 1 means "no browser running", but the lock command failing
+        // with the same code is an error that does not stop the sequence.
+        assert_eq!(failures, 1);
+        assert_eq!(runner.calls(), vec![
+            vec!["pgrep", "-f", "chrome"],
+            vec!["loginctl", "lock-session"],
+        ]);
+        assert!(!notified.get());
+    }
+
+    #[test]
+    fn test_enforce_restriction_kills_only_while_restricted() {
+        let runner = Arc::new(RecordingRunner::answering(1, ""));
+        let now = Utc::now();
+        let mut state = AppState::default_with_next_break();
+
+        assert_eq!(enforce_restriction(&state, now, &browser(&runner)).unwrap(), None);
+        assert!(runner.calls().is_empty());
+
+        state.blocked_until = Some(now + chrono::Duration::minutes(10));
+        assert_eq!(enforce_restriction(&state, now, &browser(&runner)).unwrap(), Some(Restriction::Blocked));
+        assert_eq!(runner.calls(), vec![vec!["pgrep", "-f", "chrome"]]);
+    }
+
+    #[test]
+    fn test_single_action_and_notify() {
+        let runner = Arc::new(RecordingRunner::answering(0, ""));
+        let sequence = ActionSequence::new(vec![DetectionAction::Notify, DetectionAction::Lock], lock_command())
+            .with_runner(runner.clone());
+
+        let notified = Cell::new(false);
+        assert_eq!(sequence.run(&browser(&runner), false, || notified.set(true)), 0);
+        assert!(notified.get());
+        assert_eq!(runner.calls(), vec![vec!["loginctl", "lock-session"]]);
+        assert!(!sequence.includes(DetectionAction::Kill));
+    }
+}
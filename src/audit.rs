@@ -0,0 +1,217 @@
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{debug, info, warn};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Append-only line writer that rolls `file` to `file.1` once it would grow past
+/// `max_bytes`, shifting older rotations up and deleting anything beyond `keep`.
+pub struct RotatingWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    keep: usize,
+    compress: bool,
+}
+
+impl RotatingWriter {
+    pub fn new<P: AsRef<Path>>(path: P, max_bytes: u64, keep: usize, compress: bool) -> Self {
+        RotatingWriter {
+            path: path.as_ref().to_path_buf(),
+            max_bytes,
+            keep,
+            compress,
+        }
+    }
+
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let current = fs::metadata(&self.path).map(|m| m.len()).unwrap_or(0);
+        if current > 0 && current + line.len() as u64 + 1 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+
+    /// Path of rotation `n` (1 is the newest), with `.gz` appended when compressing.
+    pub fn rotation_path(&self, n: usize) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        if self.compress {
+            name.push(".gz");
+        }
+        PathBuf::from(name)
+    }
+
+    fn rotate(&self) -> Result<()> {
+        info!("Rotating '{}' (keep={})", self.path.display(), self.keep);
+
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+            return Ok(());
+        }
+
+        let oldest = self.rotation_path(self.keep);
+        if oldest.exists() {
+            debug!("Removing expired rotation '{}'", oldest.display());
+            fs::remove_file(&oldest)?;
+        }
+
+        for n in (1..self.keep).rev() {
+            let from = self.rotation_path(n);
+            if from.exists() {
+                fs::rename(&from, self.rotation_path(n + 1))?;
+            }
+        }
+
+        let newest = self.rotation_path(1);
+        if self.compress {
+            let raw = fs::read(&self.path)?;
+            let mut encoder = GzEncoder::new(fs::File::create(&newest)?, Compression::default());
+            encoder.write_all(&raw)?;
+            encoder.finish()?;
+            fs::remove_file(&self.path)?;
+        } else {
+            fs::rename(&self.path, &newest)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Records enforcement actions (detections, blocks, breaks) as timestamped lines.
+pub struct AuditLogger {
+    writer: RotatingWriter,
+}
+
+impl AuditLogger {
+    pub fn new(writer: RotatingWriter) -> Self {
+        AuditLogger { writer }
+    }
+
+    /// Failures are logged rather than returned so auditing never stops enforcement.
+    pub fn record(&mut self, event: &str, detail: &str) {
+        let line = format!(
+            "[{}] {} {}",
+            chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
+            event,
+            detail
+        );
+        if let Err(e) = self.writer.write_line(line.trim_end()) {
+            warn!("Failed to write audit log: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn line(n: usize) -> String {
+        format!("{:09}", n)
+    }
+
+    #[test]
+    fn test_write_below_threshold_does_not_rotate() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.log");
+        let mut writer = RotatingWriter::new(&path, 100, 3, false);
+
+        writer.write_line(&line(1)).unwrap();
+        writer.write_line(&line(2)).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "000000001\n000000002\n");
+        assert!(!writer.rotation_path(1).exists());
+    }
+
+    #[test]
+    fn test_write_past_threshold_rotates() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.log");
+        // Each line is 10 bytes, so two lines fit and the third rotates.
+        let mut writer = RotatingWriter::new(&path, 20, 3, false);
+
+        writer.write_line(&line(1)).unwrap();
+        writer.write_line(&line(2)).unwrap();
+        writer.write_line(&line(3)).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "000000003\n");
+        assert_eq!(
+            fs::read_to_string(writer.rotation_path(1)).unwrap(),
+            "000000001\n000000002\n"
+        );
+    }
+
+    #[test]
+    fn test_retention_is_enforced() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.log");
+        let mut writer = RotatingWriter::new(&path, 10, 2, false);
+
+        for n in 1..=5 {
+            writer.write_line(&line(n)).unwrap();
+        }
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "000000005\n");
+        assert_eq!(fs::read_to_string(writer.rotation_path(1)).unwrap(), "000000004\n");
+        assert_eq!(fs::read_to_string(writer.rotation_path(2)).unwrap(), "000000003\n");
+        assert!(!writer.rotation_path(3).exists());
+    }
+
+    #[test]
+    fn test_keep_zero_discards_old_file() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.log");
+        let mut writer = RotatingWriter::new(&path, 10, 0, false);
+
+        writer.write_line(&line(1)).unwrap();
+        writer.write_line(&line(2)).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "000000002\n");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_compressed_rotation() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.log");
+        let mut writer = RotatingWriter::new(&path, 10, 2, true);
+
+        writer.write_line(&line(1)).unwrap();
+        writer.write_line(&line(2)).unwrap();
+
+        let rotated = writer.rotation_path(1);
+        assert!(rotated.to_string_lossy().ends_with("audit.log.1.gz"));
+
+        let mut decoded = String::new();
+        GzDecoder::new(fs::File::open(rotated).unwrap())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, "000000001\n");
+    }
+
+    #[test]
+    fn test_audit_logger_record() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("nested").join("audit.log");
+        let mut logger = AuditLogger::new(RotatingWriter::new(&path, 1024, 1, false));
+
+        logger.record("block", "title='bad' pattern='.*bad.*'");
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.ends_with(" block title='bad' pattern='.*bad.*'\n"));
+    }
+}
@@ -1,31 +1,294 @@
-use anyhow::Result;
-use log::{debug, error, info};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{debug, error, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::browser::find_executable;
+use crate::command::{CommandRunner, SpawnedPids, SystemRunner};
+use crate::config::SequenceStep;
+use crate::privileges::create_private_dir;
+use crate::state::format_minutes;
+
+/// Seconds the block message stays up before closing itself.
+const MESSAGE_TIMEOUT_SECONDS: u32 = 30;
+
+/// Pause before the blocked wallpaper is set again when `backgrounds.reassert` is on.
+pub const REASSERT_DELAY: Duration = Duration::from_millis(500);
+
+/// Upper bound on the total hold of `backgrounds.block_sequence`, so playing it
+/// never stalls the daemon loop for long.
+pub const MAX_BLOCK_SEQUENCE_SECONDS: u64 = 10;
+
+/// The steps of `sequence` with holds shortened so they add up to at most
+/// `cap_seconds`; steps that would start after the cap are dropped.
+pub fn capped_sequence(sequence: &[SequenceStep], cap_seconds: u64) -> Vec<SequenceStep> {
+    let mut remaining = cap_seconds;
+    let mut capped = Vec::new();
+    for step in sequence {
+        if remaining == 0 {
+            warn!("backgrounds.block_sequence exceeds {} second(s); skipping '{}'", cap_seconds, step.image);
+            continue;
+        }
+        let hold_seconds = step.hold_seconds.min(remaining);
+        remaining -= hold_seconds;
+        capped.push(SequenceStep { image: step.image.clone(), hold_seconds });
+    }
+    capped
+}
+
+/// Fills `{until}` in `backgrounds.block_message` with the block's local end time as `HH:MM`.
+pub fn expand_block_message(template: &str, until: NaiveDateTime) -> String {
+    template.replace("{until}", &until.format("%H:%M").to_string())
+}
+
+/// Whole minutes left until `until`, rounded up so the countdown only shows
+/// `0m` once the block is over.
+pub fn remaining_minutes(until: DateTime<Utc>, now: DateTime<Utc>) -> u64 {
+    let seconds = (until - now).num_seconds().max(0) as u64;
+    seconds.div_ceil(60)
+}
+
+/// Where `backgrounds.countdown` writes the blocked wallpaper with the time left on it:
+/// inside a private per-user directory, so another user cannot plant a symlink there.
+pub fn countdown_image_path() -> PathBuf {
+    std::env::temp_dir()
+        .join(format!("inappropriate-video-handler-{}", nix::unistd::getuid()))
+        .join("countdown.png")
+}
+
+/// Remembers the minute count last drawn so the countdown image is only rendered
+/// again when the displayed number changes.
+#[derive(Debug, Default)]
+pub struct CountdownTracker {
+    shown: Option<u64>,
+}
+
+impl CountdownTracker {
+    /// Returns `true` if `minutes` differs from what is on screen and records it.
+    pub fn should_regenerate(&mut self, minutes: u64) -> bool {
+        if self.shown == Some(minutes) {
+            return false;
+        }
+        self.shown = Some(minutes);
+        true
+    }
 
-pub struct BackgroundManager;
+    /// Forgets the shown count, e.g. once the block is over or rendering failed.
+    pub fn reset(&mut self) {
+        self.shown = None;
+    }
+}
+
+/// ImageMagick command that writes `base_image` with `text` drawn over its centre to `output`.
+fn countdown_command(base_image: &str, text: &str, output: &Path) -> Option<Vec<String>> {
+    let tool = ["magick", "convert"].into_iter().find(|tool| find_executable(tool).is_some())?;
+    Some(vec![
+        tool.to_string(),
+        base_image.to_string(),
+        "-gravity".to_string(),
+        "center".to_string(),
+        "-pointsize".to_string(),
+        "96".to_string(),
+        "-fill".to_string(),
+        "white".to_string(),
+        "-stroke".to_string(),
+        "black".to_string(),
+        "-annotate".to_string(),
+        "0".to_string(),
+        text.to_string(),
+        output.to_string_lossy().into_owned(),
+    ])
+}
+
+/// Command that shows `text` in a centred window that closes on its own, using
+/// `zenity` if installed and `xmessage` otherwise.
+fn message_command(text: &str) -> Option<Vec<String>> {
+    if find_executable("zenity").is_some() {
+        Some(vec![
+            "zenity".to_string(),
+            "--info".to_string(),
+            "--no-wrap".to_string(),
+            format!("--timeout={}", MESSAGE_TIMEOUT_SECONDS),
+            format!("--text={}", text),
+        ])
+    } else if find_executable("xmessage").is_some() {
+        Some(vec![
+            "xmessage".to_string(),
+            "-center".to_string(),
+            "-timeout".to_string(),
+            MESSAGE_TIMEOUT_SECONDS.to_string(),
+            text.to_string(),
+        ])
+    } else {
+        None
+    }
+}
+
+/// The image a `~/.fehbg` script sets: the first quoted argument of its `feh` line.
+pub fn parse_fehbg(content: &str) -> Option<String> {
+    let line = content.lines().find(|l| l.trim_start().starts_with("feh "))?;
+    let start = line.find('\'')?;
+    let mut image = String::new();
+    let mut rest = &line[start + 1..];
+    // feh quotes with '…' and writes an embedded quote as '\''.
+    loop {
+        let end = rest.find('\'')?;
+        image.push_str(&rest[..end]);
+        match rest[end..].strip_prefix("'\\''") {
+            Some(after) => {
+                image.push('\'');
+                rest = after;
+            }
+            None => break,
+        }
+    }
+    (!image.is_empty()).then_some(image)
+}
+
+/// A local path from `gsettings get org.gnome.desktop.background picture-uri` output.
+pub fn parse_gsettings_uri(output: &str) -> Option<String> {
+    let uri = output.trim().trim_matches('\'');
+    uri.strip_prefix("file://").filter(|p| !p.is_empty()).map(str::to_string)
+}
+
+/// Works out the wallpaper the user had before the daemon changed it: `hint` if set,
+/// then `~/.fehbg`, then GNOME's `picture-uri`. Images in `exclude` (the daemon's own
+/// wallpapers, left behind by an earlier run) are never returned.
+pub fn detect_user_wallpaper(
+    hint: Option<&str>,
+    home: Option<&Path>,
+    runner: &dyn CommandRunner,
+    exclude: &[&str],
+) -> Option<String> {
+    let from_fehbg = || {
+        let content = fs::read_to_string(home?.join(".fehbg")).ok()?;
+        parse_fehbg(&content)
+    };
+    let from_gsettings = || {
+        let output = runner
+            .run("gsettings", &["get", "org.gnome.desktop.background", "picture-uri"])
+            .ok()
+            .filter(|o| o.status.success())?;
+        parse_gsettings_uri(&String::from_utf8_lossy(&output.stdout))
+    };
+    let wallpaper = hint
+        .map(str::to_string)
+        .or_else(from_fehbg)
+        .or_else(from_gsettings)
+        .filter(|w| !exclude.contains(&w.as_str()));
+    debug!("Detected user wallpaper: {:?}", wallpaper);
+    wallpaper
+}
+
+/// Remembers the last background that was set so the setter only runs on a change.
+#[derive(Debug, Default)]
+pub struct BackgroundTracker {
+    current: Option<String>,
+}
+
+impl BackgroundTracker {
+    /// Returns `true` if `desired` differs from the current background and records it.
+    pub fn change_to(&mut self, desired: &str) -> bool {
+        if self.current.as_deref() == Some(desired) {
+            return false;
+        }
+        self.current = Some(desired.to_string());
+        true
+    }
+
+    /// Forgets the current background, e.g. after the setter failed, so the next call retries.
+    pub fn reset(&mut self) {
+        self.current = None;
+    }
+}
+
+/// Runs `feh` to set `image_path`; `false` if it exited with an error.
+fn run_feh(runner: &dyn CommandRunner, image_path: &str) -> Result<bool> {
+    info!("Setting background: feh --bg-scale '{}'", image_path);
+
+    let output = runner.run("feh", &["--bg-scale", image_path])?;
+
+    debug!("feh exit status: {}", output.status);
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        error!("Failed to set background: {}", stderr);
+        return Ok(false);
+    }
+    debug!("Background set successfully");
+    Ok(true)
+}
+
+pub struct BackgroundManager {
+    /// Shared with the thread that reasserts the blocked wallpaper.
+    tracker: Arc<Mutex<BackgroundTracker>>,
+    runner: Arc<dyn CommandRunner>,
+    /// Delay before the blocked wallpaper is set a second time, if at all.
+    reassert: Option<Duration>,
+    spawned: SpawnedPids,
+}
+
+impl Default for BackgroundManager {
+    fn default() -> Self {
+        BackgroundManager {
+            tracker: Arc::default(),
+            runner: Arc::new(SystemRunner),
+            reassert: None,
+            spawned: SpawnedPids::default(),
+        }
+    }
+}
 
 impl BackgroundManager {
     pub fn new() -> Self {
-        BackgroundManager
+        BackgroundManager::default()
     }
 
-    pub fn set_background(&self, image_path: &str) -> Result<()> {
-        info!("Setting background: feh --bg-scale '{}'", image_path);
+    #[allow(dead_code)]
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
 
-        let output = Command::new("feh")
-            .arg("--bg-scale")
-            .arg(image_path)
-            .output()?;
+    /// Makes [`set_blocked_background`](Self::set_blocked_background) run the setter
+    /// again `delay` after a change (`backgrounds.reassert`).
+    pub fn with_reassert(mut self, delay: Option<Duration>) -> Self {
+        self.reassert = delay;
+        self
+    }
 
-        debug!("feh exit status: {}", output.status);
+    /// Records the message windows this manager opens in `spawned`.
+    pub fn with_spawned(mut self, spawned: SpawnedPids) -> Self {
+        self.spawned = spawned;
+        self
+    }
 
-        if !output.status.success() {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            error!("Failed to set background: {}", stderr);
-        } else {
-            debug!("Background set successfully");
+    /// Runs `feh` unless `image_path` is already the background this manager last set.
+    pub fn set_background(&self, image_path: &str) -> Result<()> {
+        self.change_background(image_path).map(|_| ())
+    }
+
+    /// Like [`set_background`](Self::set_background); returns whether the setter ran.
+    fn change_background(&self, image_path: &str) -> Result<bool> {
+        if !self.tracker.lock().unwrap().change_to(image_path) {
+            debug!("Background already '{}', not setting it again", image_path);
+            return Ok(false);
+        }
+        if let Err(e) = self.run_setter(image_path) {
+            self.tracker.lock().unwrap().reset();
+            return Err(e);
         }
+        Ok(true)
+    }
 
+    fn run_setter(&self, image_path: &str) -> Result<()> {
+        if !run_feh(&*self.runner, image_path)? {
+            self.tracker.lock().unwrap().reset();
+        }
         Ok(())
     }
 
@@ -36,18 +299,87 @@ impl BackgroundManager {
 
     pub fn set_blocked_background(&self, image_path: &str) -> Result<()> {
         info!("set_blocked_background('{}')", image_path);
-        self.set_background(image_path)
+        let changed = self.change_background(image_path)?;
+        if let (true, Some(delay)) = (changed, self.reassert) {
+            self.reassert_later(image_path, delay)?;
+        }
+        Ok(())
+    }
+
+    /// Sets `image_path` again after `delay` on a thread of its own, so callers on the
+    /// daemon's async runtime are not held up. Skipped if the background has changed
+    /// in the meantime.
+    fn reassert_later(&self, image_path: &str, delay: Duration) -> Result<std::thread::JoinHandle<()>> {
+        let tracker = Arc::clone(&self.tracker);
+        let runner = Arc::clone(&self.runner);
+        let image_path = image_path.to_string();
+        let reassert = move || {
+            std::thread::sleep(delay);
+            let mut tracker = tracker.lock().unwrap();
+            if tracker.current.as_deref() != Some(image_path.as_str()) {
+                debug!("Background changed since '{}' was set; not reasserting it", image_path);
+                return;
+            }
+            debug!("Reasserting blocked background '{}'", image_path);
+            match run_feh(&*runner, &image_path) {
+                Ok(true) => {}
+                Ok(false) => tracker.reset(),
+                Err(e) => warn!("Failed to reassert background '{}': {}", image_path, e),
+            }
+        };
+        Ok(std::thread::Builder::new().name("reassert-background".to_string()).spawn(reassert)?)
+    }
+
+    /// Draws the `minutes` left over `base_image` and sets the result
+    /// (`backgrounds.countdown`). Needs ImageMagick.
+    pub fn set_countdown_background(&self, base_image: &str, minutes: u64) -> Result<()> {
+        let output = countdown_image_path();
+        if let Some(dir) = output.parent() {
+            create_private_dir(dir).map_err(|e| anyhow!("unsafe countdown directory '{}': {}", dir.display(), e))?;
+        }
+        let text = format!("Blocked: {} left", format_minutes(minutes));
+        let argv = countdown_command(base_image, &text, &output)
+            .ok_or_else(|| anyhow!("ImageMagick (magick or convert) is not installed"))?;
+        info!("Rendering countdown background: {}", text);
+        let args: Vec<&str> = argv[1..].iter().map(String::as_str).collect();
+        let rendered = self.runner.run(&argv[0], &args)?;
+        if !rendered.status.success() {
+            return Err(anyhow!("{} failed: {}", argv[0], String::from_utf8_lossy(&rendered.stderr).trim()));
+        }
+        // The path stays the same from minute to minute, so the tracker would skip it.
+        self.tracker.lock().unwrap().reset();
+        self.set_background(&output.to_string_lossy())
     }
 
     pub fn set_bathroom_break_background(&self, image_path: &str) -> Result<()> {
         info!("set_bathroom_break_background('{}')", image_path);
         self.set_background(image_path)
     }
+
+    pub fn set_warning_background(&self, image_path: &str) -> Result<()> {
+        info!("set_warning_background('{}')", image_path);
+        self.set_background(image_path)
+    }
+
+    /// Shows `text` without waiting for it to be dismissed. Does nothing if neither
+    /// `zenity` nor `xmessage` is installed.
+    pub fn show_message(&self, text: &str) {
+        let Some(argv) = message_command(text) else {
+            debug!("No message tool (zenity, xmessage) found; not showing '{}'", text);
+            return;
+        };
+        info!("Showing message via {}: '{}'", argv[0], text);
+        match self.spawned.spawn(Command::new(&argv[0]).args(&argv[1..])) {
+            Ok(child) => self.spawned.wait_in_background(child, |_| {}),
+            Err(e) => warn!("Failed to show message: {}", e),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::testing::RecordingRunner;
     use serial_test::serial;
 
     #[test]
@@ -55,6 +387,156 @@ mod tests {
         let _manager = BackgroundManager::new();
     }
 
+    fn recording_manager(reassert: Option<Duration>) -> (BackgroundManager, Arc<RecordingRunner>) {
+        let runner = Arc::new(RecordingRunner::default());
+        let manager = BackgroundManager::new().with_runner(runner.clone()).with_reassert(reassert);
+        (manager, runner)
+    }
+
+    #[test]
+    fn test_reassert_sets_blocked_background_twice() {
+        let (manager, runner) = recording_manager(Some(Duration::from_millis(10)));
+        manager.set_blocked_background("/bg/blocked.jpg").unwrap();
+        std::thread::sleep(Duration::from_millis(300));
+
+        let expected = vec!["feh", "--bg-scale", "/bg/blocked.jpg"];
+        assert_eq!(runner.calls(), vec![expected.clone(), expected]);
+
+        manager.set_blocked_background("/bg/blocked.jpg").unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(runner.calls().len(), 2, "an unchanged background is not reasserted");
+    }
+
+    #[test]
+    fn test_reassert_does_not_block_and_skips_a_changed_background() {
+        let delay = Duration::from_millis(200);
+        let (manager, runner) = recording_manager(Some(delay));
+        let start = std::time::Instant::now();
+        manager.set_blocked_background("/bg/blocked.jpg").unwrap();
+        assert!(start.elapsed() < delay, "the caller does not wait for the reassert");
+
+        manager.set_normal_background("/bg/normal.jpg").unwrap();
+        manager.reassert_later("/bg/blocked.jpg", delay).unwrap().join().unwrap();
+        assert_eq!(runner.calls(), vec![
+            vec!["feh", "--bg-scale", "/bg/blocked.jpg"],
+            vec!["feh", "--bg-scale", "/bg/normal.jpg"],
+        ]);
+    }
+
+    #[test]
+    fn test_without_reassert_sets_blocked_background_once() {
+        let (manager, runner) = recording_manager(None);
+        manager.set_blocked_background("/bg/blocked.jpg").unwrap();
+        manager.set_normal_background("/bg/normal.jpg").unwrap();
+
+        assert_eq!(runner.calls(), vec![
+            vec!["feh", "--bg-scale", "/bg/blocked.jpg"],
+            vec!["feh", "--bg-scale", "/bg/normal.jpg"],
+        ]);
+    }
+
+    fn step(image: &str, hold_seconds: u64) -> SequenceStep {
+        SequenceStep { image: image.to_string(), hold_seconds }
+    }
+
+    #[test]
+    fn test_capped_sequence_within_cap_is_unchanged() {
+        let sequence = vec![step("caught.jpg", 2), step("flash.jpg", 1)];
+        assert_eq!(capped_sequence(&sequence, MAX_BLOCK_SEQUENCE_SECONDS), sequence);
+        assert!(capped_sequence(&[], MAX_BLOCK_SEQUENCE_SECONDS).is_empty());
+    }
+
+    #[test]
+    fn test_capped_sequence_enforces_total_hold() {
+        let sequence = vec![step("a.jpg", 4), step("b.jpg", 8), step("c.jpg", 3)];
+        let capped = capped_sequence(&sequence, 10);
+        assert_eq!(capped, vec![step("a.jpg", 4), step("b.jpg", 6)]);
+        assert_eq!(capped.iter().map(|s| s.hold_seconds).sum::<u64>(), 10);
+    }
+
+    #[test]
+    fn test_expand_block_message() {
+        let until = chrono::NaiveDate::from_ymd_opt(2026, 10, 16)
+            .unwrap()
+            .and_hms_opt(15, 42, 9)
+            .unwrap();
+        assert_eq!(expand_block_message("Blocked until {until}", until), "Blocked until 15:42");
+        assert_eq!(expand_block_message("{until} / {until}", until), "15:42 / 15:42");
+        assert_eq!(expand_block_message("Take a break", until), "Take a break");
+    }
+
+    #[test]
+    fn test_remaining_minutes_rounds_up() {
+        let now = Utc::now();
+        assert_eq!(remaining_minutes(now + chrono::Duration::seconds(241), now), 5);
+        assert_eq!(remaining_minutes(now + chrono::Duration::minutes(4), now), 4);
+        assert_eq!(remaining_minutes(now + chrono::Duration::seconds(1), now), 1);
+        assert_eq!(remaining_minutes(now, now), 0);
+        assert_eq!(remaining_minutes(now - chrono::Duration::minutes(3), now), 0);
+    }
+
+    #[test]
+    fn test_countdown_image_is_in_a_per_user_directory() {
+        let path = countdown_image_path();
+        let dir = path.parent().unwrap();
+        assert_eq!(dir.parent().unwrap(), std::env::temp_dir());
+        assert_eq!(dir.file_name().unwrap().to_string_lossy(),
+            format!("inappropriate-video-handler-{}", nix::unistd::getuid()));
+    }
+
+    #[test]
+    fn test_countdown_regenerates_only_when_the_minute_changes() {
+        let mut countdown = CountdownTracker::default();
+        let until = Utc::now() + chrono::Duration::minutes(3);
+        let ticks = [0, 5, 30, 59, 60, 61, 119, 120, 180];
+        let drawn: Vec<u64> = ticks
+            .iter()
+            .map(|&s| remaining_minutes(until, until - chrono::Duration::minutes(3) + chrono::Duration::seconds(s)))
+            .filter(|&minutes| countdown.should_regenerate(minutes))
+            .collect();
+        assert_eq!(drawn, vec![3, 2, 1, 0]);
+
+        countdown.reset();
+        assert!(countdown.should_regenerate(0));
+    }
+
+    #[test]
+    fn test_parse_fehbg() {
+        let fehbg = "#!/bin/sh\nfeh --no-fehbg --bg-scale '/home/kid/Pictures/beach.jpg' \n";
+        assert_eq!(parse_fehbg(fehbg).as_deref(), Some("/home/kid/Pictures/beach.jpg"));
+
+        let quoted = "#!/bin/sh\nfeh --bg-fill '/home/kid/Kid'\\''s room.png' \n";
+        assert_eq!(parse_fehbg(quoted).as_deref(), Some("/home/kid/Kid's room.png"));
+
+        assert_eq!(parse_fehbg("#!/bin/sh\n"), None);
+        assert_eq!(parse_gsettings_uri("'file:///usr/share/backgrounds/warty.png'\n").as_deref(),
+            Some("/usr/share/backgrounds/warty.png"));
+    }
+
+    #[test]
+    fn test_detect_user_wallpaper_skips_own_images() {
+        let home = tempfile::TempDir::new().unwrap();
+        std::fs::write(home.path().join(".fehbg"), "feh --bg-scale '/bg/blocked.jpg' \n").unwrap();
+        let runner = RecordingRunner::answering(0, "'file:///home/kid/sunset.png'\n");
+
+        assert_eq!(detect_user_wallpaper(None, Some(home.path()), &runner, &[]).as_deref(),
+            Some("/bg/blocked.jpg"));
+        assert_eq!(detect_user_wallpaper(None, Some(home.path()), &runner, &["/bg/blocked.jpg"]), None);
+        assert_eq!(detect_user_wallpaper(None, None, &runner, &[]).as_deref(), Some("/home/kid/sunset.png"));
+        assert_eq!(detect_user_wallpaper(Some("/hint.jpg"), None, &runner, &[]).as_deref(), Some("/hint.jpg"));
+    }
+
+    #[test]
+    fn test_tracker_only_changes_on_new_value() {
+        let mut tracker = BackgroundTracker::default();
+        let desired = ["normal", "normal", "blocked", "blocked", "blocked", "normal", "break", "break"];
+        let set: Vec<&str> = desired.iter().copied().filter(|d| tracker.change_to(d)).collect();
+        assert_eq!(set, vec!["normal", "blocked", "normal", "break"]);
+
+        tracker.reset();
+        assert!(tracker.change_to("break"));
+    }
+
     #[test]
     #[serial]
     fn test_set_background_nonexistent_file() {
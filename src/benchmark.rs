@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crate::filter::{Filter, Verdict};
+
+/// What `benchmark` measured over a corpus of titles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchmarkReport {
+    pub titles: usize,
+    pub blocked: usize,
+    pub whitelisted: usize,
+    pub elapsed: Duration,
+    /// Blocked titles per reported pattern (or label), most frequent first.
+    pub by_pattern: Vec<(String, usize)>,
+}
+
+impl BenchmarkReport {
+    pub fn titles_per_second(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.titles as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+
+    /// The report printed by the `benchmark` subcommand.
+    pub fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Titles: {} in {:.3}s ({:.0} titles/s)",
+                self.titles, self.elapsed.as_secs_f64(), self.titles_per_second()),
+            format!("Blocked: {}, whitelisted: {}, allowed: {}",
+                self.blocked, self.whitelisted, self.titles - self.blocked - self.whitelisted),
+        ];
+        if !self.by_pattern.is_empty() {
+            lines.push("Blocked by pattern:".to_string());
+            lines.extend(self.by_pattern.iter().map(|(pattern, count)| format!("  {:>6}  {}", count, pattern)));
+        }
+        lines
+    }
+}
+
+/// Evaluates every non-blank line of `corpus` with `filter`, timing the whole run.
+pub fn run_benchmark(filter: &Filter, corpus: &str) -> BenchmarkReport {
+    let titles: Vec<&str> = corpus.lines().map(str::trim).filter(|title| !title.is_empty()).collect();
+
+    let started = Instant::now();
+    let verdicts: Vec<Verdict> = titles.iter().map(|title| filter.evaluate(title)).collect();
+    let elapsed = started.elapsed();
+
+    let mut whitelisted = 0;
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for verdict in verdicts {
+        match verdict {
+            Verdict::Allowed => {}
+            Verdict::Whitelisted => whitelisted += 1,
+            Verdict::Blocked(blocked) => *counts.entry(blocked.reported().to_string()).or_default() += 1,
+        }
+    }
+    let mut by_pattern: Vec<(String, usize)> = counts.into_iter().collect();
+    by_pattern.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+    BenchmarkReport {
+        titles: titles.len(),
+        blocked: by_pattern.iter().map(|(_, count)| count).sum(),
+        whitelisted,
+        elapsed,
+        by_pattern,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn pattern_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_benchmark_counts_matches_per_pattern() {
+        let blacklist = pattern_file(".*porn.*\n.*poker.*  #label: gambling\n");
+        let whitelist = pattern_file(".*education.*\n");
+        let filter = Filter::new(blacklist.path(), whitelist.path()).unwrap();
+        let corpus = "free porn\nTexas poker\n\nporn education\nCooking tutorial\nmore porn\nvideo poker\nporn clips\n";
+
+        let report = run_benchmark(&filter, corpus);
+
+        assert_eq!(report.titles, 7);
+        assert_eq!(report.blocked, 5);
+        assert_eq!(report.whitelisted, 1);
+        assert_eq!(report.by_pattern, vec![(".*porn.*".to_string(), 3), ("gambling".to_string(), 2)]);
+        let lines = report.lines();
+        assert_eq!(lines[1], "Blocked: 5, whitelisted: 1, allowed: 1");
+        assert_eq!(&lines[2..], ["Blocked by pattern:", "       3  .*porn.*", "       2  gambling"]);
+    }
+}
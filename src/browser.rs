@@ -1,12 +1,374 @@
-use anyhow::Result;
-use log::{debug, error, info};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use nix::errno::Errno;
 use nix::sys::signal::{self, Signal};
-use nix::unistd::Pid;
+use nix::unistd::{getuid, Pid};
+use regex::Regex;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::privileges::{check_own_dir, create_private_dir};
 use std::process::{Child, Command};
+use std::sync::{Arc, Mutex};
+
+use crate::command::{x_client, CommandRunner, SystemRunner};
+
+/// How browser PIDs are discovered.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PidBackend {
+    /// Shell out to `pgrep -f`.
+    Pgrep,
+    /// Scan `<root>/<pid>/comm` and `<root>/<pid>/cmdline` directly.
+    Proc(PathBuf),
+}
+
+impl PidBackend {
+    /// Uses `pgrep` when it is on `PATH`, otherwise falls back to reading `/proc`.
+    pub fn detect() -> Self {
+        if find_executable("pgrep").is_some() {
+            PidBackend::Pgrep
+        } else {
+            warn!("pgrep not found on PATH, falling back to scanning /proc");
+            PidBackend::Proc(PathBuf::from("/proc"))
+        }
+    }
+}
+
+/// Where `browser.ephemeral_profile` creates its profiles: one directory per user
+/// under the temp dir, holding a subdirectory per browser launch.
+pub fn ephemeral_profile_root() -> PathBuf {
+    std::env::temp_dir().join(format!("inappropriate-video-handler-profiles-{}", getuid()))
+}
+
+/// The arguments that point `executable` at the profile in `dir`: `-profile <dir>` for
+/// Firefox, `--user-data-dir=<dir>` for Chrome and Chromium.
+pub fn profile_args(executable: &str, dir: &Path) -> Vec<String> {
+    let name = Path::new(executable).file_name().and_then(|name| name.to_str()).unwrap_or(executable);
+    if name.to_lowercase().contains("firefox") {
+        vec!["-profile".to_string(), dir.display().to_string()]
+    } else {
+        vec![format!("--user-data-dir={}", dir.display())]
+    }
+}
+
+/// Creates `root` with mode 0700, or checks that an existing one is ours and private:
+/// its name in the shared temp dir is predictable.
+fn prepare_profile_root(root: &Path) -> Result<()> {
+    create_private_dir(root).map_err(|e| anyhow!("unsafe browser profile root '{}': {}", root.display(), e))
+}
+
+/// Creates a new, private profile directory under `root`.
+fn create_profile_dir(root: &Path) -> Result<PathBuf> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    prepare_profile_root(root)?;
+    let stamp = Utc::now().format("%Y%m%d%H%M%S%f");
+    let dir = root.join(format!("{}-{}", stamp, std::process::id()));
+    fs::DirBuilder::new().mode(0o700).create(&dir)
+        .map_err(|e| anyhow!("cannot create browser profile '{}': {}", dir.display(), e))?;
+    Ok(dir)
+}
+
+/// Whether `dir` may be deleted as a profile: a directory of ours directly inside the
+/// private `root`. The list comes from the state file, so it is not trusted.
+fn is_removable_profile(root: &Path, dir: &Path) -> Result<()> {
+    check_own_dir(root, true)?;
+    check_own_dir(dir, false)?;
+    let parent = dir.parent().ok_or_else(|| anyhow!("'{}' has no parent", dir.display()))?;
+    if parent.canonicalize()? != root.canonicalize()? {
+        return Err(anyhow!("'{}' is not under '{}'", dir.display(), root.display()));
+    }
+    Ok(())
+}
+
+/// Deletes the profile directories in `dirs` that are under `root`, returning the ones
+/// that could not be deleted. A directory that is already gone counts as deleted; one
+/// that fails [`is_removable_profile`] is dropped without being touched.
+pub fn remove_ephemeral_profiles(root: &Path, dirs: Vec<PathBuf>) -> Vec<PathBuf> {
+    dirs.into_iter()
+        .filter(|dir| {
+            if fs::symlink_metadata(dir).is_err_and(|e| e.kind() == std::io::ErrorKind::NotFound) {
+                return false;
+            }
+            if let Err(e) = is_removable_profile(root, dir) {
+                warn!("Not removing browser profile '{}': {}", dir.display(), e);
+                return false;
+            }
+            match fs::remove_dir_all(dir) {
+                Ok(()) => false,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+                Err(e) => {
+                    warn!("Failed to remove browser profile '{}': {}", dir.display(), e);
+                    true
+                }
+            }
+        })
+        .collect()
+}
+
+/// Resolves `name` the way a shell would: paths containing `/` are checked directly,
+/// bare names are searched for in each `PATH` entry.
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+    find_executable_in(name, std::env::var_os("PATH").as_deref())
+}
+
+/// Like [`find_executable`], but searches the given `PATH`-style list.
+pub fn find_executable_in(name: &str, path_var: Option<&std::ffi::OsStr>) -> Option<PathBuf> {
+    if name.is_empty() {
+        return None;
+    }
+    if name.contains('/') {
+        let path = PathBuf::from(name);
+        return is_executable(&path).then_some(path);
+    }
+    std::env::split_paths(path_var?)
+        .map(|dir| dir.join(name))
+        .find(|candidate| is_executable(candidate))
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path)
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Whether `pattern` as a `process_name` would match every process, or nearly: it is
+/// blank or a single character, made only of `.` and quantifiers (`..`, `.+`), or a
+/// regex that matches the empty string (`.*`, `(chrome)?`) or any single letter
+/// (`[a-z]`). `pgrep -f ""` lists the whole system, and killing that list would
+/// signal everything the user runs. Other short or loose patterns can still match
+/// unrelated processes, so name the browser's own binary.
+pub fn matches_every_process(pattern: &str) -> bool {
+    let trimmed = pattern.trim();
+    trimmed.chars().count() <= 1
+        || trimmed.chars().all(|c| matches!(c, '.' | '*' | '+' | '?'))
+        || Regex::new(pattern).is_ok_and(|regex| regex.is_match("") || regex.is_match("x"))
+}
+
+/// Mirrors `pgrep -f`: a process matches when `pattern` (a regex, or a literal if it
+/// does not compile) is found in its command line or, failing that, its `comm` name.
+/// The calling process is never returned.
+fn find_pids_in_proc(proc_root: &Path, pattern: &str) -> Result<Vec<i32>> {
+    let regex = Regex::new(pattern).ok();
+    let matches = |text: &str| match &regex {
+        Some(regex) => regex.is_match(text),
+        None => text.contains(pattern),
+    };
+    let own_pid = std::process::id() as i32;
+
+    let mut pids = Vec::new();
+    for entry in fs::read_dir(proc_root)? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let pid: i32 = match entry.file_name().to_str().and_then(|n| n.parse().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        if pid == own_pid {
+            continue;
+        }
+
+        let cmdline = fs::read(entry.path().join("cmdline"))
+            .map(|raw| {
+                String::from_utf8_lossy(&raw)
+                    .split('\0')
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        let comm = fs::read_to_string(entry.path().join("comm")).unwrap_or_default();
+
+        if matches(&cmdline) || matches(comm.trim_end()) {
+            pids.push(pid);
+        }
+    }
+
+    pids.sort_unstable();
+    Ok(pids)
+}
+
+/// What happened to each browser PID during [`BrowserManager::kill_browser_processes`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KillReport {
+    /// Exited after SIGTERM.
+    pub terminated: Vec<i32>,
+    /// Survived SIGTERM and were sent SIGKILL.
+    pub killed: Vec<i32>,
+    /// Could not be signalled.
+    pub failed: Vec<i32>,
+    /// Time spent waiting for SIGTERM to take effect.
+    pub waited: std::time::Duration,
+}
+
+impl KillReport {
+    pub fn is_empty(&self) -> bool {
+        self.terminated.is_empty() && self.killed.is_empty() && self.failed.is_empty()
+    }
+}
+
+impl std::fmt::Display for KillReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "terminated={:?} killed={:?} failed={:?}",
+            self.terminated, self.killed, self.failed)
+    }
+}
+
+/// SIGTERMs `pids`, waits, then SIGKILLs whatever `remaining` still reports.
+/// A signal failing with `ESRCH` means the process already exited and is not a failure.
+fn kill_pids<S, R>(pids: &[i32], mut send: S, remaining: R, wait: std::time::Duration) -> Result<KillReport>
+where
+    S: FnMut(i32, Signal) -> nix::Result<()>,
+    R: FnOnce() -> Result<Vec<i32>>,
+{
+    let mut report = KillReport::default();
+    if pids.is_empty() {
+        return Ok(report);
+    }
+
+    debug!("PIDs to SIGTERM: {:?}", pids);
+    let mut term_failed = Vec::new();
+    for &pid in pids {
+        match send(pid, Signal::SIGTERM) {
+            Ok(_) | Err(Errno::ESRCH) => debug!("SIGTERM sent to pid {}", pid),
+            Err(e) => {
+                error!("Failed to terminate process {}: {}", pid, e);
+                term_failed.push(pid);
+            }
+        }
+    }
+
+    std::thread::sleep(wait);
+    report.waited = wait;
+
+    let survivors = remaining()?;
+    for &pid in pids {
+        if !survivors.contains(&pid) {
+            if term_failed.contains(&pid) {
+                report.failed.push(pid);
+            } else {
+                report.terminated.push(pid);
+            }
+        }
+    }
+    info!("After SIGTERM: exited {:?}, escalating {:?} to SIGKILL", report.terminated, survivors);
+
+    for pid in survivors {
+        match send(pid, Signal::SIGKILL) {
+            Ok(_) | Err(Errno::ESRCH) => {
+                debug!("SIGKILL sent to pid {}", pid);
+                report.killed.push(pid);
+            }
+            Err(e) => {
+                error!("Failed to kill process {}: {}", pid, e);
+                report.failed.push(pid);
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+/// Sliding one-minute window over recent browser launches.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LaunchThrottle {
+    max_per_minute: u32,
+    launches: VecDeque<DateTime<Utc>>,
+}
+
+impl LaunchThrottle {
+    /// `max_per_minute == 0` never throttles. `history` seeds the window, e.g. from the state file.
+    pub fn new(max_per_minute: u32, history: &[DateTime<Utc>]) -> Self {
+        let mut launches: VecDeque<_> = history.iter().copied().collect();
+        launches.make_contiguous().sort();
+        LaunchThrottle { max_per_minute, launches }
+    }
+
+    /// Returns `true` and records the launch if fewer than `max_per_minute`
+    /// launches happened in the minute before `now`.
+    pub fn try_launch(&mut self, now: DateTime<Utc>) -> bool {
+        let window_start = now - ChronoDuration::minutes(1);
+        while self.launches.front().is_some_and(|&t| t <= window_start) {
+            self.launches.pop_front();
+        }
+        if self.max_per_minute > 0 && self.launches.len() >= self.max_per_minute as usize {
+            return false;
+        }
+        self.launches.push_back(now);
+        true
+    }
+
+    pub fn launches(&self) -> Vec<DateTime<Utc>> {
+        self.launches.iter().copied().collect()
+    }
+}
+
+/// What [`PendingRelaunch::poll`] decided for this check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelaunchDecision {
+    /// Nothing scheduled.
+    Idle,
+    /// Scheduled, but `browser.relaunch_delay_seconds` has not passed yet.
+    Wait,
+    Launch,
+    /// A browser appeared before the relaunch was due; the relaunch is dropped.
+    Abort,
+}
+
+/// `browser.auto_relaunch`: a browser start scheduled for some time after a block expired.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PendingRelaunch {
+    due: Option<DateTime<Utc>>,
+}
+
+impl PendingRelaunch {
+    pub fn schedule(&mut self, now: DateTime<Utc>, delay_seconds: u64) {
+        self.due = Some(now + ChronoDuration::seconds(delay_seconds as i64));
+    }
+
+    pub fn due(&self) -> Option<DateTime<Utc>> {
+        self.due
+    }
+
+    /// Decides at `now` whether to relaunch. Both `Launch` and `Abort` clear the schedule.
+    pub fn poll(&mut self, now: DateTime<Utc>, browser_running: bool) -> RelaunchDecision {
+        let Some(due) = self.due else {
+            return RelaunchDecision::Idle;
+        };
+        if browser_running {
+            self.due = None;
+            RelaunchDecision::Abort
+        } else if now < due {
+            RelaunchDecision::Wait
+        } else {
+            self.due = None;
+            RelaunchDecision::Launch
+        }
+    }
+}
 
 pub struct BrowserManager {
     executable: String,
     process_name: String,
+    pid_backend: PidBackend,
+    throttle: Mutex<LaunchThrottle>,
+    clock: Box<dyn Fn() -> DateTime<Utc> + Send + Sync>,
+    /// Sandbox command (and its arguments) the executable is started through.
+    launch_wrapper: Option<(String, Vec<String>)>,
+    /// Arguments passed to the executable before the URL (`browser.launch_args`).
+    launch_args: Vec<String>,
+    runner: Arc<dyn CommandRunner>,
+    /// Skip the per-process lines logged after a kill (`browser.quiet`).
+    quiet: bool,
+    /// Where fresh profiles are created per launch (`browser.ephemeral_profile`).
+    ephemeral_profiles: Option<PathBuf>,
+    /// Profiles this manager created or took over, the only ones a kill deletes.
+    owned_profiles: Mutex<Vec<PathBuf>>,
 }
 
 impl BrowserManager {
@@ -14,52 +376,207 @@ impl BrowserManager {
         BrowserManager {
             executable,
             process_name,
+            pid_backend: PidBackend::detect(),
+            throttle: Mutex::new(LaunchThrottle::default()),
+            clock: Box::new(Utc::now),
+            launch_wrapper: None,
+            launch_args: Vec::new(),
+            runner: Arc::new(SystemRunner),
+            quiet: false,
+            ephemeral_profiles: None,
+            owned_profiles: Mutex::new(Vec::new()),
         }
     }
 
+    /// Stops [`kill_browser_processes`](Self::kill_browser_processes) reporting each
+    /// process it ended; the returned [`KillReport`] is unaffected.
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Starts each launch with a new profile under `root`, and deletes the profiles it
+    /// created once a kill has ended every browser process.
+    pub fn with_ephemeral_profile(mut self, root: Option<PathBuf>) -> Self {
+        self.ephemeral_profiles = root;
+        self
+    }
+
+    /// Takes over profiles created by an earlier launch, possibly in another process,
+    /// so the next kill deletes them too.
+    pub fn adopt_profiles(&self, dirs: &[PathBuf]) {
+        let mut owned = self.owned_profiles.lock().unwrap();
+        for dir in dirs {
+            if !owned.contains(dir) {
+                owned.push(dir.clone());
+            }
+        }
+    }
+
+    /// Profiles created or taken over and not deleted yet, for persisting between runs.
+    pub fn owned_profiles(&self) -> Vec<PathBuf> {
+        let mut owned = self.owned_profiles.lock().unwrap();
+        owned.retain(|dir| dir.exists());
+        owned.clone()
+    }
+
+    /// Overrides the PID discovery chosen by [`PidBackend::detect`].
+    #[allow(dead_code)]
+    pub fn with_pid_backend(mut self, pid_backend: PidBackend) -> Self {
+        self.pid_backend = pid_backend;
+        self
+    }
+
+    /// Runs `pgrep` through `runner` instead of spawning it directly.
+    #[allow(dead_code)]
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Starts the browser as `<wrapper> <args…> <executable> <url>`, e.g. under
+    /// `firejail` or `bwrap`. Killing still matches `process_name`, i.e. the real
+    /// browser running inside the sandbox.
+    pub fn with_launch_wrapper(mut self, wrapper: String, args: Vec<String>) -> Self {
+        self.launch_wrapper = Some((wrapper, args));
+        self
+    }
+
+    /// Starts the browser as `<executable> <args…> <url>`, e.g. `firefox -P restricted <url>`.
+    pub fn with_launch_args(mut self, args: Vec<String>) -> Self {
+        self.launch_args = args;
+        self
+    }
+
+    /// Limits [`start_browser`](Self::start_browser) to `max_per_minute` launches,
+    /// counting the launch times in `history` as already made.
+    pub fn with_launch_limit(mut self, max_per_minute: u32, history: &[DateTime<Utc>]) -> Self {
+        self.throttle = Mutex::new(LaunchThrottle::new(max_per_minute, history));
+        self
+    }
+
+    #[cfg(test)]
+    fn with_clock<F: Fn() -> DateTime<Utc> + Send + Sync + 'static>(mut self, clock: F) -> Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    /// Launch times still inside the throttle window, for persisting between runs.
+    pub fn recent_launches(&self) -> Vec<DateTime<Utc>> {
+        self.throttle.lock().unwrap().launches()
+    }
+
+    /// Fails with "browser executable '<name>' not found" before spawning if the
+    /// executable is neither on `PATH` nor an executable path, and refuses to spawn
+    /// once `browser.max_launches_per_minute` launches happened in the last minute.
     pub fn start_browser(&self, url: &str) -> Result<Child> {
-        info!("Starting browser: '{}' '{}'", self.executable, url);
-        let child = Command::new(&self.executable).arg(url).spawn()?;
+        info!("Starting browser: '{}' {:?} '{}'", self.executable, self.launch_args, url);
+        if find_executable(&self.executable).is_none() {
+            return Err(anyhow!("browser executable '{}' not found", self.executable));
+        }
+        if let Some((wrapper, _)) = &self.launch_wrapper {
+            if find_executable(wrapper).is_none() {
+                return Err(anyhow!("launch wrapper '{}' not found", wrapper));
+            }
+        }
+        let mut throttle = self.throttle.lock().unwrap();
+        if !throttle.try_launch((self.clock)()) {
+            let max = throttle.max_per_minute;
+            warn!("Browser launch throttled: {} launch(es) in the last minute", max);
+            return Err(anyhow!("browser launch throttled: limit of {} per minute reached", max));
+        }
+        drop(throttle);
+        let child = self.launch_command(url)?.spawn()?;
         info!("Browser spawned with pid {}", child.id());
         Ok(child)
     }
 
-    pub fn kill_browser_processes(&self) -> Result<()> {
+    /// The command [`start_browser`](Self::start_browser) spawns for `url`. With an
+    /// ephemeral profile this creates the profile directory it points at.
+    pub fn launch_command(&self, url: &str) -> Result<Command> {
+        let profile = match &self.ephemeral_profiles {
+            Some(root) => {
+                let dir = create_profile_dir(root)?;
+                info!("Using a fresh browser profile in '{}'", dir.display());
+                let args = profile_args(&self.executable, &dir);
+                self.owned_profiles.lock().unwrap().push(dir);
+                args
+            }
+            None => Vec::new(),
+        };
+        Ok(match &self.launch_wrapper {
+            Some((wrapper, args)) => {
+                info!("Launching through wrapper '{}' {:?}", wrapper, args);
+                let mut command = Command::new(wrapper);
+                command.args(args).arg(&self.executable).args(&profile).args(&self.launch_args).arg(url);
+                x_client(&mut command);
+                command
+            }
+            None => {
+                let mut command = Command::new(&self.executable);
+                command.args(&profile).args(&self.launch_args).arg(url);
+                x_client(&mut command);
+                command
+            }
+        })
+    }
+
+    /// Deletes the owned ephemeral profiles after a kill that left no browser process
+    /// behind.
+    fn remove_profiles_after(&self, report: &KillReport) {
+        let Some(root) = &self.ephemeral_profiles else { return };
+        let mut owned = self.owned_profiles.lock().unwrap();
+        if owned.is_empty() {
+            return;
+        }
+        if !report.failed.is_empty() {
+            warn!("Keeping {} browser profile(s): some browser processes could not be killed", owned.len());
+            return;
+        }
+        let count = owned.len();
+        *owned = remove_ephemeral_profiles(root, std::mem::take(&mut *owned));
+        info!("Removed {} browser profile(s)", count - owned.len());
+    }
+
+    /// Like [`kill_browser_processes`](Self::kill_browser_processes) but without the
+    /// grace period: anything still alive right after SIGTERM is SIGKILLed.
+    pub fn force_kill_browser_processes(&self) -> Result<KillReport> {
+        let pids = self.find_browser_pids()?;
+        info!("force_kill_browser_processes: found {} pid(s) for '{}'", pids.len(), self.process_name);
+        let report = kill_pids(
+            &pids,
+            |pid, sig| signal::kill(Pid::from_raw(pid), sig),
+            || self.find_browser_pids(),
+            std::time::Duration::ZERO,
+        )?;
+        self.remove_profiles_after(&report);
+        Ok(report)
+    }
+
+    pub fn kill_browser_processes(&self) -> Result<KillReport> {
         let pids = self.find_browser_pids()?;
 
         info!("kill_browser_processes: found {} pid(s) for '{}'",
             pids.len(), self.process_name);
-        debug!("PIDs to SIGTERM: {:?}", pids);
 
-        for pid in pids {
-            match signal::kill(Pid::from_raw(pid), Signal::SIGTERM) {
-                Ok(_) => {
-                    println!("Terminated process {}", pid);
-                    debug!("SIGTERM sent to pid {}", pid);
-                }
-                Err(e) => error!("Failed to terminate process {}: {}", pid, e),
-            }
-        }
+        let report = kill_pids(
+            &pids,
+            |pid, sig| signal::kill(Pid::from_raw(pid), sig),
+            || self.find_browser_pids(),
+            std::time::Duration::from_secs(2),
+        )?;
 
-        std::thread::sleep(std::time::Duration::from_secs(2));
-
-        let remaining_pids = self.find_browser_pids()?;
-        if !remaining_pids.is_empty() {
-            info!("{} pid(s) still running after SIGTERM, sending SIGKILL", remaining_pids.len());
-            debug!("PIDs to SIGKILL: {:?}", remaining_pids);
-        }
-
-        for pid in remaining_pids {
-            match signal::kill(Pid::from_raw(pid), Signal::SIGKILL) {
-                Ok(_) => {
-                    println!("Killed process {}", pid);
-                    debug!("SIGKILL sent to pid {}", pid);
-                }
-                Err(e) => error!("Failed to kill process {}: {}", pid, e),
+        if !self.quiet {
+            for pid in &report.terminated {
+                debug!("Terminated process {}", pid);
+            }
+            for pid in &report.killed {
+                debug!("Killed process {}", pid);
             }
         }
+        self.remove_profiles_after(&report);
 
-        Ok(())
+        Ok(report)
     }
 
     pub fn get_pids(&self) -> Vec<i32> {
@@ -67,17 +584,22 @@ impl BrowserManager {
     }
 
     fn find_browser_pids(&self) -> Result<Vec<i32>> {
-        if self.process_name.is_empty() {
-            debug!("find_browser_pids: process_name is empty, returning no pids");
+        if matches_every_process(&self.process_name) {
+            warn!("browser.process_name '{}' would match (nearly) every process; not looking for browser pids",
+                self.process_name);
             return Ok(Vec::new());
         }
 
+        if let PidBackend::Proc(root) = &self.pid_backend {
+            debug!("find_browser_pids: scanning '{}' for '{}'", root.display(), self.process_name);
+            let pids = find_pids_in_proc(root, &self.process_name)?;
+            debug!("/proc scan found pids: {:?}", pids);
+            return Ok(pids);
+        }
+
         debug!("find_browser_pids: pgrep -f '{}'", self.process_name);
 
-        let output = Command::new("pgrep")
-            .arg("-f")
-            .arg(&self.process_name)
-            .output()?;
+        let output = self.runner.run("pgrep", &["-f", &self.process_name])?;
 
         if !output.status.success() {
             debug!("pgrep returned no results (exit {})", output.status);
@@ -95,7 +617,6 @@ impl BrowserManager {
         Ok(pids)
     }
 
-    #[allow(dead_code)]
     pub fn has_running_processes(&self) -> bool {
         !self.find_browser_pids().unwrap_or_default().is_empty()
     }
@@ -104,6 +625,7 @@ impl BrowserManager {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::command::testing::RecordingRunner;
     use serial_test::serial;
 
     fn make_manager(executable: &str, process_name: &str) -> BrowserManager {
@@ -132,6 +654,226 @@ mod tests {
         assert_eq!(manager.process_name, "chromium-browser");
     }
 
+    #[test]
+    fn test_launch_command_without_wrapper() {
+        let manager = make_manager("firefox", "firefox");
+        let command = manager.launch_command("https://example.com").unwrap();
+        assert_eq!(command.get_program(), "firefox");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["https://example.com"]);
+    }
+
+    #[test]
+    fn test_launch_command_with_wrapper() {
+        let manager = make_manager("firefox", "firefox")
+            .with_launch_wrapper("firejail".to_string(), vec!["--private".to_string()]);
+        let command = manager.launch_command("https://example.com").unwrap();
+        assert_eq!(command.get_program(), "firejail");
+        assert_eq!(
+            command.get_args().collect::<Vec<_>>(),
+            ["--private", "firefox", "https://example.com"]
+        );
+        assert_eq!(manager.process_name, "firefox");
+    }
+
+    #[test]
+    fn test_launch_command_with_launch_args() {
+        let manager = make_manager("firefox", "firefox").with_launch_args(vec!["--kiosk".to_string()]);
+        let command = manager.launch_command("https://example.com").unwrap();
+        assert_eq!(command.get_program(), "firefox");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["--kiosk", "https://example.com"]);
+
+        let wrapped = make_manager("firefox", "firefox")
+            .with_launch_args(vec!["-P".to_string(), "restricted".to_string()])
+            .with_launch_wrapper("firejail".to_string(), vec!["--private".to_string()]);
+        assert_eq!(
+            wrapped.launch_command("https://example.com").unwrap().get_args().collect::<Vec<_>>(),
+            ["--private", "firefox", "-P", "restricted", "https://example.com"]
+        );
+    }
+
+    fn fake_proc_entry(root: &Path, pid: &str, comm: &str, cmdline: &[&str]) {
+        let dir = root.join(pid);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        let mut raw = Vec::new();
+        for arg in cmdline {
+            raw.extend_from_slice(arg.as_bytes());
+            raw.push(0);
+        }
+        fs::write(dir.join("cmdline"), raw).unwrap();
+    }
+
+    #[test]
+    fn test_find_pids_in_proc_matches_cmdline_and_comm() {
+        let root = tempfile::TempDir::new().unwrap();
+        fake_proc_entry(root.path(), "100", "chrome", &["/opt/google/chrome/chrome", "--type=renderer"]);
+        fake_proc_entry(root.path(), "200", "bash", &["bash", "-l"]);
+        fake_proc_entry(root.path(), "300", "chrome", &[]);
+        fake_proc_entry(root.path(), "40", "python3", &["python3", "chrome_helper.py"]);
+        fs::create_dir_all(root.path().join("self")).unwrap();
+        fs::write(root.path().join("uptime"), "1.0 2.0").unwrap();
+
+        let pids = find_pids_in_proc(root.path(), "chrome").unwrap();
+        assert_eq!(pids, vec![40, 100, 300]);
+    }
+
+    #[test]
+    fn test_find_pids_in_proc_regex_and_no_match() {
+        let root = tempfile::TempDir::new().unwrap();
+        fake_proc_entry(root.path(), "100", "firefox", &["/usr/lib/firefox/firefox"]);
+        fake_proc_entry(root.path(), "200", "firefox-bin", &["firefox-bin", "-P", "kid"]);
+
+        assert_eq!(find_pids_in_proc(root.path(), "^/usr/lib/firefox").unwrap(), vec![100]);
+        assert!(find_pids_in_proc(root.path(), "chrome").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_pids_in_proc_skips_own_pid() {
+        let root = tempfile::TempDir::new().unwrap();
+        let own = std::process::id().to_string();
+        fake_proc_entry(root.path(), &own, "ivh", &["inappropriate-video-handler"]);
+
+        assert!(find_pids_in_proc(root.path(), "inappropriate").unwrap().is_empty());
+    }
+
+    fn pgrep_manager(runner: &Arc<RecordingRunner>) -> BrowserManager {
+        let mut manager = make_manager("google-chrome-stable", "chrome").with_runner(runner.clone());
+        manager.pid_backend = PidBackend::Pgrep;
+        manager
+    }
+
+    #[test]
+    fn test_pgrep_command_and_output_parsing() {
+        let runner = Arc::new(RecordingRunner::answering(0, "101\n 202\nnot-a-pid\n"));
+        let manager = pgrep_manager(&runner);
+
+        assert_eq!(manager.get_pids(), vec![101, 202]);
+        assert!(manager.has_running_processes());
+        assert_eq!(runner.calls(), vec![vec!["pgrep", "-f", "chrome"]; 2]);
+    }
+
+    #[test]
+    fn test_pgrep_no_match_is_no_pids() {
+        let runner = Arc::new(RecordingRunner::answering(1, ""));
+        let manager = pgrep_manager(&runner);
+        assert!(manager.get_pids().is_empty());
+
+        let mut unnamed = make_manager("chrome", "").with_runner(runner.clone());
+        unnamed.pid_backend = PidBackend::Pgrep;
+        assert!(unnamed.get_pids().is_empty());
+        assert_eq!(runner.calls().len(), 1);
+    }
+
+    #[test]
+    fn test_browser_manager_uses_proc_backend() {
+        let root = tempfile::TempDir::new().unwrap();
+        fake_proc_entry(root.path(), "4242", "chromium", &["chromium", "https://example.com"]);
+
+        let mut manager = make_manager("chromium", "chromium");
+        manager.pid_backend = PidBackend::Proc(root.path().to_path_buf());
+
+        assert_eq!(manager.get_pids(), vec![4242]);
+    }
+
+    #[test]
+    fn test_quiet_kill_still_returns_report() {
+        let root = tempfile::TempDir::new().unwrap();
+        // Beyond any real pid_max, so signalling it fails with ESRCH and touches nothing.
+        fake_proc_entry(root.path(), "2000000000", "chrome", &["/opt/google/chrome/chrome"]);
+        let manager = make_manager("google-chrome-stable", "chrome")
+            .with_pid_backend(PidBackend::Proc(root.path().to_path_buf()))
+            .with_quiet(true);
+
+        let report = manager.kill_browser_processes().unwrap();
+        assert_eq!(report.killed, vec![2_000_000_000]);
+        assert!(report.failed.is_empty());
+    }
+
+    #[test]
+    fn test_kill_pids_categorizes_report() {
+        use std::cell::RefCell;
+
+        let sent = RefCell::new(Vec::new());
+        let report = kill_pids(
+            &[10, 20, 30, 40, 50],
+            |pid, sig| {
+                sent.borrow_mut().push((pid, sig));
+                match (pid, sig) {
+                    (30, Signal::SIGTERM) => Err(Errno::EPERM),
+                    (40, _) => Err(Errno::EPERM),
+                    (50, Signal::SIGTERM) => Err(Errno::ESRCH),
+                    _ => Ok(()),
+                }
+            },
+            || Ok(vec![20, 30, 60]),
+            std::time::Duration::ZERO,
+        )
+        .unwrap();
+
+        assert_eq!(report.terminated, vec![10, 50]);
+        assert_eq!(report.killed, vec![20, 30, 60]);
+        assert_eq!(report.failed, vec![40]);
+
+        let kills: Vec<i32> = sent
+            .borrow()
+            .iter()
+            .filter(|(_, sig)| *sig == Signal::SIGKILL)
+            .map(|(pid, _)| *pid)
+            .collect();
+        assert_eq!(kills, vec![20, 30, 60]);
+    }
+
+    #[test]
+    fn test_kill_pids_sigkill_failure() {
+        let report = kill_pids(
+            &[7],
+            |_, sig| if sig == Signal::SIGKILL { Err(Errno::EPERM) } else { Ok(()) },
+            || Ok(vec![7]),
+            std::time::Duration::ZERO,
+        )
+        .unwrap();
+
+        assert_eq!(report, KillReport { failed: vec![7], ..KillReport::default() });
+    }
+
+    #[test]
+    fn test_kill_pids_no_pids() {
+        let report = kill_pids(
+            &[],
+            |_, _| panic!("no signal should be sent"),
+            || panic!("no rescan should happen"),
+            std::time::Duration::from_secs(60),
+        )
+        .unwrap();
+        assert!(report.is_empty());
+        assert_eq!(report.waited, std::time::Duration::ZERO);
+    }
+
+    #[test]
+    fn test_kill_report_display() {
+        let report = KillReport { terminated: vec![1], killed: vec![2, 3], ..KillReport::default() };
+        assert_eq!(report.to_string(), "terminated=[1] killed=[2, 3] failed=[]");
+    }
+
+    #[test]
+    fn test_find_executable() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let exe = dir.path().join("fake-browser");
+        fs::write(&exe, "#!/bin/sh\n").unwrap();
+        let plain = dir.path().join("not-executable");
+        fs::write(&plain, "").unwrap();
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        assert_eq!(find_executable(exe.to_str().unwrap()), Some(exe.clone()));
+        assert_eq!(find_executable(plain.to_str().unwrap()), None);
+        assert_eq!(find_executable("sh").map(|p| p.is_file()), Some(true));
+        assert_eq!(find_executable("nonexistent-executable-12345"), None);
+        assert_eq!(find_executable(""), None);
+    }
+
     #[test]
     #[serial]
     fn test_find_browser_pids_nonexistent_process() {
@@ -148,13 +890,168 @@ mod tests {
         assert!(!manager.has_running_processes());
     }
 
+    /// A private profile root inside a fresh temp dir.
+    fn profile_root() -> (tempfile::TempDir, PathBuf) {
+        let parent = tempfile::tempdir().unwrap();
+        let root = parent.path().join("profiles");
+        prepare_profile_root(&root).unwrap();
+        (parent, root)
+    }
+
+    #[test]
+    fn test_launch_command_with_ephemeral_profile() {
+        let (_parent, root) = profile_root();
+        let manager = make_manager("/usr/bin/google-chrome", "chrome")
+            .with_launch_args(vec!["--kiosk".to_string()])
+            .with_ephemeral_profile(Some(root.clone()));
+
+        let command = manager.launch_command("https://example.com").unwrap();
+        let args: Vec<String> = command.get_args().map(|arg| arg.to_string_lossy().into_owned()).collect();
+        let dir = PathBuf::from(args[0].strip_prefix("--user-data-dir=").unwrap());
+        assert!(dir.is_dir() && dir.starts_with(&root));
+        assert_eq!(&args[1..], ["--kiosk", "https://example.com"]);
+
+        let firefox = make_manager("firefox", "firefox").with_ephemeral_profile(Some(root.clone()));
+        let command = firefox.launch_command("https://example.com").unwrap();
+        let args: Vec<_> = command.get_args().collect();
+        assert_eq!(args[0], "-profile");
+        assert!(Path::new(args[1]).is_dir() && Path::new(args[1]) != dir);
+
+        assert_eq!(manager.owned_profiles(), vec![dir.clone()]);
+        assert!(remove_ephemeral_profiles(&root, vec![dir.clone(), root.join("missing")]).is_empty());
+        assert!(!dir.exists());
+        assert!(manager.owned_profiles().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_kill_removes_only_owned_profiles() {
+        let (_parent, root) = profile_root();
+        let session = root.join("session");
+        let foreign = root.join("foreign");
+        fs::create_dir(&session).unwrap();
+        fs::create_dir(&foreign).unwrap();
+        let manager = make_manager("nonexistent-browser-12345", "nonexistent-browser-12345")
+            .with_ephemeral_profile(Some(root.clone()));
+        manager.adopt_profiles(std::slice::from_ref(&session));
+
+        manager.kill_browser_processes().unwrap();
+
+        assert!(!session.exists());
+        assert!(foreign.exists(), "a profile this manager did not create is kept");
+        assert!(manager.owned_profiles().is_empty());
+    }
+
+    #[test]
+    fn test_remove_ephemeral_profiles_rejects_paths_outside_root() {
+        let (_parent, root) = profile_root();
+        let outside = tempfile::tempdir().unwrap();
+        let victim = outside.path().join("home");
+        fs::create_dir(&victim).unwrap();
+        let link = root.join("link");
+        std::os::unix::fs::symlink(&victim, &link).unwrap();
+        let escape = root.join("../..").join(outside.path().file_name().unwrap()).join("home");
+
+        let kept = remove_ephemeral_profiles(&root, vec![victim.clone(), link.clone(), escape]);
+
+        assert!(kept.is_empty());
+        assert!(victim.is_dir());
+        assert!(fs::symlink_metadata(&link).is_ok());
+    }
+
+    #[test]
+    fn test_profile_root_must_be_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let parent = tempfile::tempdir().unwrap();
+        let root = parent.path().join("profiles");
+        prepare_profile_root(&root).unwrap();
+        assert_eq!(fs::metadata(&root).unwrap().permissions().mode() & 0o777, 0o700);
+        prepare_profile_root(&root).unwrap();
+
+        fs::set_permissions(&root, fs::Permissions::from_mode(0o777)).unwrap();
+        assert!(prepare_profile_root(&root).is_err());
+        assert!(create_profile_dir(&root).is_err());
+    }
+
     #[test]
     #[serial]
     fn test_start_browser_invalid_executable() {
         let manager = make_manager("nonexistent-browser-executable-12345", "nonexistent-process");
 
-        let result = manager.start_browser("https://example.com");
-        assert!(result.is_err());
+        let err = manager.start_browser("https://example.com").unwrap_err();
+        assert_eq!(err.to_string(), "browser executable 'nonexistent-browser-executable-12345' not found");
+        assert!(err.downcast_ref::<std::io::Error>().is_none());
+
+        let absolute = make_manager("/nonexistent/dir/firefox", "nonexistent-process");
+        let err = absolute.start_browser("https://example.com").unwrap_err();
+        assert_eq!(err.to_string(), "browser executable '/nonexistent/dir/firefox' not found");
+    }
+
+    #[test]
+    fn test_launch_throttle_sliding_window() {
+        let start = Utc::now();
+        let mut throttle = LaunchThrottle::new(2, &[]);
+
+        assert!(throttle.try_launch(start));
+        assert!(throttle.try_launch(start + ChronoDuration::seconds(10)));
+        assert!(!throttle.try_launch(start + ChronoDuration::seconds(30)));
+        // The first launch leaves the window; the refused attempt was not recorded.
+        assert!(throttle.try_launch(start + ChronoDuration::seconds(61)));
+        assert_eq!(throttle.launches().len(), 2);
+
+        let mut unlimited = LaunchThrottle::new(0, &[start, start, start]);
+        assert!(unlimited.try_launch(start));
+    }
+
+    #[test]
+    fn test_relaunch_waits_for_the_delay() {
+        let start = Utc::now();
+        let mut relaunch = PendingRelaunch::default();
+        assert_eq!(relaunch.poll(start, false), RelaunchDecision::Idle);
+
+        relaunch.schedule(start, 30);
+        assert_eq!(relaunch.poll(start, false), RelaunchDecision::Wait);
+        assert_eq!(relaunch.poll(start + ChronoDuration::seconds(29), false), RelaunchDecision::Wait);
+        assert_eq!(relaunch.poll(start + ChronoDuration::seconds(30), false), RelaunchDecision::Launch);
+        assert_eq!(relaunch.poll(start + ChronoDuration::seconds(31), false), RelaunchDecision::Idle);
+
+        relaunch.schedule(start, 0);
+        assert_eq!(relaunch.poll(start, false), RelaunchDecision::Launch);
+    }
+
+    #[test]
+    fn test_relaunch_aborts_when_browser_appears() {
+        let start = Utc::now();
+        let mut relaunch = PendingRelaunch::default();
+        relaunch.schedule(start, 30);
+
+        assert_eq!(relaunch.poll(start + ChronoDuration::seconds(10), true), RelaunchDecision::Abort);
+        assert_eq!(relaunch.due(), None);
+        assert_eq!(relaunch.poll(start + ChronoDuration::seconds(30), false), RelaunchDecision::Idle);
+    }
+
+    #[test]
+    fn test_start_browser_throttled_with_injected_clock() {
+        use std::sync::{Arc, Mutex};
+
+        let start = Utc::now();
+        let now = Arc::new(Mutex::new(start));
+        let clock = Arc::clone(&now);
+        let history = [start - ChronoDuration::seconds(20)];
+        let manager = make_manager("true", "nonexistent-process")
+            .with_launch_limit(3, &history)
+            .with_clock(move || *clock.lock().unwrap());
+
+        for _ in 0..2 {
+            manager.start_browser("about:blank").unwrap().wait().unwrap();
+        }
+        let err = manager.start_browser("about:blank").unwrap_err();
+        assert_eq!(err.to_string(), "browser launch throttled: limit of 3 per minute reached");
+
+        *now.lock().unwrap() = start + ChronoDuration::seconds(61);
+        manager.start_browser("about:blank").unwrap().wait().unwrap();
+        assert_eq!(manager.recent_launches(), vec![start + ChronoDuration::seconds(61)]);
     }
 
     #[test]
@@ -162,8 +1059,8 @@ mod tests {
     fn test_kill_browser_processes_no_processes() {
         let manager = make_manager("nonexistent-browser-12345", "nonexistent-browser-12345");
 
-        let result = manager.kill_browser_processes();
-        assert!(result.is_ok());
+        let report = manager.kill_browser_processes().unwrap();
+        assert!(report.is_empty());
     }
 
     #[test]
@@ -186,6 +1083,22 @@ mod tests {
         assert_eq!(pids.len(), 0);
     }
 
+    #[test]
+    fn test_process_name_matching_everything_finds_no_pids() {
+        let runner = Arc::new(RecordingRunner::answering(0, "1\n2\n3\n"));
+        for process_name in ["", "   ", ".*", "(chrome)?", ".", "..", ".+", "c", " x ", "[a-z]", "\\w"] {
+            assert!(matches_every_process(process_name), "{:?}", process_name);
+            let mut manager = make_manager("chrome", process_name).with_runner(runner.clone());
+            manager.pid_backend = PidBackend::Pgrep;
+            assert!(manager.find_browser_pids().unwrap().is_empty());
+            assert!(manager.kill_browser_processes().unwrap().is_empty());
+        }
+        assert!(runner.calls().is_empty());
+        assert!(!matches_every_process("chrome"));
+        assert!(!matches_every_process("firefox.*-P kid"));
+        assert!(!matches_every_process("vlc"));
+    }
+
     #[test]
     fn test_start_browser_url_variants() {
         let manager = make_manager("nonexistent-browser", "nonexistent-process");
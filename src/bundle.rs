@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use crate::config::Config;
+
+const CONFIG_ENTRY: &str = "config.yaml";
+const STATE_ENTRY: &str = "state.json";
+const BLACKLIST_ENTRY: &str = "blacklist";
+const WHITELIST_ENTRY: &str = "whitelist";
+
+/// Bundle entries other than the config, paired with where they live under `config`.
+fn data_entries(config: &Config) -> [(&'static str, &str); 3] {
+    [
+        (STATE_ENTRY, config.files.state_file.as_str()),
+        (BLACKLIST_ENTRY, config.files.blacklist.as_str()),
+        (WHITELIST_ENTRY, config.files.whitelist.as_str()),
+    ]
+}
+
+/// Writes the config file, state file and pattern lists into a tar archive at `output`.
+/// The state file and lists are skipped when they do not exist. Returns the entry names written.
+pub fn export_bundle<P: AsRef<Path>>(config_path: &str, config: &Config, output: P) -> Result<Vec<&'static str>> {
+    let mut builder = tar::Builder::new(fs::File::create(output.as_ref())?);
+    builder.append_path_with_name(config_path, CONFIG_ENTRY)
+        .map_err(|e| anyhow!("cannot add config '{}': {}", config_path, e))?;
+    let mut written = vec![CONFIG_ENTRY];
+
+    for (name, path) in data_entries(config) {
+        if !Path::new(path).exists() {
+            debug!("Bundle: '{}' does not exist, skipping {}", path, name);
+            continue;
+        }
+        builder.append_path_with_name(path, name)?;
+        written.push(name);
+    }
+    builder.into_inner()?;
+
+    info!("Exported bundle '{}' with {:?}", output.as_ref().display(), written);
+    Ok(written)
+}
+
+/// Restores a bundle written by [`export_bundle`]. The bundled config is validated before
+/// anything is written; it then replaces `config_path`, and the state file and lists are
+/// written to the paths that config names. Returns the imported config.
+pub fn import_bundle<P: AsRef<Path>>(bundle: P, config_path: &str) -> Result<Config> {
+    let mut entries = BTreeMap::new();
+    let mut archive = tar::Archive::new(fs::File::open(bundle.as_ref())?);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+        entries.insert(name, content);
+    }
+
+    let raw_config = entries.get(CONFIG_ENTRY)
+        .ok_or_else(|| anyhow!("bundle has no {}", CONFIG_ENTRY))?;
+    let config = Config::from_yaml(std::str::from_utf8(raw_config)?)?;
+    config.validate().map_err(|e| anyhow!("bundled config is invalid: {}", e))?;
+
+    write_file(config_path, raw_config)?;
+    for (name, path) in data_entries(&config) {
+        if let Some(content) = entries.get(name) {
+            write_file(path, content)?;
+        }
+    }
+
+    info!("Imported bundle '{}' into '{}'", bundle.as_ref().display(), config_path);
+    Ok(config)
+}
+
+fn write_file(path: &str, content: &[u8]) -> Result<()> {
+    if let Some(parent) = Path::new(path).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    debug!("Bundle: writing '{}'", path);
+    fs::write(path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::AppState;
+    use tempfile::TempDir;
+
+    fn config_in(dir: &Path) -> Config {
+        let mut config = Config::default();
+        config.files.state_file = dir.join("state.json").to_string_lossy().into_owned();
+        config.files.blacklist = dir.join("lists").join("black.txt").to_string_lossy().into_owned();
+        config.files.whitelist = dir.join("lists").join("white.txt").to_string_lossy().into_owned();
+        config
+    }
+
+    #[test]
+    fn test_export_import_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let config = config_in(dir.path());
+        let config_path = dir.path().join("config.yaml");
+        config.save(&config_path).unwrap();
+        let state = AppState { violation_count: 2, ..AppState::default() };
+        state.save(&config.files.state_file).unwrap();
+        fs::create_dir_all(dir.path().join("lists")).unwrap();
+        fs::write(&config.files.blacklist, ".*porn.*\n").unwrap();
+        fs::write(&config.files.whitelist, ".*tutorial.*\n").unwrap();
+
+        let paths = [&config.files.state_file, &config.files.blacklist, &config.files.whitelist];
+        let originals: Vec<Vec<u8>> = paths.iter().map(|p| fs::read(p).unwrap()).collect();
+        let original_config = fs::read(&config_path).unwrap();
+
+        let bundle = dir.path().join("bundle.tar");
+        let written = export_bundle(config_path.to_str().unwrap(), &config, &bundle).unwrap();
+        assert_eq!(written, vec![CONFIG_ENTRY, STATE_ENTRY, BLACKLIST_ENTRY, WHITELIST_ENTRY]);
+
+        for path in paths {
+            fs::remove_file(path).unwrap();
+        }
+        let imported_path = dir.path().join("restored").join("config.yaml");
+        let imported = import_bundle(&bundle, imported_path.to_str().unwrap()).unwrap();
+
+        assert_eq!(imported, config);
+        assert_eq!(fs::read(&imported_path).unwrap(), original_config);
+        for (path, original) in paths.iter().zip(&originals) {
+            assert_eq!(&fs::read(path).unwrap(), original);
+        }
+        assert_eq!(AppState::load(&config.files.state_file).unwrap().violation_count, 2);
+    }
+
+    #[test]
+    fn test_export_skips_missing_files() {
+        let dir = TempDir::new().unwrap();
+        let config = config_in(dir.path());
+        let config_path = dir.path().join("config.yaml");
+        config.save(&config_path).unwrap();
+
+        let bundle = dir.path().join("bundle.tar");
+        let written = export_bundle(config_path.to_str().unwrap(), &config, &bundle).unwrap();
+        assert_eq!(written, vec![CONFIG_ENTRY]);
+    }
+
+    #[test]
+    fn test_import_rejects_invalid_config() {
+        let dir = TempDir::new().unwrap();
+        let mut config = config_in(dir.path());
+        config.monitoring.check_frequency_seconds = 0;
+        let config_path = dir.path().join("config.yaml");
+        config.save(&config_path).unwrap();
+        let bundle = dir.path().join("bundle.tar");
+        export_bundle(config_path.to_str().unwrap(), &config, &bundle).unwrap();
+
+        let target = dir.path().join("imported").join("config.yaml");
+        let err = import_bundle(&bundle, target.to_str().unwrap()).unwrap_err();
+        assert!(err.to_string().starts_with("bundled config is invalid"), "{}", err);
+        assert!(!target.exists());
+    }
+}
@@ -0,0 +1,108 @@
+use anyhow::Result;
+use chrono::{DateTime, TimeZone};
+use log::{debug, info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::command::SpawnedPids;
+use crate::config::{CaptureConfig, CaptureTool};
+
+/// Longest the daemon waits for a screenshot before going ahead with the kill.
+pub const CAPTURE_TIMEOUT: Duration = Duration::from_secs(1);
+
+const CAPTURE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// `<dir>/block-YYYYMMDD-HHMMSS.png` for a capture taken at `at`.
+pub fn capture_path<Tz: TimeZone>(dir: &Path, at: DateTime<Tz>) -> PathBuf
+where
+    Tz::Offset: std::fmt::Display,
+{
+    dir.join(format!("block-{}.png", at.format("%Y%m%d-%H%M%S")))
+}
+
+/// The program and arguments that write a screenshot of the whole screen to `path`.
+pub fn capture_command(tool: CaptureTool, path: &Path) -> (&'static str, Vec<String>) {
+    let path = path.to_string_lossy().into_owned();
+    match tool {
+        CaptureTool::Import => ("import", vec!["-window".to_string(), "root".to_string(), path]),
+        CaptureTool::Scrot => ("scrot", vec!["--overwrite".to_string(), path]),
+    }
+}
+
+/// Takes a screenshot before a kill, as evidence of what triggered the block.
+/// Waits at most [`CAPTURE_TIMEOUT`]; failures (including a missing tool) are
+/// logged and never stop enforcement.
+pub fn capture_screen(config: &CaptureConfig, spawned: &SpawnedPids) -> Option<PathBuf> {
+    match try_capture(config, spawned) {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Failed to capture the screen: {}", e);
+            None
+        }
+    }
+}
+
+fn try_capture(config: &CaptureConfig, spawned: &SpawnedPids) -> Result<Option<PathBuf>> {
+    let dir = Path::new(&config.dir);
+    fs::create_dir_all(dir)?;
+    let path = capture_path(dir, chrono::Local::now());
+    let (program, args) = capture_command(config.tool, &path);
+    debug!("Capturing the screen: {} {:?}", program, args);
+
+    let mut child = spawned.spawn(Command::new(program).args(&args))?;
+    let deadline = Instant::now() + CAPTURE_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            spawned.forget(child.id() as i32);
+            if !status.success() {
+                return Err(anyhow::anyhow!("'{}' exited with {}", program, status));
+            }
+            info!("Saved screen capture to '{}'", path.display());
+            return Ok(Some(path));
+        }
+        if Instant::now() >= deadline {
+            warn!("'{}' is still running after {:?}; not waiting for it", program, CAPTURE_TIMEOUT);
+            spawned.wait_in_background(child, |_| {});
+            return Ok(None);
+        }
+        std::thread::sleep(CAPTURE_POLL_INTERVAL);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    #[test]
+    fn test_capture_path_is_timestamped_in_dir() {
+        let at = Utc.with_ymd_and_hms(2026, 10, 16, 15, 4, 9).unwrap();
+        assert_eq!(
+            capture_path(Path::new("/var/lib/ivh/captures"), at),
+            PathBuf::from("/var/lib/ivh/captures/block-20261016-150409.png")
+        );
+    }
+
+    #[test]
+    fn test_capture_command() {
+        let path = Path::new("/tmp/block.png");
+        assert_eq!(capture_command(CaptureTool::Import, path),
+            ("import", vec!["-window".to_string(), "root".to_string(), "/tmp/block.png".to_string()]));
+        assert_eq!(capture_command(CaptureTool::Scrot, path).0, "scrot");
+    }
+
+    #[test]
+    fn test_missing_tool_is_not_fatal() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = CaptureConfig {
+            enabled: true,
+            tool: CaptureTool::Import,
+            dir: dir.path().join("captures").to_string_lossy().into_owned(),
+        };
+        // Whether or not ImageMagick is installed, this must return rather than fail.
+        let _ = capture_screen(&config, &SpawnedPids::default());
+        assert!(dir.path().join("captures").is_dir());
+    }
+}
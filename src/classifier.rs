@@ -0,0 +1,206 @@
+use anyhow::{anyhow, Result};
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::command::{CommandRunner, SystemRunner};
+use crate::privacy::TitleRedactor;
+
+/// Reported instead of a pattern when the external classifier blocks a title.
+pub const CLASSIFIER_MARKER: &str = "<external classifier>";
+
+/// Seconds `curl` may take when `filter.external_classifier` is a URL.
+const HTTP_TIMEOUT_SECONDS: &str = "2";
+
+/// A failing classifier is asked again on every scan; it is reported at `warn` at
+/// most this often, and at `debug` in between.
+const FAILURE_WARN_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Builds the command for `title`. A URL is queried with `curl`, posting the title
+/// as the `title` form field. Anything else is split on whitespace and `{title}`
+/// substituted in each argument, or the title appended when there is no placeholder.
+pub fn classifier_command(template: &str, title: &str) -> Vec<String> {
+    let template = template.trim();
+    if template.starts_with("http://") || template.starts_with("https://") {
+        return ["curl", "-fsS", "-m", HTTP_TIMEOUT_SECONDS, "--data-urlencode"]
+            .iter()
+            .map(|arg| arg.to_string())
+            .chain([format!("title={}", title), template.to_string()])
+            .collect();
+    }
+
+    let mut argv: Vec<String> = template.split_whitespace().map(|arg| arg.replace("{title}", title)).collect();
+    if !template.contains("{title}") {
+        argv.push(title.to_string());
+    }
+    argv
+}
+
+/// Reads the classifier's answer: `block` or `allow` on the first non-empty line,
+/// in any case. Anything else is an error.
+pub fn parse_verdict(stdout: &str) -> Result<bool> {
+    let answer = stdout.lines().map(str::trim).find(|line| !line.is_empty()).unwrap_or("");
+    match answer.to_ascii_lowercase().as_str() {
+        "block" => Ok(true),
+        "allow" => Ok(false),
+        _ => Err(anyhow!("unexpected classifier answer '{}'", answer)),
+    }
+}
+
+/// `filter.external_classifier`: asks an outside script or HTTP endpoint about titles
+/// the regex filter let through. Verdicts are cached for a while per title, and any
+/// failure leaves the title allowed.
+pub struct ExternalClassifier {
+    template: String,
+    cache_for: Duration,
+    runner: Arc<dyn CommandRunner>,
+    redactor: TitleRedactor,
+    cache: Mutex<HashMap<String, (Instant, bool)>>,
+    last_failure_warning: Mutex<Option<Instant>>,
+}
+
+impl ExternalClassifier {
+    pub fn new(template: String, cache_seconds: u64) -> Self {
+        info!("External classifier: '{}' (verdicts cached for {}s)", template, cache_seconds);
+        ExternalClassifier {
+            template,
+            cache_for: Duration::from_secs(cache_seconds),
+            runner: Arc::new(SystemRunner),
+            redactor: TitleRedactor::default(),
+            cache: Mutex::new(HashMap::new()),
+            last_failure_warning: Mutex::new(None),
+        }
+    }
+
+    /// Logs titles in the form `privacy.title_logging` allows.
+    pub fn with_redactor(mut self, redactor: TitleRedactor) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    #[allow(dead_code)]
+    pub fn with_runner(mut self, runner: Arc<dyn CommandRunner>) -> Self {
+        self.runner = runner;
+        self
+    }
+
+    /// Whether the classifier wants `title` blocked. Errors count as "allow".
+    pub fn is_blocked(&self, title: &str) -> bool {
+        let now = Instant::now();
+        if let Some(&(at, blocked)) = self.cache.lock().unwrap().get(title) {
+            if now.duration_since(at) < self.cache_for {
+                return blocked;
+            }
+        }
+
+        match self.query(title) {
+            Ok(blocked) => {
+                debug!("Classifier verdict for '{}': {}",
+                    self.redactor.redact(title, None), if blocked { "block" } else { "allow" });
+                let mut cache = self.cache.lock().unwrap();
+                cache.retain(|_, (at, _)| now.duration_since(*at) < self.cache_for);
+                cache.insert(title.to_string(), (now, blocked));
+                blocked
+            }
+            Err(e) => {
+                let title = self.redactor.redact(title, None);
+                if self.failure_warning_due(now) {
+                    warn!("External classifier failed, allowing '{}': {} (repeats are logged at debug for {}s)",
+                        title, e, FAILURE_WARN_INTERVAL.as_secs());
+                } else {
+                    debug!("External classifier failed, allowing '{}': {}", title, e);
+                }
+                false
+            }
+        }
+    }
+
+    /// Whether a failure at `now` gets a warning, which it does once per
+    /// [`FAILURE_WARN_INTERVAL`].
+    fn failure_warning_due(&self, now: Instant) -> bool {
+        let mut last = self.last_failure_warning.lock().unwrap();
+        if last.is_some_and(|at| now.duration_since(at) < FAILURE_WARN_INTERVAL) {
+            return false;
+        }
+        *last = Some(now);
+        true
+    }
+
+    fn query(&self, title: &str) -> Result<bool> {
+        let argv = classifier_command(&self.template, title);
+        let (program, args) = argv.split_first().ok_or_else(|| anyhow!("empty classifier command"))?;
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let output = self.runner.run(program, &args)?;
+        if !output.status.success() {
+            return Err(anyhow!("'{}' exited with {}", program, output.status));
+        }
+        parse_verdict(&String::from_utf8_lossy(&output.stdout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::testing::RecordingRunner;
+    use std::fs;
+
+    #[test]
+    fn test_classifier_command_forms() {
+        assert_eq!(classifier_command("classify --title {title}", "Bad page"), ["classify", "--title", "Bad page"]);
+        assert_eq!(classifier_command("classify -q", "Bad page"), ["classify", "-q", "Bad page"]);
+        assert_eq!(
+            classifier_command("https://ml.local/classify", "a&b"),
+            ["curl", "-fsS", "-m", "2", "--data-urlencode", "title=a&b", "https://ml.local/classify"]
+        );
+    }
+
+    #[test]
+    fn test_parse_verdict() {
+        assert!(parse_verdict("\nBLOCK\n").unwrap());
+        assert!(!parse_verdict("allow").unwrap());
+        assert!(parse_verdict("maybe").is_err());
+        assert!(parse_verdict("").is_err());
+    }
+
+    #[test]
+    fn test_fake_classifier_blocks_sentinel_titles() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("classify.sh");
+        fs::write(&script, "case \"$1\" in *SENTINEL*) echo block ;; *) echo allow ;; esac\n").unwrap();
+        let classifier = ExternalClassifier::new(format!("sh {} {{title}}", script.display()), 60);
+
+        assert!(classifier.is_blocked("a SENTINEL video"));
+        assert!(!classifier.is_blocked("cooking tutorial"));
+    }
+
+    #[test]
+    fn test_failures_warn_at_most_once_per_interval() {
+        let classifier = ExternalClassifier::new("classify".to_string(), 60);
+        let start = Instant::now();
+        assert!(classifier.failure_warning_due(start));
+        assert!(!classifier.failure_warning_due(start + Duration::from_secs(1)));
+        assert!(!classifier.failure_warning_due(start + FAILURE_WARN_INTERVAL - Duration::from_secs(1)));
+        assert!(classifier.failure_warning_due(start + FAILURE_WARN_INTERVAL));
+    }
+
+    #[test]
+    fn test_classifier_fails_open_and_caches_verdicts() {
+        let failing = ExternalClassifier::new("classify".to_string(), 60)
+            .with_runner(Arc::new(RecordingRunner::answering(1, "block")));
+        assert!(!failing.is_blocked("anything"));
+        let missing = ExternalClassifier::new("nonexistent-classifier-12345".to_string(), 60);
+        assert!(!missing.is_blocked("anything"));
+
+        let runner = Arc::new(RecordingRunner::answering(0, "block\n"));
+        let classifier = ExternalClassifier::new("classify".to_string(), 60).with_runner(runner.clone());
+        assert!(classifier.is_blocked("title"));
+        assert!(classifier.is_blocked("title"));
+        assert_eq!(runner.calls().len(), 1);
+
+        let uncached = ExternalClassifier::new("classify".to_string(), 0).with_runner(runner.clone());
+        uncached.is_blocked("title");
+        uncached.is_blocked("title");
+        assert_eq!(runner.calls().len(), 3);
+    }
+}
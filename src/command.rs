@@ -0,0 +1,151 @@
+use anyhow::Result;
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Output};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// `XAUTHORITY` for the X clients the daemon starts, once the display has been
+/// resolved. Kept here rather than in the process environment, which must not be
+/// changed while other threads may be reading it.
+static CHILD_XAUTHORITY: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Gives every program started through [`x_client`] `path` as its `XAUTHORITY`.
+pub fn set_child_xauthority(path: Option<&Path>) {
+    *CHILD_XAUTHORITY.write().unwrap() = path.map(Path::to_path_buf);
+}
+
+/// Applies [`set_child_xauthority`] to `command`.
+pub fn x_client(command: &mut Command) -> &mut Command {
+    if let Some(path) = CHILD_XAUTHORITY.read().unwrap().as_ref() {
+        command.env("XAUTHORITY", path);
+    }
+    command
+}
+
+/// Runs an external program to completion. [`SystemRunner`] is the real one;
+/// tests substitute a fake that records what would have run.
+pub trait CommandRunner: Send + Sync {
+    fn run(&self, program: &str, args: &[&str]) -> Result<Output>;
+}
+
+#[derive(Debug, Default)]
+pub struct SystemRunner;
+
+impl CommandRunner for SystemRunner {
+    fn run(&self, program: &str, args: &[&str]) -> Result<Output> {
+        Ok(x_client(Command::new(program).args(args)).output()?)
+    }
+}
+
+/// PIDs of the helper programs the daemon started itself (message boxes,
+/// notifications, hooks, screenshots), so their windows can be left out of a scan.
+/// Clones share the same set.
+#[derive(Debug, Clone, Default)]
+pub struct SpawnedPids(Arc<Mutex<BTreeSet<i32>>>);
+
+impl SpawnedPids {
+    /// Spawns `command` and remembers its pid until [`forget`](Self::forget) is called.
+    pub fn spawn(&self, command: &mut Command) -> std::io::Result<Child> {
+        let child = x_client(command).spawn()?;
+        self.0.lock().unwrap().insert(child.id() as i32);
+        Ok(child)
+    }
+
+    pub fn forget(&self, pid: i32) {
+        self.0.lock().unwrap().remove(&pid);
+    }
+
+    /// Waits for `child` on a separate thread, forgets its pid once it has exited,
+    /// then hands the result to `on_exit`.
+    pub fn wait_in_background<F>(&self, mut child: Child, on_exit: F)
+    where
+        F: FnOnce(std::io::Result<ExitStatus>) + Send + 'static,
+    {
+        let spawned = self.clone();
+        std::thread::spawn(move || {
+            let result = child.wait();
+            spawned.forget(child.id() as i32);
+            on_exit(result);
+        });
+    }
+
+    pub fn pids(&self) -> Vec<i32> {
+        self.0.lock().unwrap().iter().copied().collect()
+    }
+}
+
+#[cfg(test)]
+pub mod testing {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+    use std::sync::Mutex;
+
+    /// Records every command instead of running it, and answers each with the same
+    /// exit code and stdout.
+    #[derive(Default)]
+    pub struct RecordingRunner {
+        calls: Mutex<Vec<Vec<String>>>,
+        code: i32,
+        stdout: String,
+    }
+
+    impl RecordingRunner {
+        pub fn answering(code: i32, stdout: &str) -> Self {
+            RecordingRunner { code, stdout: stdout.to_string(), ..RecordingRunner::default() }
+        }
+
+        /// Each command run so far as `[program, args…]`.
+        pub fn calls(&self) -> Vec<Vec<String>> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    impl CommandRunner for RecordingRunner {
+        fn run(&self, program: &str, args: &[&str]) -> Result<Output> {
+            let mut call = vec![program.to_string()];
+            call.extend(args.iter().map(|a| a.to_string()));
+            self.calls.lock().unwrap().push(call);
+            Ok(Output {
+                status: ExitStatus::from_raw(self.code << 8),
+                stdout: self.stdout.clone().into_bytes(),
+                stderr: Vec::new(),
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::testing::RecordingRunner;
+    use super::*;
+
+    #[test]
+    fn test_system_runner_captures_output() {
+        let output = SystemRunner.run("echo", &["hello", "world"]).unwrap();
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "hello world\n");
+        assert!(SystemRunner.run("nonexistent-program-12345", &[]).is_err());
+    }
+
+    #[test]
+    fn test_spawned_pids_are_forgotten_after_exit() {
+        let spawned = SpawnedPids::default();
+        let child = spawned.spawn(Command::new("true").arg("helper")).unwrap();
+        let pid = child.id() as i32;
+        assert_eq!(spawned.pids(), vec![pid]);
+
+        let (done, exited) = std::sync::mpsc::channel();
+        spawned.wait_in_background(child, move |status| done.send(status.unwrap().success()).unwrap());
+        assert!(exited.recv().unwrap());
+        assert!(spawned.pids().is_empty());
+    }
+
+    #[test]
+    fn test_recording_runner() {
+        let runner = RecordingRunner::answering(1, "");
+        let output = runner.run("pgrep", &["-f", "chrome"]).unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(runner.calls(), vec![vec!["pgrep", "-f", "chrome"]]);
+    }
+}
@@ -1,30 +1,399 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Utc, Weekday};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-#[derive(Debug, Serialize, Deserialize)]
+use crate::timezone::Zone;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     pub browser: BrowserConfig,
     pub monitoring: MonitoringConfig,
     pub timeouts: TimeoutConfig,
     pub backgrounds: BackgroundConfig,
     pub files: FileConfig,
+    #[serde(default)]
+    pub filter: FilterConfig,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub notifications: NotificationConfig,
+    #[serde(default)]
+    pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub security: SecurityConfig,
+    #[serde(default)]
+    pub management: ManagementConfig,
+    /// Event name (`block`, `unblock`, ...) to command template.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub hooks: BTreeMap<String, String>,
+    /// IANA timezone for schedules and quiet hours; system local time when unset.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, ProfileConfig>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct BrowserConfig {
     pub executable: String,
     pub url: String,
     pub process_name: String,
+    /// Launches allowed in any 60-second window; `0` disables the limit.
+    #[serde(default)]
+    pub max_launches_per_minute: u32,
+    /// Sandbox command the browser is started through, e.g. `firejail`.
+    #[serde(default)]
+    pub launch_wrapper: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub launch_wrapper_args: Vec<String>,
+    /// Arguments passed to the browser before the URL, e.g. `["-P", "restricted"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub launch_args: Vec<String>,
+    /// Start the browser again when a block expires.
+    #[serde(default)]
+    pub auto_relaunch: bool,
+    /// Seconds between the block expiring and the relaunch.
+    #[serde(default)]
+    pub relaunch_delay_seconds: u64,
+    /// Do not report each terminated or killed browser process.
+    #[serde(default)]
+    pub quiet: bool,
+    /// Start every session with a new, empty profile that is deleted once the
+    /// browser is killed.
+    #[serde(default)]
+    pub ephemeral_profile: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct MonitoringConfig {
     pub check_frequency_seconds: u64,
+    #[serde(default = "default_title_history_size")]
+    pub title_history_size: usize,
+    #[serde(default)]
+    pub display: Option<String>,
+    /// Xauthority file for the display; `$XAUTHORITY` or `~/.Xauthority` when unset.
+    #[serde(default)]
+    pub xauthority: Option<String>,
+    /// Seconds to keep retrying while the X display cannot be opened; 0 tries once.
+    #[serde(default)]
+    pub display_timeout_seconds: u64,
+    /// Like `display_timeout_seconds`, for `doctor` and `scan`, which should neither
+    /// hang nor give up on a display that is briefly busy.
+    #[serde(default = "default_diagnostic_display_timeout_seconds")]
+    pub diagnostic_display_timeout_seconds: u64,
+    #[serde(default)]
+    pub fullscreen_priority: bool,
+    /// Kill a running browser at startup if the loaded state is blocked or in a break.
+    #[serde(default = "default_enforce_on_startup")]
+    pub enforce_on_startup: bool,
+    /// Kill a browser found running while blocked or on a break, on every check.
+    #[serde(default = "default_kill_while_restricted")]
+    pub kill_while_restricted: bool,
+    /// Leave windows of the daemon's own helper programs (messages, notifications,
+    /// hooks, screenshots) out of every scan.
+    #[serde(default = "default_exclude_own_windows")]
+    pub exclude_own_windows: bool,
+    /// Kill the process of any window whose class is not in `allowed_classes` or
+    /// `kiosk_safe_classes`.
+    #[serde(default)]
+    pub kiosk_mode: bool,
+    /// `WM_CLASS` values allowed to run in kiosk mode, besides the browser.
+    #[serde(default)]
+    pub allowed_classes: Vec<String>,
+    /// Desktop components kiosk mode never kills (window manager, panels).
+    #[serde(default = "default_kiosk_safe_classes")]
+    pub kiosk_safe_classes: Vec<String>,
+    /// `root` or a `WM_CLASS` to focus after the browser is killed.
+    #[serde(default)]
+    pub focus_after_kill: Option<String>,
+    /// Seconds after startup during which window titles are not evaluated.
+    #[serde(default)]
+    pub startup_delay_seconds: u64,
+    #[serde(default)]
+    pub detect_pip: PipConfig,
+    /// Screenshot taken just before the browser is killed on a detection.
+    #[serde(default)]
+    pub capture_on_block: CaptureConfig,
+    /// `WM_CLASS` values whose windows are never checked.
+    #[serde(default)]
+    pub ignore_classes: Vec<String>,
+    /// Seconds to wait for `acknowledge` before killing on a detection; 0 kills at once.
+    #[serde(default)]
+    pub ack_seconds: u64,
+    /// Shorter wait before the next check after one that saw a whitelisted title,
+    /// to catch autoplay moving on to something else; off when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub whitelist_recheck_seconds: Option<u64>,
+    /// Consecutive scans a title, or any blacklisted title, must match in before
+    /// it is blocked; 0 or 1 blocks on the first one.
+    #[serde(default)]
+    pub title_debounce_scans: u32,
+    /// Actions taken on a detection, in order.
+    #[serde(default = "default_on_detection")]
+    pub on_detection: Vec<DetectionAction>,
+    /// Command (and arguments) run by the `lock` detection action.
+    #[serde(default = "default_lock_command")]
+    pub lock_command: Vec<String>,
+    /// What to do when a browser is running but no browser window titles are found.
+    #[serde(default)]
+    pub on_empty: OnEmpty,
+    /// Whether browser windows without a title are dropped or kept as suspicious.
+    #[serde(default)]
+    pub untitled_windows: UntitledWindows,
+    /// Which browser windows a match has to come from to count as a detection.
+    #[serde(default)]
+    pub detection_scope: DetectionScope,
+    /// Window properties matched against the blacklist.
+    #[serde(default = "default_match_properties")]
+    pub match_properties: Vec<MatchProperty>,
+    /// Window sources queried each tick; their windows are merged by title.
+    #[serde(default = "default_backends")]
+    pub backends: Vec<TitleBackend>,
+    /// Encoding label (e.g. `latin1`, `gbk`) for legacy `WM_NAME` titles, used when a
+    /// window has no `_NET_WM_NAME`; lossy UTF-8 when unset.
+    #[serde(default)]
+    pub legacy_encoding: Option<String>,
+    /// Only act on a detection while a browser process is running.
+    #[serde(default)]
+    pub require_browser_running: bool,
+    /// Consecutive failures to read the window list before `on_repeated_errors` is
+    /// taken; 0 keeps retrying every tick.
+    #[serde(default)]
+    pub max_consecutive_errors: u32,
+    #[serde(default)]
+    pub on_repeated_errors: ErrorAction,
+    /// Difference between wall-clock and monotonic time between two ticks that counts
+    /// as a clock jump (suspend/resume, NTP); 0 disables detection.
+    #[serde(default = "default_clock_jump_threshold_seconds")]
+    pub clock_jump_threshold_seconds: u64,
+    #[serde(default)]
+    pub on_clock_jump: ClockJumpAction,
+    /// Most top-level windows inspected per tick; 0 means no limit.
+    #[serde(default)]
+    pub max_windows: usize,
+    /// Also block titles matching the built-in proxy/VPN evasion markers.
+    #[serde(default)]
+    pub block_evasion: bool,
+    /// Extra evasion patterns (regexes) checked alongside the built-in ones.
+    #[serde(default)]
+    pub evasion_patterns: Vec<String>,
+    /// Chromium `History` database whose new downloads and visits are checked against
+    /// the filter. Opt-in, read-only, and only available with the `history` feature.
+    #[serde(default)]
+    pub inspect_history: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnEmpty {
+    /// Treat it as all clear (titles remembered in the history are still re-checked).
+    #[default]
+    Allow,
+    /// Skip the tick entirely, leaving the title history untouched.
+    Ignore,
+    /// Treat it as a blacklist hit.
+    Block,
+}
+
+/// One step of `monitoring.on_detection`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionAction {
+    /// Close the browser.
+    Kill,
+    /// Lock the screen with `monitoring.lock_command`.
+    Lock,
+    /// Send a desktop notification.
+    Notify,
+}
+
+/// How browser windows with an empty title are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UntitledWindows {
+    /// Leave them out of the window list.
+    #[default]
+    Ignore,
+    /// Keep them; with `filter.default_action: deny` a focused one is a hit.
+    Suspicious,
+}
+
+/// Which windows `monitoring.detection_scope` checks for a detection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectionScope {
+    /// Any browser window, from any backend.
+    #[default]
+    AnyWindow,
+    /// Only the window holding the input focus, whichever backend reports it.
+    FocusedOnly,
+}
+
+/// A window property that `monitoring.match_properties` checks against the blacklist.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchProperty {
+    /// `_NET_WM_NAME` or `WM_NAME`, with its recent history.
+    Title,
+    /// `WM_ICON_NAME`.
+    IconName,
+    /// `WM_WINDOW_ROLE`.
+    Role,
+    /// The class part of `WM_CLASS`.
+    Class,
+}
+
+/// A source of window titles listed in `monitoring.backends`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleBackend {
+    /// The X server over Xlib, including XWayland clients.
+    Xlib,
+}
+
+/// What the daemon does after `monitoring.max_consecutive_errors` X failures in a row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ErrorAction {
+    /// Close the X connection and open a new one.
+    #[default]
+    Reconnect,
+    /// Exit with a distinct status so a supervisor restarts the daemon.
+    Exit,
+}
+
+/// How the break schedule is repaired after a clock jump.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClockJumpAction {
+    /// However many breaks were missed, at most one is taken straight away.
+    #[default]
+    OneBreak,
+    /// Skip missed breaks; the next one is a full interval from now.
+    Reschedule,
+}
+
+fn default_clock_jump_threshold_seconds() -> u64 {
+    120
+}
+
+/// Program used by `monitoring.capture_on_block`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CaptureTool {
+    /// ImageMagick's `import -window root`.
+    #[default]
+    Import,
+    Scrot,
+}
+
+/// Where and how evidence screenshots are taken before a kill.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CaptureConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub tool: CaptureTool,
+    #[serde(default = "default_capture_dir")]
+    pub dir: String,
+}
+
+fn default_capture_dir() -> String {
+    format!("{}/inappropriate-video-handler/captures", xdg_cache_dir())
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        CaptureConfig {
+            enabled: false,
+            tool: CaptureTool::Import,
+            dir: default_capture_dir(),
+        }
+    }
+}
+
+/// Thresholds for treating a small always-on-top browser window as picture-in-picture.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PipConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_pip_max_width")]
+    pub max_width: u32,
+    #[serde(default = "default_pip_max_height")]
+    pub max_height: u32,
+}
+
+fn default_pip_max_width() -> u32 {
+    640
+}
+
+fn default_pip_max_height() -> u32 {
+    480
+}
+
+impl Default for PipConfig {
+    fn default() -> Self {
+        PipConfig {
+            enabled: false,
+            max_width: default_pip_max_width(),
+            max_height: default_pip_max_height(),
+        }
+    }
+}
+
+fn default_title_history_size() -> usize {
+    5
+}
+
+fn default_enforce_on_startup() -> bool {
+    true
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+fn default_kill_while_restricted() -> bool {
+    true
+}
+
+fn default_diagnostic_display_timeout_seconds() -> u64 {
+    3
+}
+
+fn default_exclude_own_windows() -> bool {
+    true
+}
+
+fn default_kiosk_safe_classes() -> Vec<String> {
+    [
+        "Xfdesktop", "Xfce4-panel", "Xfwm4", "Plasmashell", "Gnome-shell", "Openbox", "Tint2", "Polybar",
+        // Notification servers, which show the daemon's own notices.
+        "Dunst", "Xfce4-notifyd", "Notify-osd", "Plasma-notifications",
+        // Screen lockers: killing one would unlock the session.
+        "Xfce4-screensaver", "Light-locker", "XScreenSaver", "Xsecurelock", "i3lock", "Kscreenlocker_greet",
+        "Gnome-screensaver", "Cinnamon-screensaver", "Mate-screensaver", "Slock",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_match_properties() -> Vec<MatchProperty> {
+    vec![MatchProperty::Title]
+}
+
+fn default_backends() -> Vec<TitleBackend> {
+    vec![TitleBackend::Xlib]
+}
+
+fn default_on_detection() -> Vec<DetectionAction> {
+    vec![DetectionAction::Kill]
+}
+
+fn default_lock_command() -> Vec<String> {
+    vec!["loginctl".to_string(), "lock-session".to_string()]
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TimeoutConfig {
     pub blacklist_timeout_minutes: u64,
     pub bathroom_break_minutes: u64,
@@ -35,6 +404,69 @@ pub struct TimeoutConfig {
     pub hard_lock_minutes: u64,
     #[serde(default = "default_retry_reset_minutes")]
     pub retry_reset_minutes: u64,
+    #[serde(default = "default_escalation_multiplier")]
+    pub escalation_multiplier: u64,
+    #[serde(default)]
+    pub max_block_minutes: Option<u64>,
+    /// The next break is scheduled up to this many minutes earlier or later than the interval.
+    #[serde(default)]
+    pub break_jitter_minutes: u64,
+    /// A due break is held back until this many minutes after the previous one ended.
+    #[serde(default)]
+    pub min_break_gap_minutes: u64,
+    /// Minutes of browser use allowed per local day; no limit when unset.
+    #[serde(default)]
+    pub daily_limit_minutes: Option<DailyLimit>,
+    /// Minutes of browser use allowed per ISO week (Monday to Sunday); no limit when unset.
+    #[serde(default)]
+    pub weekly_limit_minutes: Option<u64>,
+    /// Times per local day `snooze-break` may postpone the next break; 0 disables it.
+    #[serde(default)]
+    pub max_snoozes_per_day: u32,
+    /// After a detection, further hits within this many seconds only kill the
+    /// browser and are not counted as new violations; 0 disables it.
+    #[serde(default)]
+    pub detection_cooldown_seconds: u64,
+}
+
+/// `timeouts.daily_limit_minutes`: one number for every day, or a map from weekday
+/// (`mon`, `tuesday`, ...) to minutes. Days missing from the map have no limit.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum DailyLimit {
+    Flat(u64),
+    PerWeekday(BTreeMap<String, u64>),
+}
+
+impl DailyLimit {
+    pub fn minutes_for(&self, day: Weekday) -> Option<u64> {
+        match self {
+            DailyLimit::Flat(minutes) => Some(*minutes),
+            DailyLimit::PerWeekday(days) => days
+                .iter()
+                .find(|(name, _)| name.parse::<Weekday>().ok() == Some(day))
+                .map(|(_, minutes)| *minutes),
+        }
+    }
+
+    /// The limit for the local weekday of `now` in `zone`.
+    pub fn minutes_at(&self, zone: Zone, now: DateTime<Utc>) -> Option<u64> {
+        self.minutes_for(zone.naive_local(now).weekday())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if let DailyLimit::PerWeekday(days) = self {
+            for name in days.keys() {
+                name.parse::<Weekday>()
+                    .map_err(|_| anyhow!("timeouts.daily_limit_minutes: unknown weekday '{}'", name))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_escalation_multiplier() -> u64 {
+    1
 }
 
 fn default_grace_retries() -> u32 {
@@ -49,14 +481,64 @@ fn default_retry_reset_minutes() -> u64 {
     20
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct BackgroundConfig {
     pub normal: String,
     pub blocked: String,
     pub bathroom_break: String,
+    /// Shown while waiting for an acknowledgement; falls back to `blocked`.
+    #[serde(default)]
+    pub warning: Option<String>,
+    /// Text shown in a small centred window when blocking; `{until}` becomes the end time.
+    #[serde(default)]
+    pub block_message: Option<String>,
+    /// Wallpapers shown in turn when blocking, before settling on `blocked`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub block_sequence: Vec<SequenceStep>,
+    /// Set the blocked wallpaper a second time shortly after the first, for window
+    /// managers that occasionally ignore a change.
+    #[serde(default)]
+    pub reassert: bool,
+    /// Restore the wallpaper found at startup instead of `normal`.
+    #[serde(default)]
+    pub restore_user_wallpaper: bool,
+    /// The user's own wallpaper, when it cannot be detected from `~/.fehbg` or GNOME.
+    #[serde(default)]
+    pub user_wallpaper: Option<String>,
+    /// Draw the minutes left over the blocked wallpaper, redrawn as the minute changes.
+    #[serde(default)]
+    pub countdown: bool,
+    /// Blocked wallpaper per pattern label (`.*poker.*  # gambling`), used instead of
+    /// `blocked` when a pattern with that label causes the block.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub blocked_by_label: BTreeMap<String, String>,
+}
+
+impl BackgroundConfig {
+    /// Every image the daemon itself sets, apart from `normal`.
+    pub fn managed_images(&self) -> Vec<&str> {
+        let mut images = vec![self.blocked.as_str(), self.bathroom_break.as_str()];
+        images.extend(self.warning.as_deref());
+        images.extend(self.block_sequence.iter().map(|step| step.image.as_str()));
+        images.extend(self.blocked_by_label.values().map(String::as_str));
+        images
+    }
+
+    /// The blocked wallpaper for a block reported as `pattern` (the label of the
+    /// matching pattern, if it has one), falling back to `blocked`.
+    pub fn blocked_for(&self, pattern: &str) -> &str {
+        self.blocked_by_label.get(pattern).map_or(&self.blocked, String::as_str)
+    }
+}
+
+/// One wallpaper of `backgrounds.block_sequence` and how long it stays up.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SequenceStep {
+    pub image: String,
+    pub hold_seconds: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct FileConfig {
     pub blacklist: String,
     pub whitelist: String,
@@ -65,6 +547,308 @@ pub struct FileConfig {
     pub log_file: String,
     #[serde(default = "default_titles_file")]
     pub titles_file: String,
+    /// How `state_file` is stored.
+    #[serde(default)]
+    pub state_backend: StateBackend,
+    /// Patterns allowed during `study-mode`; every other title is blocked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub study_whitelist: Option<String>,
+}
+
+/// Storage format of `files.state_file`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateBackend {
+    /// A JSON file, replaced atomically on every save.
+    #[default]
+    Json,
+    /// A single-row SQLite database (requires the `sqlite-state` feature).
+    Sqlite,
+}
+
+/// What happens to a title that the pattern lists do not decide.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DefaultAction {
+    /// Block only blacklisted titles (whitelist overrides the blacklist).
+    #[default]
+    Allow,
+    /// Block every non-empty title that is not whitelisted; the blacklist is not consulted.
+    Deny,
+}
+
+/// What happens when the blacklist cannot be loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FailMode {
+    /// A missing blacklist is an empty one: nothing is blocked.
+    #[default]
+    Open,
+    /// A missing or unreadable blacklist keeps the browser closed until it loads.
+    Closed,
+}
+
+/// How the blacklist and whitelist are combined in allow mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterPolicy {
+    /// Whitelist patterns are matched against the title and override the blacklist.
+    #[default]
+    Standard,
+    /// Blacklist lines are keywords; whitelist lines are trusted domains. A title is
+    /// blocked when it matches a keyword and the domain extracted from it is not trusted.
+    KeywordWithDomainAllow,
+}
+
+/// How each pattern line is wrapped before it is compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoAnchor {
+    /// Compile the line as written.
+    #[default]
+    None,
+    /// Match anywhere in the title, with `.` also matching newlines and `^`/`$`
+    /// anchored to the whole title; `.*` wrappers are unnecessary.
+    Contains,
+    /// The whole title must match (`^...$`).
+    Whole,
+}
+
+/// How pattern lines are interpreted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    /// Each line is a regular expression.
+    #[default]
+    Regex,
+    /// Each line is a literal keyword found anywhere in the title; the blacklist is
+    /// matched with a single Aho-Corasick automaton.
+    Substring,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FilterConfig {
+    #[serde(default)]
+    pub default_action: DefaultAction,
+    #[serde(default)]
+    pub auto_anchor: AutoAnchor,
+    /// Strip a trailing " — Mozilla Firefox" style browser name before matching.
+    #[serde(default)]
+    pub strip_browser_suffix: bool,
+    /// Browser names recognised in addition to the built-in list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub browser_suffixes: Vec<String>,
+    /// Distinct blacklist patterns a title must match before it is blocked.
+    #[serde(default = "default_min_matches")]
+    pub min_matches: usize,
+    #[serde(default)]
+    pub policy: FilterPolicy,
+    /// Compile both lists case-insensitively.
+    #[serde(default = "default_case_insensitive")]
+    pub case_insensitive: bool,
+    /// Never let the whitelist override a blacklist match.
+    #[serde(default)]
+    pub ignore_whitelist: bool,
+    #[serde(default)]
+    pub match_mode: MatchMode,
+    /// Also match a lowercased, alphanumeric-only "slug" of the title against
+    /// the keywords in `slug_keywords`, catching `p-o-r-n` style obfuscation.
+    #[serde(default)]
+    pub match_slug: bool,
+    /// Keyword file for `match_slug`, one keyword per line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub slug_keywords: Option<String>,
+    /// Command template (`{title}` is substituted) or http(s) URL asked about titles
+    /// the patterns allowed; it answers `block` or `allow`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub external_classifier: Option<String>,
+    /// How long a classifier verdict is reused for the same title.
+    #[serde(default = "default_classifier_cache_seconds")]
+    pub classifier_cache_seconds: u64,
+    /// Total compiled size allowed for all pattern lists together; patterns past it
+    /// are dropped. 0 means no limit.
+    #[serde(default)]
+    pub max_total_pattern_bytes: usize,
+    #[serde(default)]
+    pub fail_mode: FailMode,
+}
+
+fn default_classifier_cache_seconds() -> u64 {
+    60
+}
+
+fn default_case_insensitive() -> bool {
+    true
+}
+
+fn default_min_matches() -> usize {
+    1
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig {
+            default_action: DefaultAction::default(),
+            auto_anchor: AutoAnchor::default(),
+            strip_browser_suffix: false,
+            browser_suffixes: Vec::new(),
+            min_matches: default_min_matches(),
+            policy: FilterPolicy::default(),
+            case_insensitive: default_case_insensitive(),
+            ignore_whitelist: false,
+            match_mode: MatchMode::default(),
+            match_slug: false,
+            slug_keywords: None,
+            external_classifier: None,
+            classifier_cache_seconds: default_classifier_cache_seconds(),
+            max_total_pattern_bytes: 0,
+            fail_mode: FailMode::Open,
+        }
+    }
+}
+
+impl FilterConfig {
+    /// Options for the `study-mode` filter: deny by default, so only titles matching
+    /// `files.study_whitelist` are allowed. Matching options such as case and
+    /// suffix stripping are kept.
+    pub fn for_study_mode(&self) -> FilterConfig {
+        FilterConfig {
+            default_action: DefaultAction::Deny,
+            policy: FilterPolicy::Standard,
+            ignore_whitelist: false,
+            match_slug: false,
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct AuditConfig {
+    #[serde(default = "default_audit_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_audit_file")]
+    pub file: String,
+    #[serde(default = "default_audit_max_size_mb")]
+    pub max_size_mb: u64,
+    #[serde(default = "default_audit_keep")]
+    pub keep: usize,
+    #[serde(default)]
+    pub compress: bool,
+}
+
+fn default_audit_enabled() -> bool {
+    true
+}
+
+fn default_audit_file() -> String {
+    format!("{}/inappropriate-video-handler/audit.log", xdg_cache_dir())
+}
+
+fn default_audit_max_size_mb() -> u64 {
+    10
+}
+
+fn default_audit_keep() -> usize {
+    5
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            enabled: default_audit_enabled(),
+            file: default_audit_file(),
+            max_size_mb: default_audit_max_size_mb(),
+            keep: default_audit_keep(),
+            compress: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct NotificationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHoursConfig>,
+}
+
+/// How window titles appear in the audit log, the daemon log and the titles file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TitleLogging {
+    #[default]
+    Full,
+    /// Only the part of the title the blacklist pattern matched.
+    MatchedOnly,
+    /// A salted SHA-256 prefix, so repeats can be correlated without the content.
+    Hash,
+    None,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct PrivacyConfig {
+    #[serde(default)]
+    pub title_logging: TitleLogging,
+    /// Salt mixed into hashed titles; empty when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub salt: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct SecurityConfig {
+    /// Unprivileged user a daemon started as root switches to once the display
+    /// and state store are open; no switch when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run_as_user: Option<String>,
+    /// Only windows of these users' processes are checked; everyone's when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub supervised_users: Vec<String>,
+}
+
+/// Reporting to, and taking commands from, a central server. Needs the
+/// `management` feature.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManagementConfig {
+    /// Endpoint the daemon POSTs its state to; no syncing when unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sync_url: Option<String>,
+    #[serde(default = "default_sync_interval_seconds")]
+    pub sync_interval_seconds: u64,
+}
+
+fn default_sync_interval_seconds() -> u64 {
+    300
+}
+
+impl Default for ManagementConfig {
+    fn default() -> Self {
+        ManagementConfig {
+            sync_url: None,
+            sync_interval_seconds: default_sync_interval_seconds(),
+        }
+    }
+}
+
+/// Local-time `HH:MM` bounds; `end` before `start` wraps past midnight.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QuietHoursConfig {
+    pub start: String,
+    pub end: String,
+}
+
+/// Per-user overrides layered on top of the base config when a profile is selected.
+/// Any field left unset keeps the base value, except `state_file`, which defaults to
+/// a profile-specific file next to the base one so profiles never share state.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_file: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub blacklist: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub whitelist: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeouts: Option<TimeoutConfig>,
 }
 
 fn default_log_file() -> String {
@@ -82,9 +866,54 @@ impl Default for Config {
                 executable: "google-chrome-stable".to_string(),
                 url: "https://www.youtube.com".to_string(),
                 process_name: "chrome".to_string(),
+                max_launches_per_minute: 0,
+                launch_wrapper: None,
+                launch_wrapper_args: Vec::new(),
+                launch_args: Vec::new(),
+                auto_relaunch: false,
+                relaunch_delay_seconds: 0,
+                quiet: false,
+                ephemeral_profile: false,
             },
             monitoring: MonitoringConfig {
                 check_frequency_seconds: 60,
+                title_history_size: 5,
+                display: None,
+                xauthority: None,
+                display_timeout_seconds: 0,
+                diagnostic_display_timeout_seconds: 3,
+                fullscreen_priority: false,
+                enforce_on_startup: true,
+                kill_while_restricted: true,
+                exclude_own_windows: true,
+                kiosk_mode: false,
+                allowed_classes: Vec::new(),
+                kiosk_safe_classes: default_kiosk_safe_classes(),
+                focus_after_kill: None,
+                startup_delay_seconds: 0,
+                detect_pip: PipConfig::default(),
+                capture_on_block: CaptureConfig::default(),
+                ignore_classes: Vec::new(),
+                ack_seconds: 0,
+                whitelist_recheck_seconds: None,
+                title_debounce_scans: 0,
+                on_detection: default_on_detection(),
+                lock_command: default_lock_command(),
+                on_empty: OnEmpty::Allow,
+                untitled_windows: UntitledWindows::Ignore,
+                detection_scope: DetectionScope::AnyWindow,
+                match_properties: vec![MatchProperty::Title],
+                backends: vec![TitleBackend::Xlib],
+                legacy_encoding: None,
+                require_browser_running: false,
+                max_consecutive_errors: 0,
+                on_repeated_errors: ErrorAction::Reconnect,
+                clock_jump_threshold_seconds: default_clock_jump_threshold_seconds(),
+                on_clock_jump: ClockJumpAction::OneBreak,
+                max_windows: 0,
+                block_evasion: false,
+                evasion_patterns: Vec::new(),
+                inspect_history: None,
             },
             timeouts: TimeoutConfig {
                 blacklist_timeout_minutes: 10,
@@ -93,11 +922,27 @@ impl Default for Config {
                 grace_retries: 3,
                 hard_lock_minutes: 40,
                 retry_reset_minutes: 20,
+                escalation_multiplier: 1,
+                max_block_minutes: None,
+                break_jitter_minutes: 0,
+                min_break_gap_minutes: 0,
+                daily_limit_minutes: None,
+                weekly_limit_minutes: None,
+                max_snoozes_per_day: 0,
+                detection_cooldown_seconds: 0,
             },
             backgrounds: BackgroundConfig {
                 normal: format!("{}/inappropriate-video-handler/wallpaper/normal.jpg", xdg_config_dir()),
                 blocked: format!("{}/inappropriate-video-handler/wallpaper/blocked.jpg", xdg_config_dir()),
                 bathroom_break: format!("{}/inappropriate-video-handler/wallpaper/bathroom.jpg", xdg_config_dir()),
+                warning: None,
+                block_message: None,
+                block_sequence: Vec::new(),
+                reassert: false,
+                restore_user_wallpaper: false,
+                user_wallpaper: None,
+                countdown: false,
+                blocked_by_label: BTreeMap::new(),
             },
             files: FileConfig {
                 blacklist: format!("{}/inappropriate-video-handler/BlackList.txt", xdg_config_dir()),
@@ -105,7 +950,18 @@ impl Default for Config {
                 state_file: format!("{}/inappropriate-video-handler/state.json", xdg_cache_dir()),
                 log_file: format!("{}/inappropriate-video-handler/ivh.log", xdg_cache_dir()),
                 titles_file: format!("{}/inappropriate-video-handler/window-titles.txt", xdg_cache_dir()),
+                state_backend: StateBackend::Json,
+                study_whitelist: None,
             },
+            filter: FilterConfig::default(),
+            audit: AuditConfig::default(),
+            notifications: NotificationConfig::default(),
+            privacy: PrivacyConfig::default(),
+            security: SecurityConfig::default(),
+            management: ManagementConfig::default(),
+            hooks: BTreeMap::new(),
+            timezone: None,
+            profiles: BTreeMap::new(),
         }
     }
 }
@@ -127,33 +983,342 @@ fn xdg_cache_dir() -> String {
 }
 
 fn expand_tilde(path: String) -> String {
-    if path.starts_with("~/") {
+    if let Some(rest) = path.strip_prefix("~/") {
         if let Ok(home) = std::env::var("HOME") {
-            return format!("{}/{}", home, &path[2..]);
+            return format!("{}/{}", home, rest);
         }
     }
     path
 }
 
+const ENV_PREFIX: &str = "IVH_";
+
+/// Sets the scalar at `segments` inside `root`, creating mappings that are missing
+/// because an unset `Option` is left out of the serialized config. String fields keep
+/// the raw text; other fields parse it as YAML so numbers and booleans keep their
+/// type. An unset field has no type to go by, so `as_string` decides. Returns
+/// whether the field was already set.
+fn set_value_path(root: &mut serde_yaml::Value, segments: &[String], raw: &str, as_string: bool) -> Result<bool> {
+    let unknown = || anyhow!("unknown config key '{}'", segments.join("."));
+    let (leaf, parents) = segments.split_last().ok_or_else(unknown)?;
+    let mut current = root;
+    for segment in parents {
+        if current.is_null() {
+            *current = serde_yaml::Value::Mapping(Default::default());
+        }
+        current = current
+            .as_mapping_mut()
+            .ok_or_else(unknown)?
+            .entry(segment.as_str().into())
+            .or_insert(serde_yaml::Value::Null);
+    }
+    if current.is_null() {
+        *current = serde_yaml::Value::Mapping(Default::default());
+    }
+
+    let mapping = current.as_mapping_mut().ok_or_else(unknown)?;
+    let existing = mapping.get(leaf.as_str());
+    let was_set = existing.is_some_and(|v| !v.is_null());
+    let value = if existing.is_some_and(serde_yaml::Value::is_string) || (!was_set && as_string) {
+        serde_yaml::Value::String(raw.to_string())
+    } else {
+        serde_yaml::from_str(raw)?
+    };
+    mapping.insert(leaf.as_str().into(), value);
+    Ok(was_set)
+}
+
+fn value_at<'a>(root: &'a serde_yaml::Value, segments: &[String]) -> Option<&'a serde_yaml::Value> {
+    segments
+        .iter()
+        .try_fold(root, |current, segment| current.get(segment.as_str()))
+        .filter(|v| !v.is_null())
+}
+
+/// Derives `<stem>-<profile>.<ext>` alongside the base state file.
+fn profile_state_path(base: &str, profile: &str) -> String {
+    let path = Path::new(base);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "state".to_string());
+    let file_name = match path.extension() {
+        Some(ext) => format!("{}-{}.{}", stem, profile, ext.to_string_lossy()),
+        None => format!("{}-{}", stem, profile),
+    };
+    path.with_file_name(file_name).to_string_lossy().into_owned()
+}
+
 impl Config {
+    /// Reads a config file. Relative paths in it are resolved against the directory
+    /// the file is in, not the working directory.
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path)?;
-        let mut config: Config = serde_yaml::from_str(&content)?;
-        config.backgrounds.normal = expand_tilde(config.backgrounds.normal);
-        config.backgrounds.blocked = expand_tilde(config.backgrounds.blocked);
-        config.backgrounds.bathroom_break = expand_tilde(config.backgrounds.bathroom_break);
-        config.files.blacklist = expand_tilde(config.files.blacklist);
-        config.files.whitelist = expand_tilde(config.files.whitelist);
-        config.files.state_file = expand_tilde(config.files.state_file);
-        config.files.log_file = expand_tilde(config.files.log_file);
-        config.files.titles_file = expand_tilde(config.files.titles_file);
+        let mut config = Self::from_yaml(&fs::read_to_string(&path)?)?;
+        config.resolve_relative_paths(&Self::dir_of(path.as_ref()));
+        Ok(config)
+    }
+
+    /// The directory relative paths in the config file at `path` are resolved against.
+    pub fn dir_of(path: &Path) -> PathBuf {
+        std::path::absolute(path)
+            .ok()
+            .and_then(|p| p.parent().map(Path::to_path_buf))
+            .unwrap_or_default()
+    }
+
+    /// [`load`](Self::load) followed by the same profile overlay, environment
+    /// overrides and validation applied at startup.
+    pub fn load_effective<P: AsRef<Path>>(path: P, profile: Option<&str>) -> Result<Self> {
+        let mut config = Self::load(&path)?;
+        if let Some(name) = profile {
+            config.apply_profile(name)?;
+        }
+        config.apply_env_overrides(std::env::vars(), &Self::dir_of(path.as_ref()))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Parses a config from YAML text, expanding `~` in paths like [`load`](Self::load).
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let mut config: Config = serde_yaml::from_str(content)?;
+        config.expand_paths();
         Ok(config)
     }
+
+    /// Every file path in the config, including those of profiles.
+    fn paths_mut(&mut self) -> Vec<&mut String> {
+        let mut paths = vec![
+            &mut self.backgrounds.normal,
+            &mut self.backgrounds.blocked,
+            &mut self.backgrounds.bathroom_break,
+            &mut self.files.blacklist,
+            &mut self.files.whitelist,
+            &mut self.files.state_file,
+            &mut self.files.log_file,
+            &mut self.files.titles_file,
+            &mut self.audit.file,
+            &mut self.monitoring.capture_on_block.dir,
+        ];
+        paths.extend(self.monitoring.xauthority.as_mut());
+        paths.extend(self.monitoring.inspect_history.as_mut());
+        paths.extend(self.backgrounds.warning.as_mut());
+        paths.extend(self.backgrounds.user_wallpaper.as_mut());
+        paths.extend(self.filter.slug_keywords.as_mut());
+        paths.extend(self.files.study_whitelist.as_mut());
+        paths.extend(self.backgrounds.block_sequence.iter_mut().map(|step| &mut step.image));
+        paths.extend(self.backgrounds.blocked_by_label.values_mut());
+        for profile in self.profiles.values_mut() {
+            paths.extend(profile.state_file.as_mut());
+            paths.extend(profile.blacklist.as_mut());
+            paths.extend(profile.whitelist.as_mut());
+        }
+        paths
+    }
+
+    fn expand_paths(&mut self) {
+        for path in self.paths_mut() {
+            *path = expand_tilde(std::mem::take(path));
+        }
+    }
+
+    /// Makes every relative path absolute by joining it onto `dir`. Empty paths are
+    /// left alone.
+    pub fn resolve_relative_paths(&mut self, dir: &Path) {
+        for path in self.paths_mut() {
+            if !path.is_empty() && Path::new(path.as_str()).is_relative() {
+                *path = dir.join(path.as_str()).to_string_lossy().into_owned();
+            }
+        }
+    }
+
+    /// Applies `IVH_<SECTION>__<KEY>=value` overrides, e.g.
+    /// `IVH_TIMEOUTS__BLACKLIST_TIMEOUT_MINUTES=15`. Unrelated variables are ignored;
+    /// an `IVH_` variable naming an unknown key is an error. Overridden paths are
+    /// expanded and resolved against `dir` like those in the file.
+    pub fn apply_env_overrides<I>(&mut self, vars: I, dir: &Path) -> Result<()>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut value = serde_yaml::to_value(&*self)?;
+        let mut applied = Vec::new();
+
+        for (key, raw) in vars {
+            let Some(path) = key.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            let segments: Vec<String> = path.split("__").map(str::to_lowercase).collect();
+            let invalid = |e: anyhow::Error| anyhow!("invalid override {}: {}", key, e);
+            let mut next = value.clone();
+            let was_set = set_value_path(&mut next, &segments, &raw, false).map_err(invalid)?;
+            if !was_set && serde_yaml::from_value::<Config>(next.clone()).is_err() {
+                next = value.clone();
+                set_value_path(&mut next, &segments, &raw, true).map_err(invalid)?;
+            }
+            value = next;
+            let clears = serde_yaml::from_str::<serde_yaml::Value>(&raw).is_ok_and(|v| v.is_null());
+            applied.push((key, segments, clears));
+        }
+
+        if applied.is_empty() {
+            return Ok(());
+        }
+        let config: Config = serde_yaml::from_value(value)?;
+        // A key serde dropped while parsing was not a config field.
+        let parsed = serde_yaml::to_value(&config)?;
+        for (key, segments, clears) in &applied {
+            if !clears && value_at(&parsed, segments).is_none() {
+                return Err(anyhow!("invalid override {}: unknown config key '{}'", key, segments.join(".")));
+            }
+        }
+        *self = config;
+        self.expand_paths();
+        self.resolve_relative_paths(dir);
+        Ok(())
+    }
+
+    /// Rejects values that would make the daemon misbehave at runtime.
+    pub fn validate(&self) -> Result<()> {
+        if self.monitoring.check_frequency_seconds == 0 {
+            return Err(anyhow!("monitoring.check_frequency_seconds must be greater than 0"));
+        }
+        if self.monitoring.whitelist_recheck_seconds == Some(0) {
+            return Err(anyhow!("monitoring.whitelist_recheck_seconds must be greater than 0"));
+        }
+        if self.timeouts.bathroom_break_interval_hours == 0 {
+            return Err(anyhow!("timeouts.bathroom_break_interval_hours must be greater than 0"));
+        }
+        if self.timeouts.min_break_gap_minutes > crate::state::MAX_DURATION_MINUTES {
+            return Err(anyhow!("timeouts.min_break_gap_minutes must be at most {}", crate::state::MAX_DURATION_MINUTES));
+        }
+        if self.timeouts.detection_cooldown_seconds > crate::state::MAX_DURATION_MINUTES * 60 {
+            return Err(anyhow!("timeouts.detection_cooldown_seconds must be at most {}",
+                crate::state::MAX_DURATION_MINUTES * 60));
+        }
+        if self.browser.executable.is_empty() {
+            return Err(anyhow!("browser.executable must not be empty"));
+        }
+        if crate::browser::matches_every_process(&self.browser.process_name) {
+            return Err(anyhow!("browser.process_name '{}' would match (nearly) every process", self.browser.process_name));
+        }
+        if self.filter.min_matches == 0 {
+            return Err(anyhow!("filter.min_matches must be at least 1"));
+        }
+        if self.filter.ignore_whitelist && self.filter.default_action == DefaultAction::Deny {
+            return Err(anyhow!("filter.ignore_whitelist cannot be used with filter.default_action: deny"));
+        }
+        if self.security.run_as_user.as_ref().is_some_and(|user| user.trim().is_empty()) {
+            return Err(anyhow!("security.run_as_user must not be empty"));
+        }
+        if self.filter.external_classifier.as_ref().is_some_and(|c| c.trim().is_empty()) {
+            return Err(anyhow!("filter.external_classifier must not be empty"));
+        }
+        if self.filter.match_slug && self.filter.slug_keywords.is_none() {
+            return Err(anyhow!("filter.match_slug requires filter.slug_keywords"));
+        }
+        if self.filter.policy == FilterPolicy::KeywordWithDomainAllow
+            && self.filter.default_action == DefaultAction::Deny
+        {
+            return Err(anyhow!("filter.policy keyword_with_domain_allow requires filter.default_action: allow"));
+        }
+        if let Some(quiet) = &self.notifications.quiet_hours {
+            crate::notify::QuietHours::parse(&quiet.start, &quiet.end)
+                .map_err(|e| anyhow!("notifications.quiet_hours: {}", e))?;
+        }
+        crate::hooks::validate_hooks(&self.hooks)?;
+        if let Some(limit) = &self.timeouts.daily_limit_minutes {
+            limit.validate()?;
+        }
+        if self.monitoring.inspect_history.is_some() && !cfg!(feature = "history") {
+            return Err(anyhow!("monitoring.inspect_history requires a build with the 'history' feature"));
+        }
+        if self.management.sync_url.is_some() && !cfg!(feature = "management") {
+            return Err(anyhow!("management.sync_url requires a build with the 'management' feature"));
+        }
+        if self.management.sync_interval_seconds == 0 {
+            return Err(anyhow!("management.sync_interval_seconds must be greater than 0"));
+        }
+        if self.files.state_backend == StateBackend::Sqlite && !cfg!(feature = "sqlite-state") {
+            return Err(anyhow!("files.state_backend: sqlite requires a build with the 'sqlite-state' feature"));
+        }
+        if let Some(target) = &self.monitoring.focus_after_kill {
+            crate::focus::FocusTarget::parse(target)?;
+        }
+        if self.monitoring.backends.is_empty() {
+            return Err(anyhow!("monitoring.backends must list at least one backend"));
+        }
+        if self.monitoring.on_detection.is_empty() {
+            return Err(anyhow!("monitoring.on_detection must list at least one action"));
+        }
+        if self.monitoring.on_detection.contains(&DetectionAction::Lock) && self.monitoring.lock_command.is_empty() {
+            return Err(anyhow!("monitoring.on_detection: lock requires a monitoring.lock_command"));
+        }
+        if let Some(label) = &self.monitoring.legacy_encoding {
+            crate::title_encoding::legacy_encoding(label)?;
+        }
+        crate::timezone::Zone::parse(self.timezone.as_deref())?;
+        Ok(())
+    }
+
+    /// Fields are emitted in declaration order and every map is a `BTreeMap`,
+    /// so the output is deterministic and load → save → load is idempotent.
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    /// Writes the configuration as YAML, creating parent directories as needed.
+    /// Comments and anchors from a hand-written file are not preserved.
+    #[allow(dead_code)]
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        if let Some(parent) = path.as_ref().parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.to_yaml()?)?;
+        Ok(())
+    }
+
+    /// Picks the profile to run under. An explicitly requested profile must exist;
+    /// otherwise the login `user` is used only if a profile with that name is defined.
+    pub fn select_profile(&self, requested: Option<&str>, user: Option<&str>) -> Result<Option<String>> {
+        if let Some(name) = requested {
+            if !self.profiles.contains_key(name) {
+                return Err(anyhow!("profile '{}' is not defined in config", name));
+            }
+            return Ok(Some(name.to_string()));
+        }
+        Ok(user
+            .filter(|u| self.profiles.contains_key(*u))
+            .map(str::to_string))
+    }
+
+    /// Overlays the named profile onto the base file paths and limits.
+    pub fn apply_profile(&mut self, name: &str) -> Result<()> {
+        let profile = self
+            .profiles
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("profile '{}' is not defined in config", name))?;
+
+        self.files.state_file = match profile.state_file {
+            Some(path) => expand_tilde(path),
+            None => profile_state_path(&self.files.state_file, name),
+        };
+        if let Some(blacklist) = profile.blacklist {
+            self.files.blacklist = expand_tilde(blacklist);
+        }
+        if let Some(whitelist) = profile.whitelist {
+            self.files.whitelist = expand_tilde(whitelist);
+        }
+        if let Some(timeouts) = profile.timeouts {
+            self.timeouts = timeouts;
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use chrono::TimeZone;
     use std::io::Write;
     use tempfile::NamedTempFile;
 
@@ -165,9 +1330,26 @@ mod tests {
         assert_eq!(config.browser.url, "https://www.youtube.com");
         assert_eq!(config.browser.process_name, "chrome");
         assert_eq!(config.monitoring.check_frequency_seconds, 60);
+        assert_eq!(config.monitoring.title_history_size, 5);
+        assert!(config.monitoring.display.is_none());
+        assert!(!config.monitoring.fullscreen_priority);
+        assert!(config.monitoring.enforce_on_startup);
+        assert!(config.monitoring.focus_after_kill.is_none());
+        assert_eq!(config.monitoring.startup_delay_seconds, 0);
+        assert!(!config.monitoring.detect_pip.enabled);
+        assert_eq!(config.monitoring.detect_pip.max_width, 640);
+        assert_eq!(config.monitoring.detect_pip.max_height, 480);
+        assert!(config.monitoring.ignore_classes.is_empty());
+        assert_eq!(config.monitoring.ack_seconds, 0);
+        assert_eq!(config.monitoring.on_empty, OnEmpty::Allow);
+        assert!(!config.monitoring.require_browser_running);
+        assert!(config.monitoring.inspect_history.is_none());
+        assert!(config.backgrounds.warning.is_none());
         assert_eq!(config.timeouts.blacklist_timeout_minutes, 10);
         assert_eq!(config.timeouts.bathroom_break_minutes, 4);
         assert_eq!(config.timeouts.bathroom_break_interval_hours, 2);
+        assert_eq!(config.timeouts.escalation_multiplier, 1);
+        assert!(config.timeouts.max_block_minutes.is_none());
         assert_eq!(
             config.backgrounds.normal,
             format!("{}/inappropriate-video-handler/wallpaper/normal.jpg", xdg_config_dir())
@@ -185,6 +1367,20 @@ mod tests {
         assert_eq!(config.files.state_file, format!("{}/inappropriate-video-handler/state.json", xdg_cache_dir()));
         assert_eq!(config.files.log_file, format!("{}/inappropriate-video-handler/ivh.log", xdg_cache_dir()));
         assert_eq!(config.files.titles_file, format!("{}/inappropriate-video-handler/window-titles.txt", xdg_cache_dir()));
+        assert_eq!(config.filter.default_action, DefaultAction::Allow);
+        assert_eq!(config.filter.auto_anchor, AutoAnchor::None);
+        assert!(config.audit.enabled);
+        assert_eq!(config.audit.file, format!("{}/inappropriate-video-handler/audit.log", xdg_cache_dir()));
+        assert_eq!(config.audit.max_size_mb, 10);
+        assert_eq!(config.audit.keep, 5);
+        assert!(!config.audit.compress);
+        assert!(!config.notifications.enabled);
+        assert!(config.notifications.quiet_hours.is_none());
+        assert!(config.hooks.is_empty());
+        assert!(config.timezone.is_none());
+        assert_eq!(config.filter.min_matches, 1);
+        assert!(config.filter.case_insensitive);
+        assert!(!config.filter.ignore_whitelist);
     }
 
     #[test]
@@ -218,6 +1414,7 @@ files:
         temp_file.write_all(yaml_content.as_bytes()).unwrap();
 
         let config = Config::load(temp_file.path()).unwrap();
+        let dir = temp_file.path().parent().unwrap();
 
         assert_eq!(config.browser.executable, "chromium");
         assert_eq!(config.browser.url, "https://example.com");
@@ -229,11 +1426,59 @@ files:
         assert_eq!(config.backgrounds.normal, "/test/normal.png");
         assert_eq!(config.backgrounds.blocked, "/test/blocked.png");
         assert_eq!(config.backgrounds.bathroom_break, "/test/break.png");
-        assert_eq!(config.files.blacklist, "test_blacklist.txt");
-        assert_eq!(config.files.whitelist, "test_whitelist.txt");
+        assert_eq!(Path::new(&config.files.blacklist), dir.join("test_blacklist.txt"));
+        assert_eq!(Path::new(&config.files.whitelist), dir.join("test_whitelist.txt"));
         assert_eq!(config.files.state_file, "/test/state.json");
     }
 
+    #[test]
+    fn test_relative_paths_resolve_against_config_dir() {
+        let mut config = Config::default();
+        config.backgrounds.normal = "wallpaper/normal.jpg".to_string();
+        config.backgrounds.blocked = "/usr/share/ivh/blocked.jpg".to_string();
+        config.backgrounds.bathroom_break = String::new();
+        config.files.blacklist = "lists/adult.txt".to_string();
+        config.files.whitelist = "./lists/ok.txt".to_string();
+        config.files.state_file = "/var/lib/ivh/state.json".to_string();
+        config.profiles.insert("bob".to_string(), ProfileConfig {
+            blacklist: Some("lists/bob.txt".to_string()),
+            ..ProfileConfig::default()
+        });
+        config.resolve_relative_paths(Path::new("/etc/ivh"));
+
+        assert_eq!(config.files.blacklist, "/etc/ivh/lists/adult.txt");
+        assert_eq!(config.files.whitelist, "/etc/ivh/./lists/ok.txt");
+        assert_eq!(config.files.state_file, "/var/lib/ivh/state.json");
+        assert_eq!(config.backgrounds.normal, "/etc/ivh/wallpaper/normal.jpg");
+        assert_eq!(config.backgrounds.blocked, "/usr/share/ivh/blocked.jpg");
+        assert_eq!(config.backgrounds.bathroom_break, "");
+        assert_eq!(config.profiles["bob"].blacklist.as_deref(), Some("/etc/ivh/lists/bob.txt"));
+    }
+
+    #[test]
+    fn test_daily_limit_per_weekday() {
+        let yaml = "flat: 90\ndays: {mon: 60, tuesday: 60, Sat: 180, sun: 180}\n";
+        let limits: BTreeMap<String, DailyLimit> = serde_yaml::from_str(yaml).unwrap();
+        let (flat, days) = (&limits["flat"], &limits["days"]);
+
+        let zone = Zone::parse(Some("UTC")).unwrap();
+        let tuesday = Utc.with_ymd_and_hms(2026, 10, 13, 12, 0, 0).unwrap();
+        let saturday = Utc.with_ymd_and_hms(2026, 10, 17, 12, 0, 0).unwrap();
+        let wednesday = Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap();
+
+        assert_eq!(days.minutes_at(zone, tuesday), Some(60));
+        assert_eq!(days.minutes_at(zone, saturday), Some(180));
+        assert_eq!(days.minutes_at(zone, wednesday), None);
+        assert_eq!(flat.minutes_at(zone, tuesday), Some(90));
+        assert_eq!(flat.minutes_at(zone, saturday), Some(90));
+
+        // Late Friday in UTC is already Saturday in Auckland.
+        let auckland = Zone::parse(Some("Pacific/Auckland")).unwrap();
+        let friday_night = Utc.with_ymd_and_hms(2026, 10, 16, 20, 0, 0).unwrap();
+        assert_eq!(days.minutes_at(zone, friday_night), None);
+        assert_eq!(days.minutes_at(auckland, friday_night), Some(180));
+    }
+
     #[test]
     fn test_config_load_invalid_yaml() {
         let invalid_yaml = "invalid: yaml: content: [";
@@ -271,6 +1516,14 @@ browser:
             executable: "test_browser".to_string(),
             url: "https://test.com".to_string(),
             process_name: "test_process".to_string(),
+            max_launches_per_minute: 3,
+            launch_wrapper: Some("firejail".to_string()),
+            launch_wrapper_args: vec!["--private".to_string()],
+            launch_args: vec!["--kiosk".to_string()],
+            auto_relaunch: true,
+            relaunch_delay_seconds: 5,
+            quiet: true,
+            ephemeral_profile: false,
         };
 
         assert_eq!(config.executable, "test_browser");
@@ -282,9 +1535,56 @@ browser:
     fn test_monitoring_config_fields() {
         let config = MonitoringConfig {
             check_frequency_seconds: 120,
+            title_history_size: 3,
+            display: Some(":1".to_string()),
+            xauthority: Some("/home/kid/.Xauthority".to_string()),
+            display_timeout_seconds: 10,
+            diagnostic_display_timeout_seconds: 3,
+            fullscreen_priority: true,
+            enforce_on_startup: false,
+            kill_while_restricted: true,
+            exclude_own_windows: true,
+            kiosk_mode: false,
+            allowed_classes: Vec::new(),
+            kiosk_safe_classes: Vec::new(),
+            focus_after_kill: Some("root".to_string()),
+            startup_delay_seconds: 30,
+            detect_pip: PipConfig {
+                enabled: true,
+                max_width: 500,
+                max_height: 300,
+            },
+            capture_on_block: CaptureConfig {
+                enabled: true,
+                tool: CaptureTool::Scrot,
+                dir: "/var/lib/ivh/captures".to_string(),
+            },
+            ignore_classes: vec!["Thunar".to_string()],
+            ack_seconds: 45,
+            whitelist_recheck_seconds: None,
+            title_debounce_scans: 0,
+            on_detection: vec![DetectionAction::Kill, DetectionAction::Lock, DetectionAction::Notify],
+            lock_command: vec!["xdg-screensaver".to_string(), "lock".to_string()],
+            on_empty: OnEmpty::Block,
+            untitled_windows: UntitledWindows::Suspicious,
+            detection_scope: DetectionScope::AnyWindow,
+            match_properties: vec![MatchProperty::Title],
+            backends: vec![TitleBackend::Xlib],
+            legacy_encoding: Some("latin1".to_string()),
+            require_browser_running: true,
+            max_consecutive_errors: 5,
+            on_repeated_errors: ErrorAction::Exit,
+            clock_jump_threshold_seconds: 600,
+            on_clock_jump: ClockJumpAction::Reschedule,
+            max_windows: 200,
+            block_evasion: true,
+            evasion_patterns: vec![r"\bvpn\s+extension\b".to_string()],
+            inspect_history: Some("/home/kid/.config/chromium/Default/History".to_string()),
         };
 
         assert_eq!(config.check_frequency_seconds, 120);
+        assert_eq!(config.title_history_size, 3);
+        assert_eq!(config.display.as_deref(), Some(":1"));
     }
 
     #[test]
@@ -296,6 +1596,14 @@ browser:
             grace_retries: 3,
             hard_lock_minutes: 40,
             retry_reset_minutes: 20,
+            escalation_multiplier: 1,
+            max_block_minutes: None,
+            break_jitter_minutes: 0,
+            min_break_gap_minutes: 0,
+            daily_limit_minutes: None,
+            weekly_limit_minutes: None,
+            max_snoozes_per_day: 0,
+                detection_cooldown_seconds: 0,
         };
 
         assert_eq!(config.blacklist_timeout_minutes, 20);
@@ -309,6 +1617,14 @@ browser:
             normal: "/path/normal.jpg".to_string(),
             blocked: "/path/blocked.jpg".to_string(),
             bathroom_break: "/path/break.jpg".to_string(),
+            warning: None,
+            block_message: None,
+            block_sequence: Vec::new(),
+            reassert: false,
+            restore_user_wallpaper: true,
+            user_wallpaper: Some("/home/kid/Pictures/beach.jpg".to_string()),
+            countdown: false,
+            blocked_by_label: BTreeMap::new(),
         };
 
         assert_eq!(config.normal, "/path/normal.jpg");
@@ -324,6 +1640,8 @@ browser:
             state_file: "/test/state.json".to_string(),
             log_file: "/test/ivh.log".to_string(),
             titles_file: "/test/window-titles.txt".to_string(),
+            state_backend: StateBackend::Json,
+            study_whitelist: None,
         };
 
         assert_eq!(config.blacklist, "test_blacklist.txt");
@@ -332,4 +1650,360 @@ browser:
         assert_eq!(config.log_file, "/test/ivh.log");
         assert_eq!(config.titles_file, "/test/window-titles.txt");
     }
+
+    fn env(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_env_overrides_visible_in_printed_config() {
+        let mut config = Config::default();
+        config
+            .apply_env_overrides(
+                env(&[
+                    ("IVH_TIMEOUTS__BLACKLIST_TIMEOUT_MINUTES", "42"),
+                    ("IVH_BROWSER__EXECUTABLE", "firefox"),
+                    ("PATH", "/usr/bin"),
+                ]),
+                Path::new("/etc/ivh"),
+            )
+            .unwrap();
+
+        let printed = config.to_yaml().unwrap();
+        assert!(printed.contains("blacklist_timeout_minutes: 42"));
+        assert!(printed.contains("executable: firefox"));
+
+        let reparsed: Config = serde_yaml::from_str(&printed).unwrap();
+        assert_eq!(reparsed.timeouts.blacklist_timeout_minutes, 42);
+    }
+
+    #[test]
+    fn test_save_is_deterministic_and_round_trips() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let first = dir.path().join("first.yaml");
+        let second = dir.path().join("nested").join("second.yaml");
+
+        let mut config = config_with_profiles();
+        config.hooks.insert("unblock".to_string(), "paplay ding.wav".to_string());
+        config.hooks.insert("block".to_string(), "logger {title}".to_string());
+        config.timezone = Some("Europe/Berlin".to_string());
+
+        config.save(&first).unwrap();
+        let reloaded = Config::load(&first).unwrap();
+        reloaded.save(&second).unwrap();
+
+        assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+        assert_eq!(reloaded, config);
+        assert_eq!(Config::load(&second).unwrap(), config);
+
+        let yaml = fs::read_to_string(&first).unwrap();
+        let browser = yaml.find("browser:").unwrap();
+        let monitoring = yaml.find("monitoring:").unwrap();
+        let block = yaml.find("block:").unwrap();
+        let unblock = yaml.find("unblock:").unwrap();
+        assert!(browser < monitoring);
+        assert!(block < unblock);
+    }
+
+    #[test]
+    fn test_env_override_string_field_keeps_raw_text() {
+        let mut config = Config::default();
+        config
+            .apply_env_overrides(env(&[("IVH_BROWSER__PROCESS_NAME", "true")]), Path::new("/etc/ivh"))
+            .unwrap();
+        assert_eq!(config.browser.process_name, "true");
+    }
+
+    #[test]
+    fn test_env_override_optional_field() {
+        let mut config = Config::default();
+        config
+            .apply_env_overrides(env(&[("IVH_MONITORING__DISPLAY", ":1")]), Path::new("/etc/ivh"))
+            .unwrap();
+        assert_eq!(config.monitoring.display.as_deref(), Some(":1"));
+    }
+
+    #[test]
+    fn test_env_override_sets_unset_option() {
+        let mut config = Config::default();
+        assert!(config.privacy.salt.is_none());
+        config
+            .apply_env_overrides(env(&[("IVH_PRIVACY__SALT", "1234")]), Path::new("/etc/ivh"))
+            .unwrap();
+        assert_eq!(config.privacy.salt.as_deref(), Some("1234"));
+
+        let result = config.apply_env_overrides(env(&[("IVH_PRIVACY__NOPE", "1")]), Path::new("/etc/ivh"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_env_override_path_resolves_against_config_dir() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        Config::default().save(&config_path).unwrap();
+
+        let mut config = Config::load(&config_path).unwrap();
+        config
+            .apply_env_overrides(env(&[("IVH_FILES__BLACKLIST", "lists/bl.txt")]), &Config::dir_of(&config_path))
+            .unwrap();
+        assert_eq!(config.files.blacklist, dir.path().join("lists/bl.txt").to_string_lossy());
+    }
+
+    #[test]
+    fn test_env_override_unknown_key() {
+        let mut config = Config::default();
+        let result = config.apply_env_overrides(env(&[("IVH_TIMEOUTS__NOPE", "1")]), Path::new("/etc/ivh"));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_env_override_wrong_type() {
+        let mut config = Config::default();
+        let result = config.apply_env_overrides(
+            env(&[("IVH_MONITORING__CHECK_FREQUENCY_SECONDS", "soon")]),
+            Path::new("/etc/ivh"),
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_filter_default_action_from_env() {
+        let mut config = Config::default();
+        config
+            .apply_env_overrides(env(&[("IVH_FILTER__DEFAULT_ACTION", "deny")]), Path::new("/etc/ivh"))
+            .unwrap();
+        assert_eq!(config.filter.default_action, DefaultAction::Deny);
+    }
+
+    #[test]
+    fn test_blocked_background_follows_pattern_label() {
+        let mut blacklist = NamedTempFile::new().unwrap();
+        blacklist.write_all(b".*poker.*  #label: gambling\n.*porn.*\n").unwrap();
+        let whitelist = NamedTempFile::new().unwrap();
+        let filter = crate::filter::Filter::new(blacklist.path(), whitelist.path()).unwrap();
+        let mut backgrounds = Config::default().backgrounds;
+        backgrounds.blocked_by_label.insert("gambling".to_string(), "/bg/gambling.jpg".to_string());
+
+        let background_for = |title: &str| match filter.evaluate(title) {
+            crate::filter::Verdict::Blocked(blocked) => backgrounds.blocked_for(blocked.reported()).to_string(),
+            verdict => panic!("'{}' was not blocked: {:?}", title, verdict),
+        };
+        assert_eq!(background_for("Texas poker night"), "/bg/gambling.jpg");
+        assert_eq!(background_for("free porn"), backgrounds.blocked);
+        assert!(backgrounds.managed_images().contains(&"/bg/gambling.jpg"));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::default();
+        config.management.sync_interval_seconds = 0;
+        assert!(config.validate().is_err());
+        config.management.sync_interval_seconds = 60;
+        config.management.sync_url = Some("https://admin.example/sync".to_string());
+        assert_eq!(config.validate().is_ok(), cfg!(feature = "management"));
+
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+
+        config.browser.process_name = String::new();
+        assert!(config.validate().is_err());
+        config.browser.process_name = ".*".to_string();
+        assert!(config.validate().is_err());
+        config.browser.process_name = "chrome".to_string();
+
+        config.monitoring.check_frequency_seconds = 0;
+        assert!(config.validate().is_err());
+        config.monitoring.check_frequency_seconds = 10;
+        config.monitoring.whitelist_recheck_seconds = Some(0);
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.timeouts.min_break_gap_minutes = u64::MAX;
+        assert!(config.validate().is_err());
+        config.timeouts.min_break_gap_minutes = crate::state::MAX_DURATION_MINUTES;
+        assert!(config.validate().is_ok());
+        config.timeouts.detection_cooldown_seconds = u64::MAX;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.notifications.quiet_hours = Some(QuietHoursConfig {
+            start: "22:00".to_string(),
+            end: "late".to_string(),
+        });
+        assert!(config.validate().is_err());
+
+        let mut config = Config {
+            timezone: Some("Europe/Nowhere".to_string()),
+            ..Config::default()
+        };
+        assert!(config.validate().is_err());
+        config.timezone = Some("Europe/Berlin".to_string());
+        assert!(config.validate().is_ok());
+
+        config.timeouts.daily_limit_minutes =
+            Some(DailyLimit::PerWeekday(BTreeMap::from([("someday".to_string(), 10)])));
+        assert!(config.validate().is_err());
+        config.timeouts.daily_limit_minutes = None;
+
+        config.filter.policy = FilterPolicy::KeywordWithDomainAllow;
+        assert!(config.validate().is_ok());
+        config.filter.default_action = DefaultAction::Deny;
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.filter.match_slug = true;
+        assert!(config.validate().is_err());
+        config.filter.slug_keywords = Some("/tmp/slug.txt".to_string());
+        assert!(config.validate().is_ok());
+        config.filter.external_classifier = Some("  ".to_string());
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.monitoring.legacy_encoding = Some("gbk".to_string());
+        assert!(config.validate().is_ok());
+        config.monitoring.legacy_encoding = Some("klingon".to_string());
+        assert!(config.validate().is_err());
+
+        let mut config = Config::default();
+        config.monitoring.on_detection = vec![DetectionAction::Kill, DetectionAction::Lock];
+        assert!(config.validate().is_ok());
+        config.monitoring.lock_command.clear();
+        assert!(config.validate().is_err());
+        config.monitoring.on_detection.clear();
+        assert!(config.validate().is_err());
+    }
+
+    fn config_with_profiles() -> Config {
+        let mut config = Config::default();
+        config.files.state_file = "/test/state.json".to_string();
+        config.profiles.insert("alice".to_string(), ProfileConfig::default());
+        config.profiles.insert(
+            "bob".to_string(),
+            ProfileConfig {
+                state_file: Some("/test/bob.json".to_string()),
+                blacklist: Some("/test/bob-blacklist.txt".to_string()),
+                whitelist: None,
+                timeouts: Some(TimeoutConfig {
+                    blacklist_timeout_minutes: 30,
+                    bathroom_break_minutes: 5,
+                    bathroom_break_interval_hours: 1,
+                    grace_retries: 0,
+                    hard_lock_minutes: 60,
+                    retry_reset_minutes: 20,
+                    escalation_multiplier: 1,
+                    max_block_minutes: None,
+                    break_jitter_minutes: 0,
+                    min_break_gap_minutes: 0,
+                    daily_limit_minutes: None,
+                    weekly_limit_minutes: None,
+                    max_snoozes_per_day: 0,
+                detection_cooldown_seconds: 0,
+                }),
+            },
+        );
+        config
+    }
+
+    #[test]
+    fn test_select_profile_explicit() {
+        let config = config_with_profiles();
+        assert_eq!(config.select_profile(Some("bob"), Some("alice")).unwrap(), Some("bob".to_string()));
+        assert!(config.select_profile(Some("carol"), None).is_err());
+    }
+
+    #[test]
+    fn test_select_profile_from_user() {
+        let config = config_with_profiles();
+        assert_eq!(config.select_profile(None, Some("alice")).unwrap(), Some("alice".to_string()));
+        assert_eq!(config.select_profile(None, Some("carol")).unwrap(), None);
+        assert_eq!(config.select_profile(None, None).unwrap(), None);
+    }
+
+    #[test]
+    fn test_apply_profile_derives_state_file() {
+        let mut config = config_with_profiles();
+        config.apply_profile("alice").unwrap();
+
+        assert_eq!(config.files.state_file, "/test/state-alice.json");
+        assert_eq!(config.timeouts.blacklist_timeout_minutes, 10);
+    }
+
+    #[test]
+    fn test_apply_profile_overrides() {
+        let mut config = config_with_profiles();
+        let whitelist = config.files.whitelist.clone();
+        config.apply_profile("bob").unwrap();
+
+        assert_eq!(config.files.state_file, "/test/bob.json");
+        assert_eq!(config.files.blacklist, "/test/bob-blacklist.txt");
+        assert_eq!(config.files.whitelist, whitelist);
+        assert_eq!(config.timeouts.blacklist_timeout_minutes, 30);
+        assert_eq!(config.timeouts.grace_retries, 0);
+    }
+
+    #[test]
+    fn test_block_sequence_from_yaml() {
+        let yaml = r#"
+browser: {executable: chromium, url: "https://example.com", process_name: chromium}
+monitoring: {check_frequency_seconds: 30}
+timeouts: {blacklist_timeout_minutes: 15, bathroom_break_minutes: 5, bathroom_break_interval_hours: 2}
+backgrounds:
+  normal: "/test/normal.png"
+  blocked: "/test/blocked.png"
+  bathroom_break: "/test/break.png"
+  block_sequence:
+    - {image: "/test/caught.png", hold_seconds: 2}
+    - {image: "~/flash.png", hold_seconds: 1}
+files: {blacklist: b.txt, whitelist: w.txt, state_file: /test/state.json}
+"#;
+        let config = Config::from_yaml(yaml).unwrap();
+        let home = std::env::var("HOME").unwrap();
+        assert_eq!(config.backgrounds.block_sequence, vec![
+            SequenceStep { image: "/test/caught.png".to_string(), hold_seconds: 2 },
+            SequenceStep { image: format!("{}/flash.png", home), hold_seconds: 1 },
+        ]);
+
+        assert!(Config::from_yaml(&yaml.replace(", hold_seconds: 1", "")).is_err());
+        assert!(Config::default().backgrounds.block_sequence.is_empty());
+    }
+
+    #[test]
+    fn test_load_profiles_from_yaml() {
+        let yaml_content = r#"
+browser:
+  executable: "chromium"
+  url: "https://example.com"
+  process_name: "chromium"
+monitoring:
+  check_frequency_seconds: 30
+timeouts:
+  blacklist_timeout_minutes: 15
+  bathroom_break_minutes: 5
+  bathroom_break_interval_hours: 2
+backgrounds:
+  normal: "/test/normal.png"
+  blocked: "/test/blocked.png"
+  bathroom_break: "/test/break.png"
+files:
+  blacklist: "test_blacklist.txt"
+  whitelist: "test_whitelist.txt"
+  state_file: "/test/state.json"
+profiles:
+  kid:
+    whitelist: "/test/kid-whitelist.txt"
+"#;
+
+        let mut temp_file = NamedTempFile::new().unwrap();
+        temp_file.write_all(yaml_content.as_bytes()).unwrap();
+
+        let mut config = Config::load(temp_file.path()).unwrap();
+        assert_eq!(config.profiles.len(), 1);
+
+        config.apply_profile("kid").unwrap();
+        assert_eq!(config.files.whitelist, "/test/kid-whitelist.txt");
+        assert_eq!(config.files.state_file, "/test/state-kid.json");
+    }
 }
@@ -0,0 +1,291 @@
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+use crate::state::AppState;
+
+/// Counts consecutive failures to read windows for `monitoring.max_consecutive_errors`.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorCounter {
+    consecutive: u32,
+    max: u32,
+}
+
+impl ErrorCounter {
+    /// `max` of 0 never triggers.
+    pub fn new(max: u32) -> Self {
+        ErrorCounter { consecutive: 0, max }
+    }
+
+    /// Records a failure. Returns `true` once `max` failures in a row have been seen,
+    /// and starts counting again from zero.
+    pub fn record_failure(&mut self) -> bool {
+        self.consecutive += 1;
+        if self.max > 0 && self.consecutive >= self.max {
+            self.consecutive = 0;
+            return true;
+        }
+        false
+    }
+
+    pub fn record_success(&mut self) {
+        self.consecutive = 0;
+    }
+
+    pub fn consecutive(&self) -> u32 {
+        self.consecutive
+    }
+}
+
+/// Ticks [`TickTimings`] averages over.
+pub const TICK_SAMPLES: usize = 20;
+
+/// A tick is slow when its rolling average exceeds this fraction of the check interval.
+const SLOW_TICK_FRACTION: f64 = 0.25;
+
+/// Rolling average and maximum of how long the daemon's ticks take.
+#[derive(Debug, Clone)]
+pub struct TickTimings {
+    samples: VecDeque<Duration>,
+    capacity: usize,
+}
+
+impl TickTimings {
+    pub fn new(capacity: usize) -> Self {
+        TickTimings { samples: VecDeque::with_capacity(capacity), capacity: capacity.max(1) }
+    }
+
+    /// Adds a tick, dropping the oldest once `capacity` are held.
+    pub fn record(&mut self, elapsed: Duration) {
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(elapsed);
+    }
+
+    pub fn average(&self) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        self.samples.iter().sum::<Duration>() / self.samples.len() as u32
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.iter().copied().max().unwrap_or_default()
+    }
+
+    /// Whether the average takes up more than a quarter of `interval`.
+    pub fn is_slow(&self, interval: Duration) -> bool {
+        self.average() > interval.mul_f64(SLOW_TICK_FRACTION)
+    }
+}
+
+/// How long to sleep before the next check: `monitoring.whitelist_recheck_seconds`
+/// after a check that saw a whitelisted title, if that is sooner, else `interval`.
+pub fn next_check_interval(interval: Duration, whitelist_recheck: Option<Duration>, saw_whitelisted: bool) -> Duration {
+    match whitelist_recheck {
+        Some(recheck) if saw_whitelisted => recheck.min(interval),
+        _ => interval,
+    }
+}
+
+/// Per-title counters for `monitoring.title_debounce_scans`: how many consecutive
+/// scans each title matched in, and how many consecutive scans matched at all. A
+/// title missing from a scan is forgotten, so a single transient match never blocks,
+/// while flicker through different bad titles blocks as soon as matches have been
+/// seen in `required` scans in a row.
+#[derive(Debug, Clone, Default)]
+pub struct TitleDebounce {
+    required: u32,
+    /// Titles that matched in the previous scan, each with its run of scans.
+    streaks: HashMap<String, u32>,
+    /// Scans in a row in which any title matched.
+    any_streak: u32,
+}
+
+impl TitleDebounce {
+    pub fn new(required: u32) -> Self {
+        TitleDebounce { required, streaks: HashMap::new(), any_streak: 0 }
+    }
+
+    /// Records one scan with every title that matched in it. Returns whether a title
+    /// has matched in `required` consecutive scans, or some title has in each of them.
+    pub fn observe(&mut self, matched: &[String]) -> bool {
+        if self.required <= 1 {
+            return true;
+        }
+        let previous = std::mem::take(&mut self.streaks);
+        for title in matched {
+            let count = previous.get(title).copied().unwrap_or(0) + 1;
+            self.streaks.insert(title.clone(), count);
+        }
+        self.any_streak = if matched.is_empty() { 0 } else { self.any_streak + 1 };
+        self.any_streak >= self.required || self.streaks.values().any(|&count| count >= self.required)
+    }
+
+    /// Consecutive scans `title` has matched in, up to the last observed one.
+    pub fn streak(&self, title: &str) -> u32 {
+        self.streaks.get(title).copied().unwrap_or(0)
+    }
+}
+
+/// `avg scan 18ms, max 240ms`.
+/// What `allow-once` did in one scan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllowOnce {
+    /// The token was used up by this detection, whose title is now exempt.
+    Used,
+    /// The detection is the exempt title, still on screen.
+    Exempt,
+    /// The exempt title is gone, so it blocks again from the next scan.
+    Ended,
+}
+
+/// Applies `allow-once` to a scan whose windows show `on_screen` and which
+/// detected `detection`. The token lets the first detection through and exempts
+/// its title until a scan neither shows nor detects it.
+pub fn apply_allow_once(state: &mut AppState, on_screen: &[String], detection: Option<&str>) -> Option<AllowOnce> {
+    if let Some(title) = detection {
+        if state.allowed_once_title.as_deref() == Some(title) {
+            return Some(AllowOnce::Exempt);
+        }
+        if state.take_allow_once() {
+            state.allowed_once_title = Some(title.to_string());
+            return Some(AllowOnce::Used);
+        }
+    }
+    state
+        .allowed_once_title
+        .take_if(|title| !on_screen.contains(title))
+        .map(|_| AllowOnce::Ended)
+}
+
+pub fn format_scan_timings(avg_ms: u64, max_ms: u64) -> String {
+    format!("avg scan {}ms, max {}ms", avg_ms, max_ms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_check_interval_after_whitelisted_title() {
+        let secs = Duration::from_secs;
+        assert_eq!(next_check_interval(secs(10), Some(secs(3)), true), secs(3));
+        assert_eq!(next_check_interval(secs(10), Some(secs(3)), false), secs(10));
+        assert_eq!(next_check_interval(secs(10), None, true), secs(10));
+        assert_eq!(next_check_interval(secs(2), Some(secs(3)), true), secs(2));
+    }
+
+    #[test]
+    fn test_title_debounce_counts_each_title() {
+        let titles = |names: &[&str]| names.iter().map(|name| name.to_string()).collect::<Vec<_>>();
+        let mut debounce = TitleDebounce::new(3);
+        assert!(!debounce.observe(&titles(&["bad A", "bad B"])));
+        assert!(!debounce.observe(&titles(&["bad B"])));
+        assert_eq!(debounce.streak("bad B"), 2);
+        assert_eq!(debounce.streak("bad A"), 0, "a title missing from a scan is forgotten");
+        assert!(debounce.observe(&titles(&["bad B"])));
+
+        // A single transient match, or matches broken by a clean scan, never block.
+        let mut debounce = TitleDebounce::new(3);
+        assert!(!debounce.observe(&titles(&["flicker"])));
+        assert!(!debounce.observe(&[]));
+        assert!(!debounce.observe(&titles(&["flicker"])));
+        assert!(!debounce.observe(&titles(&["flicker"])));
+        assert!(debounce.observe(&titles(&["flicker"])));
+
+        let mut off = TitleDebounce::new(1);
+        assert!(off.observe(&titles(&["bad A"])));
+    }
+
+    #[test]
+    fn test_title_debounce_blocks_flicker_between_titles() {
+        let mut debounce = TitleDebounce::new(3);
+        assert!(!debounce.observe(&["bad A".to_string()]));
+        assert!(!debounce.observe(&["bad B".to_string()]));
+        assert_eq!(debounce.streak("bad B"), 1);
+        assert!(debounce.observe(&["bad C".to_string()]), "three bad scans in a row block");
+    }
+
+    #[test]
+    fn test_allow_once_exempts_the_title_until_it_leaves_the_screen() {
+        use crate::title_history::TitleHistory;
+        use crate::window_info::WindowInfo;
+
+        let window = |title: &str| WindowInfo { id: 1, title: title.to_string(), ..WindowInfo::default() };
+        let mut history = TitleHistory::new(1);
+        let mut state = AppState::default_with_next_break();
+        state.allow_next_detection = true;
+        let mut tick = |shown: &str, detected: Option<&str>| {
+            history.update(&[window(shown)]);
+            apply_allow_once(&mut state, &history.titles(), detected)
+        };
+
+        assert_eq!(tick("bad video", Some("bad video")), Some(AllowOnce::Used));
+        assert_eq!(tick("bad video", Some("bad video")), Some(AllowOnce::Exempt), "the next scan still exempts it");
+        assert_eq!(tick("bad video", Some("bad video")), Some(AllowOnce::Exempt));
+        assert_eq!(tick("other bad video", Some("other bad video")), Some(AllowOnce::Ended), "a new title blocks");
+        assert_eq!(tick("bad video", Some("bad video")), None, "once gone, the title blocks again");
+    }
+
+    #[test]
+    fn test_allow_once_without_a_token_lets_nothing_through() {
+        let mut state = AppState::default_with_next_break();
+        let shown = vec!["bad video".to_string()];
+        assert_eq!(apply_allow_once(&mut state, &shown, Some("bad video")), None);
+        assert_eq!(apply_allow_once(&mut state, &shown, None), None);
+    }
+
+    #[test]
+    fn test_tick_timings_rolling_average_and_max() {
+        let ms = Duration::from_millis;
+        let mut timings = TickTimings::new(3);
+        assert_eq!(timings.average(), Duration::ZERO);
+        assert_eq!(timings.max(), Duration::ZERO);
+
+        for elapsed in [10, 20, 240] {
+            timings.record(ms(elapsed));
+        }
+        assert_eq!(timings.average(), ms(90));
+        assert_eq!(timings.max(), ms(240));
+
+        // The oldest samples fall out of the window.
+        timings.record(ms(30));
+        timings.record(ms(30));
+        assert_eq!(timings.average(), ms(100));
+        timings.record(ms(30));
+        assert_eq!(timings.average(), ms(30));
+        assert_eq!(timings.max(), ms(30));
+
+        assert!(!timings.is_slow(Duration::from_secs(1)));
+        assert!(timings.is_slow(ms(100)));
+        assert_eq!(format_scan_timings(18, 240), "avg scan 18ms, max 240ms");
+    }
+
+    #[test]
+    fn test_error_counter_triggers_at_threshold() {
+        let mut counter = ErrorCounter::new(3);
+        assert!(!counter.record_failure());
+        assert!(!counter.record_failure());
+        assert!(counter.record_failure());
+        assert_eq!(counter.consecutive(), 0);
+
+        // A success in between starts the count over.
+        assert!(!counter.record_failure());
+        assert!(!counter.record_failure());
+        counter.record_success();
+        assert_eq!(counter.consecutive(), 0);
+        assert!(!counter.record_failure());
+        assert!(!counter.record_failure());
+        assert!(counter.record_failure());
+    }
+
+    #[test]
+    fn test_error_counter_disabled() {
+        let mut counter = ErrorCounter::new(0);
+        for _ in 0..100 {
+            assert!(!counter.record_failure());
+        }
+        assert_eq!(counter.consecutive(), 100);
+    }
+}
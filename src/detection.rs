@@ -0,0 +1,295 @@
+use log::debug;
+
+use crate::classifier::{ExternalClassifier, CLASSIFIER_MARKER};
+use crate::config::MatchProperty;
+use crate::filter::{BlockedBy, Filter, Verdict, NOT_WHITELISTED};
+use crate::privacy::TitleRedactor;
+use crate::title_history::TitleHistory;
+use crate::window_info::WindowInfo;
+
+/// A title the filter blocked, together with the window it came from when known.
+/// Detections that do not come from a live window (history, a resolved
+/// acknowledgement, `on_empty: block`) have no window identity.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DetectionRecord {
+    pub title: String,
+    /// The blacklist pattern as written (or a marker); redaction matches with it.
+    pub pattern: String,
+    /// The label of the pattern's line, reported instead of the pattern.
+    pub label: Option<String>,
+    /// X11 window id.
+    pub window_id: Option<u64>,
+    /// The class part of the window's `WM_CLASS`.
+    pub class: Option<String>,
+}
+
+impl DetectionRecord {
+    pub fn new(title: &str, pattern: &str, window: Option<&WindowInfo>) -> Self {
+        DetectionRecord {
+            title: title.to_string(),
+            pattern: pattern.to_string(),
+            label: None,
+            window_id: window.map(|w| w.id),
+            class: window.and_then(|w| w.class.clone()),
+        }
+    }
+
+    /// A title blocked by `blocked`, keeping its pattern and label apart.
+    pub fn blocked(title: &str, blocked: &BlockedBy, window: Option<&WindowInfo>) -> Self {
+        DetectionRecord { label: blocked.label.clone(), ..Self::new(title, &blocked.pattern, window) }
+    }
+
+    /// The label if the pattern's line has one, otherwise the pattern.
+    pub fn reported(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.pattern)
+    }
+
+    /// `title='…' pattern='…'`, followed by `window=0x… class='…'` for what is known.
+    /// The title is passed through `redactor` first.
+    pub fn audit_detail(&self, redactor: &TitleRedactor) -> String {
+        let title = redactor.redact(&self.title, Some(&self.pattern));
+        let mut detail = format!("title='{}' pattern='{}'", title, self.reported());
+        if let Some(id) = self.window_id {
+            detail.push_str(&format!(" window=0x{:x}", id));
+        }
+        if let Some(class) = &self.class {
+            detail.push_str(&format!(" class='{}'", class));
+        }
+        detail
+    }
+}
+
+/// The remembered titles of the windows in `windows`, each paired with its window.
+/// Windows left out of `windows` (e.g. by `monitoring.detection_scope`) are skipped
+/// even though their titles are still remembered.
+fn entries_in<'a>(history: &TitleHistory, windows: &'a [WindowInfo]) -> Vec<(&'a WindowInfo, String)> {
+    history.entries().into_iter()
+        .filter_map(|(id, title)| windows.iter().find(|w| w.id == id).map(|window| (window, title)))
+        .collect()
+}
+
+/// Evaluates the remembered titles of `windows` in order and returns the first one
+/// that is blocked, attributed to its window.
+pub fn find_detection(filter: &Filter, history: &TitleHistory, windows: &[WindowInfo]) -> Option<DetectionRecord> {
+    entries_in(history, windows).into_iter().find_map(|(window, title)| match filter.evaluate(&title) {
+        Verdict::Blocked(blocked) => Some(DetectionRecord::blocked(&title, &blocked, Some(window))),
+        _ => None,
+    })
+}
+
+/// Every distinct remembered title of `windows` that the filter blocks, in order.
+pub fn blocked_titles(filter: &Filter, history: &TitleHistory, windows: &[WindowInfo]) -> Vec<String> {
+    let mut titles: Vec<String> = Vec::new();
+    for (_, title) in entries_in(history, windows) {
+        if matches!(filter.evaluate(&title), Verdict::Blocked(_)) && !titles.contains(&title) {
+            titles.push(title);
+        }
+    }
+    titles
+}
+
+/// Checks the window properties other than the title listed in `properties`
+/// (`monitoring.match_properties`) against the blacklist. The first blocked value
+/// is reported with its window's title. A deny-mode "not whitelisted" verdict does
+/// not count: property values are never on an allow-list.
+pub fn find_property_detection(filter: &Filter, windows: &[WindowInfo], properties: &[MatchProperty]) -> Option<DetectionRecord> {
+    windows.iter().find_map(|window| {
+        properties.iter().find_map(|property| {
+            let value = match property {
+                MatchProperty::Title => None,
+                MatchProperty::IconName => window.icon_name.as_deref(),
+                MatchProperty::Role => window.role.as_deref(),
+                MatchProperty::Class => window.class.as_deref(),
+            }?;
+            match filter.evaluate(value) {
+                Verdict::Blocked(blocked) if blocked.pattern != NOT_WHITELISTED => {
+                    debug!("Window 0x{:x} {:?} '{}' matched '{}'", window.id, property, value, blocked);
+                    Some(DetectionRecord::blocked(&window.title, &blocked, Some(window)))
+                }
+                _ => None,
+            }
+        })
+    })
+}
+
+/// Like [`find_detection`], but for titles the filter allowed outright (not
+/// whitelisted ones): the first that `classifier` wants blocked, reported as
+/// [`CLASSIFIER_MARKER`].
+pub fn find_classified(
+    classifier: &ExternalClassifier,
+    filter: &Filter,
+    history: &TitleHistory,
+    windows: &[WindowInfo],
+) -> Option<DetectionRecord> {
+    entries_in(history, windows).into_iter().find_map(|(window, title)| {
+        if filter.evaluate(&title) != Verdict::Allowed || !classifier.is_blocked(&title) {
+            return None;
+        }
+        Some(DetectionRecord::new(&title, CLASSIFIER_MARKER, Some(window)))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::testing::RecordingRunner;
+    use crate::config::{DetectionScope, PrivacyConfig, TitleLogging};
+    use crate::window_info::keep_in_scope;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn window(id: u64, title: &str, class: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            pid: Some(42),
+            title: title.to_string(),
+            class: Some(class.to_string()),
+            ..WindowInfo::default()
+        }
+    }
+
+    fn porn_filter() -> Filter {
+        let mut blacklist = NamedTempFile::new().unwrap();
+        blacklist.write_all(b".*porn.*\n").unwrap();
+        let whitelist = NamedTempFile::new().unwrap();
+        Filter::new(blacklist.path(), whitelist.path()).unwrap()
+    }
+
+    #[test]
+    fn test_window_role_is_matched_when_listed() {
+        let windows = vec![
+            window(1, "Cooking tutorial", "firefox"),
+            WindowInfo { role: Some("porn-player".to_string()), ..window(2, "Untitled", "Google-chrome") },
+        ];
+
+        assert_eq!(find_property_detection(&porn_filter(), &windows, &[MatchProperty::Title]), None);
+        let record = find_property_detection(&porn_filter(), &windows, &[MatchProperty::Title, MatchProperty::Role]).unwrap();
+        assert_eq!((record.title.as_str(), record.pattern.as_str(), record.window_id), ("Untitled", ".*porn.*", Some(2)));
+    }
+
+    #[test]
+    fn test_detection_from_window_carries_id_and_class() {
+        let windows = vec![
+            window(0x1a00003, "Cooking tutorial", "firefox"),
+            window(0x2c00007, "free porn videos", "Google-chrome"),
+        ];
+        let mut history = TitleHistory::new(5);
+        history.update(&windows);
+
+        let record = find_detection(&porn_filter(), &history, &windows).unwrap();
+        assert_eq!(record, DetectionRecord {
+            title: "free porn videos".to_string(),
+            pattern: ".*porn.*".to_string(),
+            label: None,
+            window_id: Some(0x2c00007),
+            class: Some("Google-chrome".to_string()),
+        });
+        assert_eq!(
+            record.audit_detail(&TitleRedactor::default()),
+            "title='free porn videos' pattern='.*porn.*' window=0x2c00007 class='Google-chrome'"
+        );
+    }
+
+    #[test]
+    fn test_detection_of_earlier_title_uses_its_window() {
+        let mut history = TitleHistory::new(5);
+        history.update(&[window(7, "free porn videos", "firefox")]);
+        let now = [window(7, "Cooking tutorial", "firefox")];
+        history.update(&now);
+
+        let record = find_detection(&porn_filter(), &history, &now).unwrap();
+        assert_eq!(record.title, "free porn videos");
+        assert_eq!(record.window_id, Some(7));
+        assert_eq!(record.class.as_deref(), Some("firefox"));
+    }
+
+    #[test]
+    fn test_blocked_titles_lists_every_match_once() {
+        let windows = [
+            window(1, "free porn videos", "firefox"),
+            window(2, "Cooking tutorial", "firefox"),
+            window(3, "porn clips", "firefox"),
+            window(4, "free porn videos", "firefox"),
+        ];
+        let mut history = TitleHistory::new(5);
+        history.update(&windows);
+
+        assert_eq!(blocked_titles(&porn_filter(), &history, &windows), vec!["free porn videos", "porn clips"]);
+    }
+
+    #[test]
+    fn test_classifier_only_sees_titles_the_filter_allowed() {
+        let mut blacklist = NamedTempFile::new().unwrap();
+        blacklist.write_all(b".*porn.*\n").unwrap();
+        let mut whitelist = NamedTempFile::new().unwrap();
+        whitelist.write_all(b".*documentary.*\n").unwrap();
+        let filter = Filter::new(blacklist.path(), whitelist.path()).unwrap();
+        let runner = std::sync::Arc::new(RecordingRunner::answering(0, "block"));
+        let classifier = ExternalClassifier::new("classify".to_string(), 60).with_runner(runner.clone());
+
+        let windows = [window(1, "porn documentary", "firefox"), window(2, "borderline clip", "firefox")];
+        let mut history = TitleHistory::new(5);
+        history.update(&windows);
+
+        let record = find_classified(&classifier, &filter, &history, &windows).unwrap();
+        assert_eq!(record.title, "borderline clip");
+        assert_eq!(record.pattern, CLASSIFIER_MARKER);
+        assert_eq!(record.window_id, Some(2));
+        assert_eq!(runner.calls(), vec![vec!["classify", "borderline clip"]]);
+    }
+
+    #[test]
+    fn test_detection_scope_with_mixed_focus() {
+        let unfocused_match = || vec![
+            window(1, "free porn videos", "firefox"),
+            WindowInfo { focused: true, ..window(2, "Cooking tutorial", "firefox") },
+        ];
+        let focused_match = || vec![
+            window(1, "Cooking tutorial", "firefox"),
+            WindowInfo { focused: true, ..window(2, "free porn videos", "firefox") },
+        ];
+        // Same order as the daemon: every window's title is remembered, then the
+        // windows are narrowed to the scope before detection.
+        let detect = |mut windows: Vec<WindowInfo>, scope| {
+            let mut history = TitleHistory::new(5);
+            history.update(&windows);
+            keep_in_scope(&mut windows, scope);
+            find_detection(&porn_filter(), &history, &windows).map(|record| record.window_id)
+        };
+
+        assert_eq!(detect(unfocused_match(), DetectionScope::AnyWindow), Some(Some(1)));
+        assert_eq!(detect(focused_match(), DetectionScope::AnyWindow), Some(Some(2)));
+        assert_eq!(detect(unfocused_match(), DetectionScope::FocusedOnly), None);
+        assert_eq!(detect(focused_match(), DetectionScope::FocusedOnly), Some(Some(2)));
+    }
+
+    #[test]
+    fn test_labelled_pattern_redacts_with_its_source() {
+        let mut blacklist = NamedTempFile::new().unwrap();
+        blacklist.write_all(b".*poker.*  #label: online poker\n").unwrap();
+        let whitelist = NamedTempFile::new().unwrap();
+        let filter = Filter::new(blacklist.path(), whitelist.path()).unwrap();
+        let windows = [window(3, "Texas poker night", "firefox")];
+        let mut history = TitleHistory::new(5);
+        history.update(&windows);
+        let redactor = TitleRedactor::new(
+            &PrivacyConfig { title_logging: TitleLogging::MatchedOnly, salt: None },
+            true,
+        );
+
+        let record = find_detection(&filter, &history, &windows).unwrap();
+
+        assert_eq!((record.pattern.as_str(), record.reported()), (".*poker.*", "online poker"));
+        assert_eq!(
+            record.audit_detail(&redactor),
+            "title='poker' pattern='online poker' window=0x3 class='firefox'"
+        );
+    }
+
+    #[test]
+    fn test_record_without_window() {
+        let record = DetectionRecord::new("x", "<no windows>", None);
+        assert_eq!(record.audit_detail(&TitleRedactor::default()), "title='x' pattern='<no windows>'");
+        assert!(find_detection(&porn_filter(), &TitleHistory::new(5), &[]).is_none());
+    }
+}
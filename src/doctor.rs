@@ -0,0 +1,287 @@
+use crate::browser::find_executable;
+use crate::config::{Config, StateBackend};
+use crate::filter::{read_pattern_file, Filter};
+use crate::state::{open_state_store, AppState};
+use std::fs;
+use std::path::Path;
+
+/// Result of a single diagnostic. A failed `hard` check makes `doctor` exit non-zero;
+/// a failed soft check is reported as a warning only.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CheckResult {
+    pub name: String,
+    pub passed: bool,
+    pub hard: bool,
+    pub detail: String,
+}
+
+impl CheckResult {
+    fn pass(name: &str, detail: String) -> Self {
+        CheckResult { name: name.to_string(), passed: true, hard: true, detail }
+    }
+
+    fn fail(name: &str, hard: bool, detail: String) -> Self {
+        CheckResult { name: name.to_string(), passed: false, hard, detail }
+    }
+
+    pub fn line(&self) -> String {
+        let tag = match (self.passed, self.hard) {
+            (true, _) => "PASS",
+            (false, true) => "FAIL",
+            (false, false) => "WARN",
+        };
+        format!("[{}] {}: {}", tag, self.name, self.detail)
+    }
+}
+
+pub fn check_executable(name: &str, executable: &str, hard: bool) -> CheckResult {
+    match find_executable(executable) {
+        Some(path) => CheckResult::pass(name, format!("'{}' found at {}", executable, path.display())),
+        None => CheckResult::fail(name, hard, format!("'{}' not found on PATH", executable)),
+    }
+}
+
+pub fn check_config(config_path: &str) -> CheckResult {
+    let name = "config";
+    match Config::load(config_path) {
+        Ok(config) => match config.validate() {
+            Ok(()) => CheckResult::pass(name, format!("'{}' is valid", config_path)),
+            Err(e) => CheckResult::fail(name, true, format!("'{}' is invalid: {}", config_path, e)),
+        },
+        Err(e) => CheckResult::fail(name, true, format!("'{}' could not be loaded: {}", config_path, e)),
+    }
+}
+
+/// A missing list is only a warning (the filter treats it as empty); an unreadable one fails.
+pub fn check_pattern_files(config: &Config) -> Vec<CheckResult> {
+    let mut results = Vec::new();
+    for (name, path) in [("blacklist", &config.files.blacklist), ("whitelist", &config.files.whitelist)] {
+        if !Path::new(path).exists() {
+            results.push(CheckResult::fail(name, false, format!("'{}' does not exist", path)));
+        } else if let Err(e) = read_pattern_file(path) {
+            results.push(CheckResult::fail(name, true, format!("'{}' is unreadable: {}", path, e)));
+        }
+    }
+
+    match Filter::with_config(&config.files.blacklist, &config.files.whitelist, config.filter.clone()) {
+        Ok(filter) => {
+            results.push(CheckResult::pass(
+                "patterns",
+                format!("{} blacklist pattern(s), {} whitelist pattern(s)",
+                    filter.blacklist_len(), filter.whitelist_len()),
+            ));
+            if !filter.skipped().is_empty() {
+                let lines: Vec<String> = filter.skipped().iter()
+                    .map(|s| format!("{} '{}'", s.list, s.source))
+                    .collect();
+                results.push(CheckResult::fail("invalid patterns", false,
+                    format!("{} skipped: {}", lines.len(), lines.join(", "))));
+            }
+        }
+        Err(e) => results.push(CheckResult::fail("patterns", true, format!("failed to load: {}", e))),
+    }
+    results
+}
+
+pub fn check_state_file(state_file: &str) -> CheckResult {
+    let name = "state file";
+    let path = Path::new(state_file);
+
+    if path.exists() {
+        if let Err(e) = AppState::load(path) {
+            return CheckResult::fail(name, true, format!("'{}' is unreadable: {}", state_file, e));
+        }
+        return match fs::OpenOptions::new().append(true).open(path) {
+            Ok(_) => CheckResult::pass(name, format!("'{}' is readable and writable", state_file)),
+            Err(e) => CheckResult::fail(name, true, format!("'{}' is not writable: {}", state_file, e)),
+        };
+    }
+
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let probe = parent.join(".ivh-doctor-probe");
+    let writable = fs::create_dir_all(parent)
+        .and_then(|_| fs::write(&probe, b""))
+        .and_then(|_| fs::remove_file(&probe));
+    match writable {
+        Ok(()) => CheckResult::pass(name, format!("'{}' does not exist yet but can be created", state_file)),
+        Err(e) => CheckResult::fail(name, true, format!("cannot create '{}': {}", state_file, e)),
+    }
+}
+
+/// The JSON backend is checked by [`check_state_file`]; other backends by opening
+/// the store and loading the state from it.
+pub fn check_state_store(config: &Config) -> CheckResult {
+    let state_file = &config.files.state_file;
+    match config.files.state_backend {
+        StateBackend::Json => check_state_file(state_file),
+        backend => match open_state_store(backend, state_file).and_then(|store| store.load()) {
+            Ok(_) => CheckResult::pass("state file", format!("'{}' opened as {:?}", state_file, backend)),
+            Err(e) => CheckResult::fail("state file", true, format!("'{}' cannot be used: {}", state_file, e)),
+        },
+    }
+}
+
+/// `probe` opens the display (with its bounded retries) and counts the windows on it.
+/// Only a successful open is reported as reachable; a display without windows is a
+/// warning, since the daemon has nothing to check there.
+pub fn check_display<F>(probe: F) -> CheckResult
+where
+    F: FnOnce() -> anyhow::Result<usize>,
+{
+    let name = "X11 display";
+    match probe() {
+        Ok(0) => CheckResult::fail(name, false, "reachable, but no windows found (is the session running?)".to_string()),
+        Ok(windows) => CheckResult::pass(name, format!("reachable, {} window(s)", windows)),
+        Err(e) => CheckResult::fail(name, true, format!("unreachable: {}", e)),
+    }
+}
+
+/// Runs every check. The X11 probe is passed in so this module stays free of Xlib.
+pub fn run_checks<F>(config: &Config, config_path: &str, probe_display: F) -> Vec<CheckResult>
+where
+    F: FnOnce() -> anyhow::Result<usize>,
+{
+    let mut results = vec![check_display(probe_display)];
+    results.push(check_executable("background setter", "feh", false));
+    results.push(check_executable("pgrep", "pgrep", false));
+    results.push(check_config(config_path));
+    results.extend(check_pattern_files(config));
+    results.push(check_state_store(config));
+    results.push(check_executable("browser", &config.browser.executable, true));
+    if let Some(wrapper) = &config.browser.launch_wrapper {
+        results.push(check_executable("launch wrapper", wrapper, true));
+    }
+    results
+}
+
+/// Prints one line per check plus a summary; returns `true` if no hard check failed.
+pub fn report(results: &[CheckResult]) -> bool {
+    for result in results {
+        println!("{}", result.line());
+    }
+    let passed = results.iter().filter(|r| r.passed).count();
+    let failed = results.iter().filter(|r| !r.passed && r.hard).count();
+    let warnings = results.iter().filter(|r| !r.passed && !r.hard).count();
+    println!("{} passed, {} failed, {} warning(s)", passed, failed, warnings);
+    failed == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_executable_found_in_temp_dir() {
+        let dir = TempDir::new().unwrap();
+        let exe = dir.path().join("fake-browser");
+        fs::write(&exe, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let result = check_executable("browser", exe.to_str().unwrap(), true);
+        assert!(result.passed, "{}", result.line());
+    }
+
+    #[test]
+    fn test_check_executable_missing() {
+        let result = check_executable("browser", "nonexistent-browser-12345", true);
+        assert!(!result.passed);
+        assert!(result.hard);
+        assert!(result.line().starts_with("[FAIL] browser:"));
+
+        let soft = check_executable("background setter", "nonexistent-feh-12345", false);
+        assert!(soft.line().starts_with("[WARN]"));
+    }
+
+    #[test]
+    fn test_find_executable_in_temp_path() {
+        let dir = TempDir::new().unwrap();
+        let exe = dir.path().join("my-browser");
+        fs::write(&exe, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let path_var = std::env::join_paths(["/nonexistent-dir", dir.path().to_str().unwrap()]).unwrap();
+        assert_eq!(crate::browser::find_executable_in("my-browser", Some(&path_var)), Some(exe));
+        assert_eq!(crate::browser::find_executable_in("other", Some(&path_var)), None);
+    }
+
+    #[test]
+    fn test_check_config() {
+        assert!(!check_config("/nonexistent/config.yaml").passed);
+
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("config.yaml");
+        fs::write(&path, Config::default().to_yaml().unwrap()).unwrap();
+        assert!(check_config(path.to_str().unwrap()).passed);
+    }
+
+    #[test]
+    fn test_check_state_file() {
+        let dir = TempDir::new().unwrap();
+
+        let fresh = dir.path().join("sub").join("state.json");
+        assert!(check_state_file(fresh.to_str().unwrap()).passed);
+
+        let existing = dir.path().join("state.json");
+        AppState::default().save(&existing).unwrap();
+        assert!(check_state_file(existing.to_str().unwrap()).passed);
+
+        let corrupt = dir.path().join("corrupt.json");
+        fs::write(&corrupt, "not json").unwrap();
+        assert!(!check_state_file(corrupt.to_str().unwrap()).passed);
+    }
+
+    #[test]
+    fn test_check_state_store_sqlite() {
+        let dir = TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.files.state_file = dir.path().join("state.db").to_string_lossy().into_owned();
+        config.files.state_backend = StateBackend::Sqlite;
+
+        let result = check_state_store(&config);
+        assert_eq!(result.passed, cfg!(feature = "sqlite-state"), "{}", result.line());
+    }
+
+    #[test]
+    fn test_check_pattern_files() {
+        let dir = TempDir::new().unwrap();
+        let blacklist = dir.path().join("black.txt");
+        fs::write(&blacklist, ".*porn.*\n.*adult.*\n[unclosed\n").unwrap();
+
+        let mut config = Config::default();
+        config.files.blacklist = blacklist.to_string_lossy().into_owned();
+        config.files.whitelist = dir.path().join("missing.txt").to_string_lossy().into_owned();
+
+        let results = check_pattern_files(&config);
+        let whitelist = results.iter().find(|r| r.name == "whitelist").unwrap();
+        assert!(!whitelist.passed && !whitelist.hard);
+        let patterns = results.iter().find(|r| r.name == "patterns").unwrap();
+        assert!(patterns.passed);
+        assert!(patterns.detail.starts_with("2 blacklist pattern(s), 0 whitelist"));
+        let invalid = results.iter().find(|r| r.name == "invalid patterns").unwrap();
+        assert!(!invalid.passed && !invalid.hard);
+        assert_eq!(invalid.detail, "1 skipped: blacklist '[unclosed'");
+    }
+
+    #[test]
+    fn test_run_checks_and_report() {
+        let config = Config::default();
+        let results = run_checks(&config, "/nonexistent/config.yaml", || {
+            Err(anyhow::anyhow!("Failed to open X11 display"))
+        });
+
+        assert_eq!(results[0].name, "X11 display");
+        assert!(!results[0].passed);
+        assert!(!report(&results));
+    }
+
+    #[test]
+    fn test_check_display_tells_empty_from_unreachable() {
+        assert_eq!(check_display(|| Ok(7)).line(), "[PASS] X11 display: reachable, 7 window(s)");
+        let empty = check_display(|| Ok(0));
+        assert!(!empty.passed && !empty.hard, "{}", empty.line());
+        let unreachable = check_display(|| Err(anyhow::anyhow!("Failed to open X11 display ':9'")));
+        assert_eq!(unreachable.line(), "[FAIL] X11 display: unreachable: Failed to open X11 display ':9'");
+    }
+}
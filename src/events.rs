@@ -0,0 +1,120 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt::Display;
+use std::io::Write;
+
+/// A significant daemon event, as emitted by `--events-json`. Serialized with the
+/// variant name in `event` and the variant's fields next to it.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    Startup {
+        version: String,
+        pid: u32,
+    },
+    /// One monitoring pass.
+    Tick {
+        windows: usize,
+        browser_processes: usize,
+        duration_ms: u64,
+    },
+    /// A title (or other trigger) matched. `title` has been through the privacy redactor.
+    Detection {
+        title: String,
+        pattern: String,
+        window_id: Option<u64>,
+        class: Option<String>,
+    },
+    Block {
+        minutes: u64,
+        until: DateTime<Utc>,
+        hard_lock: bool,
+        violations: u32,
+        pattern: String,
+    },
+    Unblock,
+    BreakStart {
+        minutes: u64,
+        until: Option<DateTime<Utc>>,
+    },
+    BreakEnd,
+}
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+/// The JSON line for `event` at `at`: `{"timestamp":"…","event":"…",…}`.
+pub fn event_line(event: &Event, at: DateTime<Utc>) -> String {
+    serde_json::to_string(&Record { timestamp: at, event }).expect("events always serialize")
+}
+
+/// Writes daemon events to stdout as JSON lines when `--events-json` is given.
+/// In that mode stdout carries nothing else: human-readable messages move to stderr.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EventStream {
+    enabled: bool,
+}
+
+impl EventStream {
+    pub fn new(enabled: bool) -> Self {
+        EventStream { enabled }
+    }
+
+    pub fn emit(&self, event: Event) {
+        if !self.enabled {
+            return;
+        }
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{}", event_line(&event, Utc::now()));
+        let _ = stdout.flush();
+    }
+
+    /// A message for the person watching the terminal.
+    pub fn say(&self, message: impl Display) {
+        if self.enabled {
+            eprintln!("{}", message);
+        } else {
+            println!("{}", message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_block_event_schema() {
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let event = Event::Block {
+            minutes: 10,
+            until: at + chrono::Duration::minutes(10),
+            hard_lock: false,
+            violations: 2,
+            pattern: ".*porn.*".to_string(),
+        };
+
+        let value: serde_json::Value = serde_json::from_str(&event_line(&event, at)).unwrap();
+        assert_eq!(value, serde_json::json!({
+            "timestamp": "2024-05-01T12:00:00Z",
+            "event": "block",
+            "minutes": 10,
+            "until": "2024-05-01T12:10:00Z",
+            "hard_lock": false,
+            "violations": 2,
+            "pattern": ".*porn.*",
+        }));
+    }
+
+    #[test]
+    fn test_unit_events_carry_only_timestamp_and_name() {
+        let at = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        assert_eq!(event_line(&Event::Unblock, at), r#"{"timestamp":"2024-05-01T12:00:00Z","event":"unblock"}"#);
+        assert_eq!(event_line(&Event::BreakEnd, at), r#"{"timestamp":"2024-05-01T12:00:00Z","event":"break_end"}"#);
+    }
+}
@@ -1,26 +1,539 @@
-use anyhow::Result;
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder};
+use anyhow::{anyhow, Result};
+use flate2::read::GzDecoder;
 use log::{debug, info, trace, warn};
-use regex::{Regex, RegexBuilder};
+use regex::{Regex, RegexBuilder, RegexSet, RegexSetBuilder};
 use std::fs;
+use std::io::Read;
 use std::path::Path;
 
+use crate::config::{AutoAnchor, DefaultAction, FailMode, FilterConfig, FilterPolicy, MatchMode};
+
+/// Marker reported instead of a pattern when deny mode blocks a title.
+pub const NOT_WHITELISTED: &str = "<not whitelisted>";
+
+/// Reported for every title while `filter.fail_mode: closed` holds the browser
+/// closed because the blacklist could not be loaded.
+pub const FILTER_UNAVAILABLE: &str = "<pattern lists unavailable>";
+
+/// Outcome of evaluating a single title.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allowed,
+    /// Matched the blacklist but was overridden by the whitelist.
+    Whitelisted,
+    /// Blocked by a blacklist pattern (or [`NOT_WHITELISTED`] in deny mode).
+    Blocked(BlockedBy),
+}
+
+/// What blocked a title: the pattern as written, which is what redaction matches
+/// with, and the label of its line, which is what gets reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockedBy {
+    pub pattern: String,
+    pub label: Option<String>,
+}
+
+impl BlockedBy {
+    /// A pattern (or marker) without a label.
+    pub fn new(pattern: &str) -> Self {
+        BlockedBy { pattern: pattern.to_string(), label: None }
+    }
+
+    /// The label if the line has one, otherwise the pattern itself.
+    pub fn reported(&self) -> &str {
+        self.label.as_deref().unwrap_or(&self.pattern)
+    }
+}
+
+impl std::fmt::Display for BlockedBy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.reported())
+    }
+}
+
+/// A compiled pattern together with the line it came from, which is what gets reported
+/// unless the line carries a label.
+struct Pattern {
+    source: String,
+    /// Text after an inline ` # ` on the pattern line.
+    label: Option<String>,
+    regex: Regex,
+}
+
+impl Pattern {
+    fn blocked_by(&self) -> BlockedBy {
+        BlockedBy { pattern: self.source.clone(), label: self.label.clone() }
+    }
+}
+
+/// Starts the inline label of a pattern line when preceded by whitespace.
+pub const LABEL_MARKER: &str = "#label:";
+
+/// Splits a pattern line into the pattern and its inline label, which follows
+/// [`LABEL_MARKER`] after whitespace: `.*poker.*  #label: online poker` is labelled
+/// `online poker`. Any other `#`, such as a comment in an `(?x)` pattern, is part
+/// of the pattern.
+pub fn split_label(line: &str) -> (&str, Option<&str>) {
+    let marker = line
+        .match_indices(LABEL_MARKER)
+        .find(|&(i, _)| line[..i].ends_with(char::is_whitespace))
+        .map(|(i, _)| i);
+    match marker {
+        Some(i) => {
+            let label = line[i + LABEL_MARKER.len()..].trim();
+            (line[..i].trim_end(), (!label.is_empty()).then_some(label))
+        }
+        None => (line, None),
+    }
+}
+
+/// Whether `pattern` contains a `#` after whitespace, which labelled a line before
+/// labels needed [`LABEL_MARKER`].
+fn looks_like_unmarked_label(pattern: &str) -> bool {
+    pattern.char_indices().any(|(i, c)| c == '#' && pattern[..i].ends_with(char::is_whitespace))
+}
+
+/// A pattern line that failed to compile and was left out of the filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedPattern {
+    /// `blacklist`, `whitelist` or `evasion`.
+    pub list: &'static str,
+    pub source: String,
+    pub error: String,
+}
+
+/// Pattern counts of a loaded filter, for a quick sanity check of the setup.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterSummary {
+    /// Each blacklist file with the number of patterns loaded from it, in load order.
+    pub blacklist_files: Vec<(String, usize)>,
+    pub whitelist: usize,
+    /// Lines that did not compile, across all lists.
+    pub skipped: usize,
+}
+
+impl FilterSummary {
+    /// The lines printed by `--status --filter-summary`.
+    pub fn lines(&self) -> Vec<String> {
+        let total: usize = self.blacklist_files.iter().map(|(_, count)| count).sum();
+        let mut lines = vec![format!("Filter: {} blacklist pattern(s) in {} file(s), {} whitelist pattern(s), {} invalid",
+            total, self.blacklist_files.len(), self.whitelist, self.skipped)];
+        lines.extend(self.blacklist_files.iter().map(|(path, count)| format!("  {}: {}", path, count)));
+        lines
+    }
+}
+
+/// Smallest compiled size charged against `filter.max_total_pattern_bytes`.
+const MIN_CHARGED_PATTERN_BYTES: usize = 1024;
+
+/// Blacklist patterns per compiled `RegexSet`. One set for a list of tens of
+/// thousands of patterns exceeds the regex crate's default size limit and takes
+/// minutes to build, so the blacklist is split into sets of this many.
+const BLACKLIST_SET_CHUNK: usize = 1000;
+
+/// `size_limit` for each blacklist set, well above what a chunk of ordinary
+/// patterns needs; `filter.max_total_pattern_bytes` is the limit meant for users.
+const BLACKLIST_SET_SIZE_LIMIT: usize = 256 * 1024 * 1024;
+
+/// `filter.max_total_pattern_bytes`, shared by every list a filter loads. Once a
+/// pattern does not fit, it and every later pattern are dropped.
+struct PatternBudget {
+    remaining: Option<usize>,
+    dropped: usize,
+}
+
+impl PatternBudget {
+    fn new(max_total_bytes: usize) -> Self {
+        PatternBudget { remaining: (max_total_bytes > 0).then_some(max_total_bytes), dropped: 0 }
+    }
+
+    /// Compiles `pattern` and charges it against the budget; `None` if it was dropped.
+    /// The regex crate does not report compiled sizes, so a pattern is charged the
+    /// smallest power-of-two `size_limit` (from 1 KiB) that it compiles under.
+    fn compile(&mut self, pattern: &str, case_insensitive: bool) -> Option<Result<Regex, regex::Error>> {
+        let build = |limit: Option<usize>| {
+            let mut builder = RegexBuilder::new(pattern);
+            builder.case_insensitive(case_insensitive);
+            if let Some(limit) = limit {
+                builder.size_limit(limit);
+            }
+            builder.build()
+        };
+        let Some(remaining) = self.remaining else {
+            return Some(build(None));
+        };
+        if self.dropped > 0 {
+            self.dropped += 1;
+            return None;
+        }
+
+        let mut limit = MIN_CHARGED_PATTERN_BYTES.min(remaining);
+        loop {
+            match build(Some(limit)) {
+                Ok(regex) => {
+                    self.remaining = Some(remaining - limit);
+                    return Some(Ok(regex));
+                }
+                Err(regex::Error::CompiledTooBig(_)) if limit < remaining => limit = (limit * 2).min(remaining),
+                Err(regex::Error::CompiledTooBig(_)) => {
+                    self.dropped = 1;
+                    return None;
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Wraps a pattern line according to `filter.auto_anchor` before compilation.
+pub fn anchor_pattern(pattern: &str, mode: AutoAnchor) -> String {
+    match mode {
+        AutoAnchor::None => pattern.to_string(),
+        AutoAnchor::Contains => format!("(?s-m:{})", pattern),
+        AutoAnchor::Whole => format!("^(?:{})$", pattern),
+    }
+}
+
+/// Title markers of web proxies, unblockers and VPN tools used to get around
+/// network-level filtering. Checked by `monitoring.block_evasion`, in addition to
+/// `monitoring.evasion_patterns`.
+pub const BUILTIN_EVASION_PATTERNS: &[&str] = &[
+    r"\bproxy\s*[-\x{2013}\x{2014}|:]\s*unblock",
+    r"\bunblock(ed)?\s+(any\s+)?(sites?|websites?|youtube|games)\b",
+    r"\b(free|web|online|anonymous)\s+(web\s+)?proxy\b",
+    r"\b(croxyproxy|proxysite|kproxy|hidester|hidemyass|4everproxy|proxyium)\b",
+    r"\b(ultrasurf|psiphon|hola\s+vpn)\b",
+    r"\bbypass\s+(school\s+)?(filters?|blocks?|firewall)\b",
+];
+
+/// Browser names recognised by `filter.strip_browser_suffix`, in addition to any
+/// configured in `filter.browser_suffixes`.
+pub const BUILTIN_BROWSER_SUFFIXES: &[&str] = &[
+    "Mozilla Firefox",
+    "Firefox Developer Edition",
+    "Google Chrome",
+    "Chromium",
+    "Brave",
+    "Microsoft Edge",
+    "Vivaldi",
+    "Opera",
+];
+
+/// Separators browsers put between the page title and their own name.
+const SUFFIX_SEPARATORS: &[&str] = &[" \u{2014} ", " \u{2013} ", " - "];
+
+/// Removes a trailing `<separator><browser name>` from a window title, e.g.
+/// `"Bad Page — Mozilla Firefox"` becomes `"Bad Page"`. Titles without a known
+/// suffix are returned unchanged.
+pub fn strip_browser_suffix<'a>(title: &'a str, extra: &[String]) -> &'a str {
+    let names = BUILTIN_BROWSER_SUFFIXES.iter().copied().chain(extra.iter().map(String::as_str));
+    for name in names {
+        let Some(rest) = title.strip_suffix(name) else {
+            continue;
+        };
+        for separator in SUFFIX_SEPARATORS {
+            if let Some(page) = rest.strip_suffix(separator) {
+                return page;
+            }
+        }
+    }
+    title
+}
+
+/// Lowercases `title` and drops everything that is not a letter or digit, so
+/// `"P-O-R-N video"` becomes `"pornvideo"`. Used by `filter.match_slug`.
+pub fn slugify(title: &str) -> String {
+    title.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Finds the first word in `title` that looks like a host name (`example.com`,
+/// `https://www.example.com/path`) and returns it lowercased without `www.`.
+pub fn extract_domain(title: &str) -> Option<String> {
+    title.split_whitespace().find_map(|word| {
+        let word = word.trim_matches(|c: char| !c.is_alphanumeric());
+        let word = word.split_once("://").map_or(word, |(_, rest)| rest);
+        let host = word.split(['/', ':', '?', '#']).next()?.to_lowercase();
+        let host = host.strip_prefix("www.").unwrap_or(&host);
+
+        let labels: Vec<&str> = host.split('.').collect();
+        let tld = labels.last()?;
+        let valid = labels.len() >= 2
+            && labels.iter().all(|label| {
+                !label.is_empty() && label.chars().all(|c| c.is_alphanumeric() || c == '-')
+            })
+            && tld.len() >= 2
+            && tld.chars().all(char::is_alphabetic);
+        valid.then(|| host.to_string())
+    })
+}
+
+/// A `keyword_with_domain_allow` whitelist line as the domain it trusts, lowercased
+/// and without `www.`. It may be written as `example.com`, `.example.com` or
+/// `*.example.com`; `None` if it is not a domain at all.
+fn trusted_domain(line: &str) -> Option<String> {
+    let line = line.trim().trim_start_matches("*.").trim_start_matches('.');
+    extract_domain(line).filter(|_| !line.contains(char::is_whitespace))
+}
+
+/// `domain` equals the `trusted` whitelist line's domain or is a subdomain of it.
+fn domain_matches(domain: &str, trusted: &str) -> bool {
+    trusted_domain(trusted).is_some_and(|trusted| {
+        domain == trusted || domain.strip_suffix(&trusted).is_some_and(|rest| rest.ends_with('.'))
+    })
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Reads a pattern list, transparently decompressing it when the path ends in
+/// `.gz` or the content starts with the gzip magic bytes.
+pub fn read_pattern_file<P: AsRef<Path>>(path: P) -> Result<String> {
+    let path = path.as_ref();
+    let raw = fs::read(path)?;
+    let gzipped = path.extension().is_some_and(|ext| ext == "gz") || raw.starts_with(&GZIP_MAGIC);
+    if !gzipped {
+        return Ok(String::from_utf8(raw)?);
+    }
+
+    debug!("Decompressing gzipped pattern file '{}'", path.display());
+    let mut content = String::new();
+    GzDecoder::new(raw.as_slice()).read_to_string(&mut content)?;
+    Ok(content)
+}
+
+/// The whole blacklist compiled into one matcher, so every matching pattern is found
+/// in a single pass over the title (one per chunk for regexes).
+enum BlacklistMatcher {
+    /// Sets of up to [`BLACKLIST_SET_CHUNK`] patterns, in blacklist order.
+    Regex(Vec<RegexSet>),
+    /// `filter.match_mode: substring`; keywords are literals. When `lowercase` is set
+    /// the keywords are lowercased and so is each title, which folds non-ASCII letters
+    /// too.
+    Keywords { automaton: AhoCorasick, lowercase: bool },
+}
+
+impl BlacklistMatcher {
+    fn build(blacklist: &[Pattern], options: &FilterConfig) -> Result<Self> {
+        // An anchored keyword is no longer a plain literal; its compiled regex is.
+        let literal = options.match_mode == MatchMode::Substring && options.auto_anchor != AutoAnchor::Whole;
+        if !literal {
+            let sets = blacklist
+                .chunks(BLACKLIST_SET_CHUNK)
+                .map(|chunk| {
+                    RegexSetBuilder::new(chunk.iter().map(|p| p.regex.as_str()))
+                        .case_insensitive(options.case_insensitive)
+                        .size_limit(BLACKLIST_SET_SIZE_LIMIT)
+                        .build()
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(BlacklistMatcher::Regex(sets));
+        }
+        let lowercase = options.case_insensitive;
+        let keywords = blacklist
+            .iter()
+            .map(|p| if lowercase { p.source.to_lowercase() } else { p.source.clone() });
+        Ok(BlacklistMatcher::Keywords { automaton: AhoCorasickBuilder::new().build(keywords)?, lowercase })
+    }
+
+    /// Indices into the blacklist of every pattern matching `title`, ascending.
+    fn matches(&self, title: &str) -> Vec<usize> {
+        match self {
+            BlacklistMatcher::Regex(sets) => sets
+                .iter()
+                .enumerate()
+                .flat_map(|(chunk, set)| set.matches(title).into_iter().map(move |i| chunk * BLACKLIST_SET_CHUNK + i))
+                .collect(),
+            BlacklistMatcher::Keywords { automaton, lowercase } => {
+                let title = if *lowercase { title.to_lowercase() } else { title.to_string() };
+                let mut indices: Vec<usize> = automaton
+                    .find_overlapping_iter(&title)
+                    .map(|m| m.pattern().as_usize())
+                    .collect();
+                indices.sort_unstable();
+                indices.dedup();
+                indices
+            }
+        }
+    }
+}
+
 pub struct Filter {
-    blacklist: Vec<Regex>,
-    whitelist: Vec<Regex>,
+    blacklist: Vec<Pattern>,
+    blacklist_matcher: BlacklistMatcher,
+    whitelist: Vec<Pattern>,
+    skipped: Vec<SkippedPattern>,
+    options: FilterConfig,
+    /// Each blacklist source and how many patterns it contributed.
+    blacklist_files: Vec<(String, usize)>,
+    /// Slugified `filter.slug_keywords`, paired with the line they came from.
+    slug_keywords: Vec<(String, String)>,
+    over_budget: usize,
+    /// Stand-in for a blacklist that failed to load under `filter.fail_mode: closed`.
+    fail_closed: bool,
 }
 
 impl Filter {
+    #[allow(dead_code)]
     pub fn new<P: AsRef<Path>>(blacklist_path: P, whitelist_path: P) -> Result<Self> {
-        let blacklist = Self::load_patterns(blacklist_path, "blacklist")?;
-        let whitelist = Self::load_patterns(whitelist_path, "whitelist")?;
+        Self::with_config(blacklist_path, whitelist_path, FilterConfig::default())
+    }
+
+    pub fn with_config<P: AsRef<Path>>(blacklist_path: P, whitelist_path: P, options: FilterConfig) -> Result<Self> {
+        Self::from_files(std::slice::from_ref(&blacklist_path), whitelist_path, options)
+    }
+
+    /// Like [`with_config`](Self::with_config), with the blacklist split over several
+    /// files whose patterns are loaded in order.
+    pub fn from_files<P: AsRef<Path>>(blacklist_paths: &[P], whitelist_path: P, options: FilterConfig) -> Result<Self> {
+        let mut skipped = Vec::new();
+        let mut budget = PatternBudget::new(options.max_total_pattern_bytes);
+        let mut blacklist = Vec::new();
+        let mut blacklist_files = Vec::new();
+        for path in blacklist_paths {
+            if options.fail_mode == FailMode::Closed && !path.as_ref().exists() {
+                return Err(anyhow!("blacklist file '{}' not found", path.as_ref().display()));
+            }
+            let patterns = Self::load_patterns(path, "blacklist", &options, &mut skipped, &mut budget)?;
+            blacklist_files.push((path.as_ref().display().to_string(), patterns.len()));
+            blacklist.extend(patterns);
+        }
+        let whitelist = match options.policy {
+            FilterPolicy::Standard => Self::load_patterns(whitelist_path, "whitelist", &options, &mut skipped, &mut budget)?,
+            FilterPolicy::KeywordWithDomainAllow => Self::load_trusted_domains(whitelist_path, &options, &mut skipped, &mut budget)?,
+        };
+        if budget.dropped > 0 {
+            warn!("filter.max_total_pattern_bytes ({} bytes) reached: {} pattern(s) dropped",
+                options.max_total_pattern_bytes, budget.dropped);
+        }
+
+        let slug_keywords = match (&options.match_slug, &options.slug_keywords) {
+            (true, Some(path)) => Self::load_slug_keywords(path)?,
+            _ => Vec::new(),
+        };
+
+        let mut filter = Self::build(blacklist, whitelist, skipped, options)?;
+        filter.blacklist_files = blacklist_files;
+        filter.slug_keywords = slug_keywords;
+        filter.over_budget = budget.dropped;
+        info!("Filter: {} blacklist pattern(s), {} whitelist pattern(s), default_action={:?} auto_anchor={:?} min_matches={} match_mode={:?}",
+            filter.blacklist.len(), filter.whitelist.len(), filter.options.default_action,
+            filter.options.auto_anchor, filter.options.min_matches, filter.options.match_mode);
+        if filter.options.ignore_whitelist {
+            info!("Filter: whitelist overrides disabled (ignore_whitelist)");
+        }
+        if filter.options.match_slug {
+            info!("Filter: {} slug keyword(s)", filter.slug_keywords.len());
+        }
+        Ok(filter)
+    }
+
+    /// [`with_config`](Self::with_config), except that with `filter.fail_mode: closed`
+    /// a blacklist that cannot be loaded yields a [`fail_closed`](Self::fail_closed)
+    /// filter instead of an error.
+    pub fn with_fail_mode<P: AsRef<Path>>(blacklist_path: P, whitelist_path: P, options: FilterConfig) -> Result<Self> {
+        let fail_mode = options.fail_mode;
+        match Self::with_config(blacklist_path, whitelist_path, options.clone()) {
+            Err(e) if fail_mode == FailMode::Closed => {
+                warn!("Pattern lists failed to load, keeping the browser closed (filter.fail_mode: closed): {}", e);
+                Self::fail_closed(options)
+            }
+            result => result,
+        }
+    }
+
+    /// A filter that blocks every title, empty ones included, as [`FILTER_UNAVAILABLE`].
+    pub fn fail_closed(options: FilterConfig) -> Result<Self> {
+        let mut filter = Self::build(Vec::new(), Vec::new(), Vec::new(), options)?;
+        filter.fail_closed = true;
+        Ok(filter)
+    }
+
+    /// Whether this is the [`fail_closed`](Self::fail_closed) stand-in.
+    pub fn is_fail_closed(&self) -> bool {
+        self.fail_closed
+    }
 
-        info!("Filter: {} blacklist pattern(s), {} whitelist pattern(s)",
-            blacklist.len(), whitelist.len());
+    /// The `monitoring.block_evasion` filter: [`BUILTIN_EVASION_PATTERNS`] followed by
+    /// `extra`, as regexes with no whitelist. It is independent of the blacklist, so
+    /// the user's whitelist cannot exempt an evasion marker.
+    pub fn evasion(extra: &[String], case_insensitive: bool) -> Result<Self> {
+        let options = FilterConfig { case_insensitive, ..FilterConfig::default() };
+        let lines = BUILTIN_EVASION_PATTERNS.iter().copied().chain(extra.iter().map(String::as_str));
+        let mut skipped = Vec::new();
+        let blacklist = Self::compile_patterns(lines, "evasion", &options, &mut skipped, &mut PatternBudget::new(0));
+        info!("Evasion filter: {} pattern(s) ({} built in)", blacklist.len(), BUILTIN_EVASION_PATTERNS.len());
+        let mut filter = Self::build(blacklist, Vec::new(), skipped, options)?;
+        filter.blacklist_files = vec![("<evasion>".to_string(), filter.blacklist.len())];
+        Ok(filter)
+    }
+
+    fn build(blacklist: Vec<Pattern>, whitelist: Vec<Pattern>, skipped: Vec<SkippedPattern>, options: FilterConfig) -> Result<Self> {
+        let blacklist_matcher = BlacklistMatcher::build(&blacklist, &options)?;
+        Ok(Filter {
+            blacklist,
+            blacklist_matcher,
+            whitelist,
+            skipped,
+            options,
+            blacklist_files: Vec::new(),
+            slug_keywords: Vec::new(),
+            over_budget: 0,
+            fail_closed: false,
+        })
+    }
+
+    /// Reads the `filter.slug_keywords` file. Keywords are slugified like titles, so
+    /// `"p o r n"` and `"porn"` are the same keyword; lines that slugify to nothing are dropped.
+    fn load_slug_keywords(path: &str) -> Result<Vec<(String, String)>> {
+        if !Path::new(path).exists() {
+            info!("slug keyword file '{}' not found, using empty keyword list", path);
+            return Ok(Vec::new());
+        }
 
-        Ok(Filter { blacklist, whitelist })
+        info!("Loading slug keywords from '{}'", path);
+        let content = read_pattern_file(path)?;
+        Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let slug = slugify(line);
+                (!slug.is_empty()).then(|| (slug, line.to_string()))
+            })
+            .collect())
     }
 
-    fn load_patterns<P: AsRef<Path>>(path: P, label: &str) -> Result<Vec<Regex>> {
+    /// The `keyword_with_domain_allow` whitelist: each line is a domain, read literally
+    /// rather than as a regex so that `*.example.com` is accepted. Lines that are not a
+    /// domain are recorded in `skipped`.
+    fn load_trusted_domains<P: AsRef<Path>>(
+        path: P,
+        options: &FilterConfig,
+        skipped: &mut Vec<SkippedPattern>,
+        budget: &mut PatternBudget,
+    ) -> Result<Vec<Pattern>> {
+        let literal = FilterConfig { match_mode: MatchMode::Substring, auto_anchor: AutoAnchor::Contains, ..options.clone() };
+        let (domains, invalid): (Vec<Pattern>, Vec<Pattern>) = Self::load_patterns(path, "whitelist", &literal, skipped, budget)?
+            .into_iter()
+            .partition(|pattern| trusted_domain(&pattern.source).is_some());
+        for pattern in invalid {
+            warn!("Whitelist line '{}' is not a domain (filter.policy: keyword_with_domain_allow)", pattern.source);
+            skipped.push(SkippedPattern {
+                list: "whitelist",
+                source: pattern.source,
+                error: "not a domain".to_string(),
+            });
+        }
+        Ok(domains)
+    }
+
+    fn load_patterns<P: AsRef<Path>>(
+        path: P,
+        label: &'static str,
+        options: &FilterConfig,
+        skipped: &mut Vec<SkippedPattern>,
+        budget: &mut PatternBudget,
+    ) -> Result<Vec<Pattern>> {
         if !path.as_ref().exists() {
             info!("{} file '{}' not found, using empty pattern list",
                 label, path.as_ref().display());
@@ -29,23 +542,64 @@ impl Filter {
 
         info!("Loading {} patterns from '{}'", label, path.as_ref().display());
 
-        let content = fs::read_to_string(path)?;
+        let content = read_pattern_file(path)?;
+        Ok(Self::compile_patterns(content.lines(), label, options, skipped, budget))
+    }
+
+    /// Compiles pattern lines, skipping blanks and `#` comments and splitting off
+    /// inline labels. Lines that do not compile are recorded in `skipped`; lines past
+    /// the size budget are only counted in it.
+    fn compile_patterns<'a>(
+        lines: impl Iterator<Item = &'a str>,
+        label: &'static str,
+        options: &FilterConfig,
+        skipped: &mut Vec<SkippedPattern>,
+        budget: &mut PatternBudget,
+    ) -> Vec<Pattern> {
         let mut patterns = Vec::new();
 
-        for line in content.lines() {
+        for line in lines {
             let line = line.trim();
             if !line.is_empty() && !line.starts_with('#') {
-                match RegexBuilder::new(line).case_insensitive(true).build() {
+                let (line, pattern_label) = split_label(line);
+                if looks_like_unmarked_label(line) {
+                    warn!("{} pattern '{}' contains ' #', which is matched literally; write a label as '{} <label>'",
+                        label, line, LABEL_MARKER);
+                }
+                let literal;
+                let line_pattern = match options.match_mode {
+                    MatchMode::Regex => line,
+                    MatchMode::Substring => {
+                        literal = regex::escape(line);
+                        &literal
+                    }
+                };
+                let anchored = anchor_pattern(line_pattern, options.auto_anchor);
+                let Some(compiled) = budget.compile(&anchored, options.case_insensitive) else {
+                    continue;
+                };
+                match compiled {
                     Ok(regex) => {
-                        trace!("Loaded {} pattern: '{}'", label, line);
-                        patterns.push(regex);
+                        trace!("Loaded {} pattern: '{}' (compiled as '{}')", label, line, anchored);
+                        patterns.push(Pattern {
+                            source: line.to_string(),
+                            label: pattern_label.map(str::to_string),
+                            regex,
+                        });
+                    }
+                    Err(e) => {
+                        warn!("Invalid regex pattern '{}': {}", line, e);
+                        skipped.push(SkippedPattern {
+                            list: label,
+                            source: line.to_string(),
+                            error: e.to_string(),
+                        });
                     }
-                    Err(e) => warn!("Invalid regex pattern '{}': {}", line, e),
                 }
             }
         }
 
-        Ok(patterns)
+        patterns
     }
 
     pub fn blacklist_len(&self) -> usize {
@@ -56,29 +610,157 @@ impl Filter {
         self.whitelist.len()
     }
 
+    /// Every loaded blacklist pattern as written in its file, in load order.
+    #[allow(dead_code)]
+    pub fn blacklist_sources(&self) -> Vec<&str> {
+        self.blacklist.iter().map(|p| p.source.as_str()).collect()
+    }
+
+    /// Every loaded whitelist pattern as written in its file, in load order.
+    #[allow(dead_code)]
+    pub fn whitelist_sources(&self) -> Vec<&str> {
+        self.whitelist.iter().map(|p| p.source.as_str()).collect()
+    }
+
+    /// Lines that were dropped because they did not compile.
+    pub fn skipped(&self) -> &[SkippedPattern] {
+        &self.skipped
+    }
+
+    /// Patterns left out because `filter.max_total_pattern_bytes` was used up.
+    #[allow(dead_code)]
+    pub fn over_budget(&self) -> usize {
+        self.over_budget
+    }
+
+    /// Pattern counts per blacklist file, whitelist size and skipped lines.
+    pub fn summary(&self) -> FilterSummary {
+        FilterSummary {
+            blacklist_files: self.blacklist_files.clone(),
+            whitelist: self.whitelist.len(),
+            skipped: self.skipped.len(),
+        }
+    }
+
+    /// The report printed by `list-patterns`: each list's patterns, any skipped
+    /// lines, then the total.
+    pub fn listing(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (name, patterns) in [("Blacklist", &self.blacklist), ("Whitelist", &self.whitelist)] {
+            lines.push(format!("{} ({} pattern(s)):", name, patterns.len()));
+            lines.extend(patterns.iter().map(|p| match &p.label {
+                Some(label) => format!("  {}  {} {}", p.source, LABEL_MARKER, label),
+                None => format!("  {}", p.source),
+            }));
+        }
+        if !self.skipped.is_empty() {
+            lines.push(format!("Skipped {} invalid pattern(s):", self.skipped.len()));
+            lines.extend(self.skipped.iter().map(|s| format!("  [{}] {} — {}", s.list, s.source, s.error)));
+        }
+        lines.push(format!("Total: {} pattern(s)", self.blacklist.len() + self.whitelist.len()));
+        lines
+    }
+
     #[allow(dead_code)]
     pub fn is_blacklisted(&self, title: &str) -> bool {
+        matches!(self.evaluate(title), Verdict::Blocked(_))
+    }
+
+    /// Applies the title-level matching options. Both lists see the same prepared title.
+    fn prepare_title<'a>(&self, title: &'a str) -> &'a str {
+        if self.options.strip_browser_suffix {
+            strip_browser_suffix(title, &self.options.browser_suffixes)
+        } else {
+            title
+        }
+    }
+
+    pub fn evaluate(&self, title: &str) -> Verdict {
         debug!("Checking title: '{}'", title);
-        for pattern in &self.blacklist {
-            let matched = pattern.is_match(title);
-            trace!("  Blacklist pattern '{}': {}",
-                pattern.as_str(), if matched { "MATCH" } else { "no match" });
-            if matched {
-                let whitelisted = self.is_whitelisted(title);
-                debug!("  Blacklist match for '{}', whitelisted={}", title, whitelisted);
-                if !whitelisted {
-                    return true;
+        if self.fail_closed {
+            return Verdict::Blocked(BlockedBy::new(FILTER_UNAVAILABLE));
+        }
+
+        let title = self.prepare_title(title);
+
+        if self.options.default_action == DefaultAction::Deny {
+            // Blank titles show up transiently while windows open; never block on them.
+            if title.trim().is_empty() {
+                return Verdict::Allowed;
+            }
+            if self.whitelist_matches(title) {
+                return Verdict::Allowed;
+            }
+            debug!("  Deny mode: '{}' is not whitelisted", title);
+            return Verdict::Blocked(BlockedBy::new(NOT_WHITELISTED));
+        }
+
+        let matches = self.blacklist_matcher.matches(title);
+        for &index in &matches {
+            trace!("  '{}' matched pattern '{}'", title, self.blacklist[index].source);
+        }
+
+        let count = matches.len();
+        let pattern = match matches.first() {
+            Some(&first) if count >= self.options.min_matches => self.blacklist[first].blocked_by(),
+            _ => {
+                if count > 0 {
+                    debug!("  '{}' matched {} pattern(s), below min_matches={}",
+                        title, count, self.options.min_matches);
+                }
+                match self.slug_match(title) {
+                    Some(keyword) => BlockedBy::new(&keyword),
+                    None => return Verdict::Allowed,
                 }
             }
+        };
+
+        let whitelisted = !self.options.ignore_whitelist
+            && match self.options.policy {
+                FilterPolicy::Standard => self.whitelist_matches(title),
+                FilterPolicy::KeywordWithDomainAllow => self.is_trusted_domain(title),
+            };
+        debug!("  Blacklist match for '{}' ({} pattern(s)), whitelisted={}", title, count, whitelisted);
+        if whitelisted {
+            Verdict::Whitelisted
+        } else {
+            Verdict::Blocked(pattern)
         }
-        false
     }
 
+    /// The first `filter.slug_keywords` line whose slug occurs in the title's slug.
+    fn slug_match(&self, title: &str) -> Option<String> {
+        if self.slug_keywords.is_empty() {
+            return None;
+        }
+        let slug = slugify(title);
+        let (_, keyword) = self.slug_keywords.iter().find(|(keyword, _)| slug.contains(keyword.as_str()))?;
+        debug!("  Slug '{}' contains keyword '{}'", slug, keyword);
+        Some(keyword.clone())
+    }
+
+    /// With `filter.policy: keyword_with_domain_allow`, whitelist lines are domains.
+    fn is_trusted_domain(&self, title: &str) -> bool {
+        let Some(domain) = extract_domain(title) else {
+            trace!("  No domain found in '{}'", title);
+            return false;
+        };
+        let trusted = self.whitelist.iter().any(|pattern| domain_matches(&domain, &pattern.source));
+        trace!("  Domain '{}' trusted={}", domain, trusted);
+        trusted
+    }
+
+    /// Matches `title` against the whitelist with the same options [`evaluate`](Self::evaluate) uses.
+    #[allow(dead_code)]
     pub fn is_whitelisted(&self, title: &str) -> bool {
+        self.whitelist_matches(self.prepare_title(title))
+    }
+
+    fn whitelist_matches(&self, title: &str) -> bool {
         for pattern in &self.whitelist {
-            let matched = pattern.is_match(title);
+            let matched = pattern.regex.is_match(title);
             trace!("  Whitelist pattern '{}': {}",
-                pattern.as_str(), if matched { "MATCH" } else { "no match" });
+                pattern.source, if matched { "MATCH" } else { "no match" });
             if matched {
                 return true;
             }
@@ -86,20 +768,19 @@ impl Filter {
         false
     }
 
-    /// Returns the first (title, pattern_string) pair that is blacklisted, or None.
+    /// Returns the first (title, reported pattern) pair that is blacklisted, or None.
     pub fn find_blacklisted_title(&self, titles: &[String]) -> Option<(String, String)> {
+        self.find_blocked_title(titles).map(|(title, blocked)| (title, blocked.reported().to_string()))
+    }
+
+    /// Like [`find_blacklisted_title`](Self::find_blacklisted_title), keeping the
+    /// pattern and its label apart.
+    pub fn find_blocked_title(&self, titles: &[String]) -> Option<(String, BlockedBy)> {
         info!("find_blacklisted_title: checking {} title(s)", titles.len());
         for title in titles {
-            debug!("  Checking: '{}'", title);
-            for pattern in &self.blacklist {
-                let matched = pattern.is_match(title);
-                trace!("  '{}' vs pattern '{}': {}",
-                    title, pattern.as_str(), if matched { "MATCH" } else { "no match" });
-                if matched && !self.is_whitelisted(title) {
-                    info!("Blacklist hit: title='{}' pattern='{}'",
-                        title, pattern.as_str());
-                    return Some((title.clone(), pattern.as_str().to_string()));
-                }
+            if let Verdict::Blocked(blocked) = self.evaluate(title) {
+                info!("Blacklist hit: title='{}' pattern='{}'", title, blocked);
+                return Some((title.clone(), blocked));
             }
         }
         debug!("No blacklisted titles found");
@@ -130,6 +811,511 @@ mod tests {
         Filter::new(bl.path(), wl.path()).unwrap()
     }
 
+    fn make_filter_with(blacklist: &str, whitelist: &str, options: FilterConfig) -> Filter {
+        let bl = create_temp_file_with_content(blacklist);
+        let wl = create_temp_file_with_content(whitelist);
+        Filter::with_config(bl.path(), wl.path(), options).unwrap()
+    }
+
+    fn deny_mode() -> FilterConfig {
+        FilterConfig {
+            default_action: DefaultAction::Deny,
+            ..FilterConfig::default()
+        }
+    }
+
+    fn anchored(mode: AutoAnchor) -> FilterConfig {
+        FilterConfig {
+            auto_anchor: mode,
+            ..FilterConfig::default()
+        }
+    }
+
+    fn gzip(content: &str) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(content.as_bytes()).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_case_insensitive_whitelist_overrides_blacklist() {
+        let filter = make_filter(".*porn.*", ".*PornHub Documentary.*");
+        assert_eq!(filter.evaluate("The pornhub DOCUMENTARY trailer"), Verdict::Whitelisted);
+        assert!(filter.is_whitelisted("The pornhub DOCUMENTARY trailer"));
+        assert!(filter.is_blacklisted("PORN clips"));
+    }
+
+    fn substring_mode() -> FilterConfig {
+        FilterConfig { match_mode: MatchMode::Substring, ..FilterConfig::default() }
+    }
+
+    #[test]
+    fn test_substring_mode_matches_naive_loop() {
+        let keywords = ["porn", "xxx", "c++ hacks", "nsfw", "Adult", "hub", "PORNÓ"];
+        let titles = [
+            "Free PORN videos",
+            "Learning C++ Hacks - YouTube",
+            "cooking tutorial",
+            "XXX rated",
+            "adult swim",
+            "GitHub - rust-lang/rust",
+            "nothing to see here",
+            "",
+            "nsfw pornhub compilation",
+            "Películas pornó gratis",
+            "ÜBER PORNÓ",
+        ];
+        let filter = make_filter_with(&keywords.join("\n"), "", substring_mode());
+
+        for title in titles {
+            let lowered = title.to_lowercase();
+            let naive = keywords
+                .iter()
+                .find(|k| lowered.contains(&k.to_lowercase()))
+                .map(|k| Verdict::Blocked(BlockedBy::new(k)));
+            let expected = naive.unwrap_or(Verdict::Allowed);
+            assert_eq!(filter.evaluate(title), expected, "title '{}'", title);
+        }
+    }
+
+    #[test]
+    fn test_substring_mode_honours_whole_anchor() {
+        let options = FilterConfig { auto_anchor: AutoAnchor::Whole, ..substring_mode() };
+        let filter = make_filter_with("c++\nporn", "", options);
+        assert_eq!(filter.evaluate("C++"), Verdict::Blocked(BlockedBy::new("c++")));
+        assert_eq!(filter.evaluate("learn c++"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("porn"), Verdict::Blocked(BlockedBy::new("porn")));
+        assert_eq!(filter.evaluate("free porn"), Verdict::Allowed);
+    }
+
+    #[test]
+    fn test_substring_mode_counts_distinct_keywords() {
+        let options = FilterConfig { min_matches: 2, ..substring_mode() };
+        let filter = make_filter_with("porn\nhub\n", "", options);
+        assert_eq!(filter.evaluate("porn porn porn"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("pornhub"), Verdict::Blocked(BlockedBy::new("porn")));
+    }
+
+    #[test]
+    fn test_substring_mode_treats_lines_literally() {
+        let filter = make_filter_with(".*porn.*\n", "(documentary)\n", substring_mode());
+        assert_eq!(filter.evaluate("free porn"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("a .*porn.* title"), Verdict::Blocked(BlockedBy::new(".*porn.*")));
+        assert_eq!(filter.evaluate("a .*porn.* (documentary)"), Verdict::Whitelisted);
+        assert_eq!(filter.evaluate("a .*porn.* documentary"), Verdict::Blocked(BlockedBy::new(".*porn.*")));
+    }
+
+    #[test]
+    fn test_listing_includes_both_lists_and_omits_invalid() {
+        let filter = make_filter(".*porn.*\n# comment\n[unclosed\n.*adult.*\n", "documentary\n(bad\n");
+
+        assert_eq!(filter.blacklist_sources(), vec![".*porn.*", ".*adult.*"]);
+        assert_eq!(filter.whitelist_sources(), vec!["documentary"]);
+        let skipped: Vec<_> = filter.skipped().iter().map(|s| (s.list, s.source.as_str())).collect();
+        assert_eq!(skipped, vec![("blacklist", "[unclosed"), ("whitelist", "(bad")]);
+
+        let listing = filter.listing();
+        assert_eq!(&listing[..6], [
+            "Blacklist (2 pattern(s)):",
+            "  .*porn.*",
+            "  .*adult.*",
+            "Whitelist (1 pattern(s)):",
+            "  documentary",
+            "Skipped 2 invalid pattern(s):",
+        ]);
+        assert!(listing[6].starts_with("  [blacklist] [unclosed — "), "{}", listing[6]);
+        assert_eq!(listing.last().unwrap(), "Total: 3 pattern(s)");
+    }
+
+    #[test]
+    fn test_pattern_budget_stops_loading_once_spent() {
+        let blacklist: String = (0..10).map(|n| format!("keyword{}\n", n)).collect();
+        let options = FilterConfig { max_total_pattern_bytes: 4 * MIN_CHARGED_PATTERN_BYTES, ..FilterConfig::default() };
+        let filter = make_filter_with(&blacklist, "documentary\n", options);
+
+        assert_eq!(filter.blacklist_sources(), vec!["keyword0", "keyword1", "keyword2", "keyword3"]);
+        assert_eq!(filter.whitelist_len(), 0);
+        assert_eq!(filter.over_budget(), 7);
+        assert!(filter.skipped().is_empty());
+
+        let unlimited = make_filter(&blacklist, "documentary\n");
+        assert_eq!((unlimited.blacklist_len(), unlimited.over_budget()), (10, 0));
+    }
+
+    #[test]
+    fn test_fail_closed_blocks_everything_when_blacklist_is_missing() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("BlackList.txt");
+        let whitelist = create_temp_file_with_content(".*education.*");
+        let closed = FilterConfig { fail_mode: FailMode::Closed, ..FilterConfig::default() };
+
+        assert!(Filter::with_config(missing.as_path(), whitelist.path(), closed.clone()).is_err());
+        let filter = Filter::with_fail_mode(missing.as_path(), whitelist.path(), closed.clone()).unwrap();
+        assert!(filter.is_fail_closed());
+        assert_eq!(filter.evaluate("sex education"), Verdict::Blocked(BlockedBy::new(FILTER_UNAVAILABLE)));
+        assert!(filter.check_titles(&["".to_string()]));
+
+        let open = Filter::with_fail_mode(missing.as_path(), whitelist.path(), FilterConfig::default()).unwrap();
+        assert!(!open.is_fail_closed());
+        assert_eq!(open.evaluate("anything"), Verdict::Allowed);
+
+        fs::write(&missing, ".*porn.*\n").unwrap();
+        let loaded = Filter::with_fail_mode(missing.as_path(), whitelist.path(), closed).unwrap();
+        assert!(!loaded.is_fail_closed());
+        assert_eq!(loaded.evaluate("cooking"), Verdict::Allowed);
+    }
+
+    #[test]
+    fn test_split_label() {
+        assert_eq!(split_label(".*poker.*  #label: online poker"), (".*poker.*", Some("online poker")));
+        assert_eq!(split_label(".*poker.*\t#label:gambling"), (".*poker.*", Some("gambling")));
+        assert_eq!(split_label("c#|f#"), ("c#|f#", None));
+        assert_eq!(split_label(".*poker.* #label:"), (".*poker.*", None));
+        assert_eq!(split_label(".*poker.*"), (".*poker.*", None));
+        // Without the marker a `#` after whitespace stays in the pattern.
+        assert_eq!(split_label("(?x) poker # the card game"), ("(?x) poker # the card game", None));
+        assert_eq!(split_label("a#label:b"), ("a#label:b", None));
+        assert!(looks_like_unmarked_label(".*poker.*  # online poker"));
+        assert!(!looks_like_unmarked_label("c#|f#"));
+    }
+
+    #[test]
+    fn test_labelled_pattern_is_reported_by_its_label() {
+        let filter = make_filter("# gambling\n.*poker.*  #label: online poker\n.*casino.*\n", "");
+
+        assert_eq!(filter.blacklist_sources(), vec![".*poker.*", ".*casino.*"]);
+        assert_eq!(filter.evaluate("Texas Poker Night"), Verdict::Blocked(BlockedBy {
+            pattern: ".*poker.*".to_string(),
+            label: Some("online poker".to_string()),
+        }));
+        assert_eq!(filter.evaluate("Casino Royale"), Verdict::Blocked(BlockedBy::new(".*casino.*")));
+        assert_eq!(filter.listing()[1], "  .*poker.*  #label: online poker");
+    }
+
+    #[test]
+    fn test_summary_counts_each_file() {
+        let mut adult = NamedTempFile::new().unwrap();
+        adult.write_all(b".*porn.*\n.*adult.*\n# comment\n").unwrap();
+        let mut gambling = NamedTempFile::new().unwrap();
+        gambling.write_all(b"casino\n[unclosed\npoker\nroulette\n").unwrap();
+        let mut whitelist = NamedTempFile::new().unwrap();
+        whitelist.write_all(b"documentary\n").unwrap();
+
+        let filter = Filter::from_files(
+            &[adult.path(), gambling.path()],
+            whitelist.path(),
+            FilterConfig::default(),
+        ).unwrap();
+        let summary = filter.summary();
+        assert_eq!(summary, FilterSummary {
+            blacklist_files: vec![
+                (adult.path().display().to_string(), 2),
+                (gambling.path().display().to_string(), 3),
+            ],
+            whitelist: 1,
+            skipped: 1,
+        });
+        assert_eq!(summary.lines()[0], "Filter: 5 blacklist pattern(s) in 2 file(s), 1 whitelist pattern(s), 1 invalid");
+        assert!(filter.is_blacklisted("online poker night"));
+    }
+
+    #[test]
+    fn test_evasion_filter_matches_proxy_titles() {
+        let filter = Filter::evasion(&[], true).unwrap();
+        assert_eq!(filter.blacklist_len(), BUILTIN_EVASION_PATTERNS.len());
+
+        for title in [
+            "Proxy - Unblock Websites - Mozilla Firefox",
+            "CroxyProxy - The most advanced secure and free web proxy",
+            "Hidester | Free Web Proxy",
+            "How to bypass school filters in 2026",
+        ] {
+            assert!(filter.is_blacklisted(title), "{}", title);
+        }
+        assert_eq!(filter.evaluate("Nginx reverse proxy configuration - Google Chrome"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("Cooking tutorial - YouTube"), Verdict::Allowed);
+    }
+
+    #[test]
+    fn test_evasion_filter_extra_patterns() {
+        let extra = vec![r"\bsecret\s+tunnel\b".to_string(), "[unclosed".to_string()];
+        let filter = Filter::evasion(&extra, true).unwrap();
+        assert_eq!(filter.evaluate("Secret Tunnel VPN"), Verdict::Blocked(BlockedBy::new(&extra[0])));
+        assert_eq!(filter.skipped().len(), 1);
+        assert_eq!(filter.skipped()[0].list, "evasion");
+    }
+
+    #[test]
+    fn test_ignore_whitelist() {
+        let title = "porn education documentary";
+        let lenient = make_filter(".*porn.*", ".*education.*");
+        assert!(!lenient.is_blacklisted(title));
+
+        let options = FilterConfig { ignore_whitelist: true, ..FilterConfig::default() };
+        let strict = make_filter_with(".*porn.*", ".*education.*", options);
+        assert!(strict.is_blacklisted(title));
+        assert_eq!(strict.evaluate(title), Verdict::Blocked(BlockedBy::new(".*porn.*")));
+        assert_eq!(strict.evaluate("education"), Verdict::Allowed);
+    }
+
+    #[test]
+    fn test_case_sensitive_applies_to_both_lists() {
+        let options = FilterConfig { case_insensitive: false, ..FilterConfig::default() };
+        let filter = make_filter_with(".*porn.*", ".*Documentary.*", options);
+        assert_eq!(filter.evaluate("porn documentary"), Verdict::Blocked(BlockedBy::new(".*porn.*")));
+        assert_eq!(filter.evaluate("porn Documentary"), Verdict::Whitelisted);
+        assert_eq!(filter.evaluate("PORN documentary"), Verdict::Allowed);
+    }
+
+    #[test]
+    fn test_is_whitelisted_strips_browser_suffix() {
+        let options = FilterConfig {
+            auto_anchor: AutoAnchor::Whole,
+            strip_browser_suffix: true,
+            ..FilterConfig::default()
+        };
+        let filter = make_filter_with("", "cooking tutorial", options);
+        assert!(filter.is_whitelisted("Cooking Tutorial — Mozilla Firefox"));
+    }
+
+    #[test]
+    fn test_extract_domain() {
+        assert_eq!(extract_domain("porn on trusted.com"), Some("trusted.com".to_string()));
+        assert_eq!(extract_domain("Video (https://www.Example.co.uk/watch?v=1)"), Some("example.co.uk".to_string()));
+        assert_eq!(extract_domain("news.ycombinator.com: Hacker News"), Some("news.ycombinator.com".to_string()));
+        assert_eq!(extract_domain("Episode 1.5 - e.g. a video"), None);
+        assert_eq!(extract_domain("Cooking - YouTube"), None);
+    }
+
+    #[test]
+    fn test_domain_matches() {
+        assert!(domain_matches("trusted.com", "trusted.com"));
+        assert!(domain_matches("videos.trusted.com", "*.trusted.com"));
+        assert!(domain_matches("trusted.com", "www.Trusted.com"));
+        assert!(!domain_matches("untrusted.com", "trusted.com"));
+        assert!(!domain_matches("trusted.com", ""));
+        assert!(!domain_matches("trusted.com", "not a domain"));
+    }
+
+    #[test]
+    fn test_keyword_with_domain_allow_policy() {
+        let options = FilterConfig {
+            auto_anchor: AutoAnchor::Contains,
+            policy: FilterPolicy::KeywordWithDomainAllow,
+            ..FilterConfig::default()
+        };
+        let filter = make_filter_with("porn\n", "trusted.com\n", options);
+
+        assert_eq!(filter.evaluate("porn on trusted.com"), Verdict::Whitelisted);
+        assert_eq!(filter.evaluate("porn on random.com"), Verdict::Blocked(BlockedBy::new("porn")));
+        assert_eq!(filter.evaluate("cooking on random.com"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("porn with no domain"), Verdict::Blocked(BlockedBy::new("porn")));
+        // The whitelist line is a domain, not a title pattern.
+        assert_eq!(filter.evaluate("porn about trusted.com.evil.org"), Verdict::Blocked(BlockedBy::new("porn")));
+    }
+
+    #[test]
+    fn test_domain_allow_whitelist_lines_are_literal_domains() {
+        let options = FilterConfig {
+            auto_anchor: AutoAnchor::Contains,
+            policy: FilterPolicy::KeywordWithDomainAllow,
+            ..FilterConfig::default()
+        };
+        let filter = make_filter_with("porn\n", "*.trusted.com\n.other.org\n(unclosed\n", options);
+
+        assert_eq!(filter.whitelist_len(), 2);
+        assert_eq!(filter.evaluate("porn on videos.trusted.com"), Verdict::Whitelisted);
+        assert_eq!(filter.evaluate("porn on trusted.com"), Verdict::Whitelisted);
+        assert_eq!(filter.evaluate("porn on www.other.org"), Verdict::Whitelisted);
+        assert_eq!(filter.evaluate("porn on nottrusted.com"), Verdict::Blocked(BlockedBy::new("porn")));
+        let skipped: Vec<_> = filter.skipped().iter().map(|s| (s.list, s.source.as_str())).collect();
+        assert_eq!(skipped, vec![("whitelist", "(unclosed")]);
+    }
+
+    #[test]
+    fn test_gzipped_blacklist() {
+        let patterns = ".*porn.*\n# comment\n.*adult.*\n";
+        let dir = tempfile::TempDir::new().unwrap();
+        let gz_path = dir.path().join("blacklist.txt.gz");
+        fs::write(&gz_path, gzip(patterns)).unwrap();
+        // No .gz extension: detected by the magic bytes instead.
+        let magic_path = dir.path().join("blacklist.bin");
+        fs::write(&magic_path, gzip(patterns)).unwrap();
+        let plain = create_temp_file_with_content(patterns);
+        let whitelist = dir.path().join("missing.txt");
+
+        let plain_filter = Filter::new(plain.path(), &whitelist).unwrap();
+        for path in [&gz_path, &magic_path] {
+            let filter = Filter::new(path, &whitelist).unwrap();
+            assert_eq!(filter.blacklist_len(), plain_filter.blacklist_len());
+            assert!(filter.is_blacklisted("free porn videos"));
+            assert!(filter.is_blacklisted("adult site"));
+            assert!(!filter.is_blacklisted("cooking tutorial"));
+        }
+    }
+
+    #[test]
+    fn test_anchor_pattern() {
+        assert_eq!(anchor_pattern("porn", AutoAnchor::None), "porn");
+        assert_eq!(anchor_pattern("porn", AutoAnchor::Contains), "(?s-m:porn)");
+        assert_eq!(anchor_pattern("a|b", AutoAnchor::Whole), "^(?:a|b)$");
+    }
+
+    #[test]
+    fn test_auto_anchor_contains() {
+        let filter = make_filter_with("porn\nadult content", "", anchored(AutoAnchor::Contains));
+
+        assert!(filter.is_blacklisted("free porn videos"));
+        assert!(filter.is_blacklisted("PORN"));
+        assert!(filter.is_blacklisted("some adult content here"));
+        assert!(!filter.is_blacklisted("cooking tutorial"));
+
+        let (_, pattern) = filter.find_blacklisted_title(&["free porn".to_string()]).unwrap();
+        assert_eq!(pattern, "porn");
+    }
+
+    #[test]
+    fn test_auto_anchor_contains_dot_matches_newline() {
+        let filter = make_filter_with("bad.title", "", anchored(AutoAnchor::Contains));
+        assert!(filter.is_blacklisted("bad\ntitle"));
+    }
+
+    #[test]
+    fn test_auto_anchor_whole() {
+        let filter = make_filter_with("porn|xxx\nadult .*", "", anchored(AutoAnchor::Whole));
+
+        assert!(filter.is_blacklisted("porn"));
+        assert!(filter.is_blacklisted("XXX"));
+        assert!(!filter.is_blacklisted("free porn videos"));
+        assert!(!filter.is_blacklisted("xxx movies"));
+        assert!(filter.is_blacklisted("adult videos"));
+        assert!(!filter.is_blacklisted("my adult videos"));
+    }
+
+    #[test]
+    fn test_auto_anchor_applies_to_whitelist() {
+        let filter = make_filter_with("porn.*", "porn education", anchored(AutoAnchor::Whole));
+
+        assert!(filter.is_whitelisted("porn education"));
+        assert!(!filter.is_whitelisted("porn education videos"));
+        assert!(!filter.is_blacklisted("porn education"));
+        assert!(filter.is_blacklisted("porn education videos"));
+    }
+
+    fn stripping() -> FilterConfig {
+        FilterConfig {
+            strip_browser_suffix: true,
+            ..FilterConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_strip_browser_suffix() {
+        assert_eq!(strip_browser_suffix("Bad Page \u{2014} Mozilla Firefox", &[]), "Bad Page");
+        assert_eq!(strip_browser_suffix("Bad Page - Google Chrome", &[]), "Bad Page");
+        assert_eq!(strip_browser_suffix("News - Site - Chromium", &[]), "News - Site");
+        assert_eq!(strip_browser_suffix("Google Chrome", &[]), "Google Chrome");
+        assert_eq!(strip_browser_suffix("Bad Page - Mozilla Firefox Nightly", &[]),
+            "Bad Page - Mozilla Firefox Nightly");
+
+        let extra = vec!["LibreWolf".to_string()];
+        assert_eq!(strip_browser_suffix("Bad Page \u{2014} LibreWolf", &extra), "Bad Page");
+        assert_eq!(strip_browser_suffix("Bad Page \u{2014} LibreWolf", &[]), "Bad Page \u{2014} LibreWolf");
+    }
+
+    #[test]
+    fn test_evaluate_strips_browser_suffix() {
+        let whole = FilterConfig {
+            auto_anchor: AutoAnchor::Whole,
+            ..stripping()
+        };
+        let filter = make_filter_with("bad page", "", whole);
+        assert!(filter.is_blacklisted("Bad Page \u{2014} Mozilla Firefox"));
+        assert!(!filter.is_blacklisted("Bad Page \u{2014} Mozilla Firefox (Private Browsing)"));
+
+        let unstripped = make_filter_with("bad page", "", anchored(AutoAnchor::Whole));
+        assert!(!unstripped.is_blacklisted("Bad Page \u{2014} Mozilla Firefox"));
+
+        // The browser name itself no longer causes accidental matches.
+        let filter = make_filter_with(".*chrome.*", "", stripping());
+        assert!(!filter.is_blacklisted("Cooking - Google Chrome"));
+        assert!(filter.is_blacklisted("Chrome plating tips - Google Chrome"));
+    }
+
+    fn requiring(min_matches: usize) -> FilterConfig {
+        FilterConfig {
+            min_matches,
+            ..FilterConfig::default()
+        }
+    }
+
+    #[test]
+    fn test_min_matches_one() {
+        let filter = make_filter_with(".*video.*\n.*xxx.*", "", requiring(1));
+
+        assert_eq!(filter.evaluate("cat video"), Verdict::Blocked(BlockedBy::new(".*video.*")));
+        assert_eq!(filter.evaluate("xxx video"), Verdict::Blocked(BlockedBy::new(".*video.*")));
+    }
+
+    #[test]
+    fn test_min_matches_two() {
+        let filter = make_filter_with(".*video.*\n.*xxx.*", ".*trailer.*", requiring(2));
+
+        assert_eq!(filter.evaluate("cat video"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("xxx"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("xxx video"), Verdict::Blocked(BlockedBy::new(".*video.*")));
+        assert_eq!(filter.evaluate("xxx video trailer"), Verdict::Whitelisted);
+    }
+
+    #[test]
+    fn test_evaluate_verdicts() {
+        let filter = make_filter(".*porn.*", ".*education.*");
+
+        assert_eq!(filter.evaluate("cooking tutorial"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("free porn"), Verdict::Blocked(BlockedBy::new(".*porn.*")));
+        assert_eq!(filter.evaluate("porn education"), Verdict::Whitelisted);
+    }
+
+    #[test]
+    fn test_deny_mode_allows_only_whitelisted() {
+        let filter = make_filter_with(".*porn.*", ".*education.*\n.*medical.*", deny_mode());
+
+        assert_eq!(filter.evaluate("sex education documentary"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("medical lecture"), Verdict::Allowed);
+        assert_eq!(filter.evaluate("cooking tutorial"), Verdict::Blocked(BlockedBy::new(NOT_WHITELISTED)));
+        assert!(filter.is_blacklisted("cooking tutorial"));
+
+        let titles = vec!["medical lecture".to_string(), "cooking tutorial".to_string()];
+        let (title, pattern) = filter.find_blacklisted_title(&titles).unwrap();
+        assert_eq!(title, "cooking tutorial");
+        assert_eq!(pattern, NOT_WHITELISTED);
+    }
+
+    #[test]
+    fn test_deny_mode_exempts_empty_titles() {
+        let filter = make_filter_with("", ".*education.*", deny_mode());
+
+        assert_eq!(filter.evaluate(""), Verdict::Allowed);
+        assert_eq!(filter.evaluate("   "), Verdict::Allowed);
+        assert!(!filter.check_titles(&["".to_string(), " ".to_string()]));
+    }
+
+    #[test]
+    fn test_deny_mode_with_empty_whitelist_blocks_everything() {
+        let filter = make_filter_with("", "", deny_mode());
+        assert!(filter.check_titles(&["anything".to_string()]));
+    }
+
+    #[test]
+    fn test_study_mode_blocks_titles_outside_the_study_whitelist() {
+        let study = make_filter_with(".*porn.*", ".*khan academy.*\n.*wikipedia.*", FilterConfig::default().for_study_mode());
+
+        assert_eq!(study.evaluate("Algebra - Khan Academy"), Verdict::Allowed);
+        assert_eq!(study.evaluate("Funny cat videos"), Verdict::Blocked(BlockedBy::new(NOT_WHITELISTED)));
+        let normal = make_filter_with(".*porn.*", ".*khan academy.*", FilterConfig::default());
+        assert_eq!(normal.evaluate("Funny cat videos"), Verdict::Allowed);
+    }
+
     #[test]
     fn test_filter_new_with_valid_files() {
         let blacklist_content = ".*porn.*\n.*adult.*\n.*xxx.*";
@@ -144,6 +1330,19 @@ mod tests {
         assert_eq!(filter.whitelist.len(), 2);
     }
 
+    #[test]
+    fn test_blacklist_spanning_several_sets_reports_the_right_pattern() {
+        let count = 2 * BLACKLIST_SET_CHUNK + 5;
+        let blacklist: String = (0..count).map(|i| format!("\\bbanned{i}\\b\n")).collect();
+        let filter = make_filter(&blacklist, "");
+
+        assert_eq!(filter.blacklist_len(), count);
+        let last = format!("banned{}", count - 1);
+        let (_, pattern) = filter.find_blacklisted_title(&[format!("watching {last} now")]).unwrap();
+        assert_eq!(pattern, format!("\\b{last}\\b"));
+        assert!(!filter.is_blacklisted(&format!("watching banned{count} now")));
+    }
+
     #[test]
     fn test_filter_new_with_nonexistent_files() {
         let filter =
@@ -383,6 +1582,43 @@ mod tests {
         assert!(filter.find_blacklisted_title(&titles).is_none());
     }
 
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("P-O-R-N video"), "pornvideo");
+        assert_eq!(slugify("  x.x.x  "), "xxx");
+        assert_eq!(slugify("Café_Ünïcode 42!"), "caféünïcode42");
+        assert_eq!(slugify("--- !!! ---"), "");
+    }
+
+    #[test]
+    fn test_match_slug_catches_obfuscated_keywords() {
+        let keywords = create_temp_file_with_content("# comment\nporn\nx x x\n---\n");
+        let options = FilterConfig {
+            match_slug: true,
+            slug_keywords: Some(keywords.path().display().to_string()),
+            ..FilterConfig::default()
+        };
+        let filter = make_filter_with("", ".*documentary.*", options.clone());
+
+        let cases = [
+            ("p-o-r-n video", Some("porn")),
+            ("P.O.R.N", Some("porn")),
+            ("p o r n", Some("porn")),
+            ("p_0rn", None),
+            ("XxX clips", Some("x x x")),
+            ("x-x-x", Some("x x x")),
+        ];
+        for (title, keyword) in cases {
+            let expected = keyword.map_or(Verdict::Allowed, |k| Verdict::Blocked(BlockedBy::new(k)));
+            assert_eq!(filter.evaluate(title), expected, "title '{}'", title);
+        }
+        assert_eq!(filter.evaluate("p-o-r-n documentary"), Verdict::Whitelisted);
+        assert_eq!(filter.evaluate("cooking tutorial"), Verdict::Allowed);
+
+        let disabled = make_filter_with("", "", FilterConfig { match_slug: false, ..options });
+        assert_eq!(disabled.evaluate("p-o-r-n video"), Verdict::Allowed);
+    }
+
     #[test]
     fn test_find_blacklisted_title_pattern_string_correct() {
         let filter = make_filter(r"(?i).*\bxxx\b.*", "");
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::process::Command;
+
+use crate::command::x_client;
+
+/// Where keyboard focus is moved after the browser is killed, so keystrokes
+/// meant for the browser don't land in whatever window the WM picks next.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FocusTarget {
+    /// The desktop root window.
+    Root,
+    /// The first window whose `WM_CLASS` matches.
+    Class(String),
+}
+
+impl FocusTarget {
+    /// `root` selects the root window; anything else is taken as a `WM_CLASS`.
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim() {
+            "" => Err(anyhow!("monitoring.focus_after_kill must not be empty")),
+            "root" => Ok(FocusTarget::Root),
+            class => Ok(FocusTarget::Class(class.to_string())),
+        }
+    }
+}
+
+/// `xdotool` invocation that activates the first window of `class`.
+pub fn focus_command(class: &str) -> Vec<String> {
+    ["xdotool", "search", "--limit", "1", "--class", class, "windowactivate"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// Moves focus to `target`. Root focus goes through `focus_root` (Xlib) so this
+/// module doesn't need a display connection.
+pub fn focus_safe_window<F>(target: &FocusTarget, focus_root: F) -> Result<()>
+where
+    F: FnOnce() -> Result<()>,
+{
+    match target {
+        FocusTarget::Root => {
+            info!("Focusing root window after kill");
+            focus_root()
+        }
+        FocusTarget::Class(class) => {
+            let argv = focus_command(class);
+            info!("Focusing '{}' after kill: {:?}", class, argv);
+            let status = x_client(Command::new(&argv[0]).args(&argv[1..])).status()?;
+            if !status.success() {
+                warn!("xdotool could not activate a '{}' window ({})", class, status);
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_focus_target() {
+        assert_eq!(FocusTarget::parse("root").unwrap(), FocusTarget::Root);
+        assert_eq!(FocusTarget::parse(" Thunar ").unwrap(), FocusTarget::Class("Thunar".to_string()));
+        assert!(FocusTarget::parse("  ").is_err());
+    }
+
+    #[test]
+    fn test_focus_command_for_class() {
+        assert_eq!(focus_command("xfdesktop"), vec![
+            "xdotool", "search", "--limit", "1", "--class", "xfdesktop", "windowactivate",
+        ]);
+    }
+
+    #[test]
+    fn test_focus_root_uses_callback() {
+        let mut called = false;
+        focus_safe_window(&FocusTarget::Root, || {
+            called = true;
+            Ok(())
+        })
+        .unwrap();
+        assert!(called);
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{debug, info};
+use rusqlite::{Connection, OpenFlags};
+use std::path::Path;
+
+use crate::filter::{BlockedBy, Filter};
+
+/// Microseconds between 1601-01-01 (Chromium's epoch) and 1970-01-01.
+const CHROME_EPOCH_OFFSET_MICROS: i64 = 11_644_473_600_000_000;
+
+/// Converts `instant` to Chromium's timestamp format (microseconds since 1601).
+pub fn to_chrome_time(instant: DateTime<Utc>) -> i64 {
+    instant.timestamp_micros() + CHROME_EPOCH_OFFSET_MICROS
+}
+
+/// Opens a Chromium `History` database read-only. `immutable=1` lets it be read
+/// while the browser holds its lock, and guarantees nothing is ever written.
+fn open_read_only(db_path: &Path) -> Result<Connection> {
+    let uri = format!("file:{}?immutable=1", db_path.display());
+    let flags = OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    Ok(Connection::open_with_flags(uri, flags)?)
+}
+
+fn has_table(conn: &Connection, table: &str) -> Result<bool> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
+
+/// Download target paths, download URLs, visited URLs and page titles recorded after
+/// `since`. Tables missing from the database are skipped.
+pub fn recent_entries(db_path: &Path, since: DateTime<Utc>) -> Result<Vec<String>> {
+    let conn = open_read_only(db_path)?;
+    let since = to_chrome_time(since);
+    let mut entries = Vec::new();
+
+    if has_table(&conn, "downloads")? {
+        let mut stmt = conn.prepare(
+            "SELECT target_path, tab_url FROM downloads WHERE start_time > ?1 ORDER BY start_time",
+        )?;
+        let rows = stmt.query_map([since], |row| {
+            Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+        for row in rows {
+            let (path, url) = row?;
+            entries.extend(path.into_iter().chain(url).filter(|s| !s.is_empty()));
+        }
+    }
+
+    if has_table(&conn, "urls")? {
+        let mut stmt = conn.prepare(
+            "SELECT url, title FROM urls WHERE last_visit_time > ?1 ORDER BY last_visit_time",
+        )?;
+        let rows = stmt.query_map([since], |row| {
+            Ok((row.get::<_, Option<String>>(0)?, row.get::<_, Option<String>>(1)?))
+        })?;
+        for row in rows {
+            let (url, title) = row?;
+            entries.extend(url.into_iter().chain(title).filter(|s| !s.is_empty()));
+        }
+    }
+
+    debug!("History '{}': {} entr(ies) since {}", db_path.display(), entries.len(), since);
+    Ok(entries)
+}
+
+/// The first history entry since `since` that the filter blocks, with its pattern.
+pub fn find_history_hit(db_path: &Path, since: DateTime<Utc>, filter: &Filter) -> Result<Option<(String, BlockedBy)>> {
+    let entries = recent_entries(db_path, since)?;
+    let hit = filter.find_blocked_title(&entries);
+    if let Some((_, blocked)) = &hit {
+        info!("History hit matched '{}'", blocked);
+    }
+    Ok(hit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::{NamedTempFile, TempDir};
+
+    fn seeded_db(dir: &Path, now: DateTime<Utc>) -> std::path::PathBuf {
+        let path = dir.join("History");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE downloads (id INTEGER PRIMARY KEY, target_path TEXT, tab_url TEXT, start_time INTEGER);",
+        )
+        .unwrap();
+        let old = to_chrome_time(now - chrono::Duration::hours(2));
+        let recent = to_chrome_time(now - chrono::Duration::minutes(1));
+        for (target, url, time) in [
+            ("/home/kid/Downloads/old-porn.mp4", "https://old.example/x", old),
+            ("/home/kid/Downloads/recipes.pdf", "https://cooking.example/r", recent),
+            ("/home/kid/Downloads/clip.mp4", "https://xxx.example/free-porn", recent),
+        ] {
+            conn.execute(
+                "INSERT INTO downloads (target_path, tab_url, start_time) VALUES (?1, ?2, ?3)",
+                rusqlite::params![target, url, time],
+            )
+            .unwrap();
+        }
+        path
+    }
+
+    fn porn_filter() -> Filter {
+        let mut blacklist = NamedTempFile::new().unwrap();
+        blacklist.write_all(b".*porn.*\n").unwrap();
+        let whitelist = NamedTempFile::new().unwrap();
+        Filter::new(blacklist.path(), whitelist.path()).unwrap()
+    }
+
+    #[test]
+    fn test_recent_entries_from_downloads() {
+        let dir = TempDir::new().unwrap();
+        let now = Utc::now();
+        let db = seeded_db(dir.path(), now);
+
+        let entries = recent_entries(&db, now - chrono::Duration::minutes(30)).unwrap();
+        assert_eq!(entries, vec![
+            "/home/kid/Downloads/recipes.pdf",
+            "https://cooking.example/r",
+            "/home/kid/Downloads/clip.mp4",
+            "https://xxx.example/free-porn",
+        ]);
+    }
+
+    #[test]
+    fn test_find_history_hit() {
+        let dir = TempDir::new().unwrap();
+        let now = Utc::now();
+        let db = seeded_db(dir.path(), now);
+        let filter = porn_filter();
+
+        let hit = find_history_hit(&db, now - chrono::Duration::minutes(30), &filter).unwrap();
+        assert_eq!(hit, Some(("https://xxx.example/free-porn".to_string(), BlockedBy::new(".*porn.*"))));
+
+        assert_eq!(find_history_hit(&db, now, &filter).unwrap(), None);
+    }
+
+    #[test]
+    fn test_database_is_not_modified() {
+        let dir = TempDir::new().unwrap();
+        let now = Utc::now();
+        let db = seeded_db(dir.path(), now);
+        let before = std::fs::read(&db).unwrap();
+
+        recent_entries(&db, now - chrono::Duration::days(1)).unwrap();
+        assert_eq!(std::fs::read(&db).unwrap(), before);
+    }
+}
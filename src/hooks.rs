@@ -0,0 +1,126 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use crate::command::SpawnedPids;
+
+/// Events a hook can be attached to under the `hooks` config section.
+pub const HOOK_EVENTS: &[&str] = &["block", "hard_lock", "unblock", "break_start", "break_end"];
+
+/// Splits `template` on whitespace and substitutes `{event}`, `{timestamp}` and
+/// `{title}` in each argument. Splitting happens before substitution, so a title
+/// containing spaces or shell metacharacters stays a single, uninterpreted argument.
+pub fn expand_hook(template: &str, event: &str, timestamp: &str, title: &str) -> Vec<String> {
+    template
+        .split_whitespace()
+        .map(|arg| {
+            arg.replace("{event}", event)
+                .replace("{timestamp}", timestamp)
+                .replace("{title}", title)
+        })
+        .collect()
+}
+
+pub fn validate_hooks(hooks: &BTreeMap<String, String>) -> Result<()> {
+    for (event, template) in hooks {
+        if !HOOK_EVENTS.contains(&event.as_str()) {
+            return Err(anyhow!("unknown hook event '{}' (expected one of {})",
+                event, HOOK_EVENTS.join(", ")));
+        }
+        if template.trim().is_empty() {
+            return Err(anyhow!("hook '{}' has an empty command", event));
+        }
+    }
+    Ok(())
+}
+
+/// Runs the configured command for each state transition without waiting for it.
+pub struct HookRunner {
+    hooks: BTreeMap<String, String>,
+    spawned: SpawnedPids,
+}
+
+impl HookRunner {
+    pub fn new(hooks: BTreeMap<String, String>) -> Self {
+        HookRunner { hooks, spawned: SpawnedPids::default() }
+    }
+
+    /// Records the hook processes this runner starts in `spawned`.
+    pub fn with_spawned(mut self, spawned: SpawnedPids) -> Self {
+        self.spawned = spawned;
+        self
+    }
+
+    /// Failures to spawn are logged; a non-zero exit is logged once the hook finishes.
+    pub fn fire(&self, event: &str, title: &str) {
+        let Some(template) = self.hooks.get(event) else {
+            return;
+        };
+        let timestamp = chrono::Local::now().to_rfc3339();
+        let argv = expand_hook(template, event, &timestamp, title);
+        let Some((program, args)) = argv.split_first() else {
+            return;
+        };
+
+        info!("Running '{}' hook: {:?}", event, argv);
+        match self.spawned.spawn(Command::new(program).args(args)) {
+            Ok(child) => {
+                let event = event.to_string();
+                self.spawned.wait_in_background(child, move |result| match result {
+                    Ok(status) if !status.success() => warn!("'{}' hook exited with {}", event, status),
+                    Err(e) => warn!("Failed to wait for '{}' hook: {}", event, e),
+                    Ok(_) => {}
+                });
+            }
+            Err(e) => warn!("Failed to run '{}' hook '{}': {}", event, program, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_hook_for_block_event() {
+        let argv = expand_hook(
+            "/usr/local/bin/report --event={event} --at {timestamp} {title}",
+            "block",
+            "2026-01-02T03:04:05+00:00",
+            "Bad Page; rm -rf ~",
+        );
+        assert_eq!(argv, vec![
+            "/usr/local/bin/report",
+            "--event=block",
+            "--at",
+            "2026-01-02T03:04:05+00:00",
+            "Bad Page; rm -rf ~",
+        ]);
+    }
+
+    #[test]
+    fn test_expand_hook_without_placeholders() {
+        assert_eq!(expand_hook("  paplay   /tmp/ding.wav ", "unblock", "t", ""),
+            vec!["paplay", "/tmp/ding.wav"]);
+    }
+
+    #[test]
+    fn test_validate_hooks() {
+        let mut hooks = BTreeMap::new();
+        hooks.insert("block".to_string(), "logger {title}".to_string());
+        assert!(validate_hooks(&hooks).is_ok());
+
+        hooks.insert("unblocked".to_string(), "logger".to_string());
+        assert!(validate_hooks(&hooks).is_err());
+
+        let mut empty = BTreeMap::new();
+        empty.insert("break_end".to_string(), "  ".to_string());
+        assert!(validate_hooks(&empty).is_err());
+    }
+
+    #[test]
+    fn test_fire_unknown_event_is_noop() {
+        HookRunner::new(BTreeMap::new()).fire("block", "title");
+    }
+}
@@ -1,7 +1,36 @@
+pub mod actions;
+pub mod audit;
 pub mod background;
+pub mod benchmark;
 pub mod browser;
+pub mod bundle;
+pub mod capture;
+pub mod classifier;
+pub mod command;
 pub mod config;
+pub mod daemon_loop;
+pub mod detection;
+pub mod doctor;
+pub mod events;
 pub mod filter;
+pub mod focus;
+#[cfg(feature = "history")]
+pub mod history;
+pub mod hooks;
+#[cfg(feature = "management")]
+pub mod management;
+pub mod notify;
+pub mod privacy;
+pub mod privileges;
+pub mod reload;
+pub mod scan;
+#[cfg(feature = "sqlite-state")]
+pub mod sqlite_store;
 pub mod state;
+pub mod timezone;
+pub mod title_encoding;
+pub mod title_history;
 #[cfg(not(test))]
 pub mod window_monitor;
+pub mod window_info;
+pub mod xauthority;
@@ -1,9 +1,38 @@
+mod actions;
+mod audit;
 mod background;
+mod benchmark;
 mod browser;
+mod bundle;
+mod capture;
+mod classifier;
+mod command;
 mod config;
+mod daemon_loop;
+mod detection;
+mod doctor;
+mod events;
 mod filter;
+mod focus;
+#[cfg(feature = "history")]
+mod history;
+mod hooks;
+#[cfg(feature = "management")]
+mod management;
+mod notify;
+mod privacy;
+mod privileges;
+mod reload;
+mod scan;
+#[cfg(feature = "sqlite-state")]
+mod sqlite_store;
 mod state;
+mod timezone;
+mod title_encoding;
+mod title_history;
+mod window_info;
 mod window_monitor;
+mod xauthority;
 
 use chrono::Utc;
 use clap::{Arg, Command};
@@ -16,12 +45,46 @@ use std::sync::Arc;
 use tokio::signal::unix::{signal, SignalKind};
 use tokio::time::{sleep, Duration};
 
-use background::BackgroundManager;
-use browser::BrowserManager;
-use config::Config;
-use filter::Filter;
-use state::AppState;
-use window_monitor::WindowMonitor;
+use actions::ActionSequence;
+use audit::{AuditLogger, RotatingWriter};
+use background::{BackgroundManager, CountdownTracker};
+use browser::{BrowserManager, KillReport, PendingRelaunch, RelaunchDecision};
+use classifier::ExternalClassifier;
+use command::{SpawnedPids, SystemRunner};
+use config::{
+    Config, DetectionAction, DetectionScope, ErrorAction, FailMode, MatchProperty, TitleBackend,
+    UntitledWindows,
+};
+use daemon_loop::{
+    apply_allow_once, format_scan_timings, next_check_interval, AllowOnce, ErrorCounter, TickTimings, TitleDebounce,
+    TICK_SAMPLES,
+};
+use detection::{blocked_titles, find_classified, find_detection, find_property_detection, DetectionRecord};
+use events::{Event, EventStream};
+use filter::{Filter, Verdict};
+use focus::FocusTarget;
+use hooks::HookRunner;
+use notify::Notifier;
+use privacy::TitleRedactor;
+use state::{
+    clamp_block_minutes, clock_jump, format_minutes, in_warmup, open_state_store, update_state,
+    AckOutcome, AppState, Restriction, StartupEnforcement, UsageLimit,
+};
+use timezone::Zone;
+use title_history::TitleHistory;
+use window_info::{
+    empty_decision, find_pip_window, gate_detection, is_fullscreen_title, keep_in_scope,
+    kiosk_should_kill, prioritize_fullscreen, remove_ignored_classes, remove_spawned,
+    remove_unsupervised, suspicious_untitled, EmptyDecision, MergedTitles, WindowTitles,
+    NO_WINDOWS_MARKER, PIP_MARKER, UNTITLED_MARKER,
+};
+use window_monitor::{DisplayTarget, WindowMonitor};
+
+/// Exit status after `monitoring.max_consecutive_errors` with `on_repeated_errors: exit`,
+/// so a supervisor can tell an X failure from other errors.
+const X_ERROR_EXIT_CODE: i32 = 3;
+/// Minimum time between writes of the scan timings shown by `status`.
+const TIMINGS_REPORT_INTERVAL: Duration = Duration::from_secs(60);
 
 fn init_logging(log_level: &str, log_file: &str) -> anyhow::Result<()> {
     let level_filter = log_level.parse().unwrap_or(log::LevelFilter::Warn);
@@ -49,7 +112,10 @@ fn init_logging(log_level: &str, log_file: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn record_titles(titles_file: &str, titles: &[String]) -> anyhow::Result<()> {
+fn record_titles(titles_file: &str, titles: &[String], redactor: &TitleRedactor) -> anyhow::Result<()> {
+    if !redactor.logs_unmatched() {
+        return Ok(());
+    }
     let mut existing: BTreeSet<String> = BTreeSet::new();
 
     if let Ok(content) = fs::read_to_string(titles_file) {
@@ -63,7 +129,7 @@ fn record_titles(titles_file: &str, titles: &[String]) -> anyhow::Result<()> {
 
     let before = existing.len();
     for title in titles {
-        existing.insert(title.clone());
+        existing.insert(redactor.redact(title, None));
     }
 
     if existing.len() != before {
@@ -83,6 +149,121 @@ fn record_titles(titles_file: &str, titles: &[String]) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn open_window_monitor(config: &Config) -> anyhow::Result<WindowMonitor> {
+    open_window_monitor_within(config, std::time::Duration::from_secs(config.monitoring.display_timeout_seconds))
+}
+
+/// The display for `doctor` and `scan`, retried for at most
+/// `monitoring.diagnostic_display_timeout_seconds`.
+fn open_diagnostic_monitor(config: &Config) -> anyhow::Result<WindowMonitor> {
+    open_window_monitor_within(config,
+        std::time::Duration::from_secs(config.monitoring.diagnostic_display_timeout_seconds))
+}
+
+fn open_window_monitor_within(config: &Config, timeout: std::time::Duration) -> anyhow::Result<WindowMonitor> {
+    let target = DisplayTarget::discover(
+        config.monitoring.display.as_deref(),
+        config.monitoring.xauthority.as_deref(),
+    );
+    let legacy_encoding = config.monitoring.legacy_encoding.as_deref()
+        .map(title_encoding::legacy_encoding)
+        .transpose()?;
+    WindowMonitor::new_for_target(&target, timeout)
+        .map(|monitor| {
+            monitor
+                .with_max_windows(config.monitoring.max_windows)
+                .with_untitled_windows(config.monitoring.untitled_windows == UntitledWindows::Suspicious)
+                .with_focus_tracking(config.monitoring.detection_scope == DetectionScope::FocusedOnly)
+                .with_legacy_encoding(legacy_encoding)
+                .with_extra_properties(
+                    config.monitoring.match_properties.contains(&MatchProperty::IconName),
+                    config.monitoring.match_properties.contains(&MatchProperty::Role),
+                )
+        })
+}
+
+fn background_manager(config: &Config) -> BackgroundManager {
+    BackgroundManager::new()
+        .with_reassert(config.backgrounds.reassert.then_some(background::REASSERT_DELAY))
+}
+
+fn kill_browser(browser_manager: &BrowserManager) -> anyhow::Result<KillReport> {
+    let report = browser_manager.kill_browser_processes()?;
+    if !report.is_empty() {
+        info!("Kill report: {}", report);
+    }
+    if !report.failed.is_empty() {
+        warn!("Could not signal browser pid(s) {:?}", report.failed);
+    }
+    Ok(report)
+}
+
+/// The window sources named in `monitoring.backends`, each listed once.
+fn title_sources<'a>(backends: &[TitleBackend], xlib: &'a WindowMonitor) -> MergedTitles<'a> {
+    let mut seen = Vec::new();
+    let mut sources: Vec<&dyn WindowTitles> = Vec::new();
+    for backend in backends {
+        if seen.contains(backend) {
+            continue;
+        }
+        seen.push(*backend);
+        sources.push(match backend {
+            TitleBackend::Xlib => xlib,
+        });
+    }
+    MergedTitles(sources)
+}
+
+/// `monitoring.kiosk_mode`: sends SIGTERM to every process owning a window whose
+/// class is not allowed. The daemon, its helpers and the browser are spared.
+fn enforce_kiosk(config: &Config, window_monitor: &WindowMonitor, spawned: &SpawnedPids, browser_pids: &[i32]) {
+    let mut windows = match window_monitor.get_all_windows() {
+        Ok(windows) => windows,
+        Err(e) => {
+            warn!("Kiosk mode: failed to list windows: {}", e);
+            return;
+        }
+    };
+    remove_unsupervised(&mut windows, &config.security.supervised_users,
+        |pid| privileges::process_user(Path::new("/proc"), pid));
+    let mut protected = vec![std::process::id() as i32];
+    protected.extend(spawned.pids());
+    protected.extend_from_slice(browser_pids);
+    let local_host = match nix::unistd::gethostname() {
+        Ok(name) => name.to_string_lossy().into_owned(),
+        Err(e) => {
+            warn!("Kiosk mode: cannot read the host name: {}", e);
+            return;
+        }
+    };
+
+    let mut killed = Vec::new();
+    for window in &windows {
+        if !kiosk_should_kill(window, &config.monitoring.allowed_classes, &config.monitoring.kiosk_safe_classes,
+            &protected, &local_host, |pid| privileges::process_uid(Path::new("/proc"), pid))
+        {
+            continue;
+        }
+        let Some(pid) = window.pid.filter(|pid| !killed.contains(pid)) else {
+            continue;
+        };
+        killed.push(pid);
+        warn!("Kiosk mode: killing pid {} — class '{}' is not allowed ('{}')",
+            pid, window.class.as_deref().unwrap_or_default(), window.title);
+        if let Err(e) = nix::sys::signal::kill(nix::unistd::Pid::from_raw(pid), nix::sys::signal::Signal::SIGTERM) {
+            warn!("Kiosk mode: could not signal pid {}: {}", pid, e);
+        }
+    }
+}
+
+fn focus_after_kill(target: Option<&FocusTarget>, window_monitor: &WindowMonitor) {
+    if let Some(target) = target {
+        if let Err(e) = focus::focus_safe_window(target, || window_monitor.focus_root()) {
+            warn!("Failed to move focus after kill: {}", e);
+        }
+    }
+}
+
 #[tokio::main]
 async fn main() {
     let matches = Command::new("Inappropriate Video Handler")
@@ -103,6 +284,38 @@ async fn main() {
                 .help("Start browser with configured URL")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("profile")
+                .short('p')
+                .long("profile")
+                .value_name("NAME")
+                .help("Profile to run under (defaults to the USER name if a matching profile exists)"),
+        )
+        .arg(
+            Arg::new("status")
+                .long("status")
+                .help("Print the block, break and clean streak state and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("filter-summary")
+                .long("filter-summary")
+                .help("With --status, also summarise the loaded pattern lists")
+                .requires("status")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("print-config")
+                .long("print-config")
+                .help("Print the fully resolved configuration as YAML and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("events-json")
+                .long("events-json")
+                .help("Daemon: write one JSON object per event to stdout (human messages go to stderr)")
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("log-level")
                 .long("log-level")
@@ -111,12 +324,95 @@ async fn main() {
                 .value_parser(["error", "warn", "info", "debug", "trace"])
                 .default_value("info"),
         )
+        .subcommand(
+            Command::new("lockdown")
+                .about("Block the browser immediately for the given number of minutes")
+                .arg(
+                    Arg::new("minutes")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("no-cap")
+                        .long("no-cap")
+                        .help("Ignore timeouts.max_block_minutes")
+                        .action(clap::ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("extend-break")
+                .about("Make the current bathroom break longer by the given number of minutes")
+                .arg(
+                    Arg::new("minutes")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("snooze-break")
+                .about("Postpone the next bathroom break by the given number of minutes")
+                .arg(
+                    Arg::new("minutes")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("acknowledge")
+                .about("Acknowledge a pending detection so the browser is closed gently"),
+        )
+        .subcommand(
+            Command::new("allow-once")
+                .about("Let the next detection through instead of blocking it"),
+        )
+        .subcommand(
+            Command::new("study-mode")
+                .about("Allow only titles matching files.study_whitelist for the given number of minutes")
+                .arg(
+                    Arg::new("minutes")
+                        .required(true)
+                        .value_parser(clap::value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("scan")
+                .about("Print the filter decision for every browser window title right now"),
+        )
+        .subcommand(
+            Command::new("benchmark")
+                .about("Time the filter over a file of sample titles, one per line")
+                .arg(Arg::new("titles-file").required(true)),
+        )
+        .subcommand(
+            Command::new("list-patterns")
+                .about("Print the blacklist and whitelist patterns the filter would use"),
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Check the environment and configuration, then exit"),
+        )
+        .subcommand(
+            Command::new("export")
+                .about("Write the config, state file and pattern lists to a tar bundle")
+                .arg(
+                    Arg::new("output")
+                        .short('o')
+                        .long("output")
+                        .value_name("FILE")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            Command::new("import")
+                .about("Restore a bundle written by export, replacing the config file")
+                .arg(Arg::new("bundle").value_name("FILE").required(true)),
+        )
         .get_matches();
 
     let log_level = matches.get_one::<String>("log-level").map(String::as_str).unwrap_or("info");
     let config_path = matches.get_one::<String>("config").unwrap();
 
-    let config = match Config::load(config_path) {
+    let mut config = match Config::load(config_path) {
         Ok(config) => config,
         Err(e) => {
             eprintln!("Failed to load config ({}), using defaults", e);
@@ -124,44 +420,434 @@ async fn main() {
         }
     };
 
+    let requested_profile = matches.get_one::<String>("profile").map(String::as_str);
+    let user = std::env::var("USER").ok();
+    let profile = match config.select_profile(requested_profile, user.as_deref()) {
+        Ok(profile) => profile,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(name) = &profile {
+        if let Err(e) = config.apply_profile(name) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Err(e) = config.apply_env_overrides(std::env::vars(), &Config::dir_of(Path::new(config_path))) {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    }
+
+    if matches.subcommand_name() == Some("doctor") {
+        let results = doctor::run_checks(&config, config_path, || {
+            Ok(open_diagnostic_monitor(&config)?.get_all_windows()?.len())
+        });
+        let healthy = doctor::report(&results);
+        std::process::exit(if healthy { 0 } else { 1 });
+    }
+
+    if let Some(("import", sub)) = matches.subcommand() {
+        let bundle = sub.get_one::<String>("bundle").unwrap();
+        match bundle::import_bundle(bundle, config_path) {
+            Ok(_) => println!("Imported '{}' into '{}'", bundle, config_path),
+            Err(e) => {
+                eprintln!("Failed to import '{}': {}", bundle, e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Err(e) = config.validate() {
+        eprintln!("Invalid config: {}", e);
+        std::process::exit(1);
+    }
+
+    if matches.get_flag("print-config") {
+        match config.to_yaml() {
+            Ok(yaml) => print!("{}", yaml),
+            Err(e) => {
+                eprintln!("Failed to serialise config: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
     if let Err(e) = init_logging(log_level, &config.files.log_file) {
         eprintln!("Failed to initialise logger: {}", e);
     }
 
     info!("Config loaded from '{}'", config_path);
+    if let Some(name) = &profile {
+        info!("Using profile '{}'", name);
+    }
     debug!("  browser.executable = '{}'", config.browser.executable);
     debug!("  browser.process_name = '{}'", config.browser.process_name);
     debug!("  browser.url = '{}'", config.browser.url);
+    debug!("  browser.launch_wrapper = {:?} {:?}", config.browser.launch_wrapper, config.browser.launch_wrapper_args);
+    debug!("  browser.launch_args = {:?}", config.browser.launch_args);
+    debug!("  browser.auto_relaunch = {} (delay {}s)", config.browser.auto_relaunch, config.browser.relaunch_delay_seconds);
+    debug!("  browser.ephemeral_profile = {}", config.browser.ephemeral_profile);
     debug!("  monitoring.check_frequency_seconds = {}", config.monitoring.check_frequency_seconds);
+    debug!("  monitoring.title_history_size = {}", config.monitoring.title_history_size);
+    debug!("  monitoring.display = {:?}", config.monitoring.display);
+    debug!("  monitoring.xauthority = {:?}", config.monitoring.xauthority);
+    debug!("  monitoring.legacy_encoding = {:?}", config.monitoring.legacy_encoding);
+    debug!("  monitoring.capture_on_block = {:?}", config.monitoring.capture_on_block);
+    debug!("  monitoring.on_detection = {:?} (lock_command={:?})",
+        config.monitoring.on_detection, config.monitoring.lock_command);
+    debug!("  monitoring.max_consecutive_errors = {} (on_repeated_errors={:?})",
+        config.monitoring.max_consecutive_errors, config.monitoring.on_repeated_errors);
+    debug!("  monitoring.clock_jump_threshold_seconds = {} (on_clock_jump={:?})",
+        config.monitoring.clock_jump_threshold_seconds, config.monitoring.on_clock_jump);
+    debug!("  monitoring.max_windows = {}", config.monitoring.max_windows);
+    debug!("  monitoring.block_evasion = {}", config.monitoring.block_evasion);
+    debug!("  monitoring.evasion_patterns = {:?}", config.monitoring.evasion_patterns);
+    debug!("  monitoring.display_timeout_seconds = {}", config.monitoring.display_timeout_seconds);
+    debug!("  monitoring.diagnostic_display_timeout_seconds = {}", config.monitoring.diagnostic_display_timeout_seconds);
+    debug!("  monitoring.fullscreen_priority = {}", config.monitoring.fullscreen_priority);
+    debug!("  monitoring.enforce_on_startup = {}", config.monitoring.enforce_on_startup);
+    debug!("  monitoring.match_properties = {:?}", config.monitoring.match_properties);
+    debug!("  monitoring.backends = {:?}", config.monitoring.backends);
+    debug!("  monitoring.kill_while_restricted = {}", config.monitoring.kill_while_restricted);
+    debug!("  monitoring.exclude_own_windows = {}", config.monitoring.exclude_own_windows);
+    debug!("  monitoring.kiosk_mode = {} (allowed_classes={:?}, kiosk_safe_classes={:?})",
+        config.monitoring.kiosk_mode, config.monitoring.allowed_classes, config.monitoring.kiosk_safe_classes);
+    debug!("  monitoring.focus_after_kill = {:?}", config.monitoring.focus_after_kill);
+    debug!("  monitoring.startup_delay_seconds = {}", config.monitoring.startup_delay_seconds);
+    debug!("  monitoring.detect_pip = {:?}", config.monitoring.detect_pip);
+    debug!("  monitoring.ignore_classes = {:?}", config.monitoring.ignore_classes);
+    debug!("  monitoring.ack_seconds = {}", config.monitoring.ack_seconds);
+    debug!("  monitoring.whitelist_recheck_seconds = {:?}", config.monitoring.whitelist_recheck_seconds);
+    debug!("  monitoring.title_debounce_scans = {}", config.monitoring.title_debounce_scans);
+    debug!("  monitoring.detection_scope = {:?}", config.monitoring.detection_scope);
+    debug!("  monitoring.untitled_windows = {:?}", config.monitoring.untitled_windows);
+    debug!("  monitoring.require_browser_running = {}", config.monitoring.require_browser_running);
+    debug!("  monitoring.inspect_history = {:?}", config.monitoring.inspect_history);
     debug!("  timeouts.blacklist_timeout_minutes = {}", config.timeouts.blacklist_timeout_minutes);
     debug!("  timeouts.grace_retries = {}", config.timeouts.grace_retries);
     debug!("  timeouts.hard_lock_minutes = {}", config.timeouts.hard_lock_minutes);
     debug!("  timeouts.retry_reset_minutes = {}", config.timeouts.retry_reset_minutes);
+    debug!("  timeouts.escalation_multiplier = {}", config.timeouts.escalation_multiplier);
+    debug!("  timeouts.max_block_minutes = {:?}", config.timeouts.max_block_minutes);
     debug!("  timeouts.bathroom_break_minutes = {}", config.timeouts.bathroom_break_minutes);
     debug!("  timeouts.bathroom_break_interval_hours = {}", config.timeouts.bathroom_break_interval_hours);
+    debug!("  timeouts.break_jitter_minutes = {}", config.timeouts.break_jitter_minutes);
+    debug!("  timeouts.min_break_gap_minutes = {}", config.timeouts.min_break_gap_minutes);
+    debug!("  timeouts.daily_limit_minutes = {:?}", config.timeouts.daily_limit_minutes);
+    debug!("  timeouts.weekly_limit_minutes = {:?}", config.timeouts.weekly_limit_minutes);
+    debug!("  timeouts.max_snoozes_per_day = {}", config.timeouts.max_snoozes_per_day);
+    debug!("  timeouts.detection_cooldown_seconds = {}", config.timeouts.detection_cooldown_seconds);
     debug!("  files.blacklist = '{}'", config.files.blacklist);
     debug!("  files.whitelist = '{}'", config.files.whitelist);
     debug!("  files.state_file = '{}'", config.files.state_file);
+    debug!("  files.state_backend = {:?}", config.files.state_backend);
     debug!("  files.log_file = '{}'", config.files.log_file);
     debug!("  files.titles_file = '{}'", config.files.titles_file);
+    debug!("  filter.default_action = {:?}", config.filter.default_action);
+    debug!("  filter.ignore_whitelist = {}", config.filter.ignore_whitelist);
+    debug!("  filter.match_mode = {:?}", config.filter.match_mode);
+    debug!("  filter.match_slug = {} (keywords {:?})", config.filter.match_slug, config.filter.slug_keywords);
+    debug!("  filter.external_classifier = {:?} (cache {}s)",
+        config.filter.external_classifier, config.filter.classifier_cache_seconds);
+    debug!("  filter.fail_mode = {:?}", config.filter.fail_mode);
+    debug!("  filter.max_total_pattern_bytes = {}", config.filter.max_total_pattern_bytes);
+    debug!("  filter.strip_browser_suffix = {} (extra suffixes {:?})",
+        config.filter.strip_browser_suffix, config.filter.browser_suffixes);
+    debug!("  notifications.enabled = {}", config.notifications.enabled);
+    debug!("  notifications.quiet_hours = {:?}", config.notifications.quiet_hours);
+    debug!("  privacy.title_logging = {:?}", config.privacy.title_logging);
+    debug!("  hooks = {:?}", config.hooks);
+    debug!("  management.sync_url = {:?}", config.management.sync_url);
+    debug!("  management.sync_interval_seconds = {}", config.management.sync_interval_seconds);
+    debug!("  files.study_whitelist = {:?}", config.files.study_whitelist);
+    debug!("  security.run_as_user = {:?}", config.security.run_as_user);
+    debug!("  security.supervised_users = {:?}", config.security.supervised_users);
+    debug!("  timezone = {:?}", config.timezone);
+    debug!("  audit.enabled = {}", config.audit.enabled);
+    debug!("  audit.file = '{}'", config.audit.file);
+    debug!("  audit.max_size_mb = {}", config.audit.max_size_mb);
+    debug!("  audit.keep = {}", config.audit.keep);
+    debug!("  audit.compress = {}", config.audit.compress);
 
     let start_browser = matches.get_flag("start-browser");
     info!("Mode: start_browser={}", start_browser);
 
-    if start_browser {
-        if let Err(e) = handle_start_browser(&config).await {
+    if let Some(("lockdown", sub)) = matches.subcommand() {
+        let minutes = *sub.get_one::<u64>("minutes").unwrap();
+        if let Err(e) = handle_lockdown(&config, minutes, sub.get_flag("no-cap")) {
+            error!("Error applying lockdown: {}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(("extend-break", sub)) = matches.subcommand() {
+        let minutes = *sub.get_one::<u64>("minutes").unwrap();
+        if let Err(e) = handle_extend_break(&config, minutes) {
+            eprintln!("Cannot extend break: {}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(("snooze-break", sub)) = matches.subcommand() {
+        let minutes = *sub.get_one::<u64>("minutes").unwrap();
+        if let Err(e) = handle_snooze_break(&config, minutes) {
+            eprintln!("Cannot snooze break: {}", e);
+            std::process::exit(1);
+        }
+    } else if matches.subcommand_name() == Some("acknowledge") {
+        if let Err(e) = handle_acknowledge(&config) {
+            error!("Error acknowledging: {}", e);
+            std::process::exit(1);
+        }
+    } else if matches.subcommand_name() == Some("allow-once") {
+        if let Err(e) = handle_allow_once(&config) {
+            error!("Error granting allow-once: {}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(("study-mode", sub)) = matches.subcommand() {
+        let minutes = *sub.get_one::<u64>("minutes").unwrap();
+        if let Err(e) = handle_study_mode(&config, minutes) {
+            eprintln!("Cannot start study mode: {}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(("export", sub)) = matches.subcommand() {
+        let output = sub.get_one::<String>("output").unwrap();
+        match bundle::export_bundle(config_path, &config, output) {
+            Ok(entries) => println!("Exported {} to '{}'", entries.join(", "), output),
+            Err(e) => {
+                error!("Failed to export '{}': {}", output, e);
+                std::process::exit(1);
+            }
+        }
+    } else if matches.subcommand_name() == Some("list-patterns") {
+        match Filter::with_config(&config.files.blacklist, &config.files.whitelist, config.filter.clone()) {
+            Ok(filter) => {
+                for line in filter.listing() {
+                    println!("{}", line);
+                }
+            }
+            Err(e) => {
+                error!("Failed to load patterns: {}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if let Some(("benchmark", sub)) = matches.subcommand() {
+        let titles_file = sub.get_one::<String>("titles-file").unwrap();
+        if let Err(e) = handle_benchmark(&config, titles_file) {
+            eprintln!("Benchmark failed: {}", e);
+            std::process::exit(1);
+        }
+    } else if matches.subcommand_name() == Some("scan") {
+        if let Err(e) = handle_scan(&config) {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    } else if matches.get_flag("status") {
+        if let Err(e) = handle_status(&config, matches.get_flag("filter-summary")) {
+            eprintln!("Cannot read state: {}", e);
+            std::process::exit(1);
+        }
+    } else if start_browser {
+        if let Err(e) = handle_start_browser(&config, &EventStream::default()).await {
             error!("Error starting browser: {}", e);
         }
     } else {
-        if let Err(e) = run_daemon(&config).await {
+        let events = EventStream::new(matches.get_flag("events-json"));
+        if let Err(e) = run_daemon(config, config_path, profile.as_deref(), events).await {
             error!("Error running daemon: {}", e);
         }
     }
 }
 
-async fn handle_start_browser(config: &Config) -> anyhow::Result<()> {
-    info!("Loading state from '{}'", config.files.state_file);
-    let mut state = AppState::load(&config.files.state_file)?;
+/// The daemon reloads the state file every tick, so writing it here is picked up
+/// on the next check.
+fn handle_extend_break(config: &Config, minutes: u64) -> anyhow::Result<()> {
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    let until = update_state(&*store, |state| state.extend_bathroom_break(minutes, Utc::now()))?;
+    info!("Bathroom break extended by {} minute(s) until {}", minutes, until);
+
+    let zone = Zone::parse(config.timezone.as_deref())?;
+    println!("Bathroom break extended until {}", zone.naive_local(until).format("%H:%M"));
+    Ok(())
+}
+
+fn handle_snooze_break(config: &Config, minutes: u64) -> anyhow::Result<()> {
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    let zone = Zone::parse(config.timezone.as_deref())?;
+    let today = zone.naive_local(Utc::now()).date();
+    let (next, snoozes_used) = update_state(&*store, |state| {
+        let next = state.snooze_bathroom_break(minutes, config.timeouts.max_snoozes_per_day, today)?;
+        Ok((next, state.snoozes_used))
+    })?;
+    info!("Next bathroom break snoozed by {} minute(s) to {} ({}/{} today)",
+        minutes, next, snoozes_used, config.timeouts.max_snoozes_per_day);
+
+    println!("Next bathroom break postponed to {} ({} snooze(s) left today)",
+        zone.naive_local(next).format("%H:%M"),
+        config.timeouts.max_snoozes_per_day - snoozes_used);
+    Ok(())
+}
+
+fn handle_status(config: &Config, filter_summary: bool) -> anyhow::Result<()> {
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    let state = store.load()?;
+    let zone = Zone::parse(config.timezone.as_deref())?;
+    let local = |instant| zone.naive_local(instant).format("%H:%M");
+
+    match state.blocked_until.filter(|_| state.is_blocked()) {
+        Some(until) => println!("Blocked until {}", local(until)),
+        None => println!("Not blocked"),
+    }
+    match state.bathroom_break_until.filter(|_| state.in_bathroom_break) {
+        Some(until) => println!("On a bathroom break until {}", local(until)),
+        None => println!("Next bathroom break: {}", local(state.next_bathroom_break)),
+    }
+    if let Some(until) = state.study_until.filter(|_| state.in_study_mode(Utc::now())) {
+        println!("Study mode until {}", local(until));
+    }
+    println!("Violations: {}", state.violation_count);
+    println!("Clean streak: {}", format_minutes(state.clean_streak_minutes));
+    if let Some(max) = config.timeouts.weekly_limit_minutes {
+        let today = zone.naive_local(Utc::now()).date();
+        println!("Usage this week: {} of {}", format_minutes(state.usage_week_minutes(today)), format_minutes(max));
+    }
+    if let (Some(avg), Some(max)) = (state.scan_avg_ms, state.scan_max_ms) {
+        println!("Monitor: {}", format_scan_timings(avg, max));
+    }
+    if filter_summary {
+        let filter = Filter::with_config(&config.files.blacklist, &config.files.whitelist, config.filter.clone())?;
+        for line in filter.summary().lines() {
+            println!("{}", line);
+        }
+    }
+    Ok(())
+}
+
+fn handle_acknowledge(config: &Config) -> anyhow::Result<()> {
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    let pending = update_state(&*store, |state| {
+        Ok(state.acknowledge().then(|| state.pending_ack.clone()))
+    })?;
+    let Some(pending) = pending else {
+        println!("Nothing to acknowledge");
+        return Ok(());
+    };
+    if let Some(pending) = &pending {
+        let redactor = TitleRedactor::new(&config.privacy, config.filter.case_insensitive);
+        info!("Acknowledged detection of '{}'", redactor.redact(&pending.title, Some(&pending.pattern)));
+        println!("Acknowledged '{}' — the browser will be closed and blocked", pending.title);
+    }
+    Ok(())
+}
+
+fn handle_allow_once(config: &Config) -> anyhow::Result<()> {
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    update_state(&*store, |state| {
+        state.allow_next_detection = true;
+        Ok(())
+    })?;
+    info!("Allow-once granted: the next detection will not block");
+    println!("The next detection will be let through once");
+    Ok(())
+}
+
+fn handle_study_mode(config: &Config, minutes: u64) -> anyhow::Result<()> {
+    if config.files.study_whitelist.is_none() {
+        return Err(anyhow::anyhow!("files.study_whitelist is not set"));
+    }
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    let until = update_state(&*store, |state| Ok(state.start_study_mode(minutes, Utc::now())))?;
+    info!("Study mode started for {} minute(s) until {}", minutes, until);
+
+    let zone = Zone::parse(config.timezone.as_deref())?;
+    println!("Study mode until {}: only study-whitelisted sites are allowed", zone.naive_local(until).format("%H:%M"));
+    Ok(())
+}
+
+/// Needs no display or browser: only the pattern files and the titles file.
+fn handle_benchmark(config: &Config, titles_file: &str) -> anyhow::Result<()> {
+    let corpus = std::fs::read_to_string(titles_file)
+        .map_err(|e| anyhow::anyhow!("cannot read '{}': {}", titles_file, e))?;
+    let filter = Filter::with_config(&config.files.blacklist, &config.files.whitelist, config.filter.clone())?;
+    println!("Filter: {} blacklist pattern(s), {} whitelist pattern(s), match_mode {:?}",
+        filter.blacklist_len(), filter.whitelist_len(), config.filter.match_mode);
+    for line in benchmark::run_benchmark(&filter, &corpus).lines() {
+        println!("{}", line);
+    }
+    Ok(())
+}
+
+fn handle_scan(config: &Config) -> anyhow::Result<()> {
+    let window_monitor = open_diagnostic_monitor(config).map_err(|e| {
+        anyhow::anyhow!("Cannot scan: X11 display unreachable ({}). Is DISPLAY set?", e)
+    })?;
+    let filter = Filter::with_config(
+        &config.files.blacklist,
+        &config.files.whitelist,
+        config.filter.clone(),
+    )?;
+    let browser_manager = BrowserManager::new(
+        config.browser.executable.clone(),
+        config.browser.process_name.clone(),
+    )
+    .with_quiet(config.browser.quiet)
+    .with_ephemeral_profile(config.browser.ephemeral_profile.then(browser::ephemeral_profile_root));
+
+    let entries = scan::scan_snapshot(
+        &title_sources(&config.monitoring.backends, &window_monitor),
+        &browser_manager.get_pids(),
+        &config.monitoring.ignore_classes,
+        &filter,
+    )?;
+    if entries.is_empty() {
+        match window_monitor.get_all_windows()?.len() {
+            0 => println!("X11 display reachable, but it has no windows"),
+            others => println!("No browser windows found ({} other window(s) on the display)", others),
+        }
+    }
+    for entry in &entries {
+        println!("{}", entry.line());
+    }
+    Ok(())
+}
+
+fn handle_lockdown(config: &Config, requested_minutes: u64, no_cap: bool) -> anyhow::Result<()> {
+    let minutes = if no_cap {
+        requested_minutes
+    } else {
+        clamp_block_minutes(requested_minutes, config.timeouts.max_block_minutes)
+    };
+    if minutes != requested_minutes {
+        warn!("Lockdown of {} minute(s) capped to timeouts.max_block_minutes={}", requested_minutes, minutes);
+    }
+
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    let (blocked_until, profiles) = update_state(&*store, |state| {
+        state.block_browser(minutes);
+        Ok((state.blocked_until, state.ephemeral_profiles.clone()))
+    })?;
+    info!("Lockdown: blocked until {:?}", blocked_until);
+
+    let browser_manager = BrowserManager::new(
+        config.browser.executable.clone(),
+        config.browser.process_name.clone(),
+    )
+    .with_quiet(config.browser.quiet)
+    .with_ephemeral_profile(config.browser.ephemeral_profile.then(browser::ephemeral_profile_root));
+    browser_manager.adopt_profiles(&profiles);
+    kill_browser(&browser_manager)?;
+    background_manager(config).set_blocked_background(&config.backgrounds.blocked)?;
+
+    println!("Browser locked down for {} minute(s)", minutes);
+    Ok(())
+}
+
+/// `events` only decides where the messages go; starting the browser emits no events.
+async fn handle_start_browser(config: &Config, events: &EventStream) -> anyhow::Result<()> {
+    info!("Loading state from '{}' ({:?})", config.files.state_file, config.files.state_backend);
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    let mut state = store.load()?;
 
     info!("State loaded: blocked={} in_bathroom_break={} violation_count={}",
         state.is_blocked(), state.in_bathroom_break, state.violation_count);
@@ -173,67 +859,115 @@ async fn handle_start_browser(config: &Config) -> anyhow::Result<()> {
         debug!("  bathroom_break_until = {}", until);
     }
 
-    let bg = BackgroundManager::new();
-    let browser_manager = BrowserManager::new(
+    let bg = background_manager(config);
+    let mut browser_manager = BrowserManager::new(
         config.browser.executable.clone(),
         config.browser.process_name.clone(),
-    );
+    )
+    .with_quiet(config.browser.quiet)
+    .with_ephemeral_profile(config.browser.ephemeral_profile.then(browser::ephemeral_profile_root))
+    .with_launch_args(config.browser.launch_args.clone())
+    .with_launch_limit(config.browser.max_launches_per_minute, &state.recent_launches);
+    browser_manager.adopt_profiles(&state.ephemeral_profiles);
+    if let Some(wrapper) = &config.browser.launch_wrapper {
+        browser_manager = browser_manager.with_launch_wrapper(wrapper.clone(), config.browser.launch_wrapper_args.clone());
+    }
 
-    if state.is_blocked() {
-        println!("Browser is currently blocked");
-        info!("Browser blocked until {:?}", state.blocked_until);
-        browser_manager.kill_browser_processes()?;
-        bg.set_blocked_background(&config.backgrounds.blocked)?;
-        return Ok(());
+    match actions::enforce_restriction(&state, Utc::now(), &browser_manager)? {
+        Some(Restriction::Blocked) => {
+            events.say("Browser is currently blocked");
+            info!("Browser blocked until {:?}", state.blocked_until);
+            bg.set_blocked_background(&config.backgrounds.blocked)?;
+            return Ok(());
+        }
+        Some(Restriction::BathroomBreak) => {
+            events.say("It's bathroom break time");
+            info!("Bathroom break active until {:?}", state.bathroom_break_until);
+            bg.set_bathroom_break_background(&config.backgrounds.bathroom_break)?;
+            return Ok(());
+        }
+        None => {}
     }
 
-    if state.is_bathroom_break_time(config.timeouts.bathroom_break_interval_hours) {
+    if config.filter.fail_mode == FailMode::Closed {
+        if let Err(e) = Filter::with_config(&config.files.blacklist, &config.files.whitelist, config.filter.clone()) {
+            events.say("Pattern lists are unavailable — the browser stays closed");
+            info!("Not starting browser: pattern lists failed to load (filter.fail_mode: closed): {}", e);
+            kill_browser(&browser_manager)?;
+            bg.set_blocked_background(&config.backgrounds.blocked)?;
+            return Ok(());
+        }
+    }
+
+    if state.is_bathroom_break_time(config.timeouts.bathroom_break_interval_hours,
+        config.timeouts.min_break_gap_minutes)
+    {
         if !state.in_bathroom_break {
             info!("Starting bathroom break: duration={}m interval={}h",
                 config.timeouts.bathroom_break_minutes,
                 config.timeouts.bathroom_break_interval_hours);
-            browser_manager.kill_browser_processes()?;
-            state.start_bathroom_break(
+            kill_browser(&browser_manager)?;
+            state.start_bathroom_break_jittered(
                 config.timeouts.bathroom_break_minutes,
                 config.timeouts.bathroom_break_interval_hours,
+                config.timeouts.break_jitter_minutes,
+                &mut rand::rng(),
             );
-            state.save(&config.files.state_file)?;
+            store.save(&state)?;
         }
 
         if state.in_bathroom_break {
             if let Some(until) = state.bathroom_break_until {
                 if Utc::now() < until {
-                    println!("It's bathroom break time");
+                    events.say("It's bathroom break time");
                     info!("Bathroom break active until {}", until);
-                    browser_manager.kill_browser_processes()?;
+                    kill_browser(&browser_manager)?;
                     bg.set_bathroom_break_background(&config.backgrounds.bathroom_break)?;
                     return Ok(());
                 } else {
                     info!("Bathroom break expired, ending break");
                     state.end_bathroom_break();
-                    state.save(&config.files.state_file)?;
+                    store.save(&state)?;
                 }
             }
         }
     }
 
-    bg.set_normal_background(&config.backgrounds.normal)?;
+    let now = Utc::now();
+    let zone = Zone::parse(config.timezone.as_deref())?;
+    let daily_max = config.timeouts.daily_limit_minutes.as_ref().and_then(|limit| limit.minutes_at(zone, now));
+    if let Some(limit) = state.usage_limit_reached(zone.naive_local(now).date(), daily_max, config.timeouts.weekly_limit_minutes) {
+        events.say(format!("{} reached", limit));
+        info!("Not starting browser: {} reached", limit);
+        kill_browser(&browser_manager)?;
+        bg.set_blocked_background(&config.backgrounds.blocked)?;
+        return Ok(());
+    }
+
+    bg.set_normal_background(state.normal_background(&config.backgrounds))?;
 
     if state.violation_count > 0 {
         info!("Resetting violation count ({} -> 0) after cooldown expired", state.violation_count);
         state.violation_count = 0;
         state.violation_window_start = None;
-        state.save(&config.files.state_file)?;
-        println!("Violation count reset after cooldown — fresh start");
+        store.save(&state)?;
+        events.say("Violation count reset after cooldown — fresh start");
     }
 
     info!("Starting browser: executable='{}' url='{}'",
         config.browser.executable, config.browser.url);
 
     match browser_manager.start_browser(&config.browser.url) {
-        Ok(_) => {
-            println!("Browser started successfully");
+        Ok(mut child) => {
+            // Reap the browser when it exits, so a daemon relaunch leaves no zombie behind.
+            std::thread::spawn(move || {
+                let _ = child.wait();
+            });
+            events.say("Browser started successfully");
             info!("Browser started successfully");
+            state.recent_launches = browser_manager.recent_launches();
+            state.ephemeral_profiles = browser_manager.owned_profiles();
+            store.save(&state)?;
         }
         Err(e) => error!("Failed to start browser: {}", e),
     }
@@ -241,137 +975,825 @@ async fn handle_start_browser(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn run_daemon(config: &Config) -> anyhow::Result<()> {
+/// Records the wallpaper in use at startup, before the daemon sets one of its own.
+/// Keeps the previously stored one when nothing new can be detected.
+fn capture_user_wallpaper(config: &Config, state: &mut AppState) {
+    let home = std::env::var_os("HOME").map(std::path::PathBuf::from);
+    let countdown_image = background::countdown_image_path();
+    let mut own_images = config.backgrounds.managed_images();
+    own_images.extend(countdown_image.to_str());
+    match background::detect_user_wallpaper(
+        config.backgrounds.user_wallpaper.as_deref(),
+        home.as_deref(),
+        &SystemRunner,
+        &own_images,
+    ) {
+        Some(wallpaper) => {
+            info!("User wallpaper is '{}'", wallpaper);
+            state.user_wallpaper = Some(wallpaper);
+        }
+        None => info!("Could not detect the user's wallpaper; using {:?}",
+            state.user_wallpaper.as_deref().unwrap_or(&config.backgrounds.normal)),
+    }
+}
+
+fn block_event(state: &AppState, minutes: u64, hard_lock: bool, pattern: &str) -> Event {
+    Event::Block {
+        minutes,
+        until: state.blocked_until.unwrap_or_else(Utc::now),
+        hard_lock,
+        violations: state.violation_count,
+        pattern: pattern.to_string(),
+    }
+}
+
+/// The filter used while `study-mode` is on, if `files.study_whitelist` is set.
+fn load_study_filter(config: &Config) -> anyhow::Result<Option<Filter>> {
+    config.files.study_whitelist.as_ref()
+        .map(|whitelist| Filter::with_config(&config.files.blacklist, whitelist, config.filter.for_study_mode()))
+        .transpose()
+}
+
+fn external_classifier(config: &Config) -> Option<ExternalClassifier> {
+    config.filter.external_classifier.clone()
+        .map(|template| {
+            ExternalClassifier::new(template, config.filter.classifier_cache_seconds)
+                .with_redactor(TitleRedactor::new(&config.privacy, config.filter.case_insensitive))
+        })
+}
+
+/// Reports the state to `management.sync_url` every `sync_interval_seconds` and
+/// applies the commands in the reply. Network and server errors are logged and
+/// the next sync is tried as usual.
+#[cfg(feature = "management")]
+fn spawn_management_sync(config: &Config, url: String, zone: Zone) -> anyhow::Result<()> {
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+    let interval = std::time::Duration::from_secs(config.management.sync_interval_seconds);
+    let default_minutes = config.timeouts.blacklist_timeout_minutes;
+    let max_block_minutes = config.timeouts.max_block_minutes;
+    let blocked_background = config.backgrounds.blocked.clone();
+    let bg = background_manager(config);
+    let browser_manager = BrowserManager::new(
+        config.browser.executable.clone(),
+        config.browser.process_name.clone(),
+    )
+    .with_quiet(config.browser.quiet)
+    .with_ephemeral_profile(config.browser.ephemeral_profile.then(browser::ephemeral_profile_root));
+    let host = management::host_name();
+    info!("Syncing state to '{}' every {:?}", url, interval);
+
+    // A detached thread rather than `spawn_blocking`: the runtime waits for blocking
+    // tasks when it shuts down, and this loop never ends, so SIGTERM would hang.
+    std::thread::Builder::new().name("management-sync".to_string()).spawn(move || loop {
+        let now = Utc::now();
+        let today = zone.naive_local(now).date();
+        let outcome = store.load().and_then(|state| {
+            let report = management::SyncReport::from_state(&state, &host, now, today);
+            management::sync(&SystemRunner, &url, &report)
+        });
+        match outcome {
+            Ok(commands) if !commands.is_empty() => {
+                info!("Management server sent {:?}", commands);
+                if let Err(e) = apply_management_commands(&*store, &commands, default_minutes, max_block_minutes,
+                    &browser_manager, &bg, &blocked_background)
+                {
+                    error!("Failed to apply management commands: {}", e);
+                }
+            }
+            Ok(_) => debug!("State synced; no commands"),
+            Err(e) => warn!("State sync to '{}' failed: {}", url, e),
+        }
+        std::thread::sleep(interval);
+    })?;
+    Ok(())
+}
+
+#[cfg(feature = "management")]
+fn apply_management_commands(
+    store: &dyn state::StateStore,
+    commands: &[management::ManagementCommand],
+    default_minutes: u64,
+    max_block_minutes: Option<u64>,
+    browser_manager: &BrowserManager,
+    bg: &BackgroundManager,
+    blocked_background: &str,
+) -> anyhow::Result<()> {
+    let applied = state::update_state(store, |state| {
+        browser_manager.adopt_profiles(&state.ephemeral_profiles);
+        Ok(management::apply_commands(state, commands, default_minutes, max_block_minutes))
+    })?;
+    if let Some(minutes) = applied.locked_down {
+        info!("Lockdown from the management server: blocked for {} minute(s)", minutes);
+        kill_browser(browser_manager)?;
+        bg.set_blocked_background(blocked_background)?;
+    }
+    if applied.unblocked {
+        info!("Block lifted by the management server");
+    }
+    if applied.reload {
+        info!("Config reload requested by the management server");
+        nix::sys::signal::kill(nix::unistd::Pid::this(), nix::sys::signal::Signal::SIGHUP)?;
+    }
+    Ok(())
+}
+
+/// Re-reads the config file for SIGHUP and applies what can change live.
+fn reload_config(config: &mut Config, config_path: &str, profile: Option<&str>) -> anyhow::Result<()> {
+    let changes = reload::apply_live_changes(config, Config::load_effective(config_path, profile)?);
+    if changes.is_empty() {
+        info!("Config reloaded from '{}': no changes", config_path);
+    } else if !changes.applied.is_empty() {
+        info!("Config reloaded from '{}': applied {}", config_path, changes.applied.join(", "));
+    }
+    for name in &changes.ignored {
+        warn!("Config reload: '{}' changed but needs a restart; keeping the running value", name);
+    }
+    Ok(())
+}
+
+async fn run_daemon(mut config: Config, config_path: &str, profile: Option<&str>, events: EventStream) -> anyhow::Result<()> {
     info!("Initialising window monitor");
-    let window_monitor = Arc::new(WindowMonitor::new()?);
+    let mut window_monitor = Arc::new(open_window_monitor(&config)?);
+    let mut x_errors = ErrorCounter::new(config.monitoring.max_consecutive_errors);
+    let mut title_debounce = TitleDebounce::new(config.monitoring.title_debounce_scans);
 
     info!("Loading filter patterns from '{}' (blacklist) and '{}' (whitelist)",
         config.files.blacklist, config.files.whitelist);
-    let filter = Arc::new(Filter::new(
+    let mut filter = Arc::new(Filter::with_fail_mode(
         &config.files.blacklist,
         &config.files.whitelist,
+        config.filter.clone(),
     )?);
 
     info!("Filter loaded: {} blacklist pattern(s), {} whitelist pattern(s)",
         filter.blacklist_len(), filter.whitelist_len());
 
-    let browser_manager = Arc::new(BrowserManager::new(
-        config.browser.executable.clone(),
-        config.browser.process_name.clone(),
-    ));
+    let mut classifier = external_classifier(&config);
+    let mut study_filter = load_study_filter(&config)?.map(Arc::new);
+
+    let evasion_filter = config.monitoring.block_evasion
+        .then(|| Filter::evasion(&config.monitoring.evasion_patterns, config.filter.case_insensitive))
+        .transpose()?;
+
+    let browser_manager = Arc::new(
+        BrowserManager::new(config.browser.executable.clone(), config.browser.process_name.clone())
+            .with_quiet(config.browser.quiet)
+            .with_ephemeral_profile(config.browser.ephemeral_profile.then(browser::ephemeral_profile_root)),
+    );
+
+    // Before anything writes the state, its lock or the audit log, so those files are
+    // created by the user the daemon keeps running as.
+    if let Some(user) = &config.security.run_as_user {
+        privileges::drop_privileges(user, &browser_manager.get_pids())?;
+    }
+
+    let mut audit = config.audit.enabled.then(|| {
+        AuditLogger::new(RotatingWriter::new(
+            &config.audit.file,
+            config.audit.max_size_mb * 1024 * 1024,
+            config.audit.keep,
+            config.audit.compress,
+        ))
+    });
+
+    info!("Using {:?} state store at '{}'", config.files.state_backend, config.files.state_file);
+    let store = open_state_store(config.files.state_backend, &config.files.state_file)?;
+
+    let mut history = TitleHistory::new(config.monitoring.title_history_size);
+    let redactor = TitleRedactor::new(&config.privacy, config.filter.case_insensitive);
+    let zone = Zone::parse(config.timezone.as_deref())?;
+    let spawned = SpawnedPids::default();
+    let notifier = Notifier::from_config(&config.notifications, zone)?.with_spawned(spawned.clone());
+    let hooks = HookRunner::new(config.hooks.clone()).with_spawned(spawned.clone());
+    let focus_target = config.monitoring.focus_after_kill.as_deref()
+        .map(FocusTarget::parse)
+        .transpose()?;
+    let detection_actions = ActionSequence::new(
+        config.monitoring.on_detection.clone(),
+        config.monitoring.lock_command.clone(),
+    );
 
     let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sighup = signal(SignalKind::hangup())?;
+    #[cfg(feature = "management")]
+    if let Some(url) = &config.management.sync_url {
+        spawn_management_sync(&config, url.clone(), zone)?;
+    }
 
-    println!("Starting daemon mode...");
+    events.say("Starting daemon mode...");
     info!("Daemon started");
+    events.emit(Event::Startup { version: env!("CARGO_PKG_VERSION").to_string(), pid: std::process::id() });
 
-    let initial_state = AppState::load(&config.files.state_file)?;
-    let bg = BackgroundManager::new();
-    if initial_state.is_blocked() {
-        bg.set_blocked_background(&config.backgrounds.blocked)?;
-    } else if initial_state.is_bathroom_break_time(config.timeouts.bathroom_break_interval_hours) {
-        bg.set_bathroom_break_background(&config.backgrounds.bathroom_break)?;
-    } else {
-        bg.set_normal_background(&config.backgrounds.normal)?;
+    let mut initial_state = store.load()?;
+    initial_state.resume_clean_streak(Utc::now());
+    if config.backgrounds.restore_user_wallpaper {
+        capture_user_wallpaper(&config, &mut initial_state);
+    }
+    store.save(&initial_state)?;
+    let enforcement = initial_state.startup_enforcement(
+        config.timeouts.bathroom_break_interval_hours,
+        config.timeouts.min_break_gap_minutes,
+    );
+    if config.monitoring.enforce_on_startup
+        && enforcement.should_kill(!browser_manager.get_pids().is_empty())
+    {
+        warn!("Browser running at startup while state is {:?} — killing it", enforcement);
+        kill_browser(&browser_manager)?;
+        focus_after_kill(focus_target.as_ref(), &window_monitor);
     }
 
+    let mut bg = background_manager(&config).with_spawned(spawned.clone());
+    match enforcement {
+        StartupEnforcement::Blocked => bg.set_blocked_background(&config.backgrounds.blocked)?,
+        StartupEnforcement::BathroomBreak => {
+            bg.set_bathroom_break_background(&config.backgrounds.bathroom_break)?
+        }
+        StartupEnforcement::Cooldown => {
+            info!("Detection cooldown active until {:?} — hits are not counted until it ends",
+                initial_state.detection_cooldown_until);
+            bg.set_normal_background(initial_state.normal_background(&config.backgrounds))?
+        }
+        StartupEnforcement::Normal => bg.set_normal_background(initial_state.normal_background(&config.backgrounds))?,
+    }
+    let mut was_blocked = initial_state.is_blocked();
+
+    let started = Utc::now();
+    #[cfg(feature = "history")]
+    let mut history_since = started;
+    let warmup = config.monitoring.startup_delay_seconds;
+    if warmup > 0 {
+        info!("Warming up: window titles are not evaluated for the first {} second(s)", warmup);
+    }
+
+    let mut interval = Duration::from_secs(config.monitoring.check_frequency_seconds);
+    let mut timings = TickTimings::new(TICK_SAMPLES);
+    // When the scan timings were last saved, and the (avg, max) saved.
+    let mut reported_timings: Option<(std::time::Instant, (u64, u64))> = None;
+    let mut relaunch = PendingRelaunch::default();
+    let mut last_tick: Option<(chrono::DateTime<Utc>, std::time::Instant)> = None;
+    let mut slow = false;
+    let mut countdown = CountdownTracker::default();
+    // The blocked wallpaper for the current block, per `backgrounds.blocked_by_label`.
+    let mut block_image = config.backgrounds.blocked.clone();
+
     loop {
         debug!("--- daemon tick ---");
-        let mut state = AppState::load(&config.files.state_file)?;
+        let tick_started = std::time::Instant::now();
+        // Deliberate pauses (the SIGTERM grace, block sequence holds) left out of the scan timings.
+        let mut waited = Duration::ZERO;
+        let mut state = store.load()?;
+        browser_manager.adopt_profiles(&state.ephemeral_profiles);
+
+        let now = Utc::now();
+        // Browser use is charged by the time that actually passed, since the sleep
+        // between ticks varies (e.g. `monitoring.whitelist_recheck_seconds`).
+        let since_last_tick = last_tick.map_or(Duration::ZERO, |(_, monotonic)| monotonic.elapsed());
+        if let Some((wall, monotonic)) = last_tick {
+            if let Some(jump) = clock_jump(now - wall, monotonic.elapsed(), config.monitoring.clock_jump_threshold_seconds) {
+                warn!("Clock jumped by {} second(s) since the last check (suspend/resume or time sync)", jump.num_seconds());
+                if state.settle_clock_jump(now, config.timeouts.bathroom_break_interval_hours, config.monitoring.on_clock_jump) {
+                    info!("Next bathroom break rescheduled to {} after the clock jump", state.next_bathroom_break);
+                    store.save(&state)?;
+                }
+            }
+        }
+        last_tick = Some((now, std::time::Instant::now()));
 
         debug!("State: blocked={} in_bathroom_break={} violation_count={} next_break={}",
             state.is_blocked(), state.in_bathroom_break, state.violation_count,
             state.next_bathroom_break);
 
-        let browser_pids = browser_manager.get_pids();
-        if let Ok(titles) = window_monitor.get_browser_window_titles(&browser_pids) {
+        if was_blocked && !state.is_blocked() {
+            info!("Block expired");
+            block_image = config.backgrounds.blocked.clone();
+            hooks.fire("unblock", "");
+            events.emit(Event::Unblock);
+            if config.browser.auto_relaunch {
+                info!("Relaunching the browser in {} second(s)", config.browser.relaunch_delay_seconds);
+                relaunch.schedule(Utc::now(), config.browser.relaunch_delay_seconds);
+            }
+        }
+
+        if filter.is_fail_closed() {
+            match Filter::with_config(&config.files.blacklist, &config.files.whitelist, config.filter.clone()) {
+                Ok(loaded) => {
+                    info!("Pattern lists loaded: {} blacklist pattern(s) — the browser is allowed again", loaded.blacklist_len());
+                    filter = Arc::new(loaded);
+                    if state.restriction(now).is_none() {
+                        bg.set_normal_background(state.normal_background(&config.backgrounds))?;
+                    }
+                }
+                Err(e) => {
+                    debug!("Pattern lists still unavailable: {}", e);
+                    if browser_manager.has_running_processes() {
+                        warn!("Pattern lists unavailable — killing browser (filter.fail_mode: closed)");
+                        waited += kill_browser(&browser_manager)?.waited;
+                        focus_after_kill(focus_target.as_ref(), &window_monitor);
+                    }
+                    bg.set_blocked_background(&config.backgrounds.blocked)?;
+                }
+            }
+        }
+        let fail_closed = filter.is_fail_closed();
+
+        match state.blocked_until.filter(|_| config.backgrounds.countdown && state.is_blocked() && !fail_closed) {
+            Some(until) => {
+                let minutes = background::remaining_minutes(until, now);
+                if countdown.should_regenerate(minutes) {
+                    if let Err(e) = bg.set_countdown_background(&block_image, minutes) {
+                        warn!("Failed to draw the block countdown, keeping the plain blocked background: {}", e);
+                    }
+                }
+            }
+            None => countdown.reset(),
+        }
+
+        let active_filter = match &study_filter {
+            Some(study) if state.in_study_mode(now) => {
+                debug!("Study mode until {:?}: only study-whitelisted titles are allowed", state.study_until);
+                study
+            }
+            _ => &filter,
+        };
+
+        let pids = browser_manager.get_pids();
+        let windows = if in_warmup(started, warmup, Utc::now()) {
+            debug!("Warmup in progress — skipping title checks");
+            None
+        } else if fail_closed {
+            None
+        } else {
+            match title_sources(&config.monitoring.backends, &window_monitor).browser_windows(&pids) {
+                Ok(mut windows) => {
+                    x_errors.record_success();
+                    remove_ignored_classes(&mut windows, &config.monitoring.ignore_classes);
+                    remove_unsupervised(&mut windows, &config.security.supervised_users,
+                        |pid| privileges::process_user(Path::new("/proc"), pid));
+                    if config.monitoring.exclude_own_windows {
+                        remove_spawned(&mut windows, &spawned.pids());
+                    }
+                    Some(windows)
+                }
+                Err(e) => {
+                    warn!("Failed to read browser windows ({} in a row): {}", x_errors.consecutive() + 1, e);
+                    if x_errors.record_failure() {
+                        match config.monitoring.on_repeated_errors {
+                            ErrorAction::Reconnect => {
+                                warn!("{} consecutive X errors — reconnecting to the display",
+                                    config.monitoring.max_consecutive_errors);
+                                match open_window_monitor(&config) {
+                                    Ok(monitor) => window_monitor = Arc::new(monitor),
+                                    Err(e) => error!("Reconnecting to the display failed: {}", e),
+                                }
+                            }
+                            ErrorAction::Exit => {
+                                error!("{} consecutive X errors — exiting with status {}",
+                                    config.monitoring.max_consecutive_errors, X_ERROR_EXIT_CODE);
+                                std::process::exit(X_ERROR_EXIT_CODE);
+                            }
+                        }
+                    }
+                    None
+                }
+            }
+        };
+        let window_count = windows.as_ref().map_or(0, Vec::len);
+        let saw_whitelisted = config.monitoring.whitelist_recheck_seconds.is_some()
+            && windows.as_ref().is_some_and(|windows| {
+                windows.iter().any(|w| active_filter.evaluate(&w.title) == Verdict::Whitelisted)
+            });
+        let mut hit = None;
+        let windows = windows.filter(|windows| {
+            match empty_decision(windows, !pids.is_empty(), config.monitoring.on_empty) {
+                EmptyDecision::Evaluate => true,
+                EmptyDecision::Skip => {
+                    debug!("Browser running but no windows found — skipping this tick");
+                    false
+                }
+                EmptyDecision::Block => {
+                    warn!("Browser running but no windows found — treating as a hit (on_empty: block)");
+                    hit = Some((DetectionRecord::new(NO_WINDOWS_MARKER, NO_WINDOWS_MARKER, None), false));
+                    false
+                }
+            }
+        });
+        if let Some(mut windows) = windows {
+            if config.monitoring.fullscreen_priority {
+                prioritize_fullscreen(&mut windows);
+            }
+            let titles: Vec<String> = windows.iter()
+                .filter(|w| !w.title.is_empty())
+                .map(|w| w.title.clone())
+                .collect();
+            history.update(&windows);
+
             if !titles.is_empty() {
                 info!("Checking {} browser window title(s) against filter", titles.len());
-                if let Err(e) = record_titles(&config.files.titles_file, &titles) {
+                if let Err(e) = record_titles(&config.files.titles_file, &titles, &redactor) {
                     warn!("Failed to record window titles: {}", e);
                 }
             }
+            keep_in_scope(&mut windows, config.monitoring.detection_scope);
 
-            if let Some((matched_title, matched_pattern)) = filter.find_blacklisted_title(&titles) {
-                warn!("Blacklist hit: title='{}' matched pattern='{}'",
-                    matched_title, matched_pattern);
-                browser_manager.kill_browser_processes()?;
+            let pip_hit = || {
+                find_pip_window(&windows, &config.monitoring.detect_pip).map(|w| {
+                    warn!("Suspected picture-in-picture window: '{}' {:?}", redactor.redact(&w.title, None), w.geometry);
+                    DetectionRecord::new(&w.title, PIP_MARKER, Some(w))
+                })
+            };
+            let evasion_hit = || {
+                evasion_filter.as_ref().and_then(|evasion| find_detection(evasion, &history, &windows)).inspect(|d| {
+                    warn!("Proxy/VPN evasion marker '{}' in '{}'", d.reported(), redactor.redact(&d.title, Some(&d.pattern)));
+                })
+            };
+            let untitled_hit = || {
+                suspicious_untitled(&windows, config.monitoring.untitled_windows, config.filter.default_action).map(|w| {
+                    warn!("Focused browser window 0x{:x} has no title (untitled_windows: suspicious)", w.id);
+                    DetectionRecord::new(&w.title, UNTITLED_MARKER, Some(w))
+                })
+            };
+            let classifier_hit = || {
+                classifier.as_ref().and_then(|classifier| find_classified(classifier, active_filter, &history, &windows))
+            };
+            let match_titles = config.monitoring.match_properties.contains(&MatchProperty::Title);
+            hit = match_titles.then(|| find_detection(active_filter, &history, &windows)).flatten()
+                .or_else(|| find_property_detection(active_filter, &windows, &config.monitoring.match_properties))
+                .or_else(classifier_hit)
+                .or_else(evasion_hit)
+                .or_else(untitled_hit)
+                .or_else(pip_hit)
+                .map(|detection| {
+                    let fullscreen_hit = config.monitoring.fullscreen_priority
+                        && is_fullscreen_title(&windows, &detection.title);
+                    (detection, fullscreen_hit)
+                });
+            let mut matched =
+                if match_titles { blocked_titles(active_filter, &history, &windows) } else { Vec::new() };
+            if let Some((detection, _)) = hit.as_ref().filter(|(detection, _)| !matched.contains(&detection.title)) {
+                matched.push(detection.title.clone());
+            }
+            let confirmed = title_debounce.observe(&matched);
+            if let Some((detection, _)) = hit.take_if(|_| !confirmed) {
+                info!("{} — matched in {} of {} consecutive scans (title_debounce_scans)",
+                    detection.audit_detail(&redactor), title_debounce.streak(&detection.title),
+                    config.monitoring.title_debounce_scans);
+            }
+        }
 
-                if let Some(window_start) = state.violation_window_start {
-                    let elapsed = Utc::now() - window_start;
-                    if elapsed.num_minutes() >= config.timeouts.retry_reset_minutes as i64 {
-                        info!("Violation window expired ({} minutes elapsed, reset after {}) — resetting count",
-                            elapsed.num_minutes(), config.timeouts.retry_reset_minutes);
-                        state.violation_count = 0;
-                        state.violation_window_start = None;
+        #[cfg(feature = "history")]
+        if let Some(db) = config.monitoring.inspect_history.as_ref().filter(|_| !fail_closed) {
+            let checked_at = Utc::now();
+            match history::find_history_hit(Path::new(db), history_since, active_filter) {
+                Ok(history_hit) => {
+                    history_since = checked_at;
+                    if hit.is_none() {
+                        hit = history_hit.map(|(entry, blocked)| (DetectionRecord::blocked(&entry, &blocked, None), false));
                     }
                 }
+                Err(e) => warn!("Failed to read browser history '{}': {}", db, e),
+            }
+        }
 
-                state.violation_count += 1;
+        if hit.is_some() && config.monitoring.require_browser_running {
+            hit = gate_detection(hit, true, browser_manager.has_running_processes());
+            if hit.is_none() {
+                info!("Detection ignored: no browser process is running (require_browser_running)");
+            }
+        }
+
+        if let Some((detection, _)) = hit.take_if(|_| state.in_detection_cooldown(Utc::now())) {
+            info!("Detection during cooldown (until {:?}): {} — killing without counting a violation",
+                state.detection_cooldown_until, detection.audit_detail(&redactor));
+            waited += kill_browser(&browser_manager)?.waited;
+        }
+
+        let allow_once = apply_allow_once(&mut state, &history.titles(), hit.as_ref().map(|(detection, _)| detection.title.as_str()));
+        if allow_once == Some(AllowOnce::Ended) {
+            info!("Allow-once exemption ended: the allowed title is no longer on screen");
+            store.save(&state)?;
+        }
+        if let Some((detection, _)) = hit.take_if(|_| allow_once == Some(AllowOnce::Used)) {
+            warn!("Allow-once token used: letting {} through while it stays on screen", detection.audit_detail(&redactor));
+            store.save(&state)?;
+            if let Some(audit) = audit.as_mut() {
+                audit.record("allow_once", &detection.audit_detail(&redactor));
+            }
+            notifier.notify("Allowed once", "This page is let through until it is closed; anything else will block");
+        }
+        if let Some((detection, _)) = hit.take_if(|_| allow_once == Some(AllowOnce::Exempt)) {
+            debug!("Allow-once: still letting {} through", detection.audit_detail(&redactor));
+        }
+
+        let mut ack_timed_out = false;
+        if config.monitoring.ack_seconds > 0 {
+            if let Some((detection, _)) = hit.take() {
+                if state.pending_ack.is_none() {
+                    warn!("Blacklist hit: {} — waiting {} second(s) for acknowledgement",
+                        detection.audit_detail(&redactor), config.monitoring.ack_seconds);
+                    events.say(format!("Blacklisted content detected — run 'acknowledge' within {} seconds",
+                        config.monitoring.ack_seconds));
+                    state.start_pending_ack(&detection.title, &detection.pattern, detection.label.as_deref(),
+                        config.monitoring.ack_seconds);
+                    store.save(&state)?;
+                    if let Some(audit) = audit.as_mut() {
+                        audit.record("warning", &format!("seconds={} {}",
+                            config.monitoring.ack_seconds, detection.audit_detail(&redactor)));
+                    }
+                    notifier.notify("Blocked content detected",
+                        &format!("Close it and acknowledge within {} seconds", config.monitoring.ack_seconds));
+                    let warning = config.backgrounds.warning.as_deref().unwrap_or(&config.backgrounds.blocked);
+                    bg.set_warning_background(warning)?;
+                }
+            }
+            if let Some(outcome) = state.resolve_pending_ack(Utc::now()) {
+                let pending = state.pending_ack.take().expect("resolved ack is pending");
+                info!("Pending detection resolved: {:?}", outcome);
+                ack_timed_out = outcome == AckOutcome::TimedOut;
+                let record = DetectionRecord { label: pending.label, ..DetectionRecord::new(&pending.title, &pending.pattern, None) };
+                hit = Some((record, false));
+            }
+        }
 
-                if state.violation_window_start.is_none() {
-                    state.violation_window_start = Some(Utc::now());
+        if let Some((detection, fullscreen_hit)) = hit {
+            warn!("Blacklist hit: {}", detection.audit_detail(&redactor));
+            events.emit(Event::Detection {
+                title: redactor.redact(&detection.title, Some(&detection.pattern)),
+                pattern: detection.reported().to_string(),
+                window_id: detection.window_id,
+                class: detection.class.clone(),
+            });
+            if ack_timed_out {
+                warn!("Detection was not acknowledged in time — hard killing");
+            }
+            if config.monitoring.capture_on_block.enabled {
+                if let Some(path) = capture::capture_screen(&config.monitoring.capture_on_block, &spawned) {
+                    if let Some(audit) = audit.as_mut() {
+                        audit.record("capture", &format!("file='{}'", path.display()));
+                    }
                 }
+            }
+            detection_actions.run(&browser_manager, ack_timed_out, || {
+                notifier.notify("Blocked content detected", "The browser has been blocked");
+            });
+            if detection_actions.includes(DetectionAction::Kill) {
+                focus_after_kill(focus_target.as_ref(), &window_monitor);
+            }
+            history.clear();
+            if state.clean_streak_minutes > 0 {
+                info!("Clean streak of {} ended", format_minutes(state.clean_streak_minutes));
+            }
+            state.reset_clean_streak(Utc::now());
 
-                if state.violation_count > config.timeouts.grace_retries {
-                    warn!("Grace retries exhausted ({} violations) — hard locking for {} minutes",
-                        state.violation_count, config.timeouts.hard_lock_minutes);
-                    println!("Blacklisted content detected — grace retries exhausted, hard locking for {} minutes",
-                        config.timeouts.hard_lock_minutes);
-                    state.block_browser(config.timeouts.hard_lock_minutes);
+            if let Some(window_start) = state.violation_window_start {
+                let elapsed = Utc::now() - window_start;
+                if elapsed.num_minutes() >= config.timeouts.retry_reset_minutes as i64 {
+                    info!("Violation window expired ({} minutes elapsed, reset after {}) — resetting count",
+                        elapsed.num_minutes(), config.timeouts.retry_reset_minutes);
                     state.violation_count = 0;
                     state.violation_window_start = None;
-                } else {
-                    warn!("Grace retry {}/{} — browser killed, blocking for {} minute(s)",
-                        state.violation_count, config.timeouts.grace_retries,
-                        config.timeouts.blacklist_timeout_minutes);
-                    println!("Blacklisted content detected — grace retry {}/{}, browser killed",
-                        state.violation_count, config.timeouts.grace_retries);
-                    state.block_browser(config.timeouts.blacklist_timeout_minutes);
                 }
+            }
+
+            state.violation_count += 1;
+
+            if state.violation_window_start.is_none() {
+                state.violation_window_start = Some(Utc::now());
+            }
+
+            if fullscreen_hit {
+                warn!("Match was in a fullscreen window — skipping grace retries");
+            }
 
-                state.save(&config.files.state_file)?;
-                let bg = BackgroundManager::new();
-                bg.set_blocked_background(&config.backgrounds.blocked)?;
+            if ack_timed_out || fullscreen_hit || state.violation_count > config.timeouts.grace_retries {
+                let minutes = clamp_block_minutes(config.timeouts.hard_lock_minutes,
+                    config.timeouts.max_block_minutes);
+                warn!("Grace retries exhausted ({} violations) — hard locking for {} minutes",
+                    state.violation_count, minutes);
+                events.say(format!("Blacklisted content detected — grace retries exhausted, hard locking for {} minutes",
+                    minutes));
+                state.block_browser(minutes);
+                events.emit(block_event(&state, minutes, true, detection.reported()));
+                if let Some(audit) = audit.as_mut() {
+                    audit.record("hard_lock", &format!("minutes={} {}", minutes, detection.audit_detail(&redactor)));
+                }
+                hooks.fire("hard_lock", &detection.title);
+                state.violation_count = 0;
+                state.violation_window_start = None;
+            } else {
+                let minutes = state.block_browser_escalating(
+                    config.timeouts.blacklist_timeout_minutes,
+                    config.timeouts.escalation_multiplier,
+                    config.timeouts.max_block_minutes,
+                );
+                warn!("Grace retry {}/{} — browser killed, blocking for {} minute(s)",
+                    state.violation_count, config.timeouts.grace_retries, minutes);
+                events.say(format!("Blacklisted content detected — grace retry {}/{}, browser killed",
+                    state.violation_count, config.timeouts.grace_retries));
+                events.emit(block_event(&state, minutes, false, detection.reported()));
+                if let Some(audit) = audit.as_mut() {
+                    audit.record("block", &format!("minutes={} retry={}/{} {}",
+                        minutes, state.violation_count,
+                        config.timeouts.grace_retries, detection.audit_detail(&redactor)));
+                }
+                hooks.fire("block", &detection.title);
+            }
+
+            state.start_detection_cooldown(config.timeouts.detection_cooldown_seconds, Utc::now());
+            store.save(&state)?;
+            if let Some(until) = state.blocked_until {
+                let until = zone.naive_local(until);
+                notifier.notify("Browser blocked",
+                    &format!("Blocked: {}\nBlocked until {}", detection.reported(), until.format("%H:%M")));
+                if let Some(template) = &config.backgrounds.block_message {
+                    bg.show_message(&background::expand_block_message(template, until));
+                }
+            }
+            for step in background::capped_sequence(&config.backgrounds.block_sequence,
+                background::MAX_BLOCK_SEQUENCE_SECONDS)
+            {
+                if let Err(e) = bg.set_background(&step.image) {
+                    warn!("Failed to show block sequence image '{}': {}", step.image, e);
+                    continue;
+                }
+                sleep(Duration::from_secs(step.hold_seconds)).await;
+                waited += Duration::from_secs(step.hold_seconds);
+            }
+            block_image = config.backgrounds.blocked_for(detection.reported()).to_string();
+            bg.set_blocked_background(&block_image)?;
+        } else if state.record_clean_tick(Utc::now()) {
+            debug!("Clean streak: {}", format_minutes(state.clean_streak_minutes));
+            store.save(&state)?;
+        }
+
+        if config.timeouts.daily_limit_minutes.is_some() || config.timeouts.weekly_limit_minutes.is_some() {
+            let now = Utc::now();
+            let today = zone.naive_local(now).date();
+            if !pids.is_empty() {
+                state.add_usage(today, since_last_tick.as_secs_f64().round() as u64);
+                store.save(&state)?;
+                let daily_max = config.timeouts.daily_limit_minutes.as_ref().and_then(|limit| limit.minutes_at(zone, now));
+                debug!("Usage: {} minute(s) today (limit {:?}), {} this week (limit {:?})",
+                    state.usage_minutes(today), daily_max,
+                    state.usage_week_minutes(today), config.timeouts.weekly_limit_minutes);
+                if let Some(limit) = state.usage_limit_reached(today, daily_max, config.timeouts.weekly_limit_minutes) {
+                    warn!("{} reached — killing browser", limit);
+                    events.say(format!("{} reached", limit));
+                    waited += kill_browser(&browser_manager)?.waited;
+                    focus_after_kill(focus_target.as_ref(), &window_monitor);
+                    if let Some(audit) = audit.as_mut() {
+                        audit.record(limit.audit_event(), &format!("minutes={}", limit.minutes()));
+                    }
+                    let (title, period) = match limit {
+                        UsageLimit::Daily(_) => ("Daily limit reached", "today"),
+                        UsageLimit::Weekly(_) => ("Weekly limit reached", "this week"),
+                    };
+                    notifier.notify(title, &format!("You have used your {} minutes for {}", limit.minutes(), period));
+                    bg.set_blocked_background(&config.backgrounds.blocked)?;
+                }
             }
         }
 
-        if state.is_bathroom_break_time(config.timeouts.bathroom_break_interval_hours)
+        if state.is_bathroom_break_time(config.timeouts.bathroom_break_interval_hours,
+            config.timeouts.min_break_gap_minutes)
             && !state.in_bathroom_break
         {
-            println!("Initiating bathroom break");
+            events.say("Initiating bathroom break");
             info!("Bathroom break: duration={}m next_interval={}h",
                 config.timeouts.bathroom_break_minutes,
                 config.timeouts.bathroom_break_interval_hours);
-            browser_manager.kill_browser_processes()?;
-            state.start_bathroom_break(
+            waited += kill_browser(&browser_manager)?.waited;
+            focus_after_kill(focus_target.as_ref(), &window_monitor);
+            state.start_bathroom_break_jittered(
                 config.timeouts.bathroom_break_minutes,
                 config.timeouts.bathroom_break_interval_hours,
+                config.timeouts.break_jitter_minutes,
+                &mut rand::rng(),
             );
-            state.save(&config.files.state_file)?;
-            let bg = BackgroundManager::new();
+            store.save(&state)?;
+            if let Some(audit) = audit.as_mut() {
+                audit.record("break_start", &format!("minutes={}", config.timeouts.bathroom_break_minutes));
+            }
+            hooks.fire("break_start", "");
+            events.emit(Event::BreakStart {
+                minutes: config.timeouts.bathroom_break_minutes,
+                until: state.bathroom_break_until,
+            });
+            notifier.notify("Bathroom break", &format!("Take a {} minute break",
+                config.timeouts.bathroom_break_minutes));
             bg.set_bathroom_break_background(&config.backgrounds.bathroom_break)?;
         }
 
         if state.in_bathroom_break {
             if let Some(until) = state.bathroom_break_until {
                 if Utc::now() >= until {
-                    println!("Bathroom break ended");
+                    events.say("Bathroom break ended");
                     info!("Bathroom break expired at {}", until);
                     state.end_bathroom_break();
-                    state.save(&config.files.state_file)?;
+                    store.save(&state)?;
+                    if let Some(audit) = audit.as_mut() {
+                        audit.record("break_end", "");
+                    }
+                    hooks.fire("break_end", "");
+                    events.emit(Event::BreakEnd);
                 }
             }
         }
 
-        debug!("Sleeping {} second(s) until next check", config.monitoring.check_frequency_seconds);
+        // A browser started some other way than --start-browser during a block or break.
+        if let Some(restriction) = state.restriction(Utc::now()).filter(|_| config.monitoring.kill_while_restricted) {
+            if browser_manager.has_running_processes() {
+                warn!("Browser running while {} — killing it", restriction);
+                waited += kill_browser(&browser_manager)?.waited;
+                focus_after_kill(focus_target.as_ref(), &window_monitor);
+            }
+        }
+
+        if config.monitoring.kiosk_mode {
+            enforce_kiosk(&config, &window_monitor, &spawned, &browser_manager.get_pids());
+        }
+
+        match relaunch.poll(Utc::now(), browser_manager.has_running_processes()) {
+            RelaunchDecision::Launch => {
+                info!("Relaunching the browser after the block");
+                if let Err(e) = handle_start_browser(&config, &events).await {
+                    error!("Browser relaunch failed: {}", e);
+                }
+            }
+            RelaunchDecision::Abort => info!("Browser already running — relaunch cancelled"),
+            RelaunchDecision::Idle | RelaunchDecision::Wait => {}
+        }
+
+        was_blocked = state.is_blocked();
+
+        let scan_time = tick_started.elapsed().saturating_sub(waited);
+        timings.record(scan_time);
+        events.emit(Event::Tick {
+            windows: window_count,
+            browser_processes: pids.len(),
+            duration_ms: scan_time.as_millis() as u64,
+        });
+        let (avg_ms, max_ms) = (timings.average().as_millis() as u64, timings.max().as_millis() as u64);
+        debug!("Tick took {:?} ({})", scan_time, format_scan_timings(avg_ms, max_ms));
+        let report_due = reported_timings.is_none_or(|(at, reported)| {
+            reported != (avg_ms, max_ms) && at.elapsed() >= TIMINGS_REPORT_INTERVAL
+        });
+        if report_due {
+            // Update the stored state so a subcommand's write during this tick is not overwritten.
+            let result = update_state(&*store, |latest| {
+                latest.scan_avg_ms = Some(avg_ms);
+                latest.scan_max_ms = Some(max_ms);
+                Ok(())
+            });
+            match result {
+                Ok(()) => reported_timings = Some((std::time::Instant::now(), (avg_ms, max_ms))),
+                Err(e) => warn!("Failed to save scan timings: {}", e),
+            }
+        }
+        if timings.is_slow(interval) != slow {
+            slow = !slow;
+            if slow {
+                warn!("Checks are slow ({}) for a {} second interval — consider raising monitoring.check_frequency_seconds",
+                    format_scan_timings(avg_ms, max_ms), config.monitoring.check_frequency_seconds);
+            }
+        }
+
+        let next_check = next_check_interval(interval,
+            config.monitoring.whitelist_recheck_seconds.map(Duration::from_secs), saw_whitelisted);
+        if saw_whitelisted {
+            debug!("Whitelisted title seen — checking again in {:?}", next_check);
+        }
+        debug!("Sleeping {} second(s) until next check", next_check.as_secs());
+        // Wake up early for a pending relaunch rather than at the next regular check.
+        let wait = relaunch.due().map_or(next_check, |due| (due - Utc::now()).to_std().unwrap_or_default().min(next_check));
         tokio::select! {
-            _ = sleep(Duration::from_secs(config.monitoring.check_frequency_seconds)) => {}
+            _ = sleep(wait) => {}
             _ = sigterm.recv() => {
-                println!("Received SIGTERM, shutting down");
+                events.say("Received SIGTERM, shutting down");
                 info!("Daemon stopped via SIGTERM");
                 return Ok(());
             }
+            _ = sighup.recv() => {
+                info!("Received SIGHUP, reloading config");
+                if let Err(e) = reload_config(&mut config, config_path, profile) {
+                    error!("Config reload failed, keeping the running config: {}", e);
+                    continue;
+                }
+                match Filter::with_config(&config.files.blacklist, &config.files.whitelist, config.filter.clone()) {
+                    Ok(reloaded) => {
+                        info!("Filter reloaded: {} blacklist pattern(s), {} whitelist pattern(s)",
+                            reloaded.blacklist_len(), reloaded.whitelist_len());
+                        filter = Arc::new(reloaded);
+                    }
+                    Err(e) => error!("Failed to reload patterns, keeping the old ones: {}", e),
+                }
+                match load_study_filter(&config) {
+                    Ok(reloaded) => study_filter = reloaded.map(Arc::new),
+                    Err(e) => error!("Failed to reload the study whitelist, keeping the old one: {}", e),
+                }
+                classifier = external_classifier(&config);
+                bg = background_manager(&config).with_spawned(spawned.clone());
+                interval = Duration::from_secs(config.monitoring.check_frequency_seconds);
+            }
         }
     }
 }
@@ -0,0 +1,201 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::command::CommandRunner;
+use crate::state::{clamp_block_minutes, AppState};
+
+/// Longest a single sync request may take before curl gives up.
+pub const SYNC_TIMEOUT_SECONDS: u64 = 10;
+
+/// What the daemon POSTs to `management.sync_url`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SyncReport {
+    pub host: String,
+    pub blocked_until: Option<DateTime<Utc>>,
+    pub bathroom_break_until: Option<DateTime<Utc>>,
+    pub violation_count: u32,
+    pub usage_minutes_today: u64,
+    pub usage_minutes_week: u64,
+}
+
+impl SyncReport {
+    /// Restrictions that have already ended are reported as `None`.
+    pub fn from_state(state: &AppState, host: &str, now: DateTime<Utc>, today: NaiveDate) -> Self {
+        SyncReport {
+            host: host.to_string(),
+            blocked_until: state.blocked_until.filter(|until| *until > now),
+            bathroom_break_until: state.bathroom_break_until.filter(|until| state.in_bathroom_break && *until > now),
+            violation_count: state.violation_count,
+            usage_minutes_today: state.usage_minutes(today),
+            usage_minutes_week: state.usage_week_minutes(today),
+        }
+    }
+}
+
+/// A policy change pushed back by the server in a sync response.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum ManagementCommand {
+    /// Block for `minutes`, or `timeouts.blacklist_timeout_minutes` when omitted.
+    Lockdown {
+        #[serde(default)]
+        minutes: Option<u64>,
+    },
+    /// Lift the current block.
+    Unblock,
+    /// Re-read the config file, as on SIGHUP.
+    Reload,
+    /// A command this version does not know; skipped.
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct SyncResponse {
+    #[serde(default)]
+    commands: Vec<ManagementCommand>,
+}
+
+/// The commands in a sync response body. An empty body carries none.
+pub fn parse_response(body: &str) -> Result<Vec<ManagementCommand>> {
+    if body.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let response: SyncResponse = serde_json::from_str(body)
+        .map_err(|e| anyhow!("invalid sync response: {}", e))?;
+    Ok(response.commands)
+}
+
+/// What applying a sync response did, so the daemon can follow up.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Applied {
+    /// Minutes of the lockdown that was started, if any.
+    pub locked_down: Option<u64>,
+    pub unblocked: bool,
+    pub reload: bool,
+}
+
+impl Applied {
+    #[allow(dead_code)]
+    pub fn state_changed(&self) -> bool {
+        self.locked_down.is_some() || self.unblocked
+    }
+}
+
+/// Applies `commands` to `state` in order. Lockdowns are capped by
+/// `max_block_minutes`, like the `lockdown` subcommand.
+pub fn apply_commands(
+    state: &mut AppState,
+    commands: &[ManagementCommand],
+    default_minutes: u64,
+    max_block_minutes: Option<u64>,
+) -> Applied {
+    let mut applied = Applied::default();
+    for command in commands {
+        match command {
+            ManagementCommand::Lockdown { minutes } => {
+                let minutes = clamp_block_minutes(minutes.unwrap_or(default_minutes), max_block_minutes);
+                state.block_browser(minutes);
+                applied.locked_down = Some(minutes);
+                applied.unblocked = false;
+            }
+            ManagementCommand::Unblock => {
+                state.blocked_until = None;
+                applied.locked_down = None;
+                applied.unblocked = true;
+            }
+            ManagementCommand::Reload => applied.reload = true,
+            ManagementCommand::Unknown => warn!("Ignoring an unknown command from the management server"),
+        }
+    }
+    applied
+}
+
+/// POSTs `report` to `url` with curl and returns the commands in the response.
+pub fn sync(runner: &dyn CommandRunner, url: &str, report: &SyncReport) -> Result<Vec<ManagementCommand>> {
+    let body = serde_json::to_string(report)?;
+    let timeout = SYNC_TIMEOUT_SECONDS.to_string();
+    debug!("Syncing state to '{}': {}", url, body);
+    let output = runner.run("curl", &[
+        "--silent", "--show-error", "--fail",
+        "--max-time", &timeout,
+        "--header", "Content-Type: application/json",
+        "--data-binary", &body,
+        url,
+    ])?;
+    if !output.status.success() {
+        return Err(anyhow!("curl exited with {}: {}", output.status,
+            String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    parse_response(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// This machine's name as reported to the server.
+pub fn host_name() -> String {
+    std::fs::read_to_string("/proc/sys/kernel/hostname")
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::testing::RecordingRunner;
+
+    #[test]
+    fn test_lockdown_from_response_blocks_state() {
+        let mut state = AppState::default();
+        let commands = parse_response(r#"{"commands": [{"command": "lockdown", "minutes": 90}, {"command": "reboot"}]}"#)
+            .unwrap();
+        assert_eq!(commands, vec![ManagementCommand::Lockdown { minutes: Some(90) }, ManagementCommand::Unknown]);
+
+        let applied = apply_commands(&mut state, &commands, 30, Some(60));
+
+        assert_eq!(applied, Applied { locked_down: Some(60), unblocked: false, reload: false });
+        assert!(state.is_blocked());
+        let left = state.blocked_until.unwrap() - Utc::now();
+        assert!(left <= chrono::Duration::minutes(60) && left > chrono::Duration::minutes(59));
+    }
+
+    #[test]
+    fn test_huge_lockdown_does_not_overflow() {
+        let mut state = AppState::default();
+        let commands = parse_response(r#"{"commands": [{"command": "lockdown", "minutes": 18446744073709551615}]}"#)
+            .unwrap();
+        let applied = apply_commands(&mut state, &commands, 30, None);
+        assert_eq!(applied.locked_down, Some(u64::MAX));
+        assert!(state.is_blocked());
+    }
+
+    #[test]
+    fn test_unblock_and_reload_from_response() {
+        let mut state = AppState::default();
+        state.block_browser(30);
+        let commands = parse_response(r#"{"commands": [{"command": "unblock"}, {"command": "reload"}]}"#).unwrap();
+
+        let applied = apply_commands(&mut state, &commands, 30, None);
+
+        assert!(applied.unblocked && applied.reload && applied.state_changed());
+        assert!(!state.is_blocked());
+        assert!(parse_response("").unwrap().is_empty());
+        assert!(parse_response("{}").unwrap().is_empty());
+        assert!(parse_response("<html>").is_err());
+    }
+
+    #[test]
+    fn test_sync_posts_report_with_curl() {
+        let runner = RecordingRunner::answering(0, r#"{"commands": [{"command": "lockdown"}]}"#);
+        let report = SyncReport::from_state(&AppState::default(), "kids-pc", Utc::now(), Utc::now().date_naive());
+
+        let commands = sync(&runner, "https://admin.example/sync", &report).unwrap();
+
+        assert_eq!(commands, vec![ManagementCommand::Lockdown { minutes: None }]);
+        let call = &runner.calls()[0];
+        assert_eq!(call[0], "curl");
+        assert_eq!(call.last().unwrap(), "https://admin.example/sync");
+        assert!(call.iter().any(|arg| arg.contains(r#""host":"kids-pc""#)));
+        assert!(sync(&RecordingRunner::answering(22, ""), "https://admin.example/sync", &report).is_err());
+    }
+}
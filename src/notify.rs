@@ -0,0 +1,169 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveTime;
+use log::{debug, info, warn};
+use std::process::Command;
+
+use crate::command::SpawnedPids;
+use crate::config::NotificationConfig;
+use crate::timezone::Zone;
+
+/// A daily window in configured local time during which notifications are suppressed.
+/// `end` before `start` means the window wraps past midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    /// Parses `HH:MM` (or `HH:MM:SS`) bounds.
+    pub fn parse(start: &str, end: &str) -> Result<Self> {
+        Ok(QuietHours {
+            start: parse_time(start)?,
+            end: parse_time(end)?,
+        })
+    }
+
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+fn parse_time(value: &str) -> Result<NaiveTime> {
+    NaiveTime::parse_from_str(value, "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(value, "%H:%M:%S"))
+        .map_err(|_| anyhow!("invalid time '{}', expected HH:MM", value))
+}
+
+/// Sends desktop notifications via `notify-send`. Every notification goes through
+/// [`Notifier::notify`], which applies the enabled flag and quiet hours.
+pub struct Notifier {
+    enabled: bool,
+    quiet_hours: Option<QuietHours>,
+    zone: Zone,
+    spawned: SpawnedPids,
+}
+
+impl Notifier {
+    pub fn from_config(config: &NotificationConfig, zone: Zone) -> Result<Self> {
+        let quiet_hours = match &config.quiet_hours {
+            Some(q) => Some(QuietHours::parse(&q.start, &q.end)?),
+            None => None,
+        };
+        Ok(Notifier {
+            enabled: config.enabled,
+            quiet_hours,
+            zone,
+            spawned: SpawnedPids::default(),
+        })
+    }
+
+    /// Records the `notify-send` processes this notifier starts in `spawned`.
+    pub fn with_spawned(mut self, spawned: SpawnedPids) -> Self {
+        self.spawned = spawned;
+        self
+    }
+
+    pub fn is_suppressed_at(&self, time: NaiveTime) -> bool {
+        !self.enabled || self.quiet_hours.is_some_and(|q| q.contains(time))
+    }
+
+    /// Fire-and-forget; a missing `notify-send` is logged and otherwise ignored.
+    pub fn notify(&self, summary: &str, body: &str) {
+        let now = self.zone.now_time();
+        if self.is_suppressed_at(now) {
+            debug!("Notification suppressed: '{}'", summary);
+            return;
+        }
+
+        info!("Notifying: '{}' '{}'", summary, body);
+        match self.spawned.spawn(Command::new("notify-send").arg(summary).arg(body)) {
+            Ok(child) => self.spawned.wait_in_background(child, |_| {}),
+            Err(e) => warn!("Failed to send notification: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::QuietHoursConfig;
+
+    fn t(value: &str) -> NaiveTime {
+        parse_time(value).unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_same_day() {
+        let quiet = QuietHours::parse("13:00", "15:30").unwrap();
+
+        assert!(!quiet.contains(t("12:59")));
+        assert!(quiet.contains(t("13:00")));
+        assert!(quiet.contains(t("15:29")));
+        assert!(!quiet.contains(t("15:30")));
+    }
+
+    #[test]
+    fn test_quiet_hours_spanning_midnight() {
+        let quiet = QuietHours::parse("22:00", "07:00").unwrap();
+
+        assert!(quiet.contains(t("22:00")));
+        assert!(quiet.contains(t("23:59")));
+        assert!(quiet.contains(t("00:00")));
+        assert!(quiet.contains(t("06:59")));
+        assert!(!quiet.contains(t("07:00")));
+        assert!(!quiet.contains(t("12:00")));
+        assert!(!quiet.contains(t("21:59")));
+    }
+
+    #[test]
+    fn test_quiet_hours_equal_bounds_never_quiet() {
+        let quiet = QuietHours::parse("08:00", "08:00").unwrap();
+        assert!(!quiet.contains(t("08:00")));
+        assert!(!quiet.contains(t("20:00")));
+    }
+
+    #[test]
+    fn test_quiet_hours_parse_errors() {
+        assert!(QuietHours::parse("25:00", "07:00").is_err());
+        assert!(QuietHours::parse("22:00", "soon").is_err());
+        assert_eq!(t("07:15:30"), NaiveTime::from_hms_opt(7, 15, 30).unwrap());
+    }
+
+    #[test]
+    fn test_notifier_suppression() {
+        let config = NotificationConfig {
+            enabled: true,
+            quiet_hours: Some(QuietHoursConfig {
+                start: "22:00".to_string(),
+                end: "07:00".to_string(),
+            }),
+        };
+        let notifier = Notifier::from_config(&config, Zone::Local).unwrap();
+        assert!(notifier.is_suppressed_at(t("23:00")));
+        assert!(!notifier.is_suppressed_at(t("10:00")));
+
+        let disabled = Notifier::from_config(&NotificationConfig::default(), Zone::Local).unwrap();
+        assert!(disabled.is_suppressed_at(t("10:00")));
+    }
+
+    #[test]
+    fn test_quiet_hours_in_configured_timezone() {
+        use chrono::{TimeZone, Utc};
+
+        let quiet = QuietHours::parse("22:00", "07:00").unwrap();
+        let zone = Zone::parse(Some("America/New_York")).unwrap();
+
+        // 03:00 UTC is 22:00 the previous evening in New York (EST).
+        let evening = Utc.with_ymd_and_hms(2026, 1, 15, 3, 0, 0).unwrap();
+        assert!(quiet.contains(zone.time_of(evening)));
+
+        // 15:00 UTC is 10:00 in New York, whatever the host TZ says.
+        let morning = Utc.with_ymd_and_hms(2026, 1, 15, 15, 0, 0).unwrap();
+        assert!(!quiet.contains(zone.time_of(morning)));
+    }
+}
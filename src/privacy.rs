@@ -0,0 +1,120 @@
+use regex::RegexBuilder;
+use sha2::{Digest, Sha256};
+
+use crate::config::{PrivacyConfig, TitleLogging};
+
+/// Stands in for a title that must not be logged at all.
+pub const REDACTED: &str = "<redacted>";
+
+/// Hex digits of the SHA-256 digest kept in a hashed title; enough to correlate entries.
+const HASH_HEX_LEN: usize = 16;
+
+/// Rewrites window titles for the audit log, the daemon log and the titles file
+/// according to `privacy.title_logging`.
+#[derive(Debug, Clone)]
+pub struct TitleRedactor {
+    mode: TitleLogging,
+    salt: String,
+    case_insensitive: bool,
+}
+
+impl TitleRedactor {
+    pub fn new(config: &PrivacyConfig, case_insensitive: bool) -> Self {
+        TitleRedactor {
+            mode: config.title_logging,
+            salt: config.salt.clone().unwrap_or_default(),
+            case_insensitive,
+        }
+    }
+
+    /// Whether titles that did not match anything may be written out (e.g. to the
+    /// titles file). `matched_only` and `none` have nothing to show for them.
+    pub fn logs_unmatched(&self) -> bool {
+        matches!(self.mode, TitleLogging::Full | TitleLogging::Hash)
+    }
+
+    /// The loggable form of `title`. `pattern` is the blacklist pattern it matched, if any.
+    pub fn redact(&self, title: &str, pattern: Option<&str>) -> String {
+        match self.mode {
+            TitleLogging::Full => title.to_string(),
+            TitleLogging::MatchedOnly => pattern
+                .and_then(|p| matched_span(title, p, self.case_insensitive))
+                .unwrap_or_else(|| REDACTED.to_string()),
+            TitleLogging::Hash => hash_title(title, &self.salt),
+            TitleLogging::None => REDACTED.to_string(),
+        }
+    }
+}
+
+impl Default for TitleRedactor {
+    fn default() -> Self {
+        TitleRedactor::new(&PrivacyConfig::default(), true)
+    }
+}
+
+/// The part of `title` matched by `pattern`. Leading and trailing `.*` are dropped
+/// first so a pattern like `.*porn.*` yields the keyword rather than the whole title.
+pub fn matched_span(title: &str, pattern: &str, case_insensitive: bool) -> Option<String> {
+    let core = pattern.trim_start_matches(".*").trim_end_matches(".*");
+    let regex = RegexBuilder::new(core).case_insensitive(case_insensitive).build().ok()?;
+    regex
+        .find(title)
+        .map(|m| m.as_str().to_string())
+        .filter(|span| !span.is_empty())
+}
+
+/// `sha256:` followed by the first hex digits of SHA-256(salt ‖ title).
+pub fn hash_title(title: &str, salt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(salt.as_bytes());
+    hasher.update(title.as_bytes());
+    let hex: String = hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect();
+    format!("sha256:{}", &hex[..HASH_HEX_LEN])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TITLE: &str = "free porn videos - Mozilla Firefox";
+
+    fn redactor(mode: TitleLogging, salt: &str) -> TitleRedactor {
+        TitleRedactor::new(
+            &PrivacyConfig { title_logging: mode, salt: Some(salt.to_string()) },
+            true,
+        )
+    }
+
+    #[test]
+    fn test_full_keeps_title() {
+        let redactor = redactor(TitleLogging::Full, "pepper");
+        assert_eq!(redactor.redact(TITLE, Some(".*porn.*")), TITLE);
+        assert!(redactor.logs_unmatched());
+    }
+
+    #[test]
+    fn test_matched_only_logs_span() {
+        let redactor = redactor(TitleLogging::MatchedOnly, "pepper");
+        assert_eq!(redactor.redact(TITLE, Some(".*PORN.*")), "porn");
+        assert_eq!(redactor.redact(TITLE, Some(r".*porn\s+\w+.*")), "porn videos");
+        assert_eq!(redactor.redact(TITLE, None), REDACTED);
+        assert_eq!(redactor.redact(TITLE, Some("<picture-in-picture>")), REDACTED);
+        assert!(!redactor.logs_unmatched());
+    }
+
+    #[test]
+    fn test_hash_is_stable_and_salted() {
+        let redactor = redactor(TitleLogging::Hash, "pepper");
+        assert_eq!(redactor.redact(TITLE, Some(".*porn.*")), "sha256:303d707862c817c2");
+        assert_eq!(redactor.redact(TITLE, None), "sha256:303d707862c817c2");
+        assert_eq!(hash_title(TITLE, "other"), "sha256:d3956a22a5857136");
+        assert!(redactor.logs_unmatched());
+    }
+
+    #[test]
+    fn test_none_hides_everything() {
+        let redactor = redactor(TitleLogging::None, "pepper");
+        assert_eq!(redactor.redact(TITLE, Some(".*porn.*")), REDACTED);
+        assert!(!redactor.logs_unmatched());
+    }
+}
@@ -0,0 +1,136 @@
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use nix::unistd::{getuid, setgid, setgroups, setuid, Gid, Uid, User};
+use std::fs;
+use std::path::Path;
+
+/// Checks that `path` is a real directory (not a symlink) owned by this process's uid
+/// and, with `private`, accessible to nobody else.
+pub fn check_own_dir(path: &Path, private: bool) -> Result<()> {
+    use std::os::unix::fs::MetadataExt;
+
+    let meta = fs::symlink_metadata(path)?;
+    if !meta.file_type().is_dir() {
+        return Err(anyhow!("'{}' is not a directory", path.display()));
+    }
+    if meta.uid() != getuid().as_raw() {
+        return Err(anyhow!("'{}' is owned by uid {}", path.display(), meta.uid()));
+    }
+    if private && meta.mode() & 0o777 != 0o700 {
+        return Err(anyhow!("'{}' has mode {:o}, expected 700", path.display(), meta.mode() & 0o777));
+    }
+    Ok(())
+}
+
+/// Creates `dir` with mode 0700, or checks that an existing one is ours and private.
+/// For directories with a predictable name in the shared temp dir.
+pub fn create_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => check_own_dir(dir, true),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Looks `name` up in the user database and returns its uid and primary gid.
+pub fn resolve_user(name: &str) -> Result<(Uid, Gid)> {
+    let user = User::from_name(name)
+        .map_err(|e| anyhow!("failed to look up user '{}': {}", name, e))?
+        .ok_or_else(|| anyhow!("no such user '{}'", name))?;
+    Ok((user.uid, user.gid))
+}
+
+/// Lowest uid handed to login users (`UID_MIN` in `login.defs`); lower ones are root
+/// and system accounts.
+const FIRST_SESSION_UID: u32 = 1000;
+
+/// The `nobody` user.
+const OVERFLOW_UID: u32 = 65534;
+
+/// Whether `uid` belongs to a regular login user rather than root or a system account.
+pub fn is_session_uid(uid: u32) -> bool {
+    uid >= FIRST_SESSION_UID && uid != OVERFLOW_UID
+}
+
+/// Real uid of `pid`, read from the `Uid:` line of `<proc_root>/<pid>/status`.
+pub fn process_uid(proc_root: &Path, pid: i32) -> Option<u32> {
+    let status = fs::read_to_string(proc_root.join(pid.to_string()).join("status")).ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))
+        .and_then(|ids| ids.split_whitespace().next())
+        .and_then(|uid| uid.parse().ok())
+}
+
+/// Name of the user owning `pid`, via its `status` file under `proc_root` and the
+/// user database.
+pub fn process_user(proc_root: &Path, pid: i32) -> Option<String> {
+    let uid = process_uid(proc_root, pid)?;
+    User::from_uid(Uid::from_raw(uid)).ok().flatten().map(|user| user.name)
+}
+
+/// `security.run_as_user`: when running as root, switches to `name` for good.
+/// Refuses if any of `browser_pids` belongs to another user, since the daemon
+/// could no longer signal it. Returns whether privileges were dropped.
+pub fn drop_privileges(name: &str, browser_pids: &[i32]) -> Result<bool> {
+    let (uid, gid) = resolve_user(name)?;
+    if !Uid::effective().is_root() {
+        if Uid::effective() != uid {
+            warn!("security.run_as_user '{}' ignored: the daemon is not running as root", name);
+        }
+        return Ok(false);
+    }
+
+    let proc_root = Path::new("/proc");
+    if let Some(&pid) = browser_pids
+        .iter()
+        .find(|&&pid| process_uid(proc_root, pid).is_some_and(|owner| owner != uid.as_raw()))
+    {
+        return Err(anyhow!("cannot run as '{}': browser pid {} belongs to another user and could no longer be killed",
+            name, pid));
+    }
+
+    setgroups(&[gid]).map_err(|e| anyhow!("setgroups failed: {}", e))?;
+    setgid(gid).map_err(|e| anyhow!("setgid({}) failed: {}", gid, e))?;
+    setuid(uid).map_err(|e| anyhow!("setuid({}) failed: {}", uid, e))?;
+    info!("Dropped privileges to '{}' (uid {}, gid {})", name, uid, gid);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_user() {
+        assert_eq!(resolve_user("root").unwrap(), (Uid::from_raw(0), Gid::from_raw(0)));
+        assert!(resolve_user("no-such-user-ivh-12345").is_err());
+    }
+
+    #[test]
+    fn test_process_uid_from_fake_proc() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root.path().join("4242");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("status"), "Name:\tfirefox\nPid:\t4242\nUid:\t1000\t1000\t1000\t1000\nGid:\t1000\t1000\t1000\t1000\n").unwrap();
+
+        assert_eq!(process_uid(root.path(), 4242), Some(1000));
+        assert_eq!(process_uid(root.path(), 4243), None);
+    }
+
+    #[test]
+    fn test_process_user_maps_uid_to_name() {
+        let root = tempfile::tempdir().unwrap();
+        for (pid, uid) in [("100", "0"), ("200", "4000000000")] {
+            let dir = root.path().join(pid);
+            fs::create_dir_all(&dir).unwrap();
+            fs::write(dir.join("status"), format!("Name:\tfirefox\nUid:\t{uid}\t{uid}\t{uid}\t{uid}\n")).unwrap();
+        }
+
+        assert_eq!(process_user(root.path(), 100).as_deref(), Some("root"));
+        assert_eq!(process_user(root.path(), 200), None);
+        assert_eq!(process_user(root.path(), 300), None);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::config::Config;
+
+/// What a config reload changed: settings applied to the running daemon, and
+/// settings that differ on disk but only take effect after a restart.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigChanges {
+    pub applied: Vec<&'static str>,
+    pub ignored: Vec<&'static str>,
+}
+
+impl ConfigChanges {
+    pub fn is_empty(&self) -> bool {
+        self.applied.is_empty() && self.ignored.is_empty()
+    }
+}
+
+/// Copies the settings that are safe to change while the daemon runs (timeouts,
+/// the filter and its pattern files, backgrounds, the check frequency) from `new`
+/// into `current`. Anything else that differs is left alone and reported as ignored.
+pub fn apply_live_changes(current: &mut Config, mut new: Config) -> ConfigChanges {
+    let mut changes = ConfigChanges::default();
+
+    let check_frequency = new.monitoring.check_frequency_seconds;
+    new.monitoring.check_frequency_seconds = current.monitoring.check_frequency_seconds;
+    for (name, differs) in [
+        ("browser", current.browser != new.browser),
+        ("monitoring", current.monitoring != new.monitoring),
+        ("files.state_file", current.files.state_file != new.files.state_file),
+        ("files.state_backend", current.files.state_backend != new.files.state_backend),
+        ("files.log_file", current.files.log_file != new.files.log_file),
+        ("files.titles_file", current.files.titles_file != new.files.titles_file),
+        ("audit", current.audit != new.audit),
+        ("notifications", current.notifications != new.notifications),
+        ("privacy", current.privacy != new.privacy),
+        ("security", current.security != new.security),
+        ("management", current.management != new.management),
+        ("hooks", current.hooks != new.hooks),
+        ("timezone", current.timezone != new.timezone),
+        ("profiles", current.profiles != new.profiles),
+    ] {
+        if differs {
+            changes.ignored.push(name);
+        }
+    }
+
+    if current.monitoring.check_frequency_seconds != check_frequency {
+        current.monitoring.check_frequency_seconds = check_frequency;
+        changes.applied.push("monitoring.check_frequency_seconds");
+    }
+    if current.timeouts != new.timeouts {
+        current.timeouts = new.timeouts;
+        changes.applied.push("timeouts");
+    }
+    if current.backgrounds != new.backgrounds {
+        current.backgrounds = new.backgrounds;
+        changes.applied.push("backgrounds");
+    }
+    if current.filter != new.filter {
+        current.filter = new.filter;
+        changes.applied.push("filter");
+    }
+    if current.files.blacklist != new.files.blacklist {
+        current.files.blacklist = new.files.blacklist;
+        changes.applied.push("files.blacklist");
+    }
+    if current.files.whitelist != new.files.whitelist {
+        current.files.whitelist = new.files.whitelist;
+        changes.applied.push("files.whitelist");
+    }
+    if current.files.study_whitelist != new.files.study_whitelist {
+        current.files.study_whitelist = new.files.study_whitelist;
+        changes.applied.push("files.study_whitelist");
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_live_changes() {
+        let mut current = Config::default();
+        let mut new = Config::default();
+        new.timeouts.blacklist_timeout_minutes = 45;
+        new.monitoring.check_frequency_seconds = 30;
+        new.files.blacklist = "/etc/ivh/strict.txt".to_string();
+        new.monitoring.display = Some(":7".to_string());
+        new.files.state_file = "/tmp/other-state.json".to_string();
+
+        let changes = apply_live_changes(&mut current, new);
+        assert_eq!(changes, ConfigChanges {
+            applied: vec!["monitoring.check_frequency_seconds", "timeouts", "files.blacklist"],
+            ignored: vec!["monitoring", "files.state_file"],
+        });
+        assert_eq!(current.timeouts.blacklist_timeout_minutes, 45);
+        assert_eq!(current.monitoring.check_frequency_seconds, 30);
+        assert_eq!(current.files.blacklist, "/etc/ivh/strict.txt");
+        assert_eq!(current.monitoring.display, None);
+        assert_eq!(current.files.state_file, Config::default().files.state_file);
+    }
+
+    #[test]
+    fn test_unchanged_config_reports_nothing() {
+        let mut current = Config::default();
+        assert!(apply_live_changes(&mut current, Config::default()).is_empty());
+    }
+}
@@ -0,0 +1,107 @@
+use anyhow::Result;
+
+use crate::filter::{Filter, Verdict};
+use crate::window_info::{remove_ignored_classes, WindowTitles};
+
+/// One window title from a scan together with the filter's decision.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScanEntry {
+    pub title: String,
+    pub verdict: Verdict,
+}
+
+impl ScanEntry {
+    pub fn line(&self) -> String {
+        match &self.verdict {
+            Verdict::Allowed => format!("[allowed] {}", self.title),
+            Verdict::Whitelisted => format!("[whitelisted] {}", self.title),
+            Verdict::Blocked(blocked) => format!("[blocked by '{}'] {}", blocked, self.title),
+        }
+    }
+}
+
+/// Takes a single snapshot of the browser windows and evaluates every title,
+/// skipping windows whose class is in `ignore_classes`.
+pub fn scan_snapshot<S>(
+    source: &S,
+    browser_pids: &[i32],
+    ignore_classes: &[String],
+    filter: &Filter,
+) -> Result<Vec<ScanEntry>>
+where
+    S: WindowTitles + ?Sized,
+{
+    let mut windows = source.browser_windows(browser_pids)?;
+    remove_ignored_classes(&mut windows, ignore_classes);
+    Ok(windows
+        .into_iter()
+        .map(|window| ScanEntry {
+            verdict: filter.evaluate(&window.title),
+            title: window.title,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::window_info::WindowInfo;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    struct MockTitles(Vec<&'static str>);
+
+    impl WindowTitles for MockTitles {
+        fn browser_windows(&self, browser_pids: &[i32]) -> Result<Vec<WindowInfo>> {
+            Ok(self
+                .0
+                .iter()
+                .enumerate()
+                .map(|(i, title)| WindowInfo {
+                    id: i as u64,
+                    pid: browser_pids.first().copied(),
+                    title: title.to_string(),
+                    ..WindowInfo::default()
+                })
+                .collect())
+        }
+    }
+
+    fn pattern_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_scan_snapshot_mixed_titles() {
+        let blacklist = pattern_file(".*porn.*\n.*gambling.*\n");
+        let whitelist = pattern_file(".*education.*\n");
+        let filter = Filter::new(blacklist.path(), whitelist.path()).unwrap();
+        let source = MockTitles(vec!["Cooking tutorial", "free porn", "porn education", "Online gambling"]);
+
+        let entries = scan_snapshot(&source, &[42], &[], &filter).unwrap();
+        let lines: Vec<String> = entries.iter().map(ScanEntry::line).collect();
+
+        assert_eq!(lines, vec![
+            "[allowed] Cooking tutorial",
+            "[blocked by '.*porn.*'] free porn",
+            "[whitelisted] porn education",
+            "[blocked by '.*gambling.*'] Online gambling",
+        ]);
+    }
+
+    #[test]
+    fn test_scan_snapshot_propagates_source_errors() {
+        struct NoDisplay;
+        impl WindowTitles for NoDisplay {
+            fn browser_windows(&self, _: &[i32]) -> Result<Vec<WindowInfo>> {
+                Err(anyhow::anyhow!("Failed to open X11 display"))
+            }
+        }
+
+        let blacklist = pattern_file("");
+        let filter = Filter::new(blacklist.path(), blacklist.path()).unwrap();
+        assert!(scan_snapshot(&NoDisplay, &[], &[], &filter).is_err());
+    }
+}
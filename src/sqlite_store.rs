@@ -0,0 +1,174 @@
+use anyhow::Result;
+use rusqlite::{Connection, OptionalExtension, TransactionBehavior};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::state::{AppState, StateStore};
+
+/// How long a write waits for another process holding the database lock.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Keeps [`AppState`] as a single row of an SQLite database. Updates are
+/// transactional, so a crash mid-write leaves the previous state intact and
+/// readers never see a partial one.
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        if let Some(parent) = path.as_ref().parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch(
+            "PRAGMA journal_mode = WAL;
+             CREATE TABLE IF NOT EXISTS app_state (
+                 id INTEGER PRIMARY KEY CHECK (id = 1),
+                 json TEXT NOT NULL
+             );",
+        )?;
+        Ok(SqliteStore { conn: Mutex::new(conn) })
+    }
+}
+
+fn read_state(conn: &Connection) -> Result<AppState> {
+    let json: Option<String> = conn
+        .query_row("SELECT json FROM app_state WHERE id = 1", [], |row| row.get(0))
+        .optional()?;
+    match json {
+        Some(json) => Ok(serde_json::from_str(&json)?),
+        None => Ok(AppState::default_with_next_break()),
+    }
+}
+
+fn write_state(conn: &Connection, state: &AppState) -> Result<()> {
+    let json = serde_json::to_string(state)?;
+    conn.execute(
+        "INSERT INTO app_state (id, json) VALUES (1, ?1)
+         ON CONFLICT (id) DO UPDATE SET json = excluded.json",
+        [json],
+    )?;
+    Ok(())
+}
+
+impl StateStore for SqliteStore {
+    fn load(&self) -> Result<AppState> {
+        read_state(&self.conn.lock().unwrap())
+    }
+
+    fn save(&self, state: &AppState) -> Result<()> {
+        write_state(&self.conn.lock().unwrap(), state)
+    }
+
+    /// Runs in a `BEGIN IMMEDIATE` transaction, which takes the database's write
+    /// lock before reading, so another process cannot save in between. A failing
+    /// `change` rolls the transaction back.
+    fn update(&self, change: &mut dyn FnMut(&mut AppState) -> Result<()>) -> Result<AppState> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+        let mut state = read_state(&tx)?;
+        change(&mut state)?;
+        write_state(&tx, &state)?;
+        tx.commit()?;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{JsonFileStore, PendingAck};
+    use chrono::{NaiveDate, TimeZone, Utc};
+    use tempfile::TempDir;
+
+    fn sample_state() -> AppState {
+        let at = |h, m| Utc.with_ymd_and_hms(2026, 10, 16, h, m, 0).unwrap();
+        AppState {
+            blocked_until: Some(at(15, 30)),
+            next_bathroom_break: at(17, 0),
+            violation_count: 2,
+            violation_window_start: Some(at(15, 0)),
+            pending_ack: Some(PendingAck {
+                title: "Bad Page".to_string(),
+                pattern: ".*bad.*".to_string(),
+                label: None,
+                deadline: at(15, 1),
+                acknowledged: false,
+            }),
+            recent_launches: vec![at(14, 59)],
+            usage_day: NaiveDate::from_ymd_opt(2026, 10, 16),
+            usage_seconds: 1800,
+            snoozes_used: 1,
+            ..AppState::default()
+        }
+    }
+
+    #[test]
+    fn test_missing_row_is_default_state() {
+        let dir = TempDir::new().unwrap();
+        let store = SqliteStore::open(dir.path().join("state.db")).unwrap();
+        let state = store.load().unwrap();
+        assert!(state.blocked_until.is_none());
+        assert!(state.next_bathroom_break > Utc::now());
+    }
+
+    #[test]
+    fn test_backends_round_trip_identical_state() {
+        let dir = TempDir::new().unwrap();
+        let sqlite = SqliteStore::open(dir.path().join("nested").join("state.db")).unwrap();
+        let json = JsonFileStore::new(dir.path().join("state.json"));
+        let state = sample_state();
+        let expected = serde_json::to_value(&state).unwrap();
+
+        for store in [&sqlite as &dyn StateStore, &json] {
+            store.save(&AppState::default()).unwrap();
+            store.save(&state).unwrap();
+            assert_eq!(serde_json::to_value(store.load().unwrap()).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_concurrent_updates_from_separate_connections_are_not_lost() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("state.db");
+        SqliteStore::open(&path).unwrap().save(&AppState::default()).unwrap();
+
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let store = SqliteStore::open(&path).unwrap();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        store.update(&mut |state| {
+                            state.violation_count += 1;
+                            Ok(())
+                        }).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        assert_eq!(SqliteStore::open(&path).unwrap().load().unwrap().violation_count, 100);
+    }
+
+    #[test]
+    fn test_failed_update_leaves_state_unchanged() {
+        let dir = TempDir::new().unwrap();
+        let store = SqliteStore::open(dir.path().join("state.db")).unwrap();
+        store.save(&sample_state()).unwrap();
+
+        let result = store.update(&mut |state| {
+            state.violation_count = 99;
+            Err(anyhow::anyhow!("rejected"))
+        });
+
+        assert!(result.is_err());
+        assert_eq!(store.load().unwrap().violation_count, 2);
+    }
+}
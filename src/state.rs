@@ -1,8 +1,313 @@
-use anyhow::Result;
-use chrono::{DateTime, Utc};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, IsoWeek, NaiveDate, Utc};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use nix::fcntl::{Flock, FlockArg};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::{BackgroundConfig, ClockJumpAction, StateBackend};
+
+/// Attempts [`read_state_locked`] makes before giving up.
+const LOCKED_READ_ATTEMPTS: u32 = 5;
+const LOCKED_READ_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+/// `<state file>.lock`, the file the state's readers and writers `flock`. The state
+/// file itself cannot be locked, since every save replaces it.
+fn lock_path(path: &Path) -> PathBuf {
+    let mut lock = path.as_os_str().to_owned();
+    lock.push(".lock");
+    PathBuf::from(lock)
+}
+
+/// Takes `lock` on the state file's lock file, creating it (and its directory) if needed.
+fn lock_state(path: &Path, lock: FlockArg) -> Result<Flock<fs::File>> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lock_file = lock_path(path);
+    let file = fs::OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&lock_file)
+        .map_err(|e| anyhow!("cannot open state lock '{}': {}", lock_file.display(), e))?;
+    Flock::lock(file, lock).map_err(|(_, errno)| anyhow!("cannot lock '{}': {}", lock_file.display(), errno))
+}
+
+/// Reads the state file for a CLI subcommand while the daemon may be writing it.
+/// The read holds a shared `flock` on the lock file, and a read that fails because
+/// the file is missing or does not parse is retried briefly before the error is
+/// returned. A state file that does not exist at all yields the default state.
+pub fn read_state_locked<P: AsRef<Path>>(path: P) -> Result<AppState> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(AppState::default_with_next_break());
+    }
+
+    let mut attempt = 1;
+    loop {
+        match read_once_locked(path) {
+            Ok(state) => return Ok(state),
+            Err(e) if attempt < LOCKED_READ_ATTEMPTS => {
+                log::debug!("State read {} of {} failed ({}), retrying", attempt, LOCKED_READ_ATTEMPTS, e);
+                attempt += 1;
+                std::thread::sleep(LOCKED_READ_RETRY_DELAY);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn read_once_locked(path: &Path) -> Result<AppState> {
+    // A reader without write access to the directory still reads, just unlocked;
+    // saves are atomic renames, so it cannot see a partial file either way.
+    let _lock = lock_state(path, FlockArg::LockShared)
+        .inspect_err(|e| log::debug!("Reading the state without a lock: {}", e))
+        .ok();
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// Where [`AppState`] is persisted between ticks and across restarts.
+pub trait StateStore: Send + Sync {
+    /// The stored state, or a fresh default state if nothing has been stored yet.
+    fn load(&self) -> Result<AppState>;
+    fn save(&self, state: &AppState) -> Result<()>;
+
+    /// Loads the state, applies `change` and saves the result, without another
+    /// writer's save landing in between. Nothing is saved when `change` fails.
+    fn update(&self, change: &mut dyn FnMut(&mut AppState) -> Result<()>) -> Result<AppState> {
+        let mut state = self.load()?;
+        change(&mut state)?;
+        self.save(&state)?;
+        Ok(state)
+    }
+}
+
+/// [`StateStore::update`] for a `change` that returns a value.
+pub fn update_state<T>(store: &dyn StateStore, change: impl FnOnce(&mut AppState) -> Result<T>) -> Result<T> {
+    let mut change = Some(change);
+    let mut output = None;
+    store.update(&mut |state| {
+        let change = change.take().ok_or_else(|| anyhow!("state update applied twice"))?;
+        output = Some(change(state)?);
+        Ok(())
+    })?;
+    output.ok_or_else(|| anyhow!("state update was not applied"))
+}
+
+/// The state as a pretty-printed JSON file, read under [`read_state_locked`] and
+/// replaced atomically by [`AppState::save`]. Both take a `flock` on
+/// `<state file>.lock`: shared to read, exclusive to write.
+pub struct JsonFileStore {
+    path: PathBuf,
+}
+
+impl JsonFileStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        JsonFileStore { path: path.as_ref().to_path_buf() }
+    }
+}
+
+impl StateStore for JsonFileStore {
+    fn load(&self) -> Result<AppState> {
+        read_state_locked(&self.path)
+    }
+
+    fn save(&self, state: &AppState) -> Result<()> {
+        state.save(&self.path)
+    }
+
+    fn update(&self, change: &mut dyn FnMut(&mut AppState) -> Result<()>) -> Result<AppState> {
+        let _lock = lock_state(&self.path, FlockArg::LockExclusive)?;
+        let mut state = AppState::load(&self.path)?;
+        change(&mut state)?;
+        state.write(&self.path)?;
+        Ok(state)
+    }
+}
+
+/// Opens the store selected by `files.state_backend` at `path`.
+pub fn open_state_store<P: AsRef<Path>>(backend: StateBackend, path: P) -> Result<Box<dyn StateStore>> {
+    match backend {
+        StateBackend::Json => Ok(Box::new(JsonFileStore::new(path))),
+        #[cfg(feature = "sqlite-state")]
+        StateBackend::Sqlite => Ok(Box::new(crate::sqlite_store::SqliteStore::open(path)?)),
+        #[cfg(not(feature = "sqlite-state"))]
+        StateBackend::Sqlite => Err(anyhow!("files.state_backend: sqlite requires a build with the 'sqlite-state' feature")),
+    }
+}
+
+/// Clamps a block duration to `max_block_minutes` when a cap is configured.
+pub fn clamp_block_minutes(minutes: u64, max_block_minutes: Option<u64>) -> u64 {
+    match max_block_minutes {
+        Some(max) => minutes.min(max),
+        None => minutes,
+    }
+}
+
+/// `minutes` as `2h 15m`, or just `15m` under an hour.
+pub fn format_minutes(minutes: u64) -> String {
+    match (minutes / 60, minutes % 60) {
+        (0, m) => format!("{}m", m),
+        (h, m) => format!("{}h {}m", h, m),
+    }
+}
+
+/// Longest block, break or session the state can record, about a century. Longer
+/// requests are clamped to it rather than overflowing the date arithmetic.
+pub const MAX_DURATION_MINUTES: u64 = 100 * 365 * 24 * 60;
+
+/// `at` plus `minutes`, clamped to [`MAX_DURATION_MINUTES`].
+pub fn add_minutes(at: DateTime<Utc>, minutes: u64) -> DateTime<Utc> {
+    let delta = chrono::Duration::minutes(minutes.min(MAX_DURATION_MINUTES) as i64);
+    at.checked_add_signed(delta).unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+/// Minutes for the `strike`-th block in a violation window: `base * multiplier^(strike - 1)`,
+/// saturating rather than overflowing, then clamped to the cap.
+pub fn escalated_block_minutes(base: u64, multiplier: u64, strike: u32, max_block_minutes: Option<u64>) -> u64 {
+    let factor = multiplier.max(1).saturating_pow(strike.saturating_sub(1));
+    clamp_block_minutes(base.saturating_mul(factor), max_block_minutes)
+}
+
+/// Time until the next break: `interval_hours` shifted by a uniformly random
+/// amount in `-jitter_minutes..=jitter_minutes`, never less than one minute.
+pub fn jittered_break_interval<R: Rng + ?Sized>(interval_hours: u64, jitter_minutes: u64, rng: &mut R) -> chrono::Duration {
+    let base = interval_hours as i64 * 60;
+    let jitter = jitter_minutes as i64;
+    let offset = if jitter > 0 { rng.random_range(-jitter..=jitter) } else { 0 };
+    chrono::Duration::minutes((base + offset).max(1))
+}
+
+/// Whether `now` still falls inside the `delay_seconds` warmup that began at `started`.
+pub fn in_warmup(started: DateTime<Utc>, delay_seconds: u64, now: DateTime<Utc>) -> bool {
+    now < started + chrono::Duration::seconds(delay_seconds as i64)
+}
+
+/// How far the wall clock moved beyond the monotonic time that elapsed between two
+/// ticks, when that exceeds `threshold_seconds` either way. Monotonic time stops
+/// during suspend, so a resume shows up as a forward jump.
+pub fn clock_jump(
+    wall_elapsed: chrono::Duration,
+    monotonic_elapsed: Duration,
+    threshold_seconds: u64,
+) -> Option<chrono::Duration> {
+    if threshold_seconds == 0 {
+        return None;
+    }
+    let jump = wall_elapsed - chrono::Duration::from_std(monotonic_elapsed).ok()?;
+    (jump.num_seconds().unsigned_abs() > threshold_seconds).then_some(jump)
+}
+
+/// A detection waiting for the user to run `acknowledge` before the browser is killed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingAck {
+    pub title: String,
+    pub pattern: String,
+    /// Label of the pattern's line, reported instead of the pattern.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub deadline: DateTime<Utc>,
+    #[serde(default)]
+    pub acknowledged: bool,
+}
+
+/// How a pending acknowledgement was resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckOutcome {
+    /// The user acknowledged in time: kill gently and apply the normal block.
+    Acknowledged,
+    /// The deadline passed without acknowledgement: hard-kill and hard-lock.
+    TimedOut,
+}
+
+/// What the persisted state demands when the daemon starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartupEnforcement {
+    Normal,
+    Blocked,
+    BathroomBreak,
+    /// A detection was handled moments before the restart; the browser is left
+    /// alone and hits are not counted until the cooldown ends.
+    Cooldown,
+}
+
+impl StartupEnforcement {
+    /// A browser started while the daemon was down must go if the state forbids it.
+    pub fn should_kill(self, browser_running: bool) -> bool {
+        browser_running && matches!(self, StartupEnforcement::Blocked | StartupEnforcement::BathroomBreak)
+    }
+}
+
+/// A browsing allowance that has been used up, with its size in minutes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UsageLimit {
+    Daily(u64),
+    /// Lasts until the next ISO week starts on Monday.
+    Weekly(u64),
+}
+
+impl UsageLimit {
+    pub fn minutes(self) -> u64 {
+        match self {
+            UsageLimit::Daily(minutes) | UsageLimit::Weekly(minutes) => minutes,
+        }
+    }
+
+    /// The audit log action recorded when the limit kills the browser.
+    pub fn audit_event(self) -> &'static str {
+        match self {
+            UsageLimit::Daily(_) => "daily_limit",
+            UsageLimit::Weekly(_) => "weekly_limit",
+        }
+    }
+}
+
+impl std::fmt::Display for UsageLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UsageLimit::Daily(minutes) => write!(f, "Daily limit of {} minutes", minutes),
+            UsageLimit::Weekly(minutes) => write!(f, "Weekly limit of {} minutes", minutes),
+        }
+    }
+}
+
+/// `usage_week` as `2026-W42`.
+mod iso_week {
+    use chrono::{Datelike, IsoWeek, NaiveDate, Weekday};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(week: &Option<IsoWeek>, serializer: S) -> Result<S::Ok, S::Error> {
+        match week {
+            Some(week) => serializer.serialize_str(&format!("{}-W{:02}", week.year(), week.week())),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<IsoWeek>, D::Error> {
+        let Some(text) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+        text.split_once("-W")
+            .and_then(|(year, week)| NaiveDate::from_isoywd_opt(year.parse().ok()?, week.parse().ok()?, Weekday::Mon))
+            .map(|monday| Some(monday.iso_week()))
+            .ok_or_else(|| serde::de::Error::custom(format!("invalid ISO week '{}'", text)))
+    }
+}
+
+/// Why the browser may not run right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Restriction {
+    Blocked,
+    BathroomBreak,
+}
+
+impl std::fmt::Display for Restriction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Restriction::Blocked => "blocked",
+            Restriction::BathroomBreak => "on a bathroom break",
+        })
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct AppState {
@@ -14,6 +319,63 @@ pub struct AppState {
     pub violation_count: u32,
     #[serde(default)]
     pub violation_window_start: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub pending_ack: Option<PendingAck>,
+    /// Browser launch times kept for `browser.max_launches_per_minute`.
+    #[serde(default)]
+    pub recent_launches: Vec<DateTime<Utc>>,
+    /// Profiles created by `browser.ephemeral_profile` launches, deleted by the next kill.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ephemeral_profiles: Vec<PathBuf>,
+    /// Local day that `usage_seconds` counts browser use for.
+    #[serde(default)]
+    pub usage_day: Option<NaiveDate>,
+    #[serde(default)]
+    pub usage_seconds: u64,
+    /// ISO week that `usage_seconds_week` counts browser use for.
+    #[serde(default, with = "iso_week")]
+    pub usage_week: Option<IsoWeek>,
+    #[serde(default)]
+    pub usage_seconds_week: u64,
+    /// Local day that `snoozes_used` counts snoozes for.
+    #[serde(default)]
+    pub snooze_day: Option<NaiveDate>,
+    #[serde(default)]
+    pub snoozes_used: u32,
+    /// Minutes of monitoring in a row without a detection.
+    #[serde(default)]
+    pub clean_streak_minutes: u64,
+    /// When `clean_streak_minutes` was last brought up to date.
+    #[serde(default)]
+    pub clean_streak_at: Option<DateTime<Utc>>,
+    /// Set by `allow-once`: the next detection is let through instead of blocked.
+    #[serde(default)]
+    pub allow_next_detection: bool,
+    /// Title of the detection that used the `allow-once` token. It stays exempt
+    /// until it is no longer on screen.
+    #[serde(default)]
+    pub allowed_once_title: Option<String>,
+    /// Rolling average and maximum daemon tick duration, in milliseconds.
+    #[serde(default)]
+    pub scan_avg_ms: Option<u64>,
+    #[serde(default)]
+    pub scan_max_ms: Option<u64>,
+    /// When the last bathroom break ended, for `timeouts.min_break_gap_minutes`.
+    #[serde(default)]
+    pub last_break_end: Option<DateTime<Utc>>,
+    /// Wallpaper detected at daemon startup, restored instead of `backgrounds.normal`
+    /// when `backgrounds.restore_user_wallpaper` is on.
+    #[serde(default)]
+    pub user_wallpaper: Option<String>,
+    /// End of the `timeouts.detection_cooldown_seconds` window after the last
+    /// detection. Persisted so a restarted daemon does not count the same
+    /// on-screen title again.
+    #[serde(default)]
+    pub detection_cooldown_until: Option<DateTime<Utc>>,
+    /// End of the `study-mode` session: until then only `files.study_whitelist`
+    /// titles are allowed.
+    #[serde(default)]
+    pub study_until: Option<DateTime<Utc>>,
 }
 
 impl AppState {
@@ -27,12 +389,26 @@ impl AppState {
         Ok(state)
     }
 
+    /// Replaces the state file while holding the exclusive lock on `<path>.lock`.
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
-        if let Some(parent) = path.as_ref().parent() {
-            fs::create_dir_all(parent)?;
-        }
-        let content = serde_json::to_string_pretty(self)?;
-        fs::write(path, content)?;
+        let _lock = lock_state(path.as_ref(), FlockArg::LockExclusive)?;
+        self.write(path.as_ref())
+    }
+
+    /// Writes a uniquely named sibling file and renames it over `path`, so readers
+    /// never see a partial file and concurrent writers never share a temp file.
+    fn write(&self, path: &Path) -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::create_dir_all(dir)?;
+        let mut tmp = tempfile::NamedTempFile::new_in(dir)?;
+        serde_json::to_writer_pretty(&mut tmp, self)?;
+        tmp.as_file().set_permissions(fs::Permissions::from_mode(0o644))?;
+        tmp.persist(path).map_err(|e| e.error)?;
         Ok(())
     }
 
@@ -44,17 +420,100 @@ impl AppState {
         }
     }
 
-    pub fn is_bathroom_break_time(&self, _interval_hours: u64) -> bool {
+    /// Whether a break is running or due. A due break is held back until
+    /// `min_gap_minutes` have passed since the last one ended.
+    pub fn is_bathroom_break_time(&self, _interval_hours: u64, min_gap_minutes: u64) -> bool {
         if self.in_bathroom_break {
             if let Some(until) = self.bathroom_break_until {
                 return Utc::now() < until;
             }
         }
-        Utc::now() >= self.next_bathroom_break
+        let now = Utc::now();
+        now >= self.next_bathroom_break && self.break_gap_elapsed(min_gap_minutes, now)
+    }
+
+    /// Whether at least `min_gap_minutes` have passed since the last break ended.
+    pub fn break_gap_elapsed(&self, min_gap_minutes: u64, now: DateTime<Utc>) -> bool {
+        self.last_break_end
+            .is_none_or(|end| now - end >= chrono::Duration::minutes(min_gap_minutes as i64))
+    }
+
+    /// Repairs the break schedule after a clock jump so that at most one break is
+    /// due: a break pushed more than an interval into the future (clock moved back)
+    /// is pulled in to one interval from `now`, and an overdue one is taken once now
+    /// or, with [`ClockJumpAction::Reschedule`], skipped. Returns whether it changed.
+    pub fn settle_clock_jump(&mut self, now: DateTime<Utc>, interval_hours: u64, action: ClockJumpAction) -> bool {
+        let interval = chrono::Duration::hours(interval_hours as i64);
+        let next = if self.next_bathroom_break > now + interval {
+            now + interval
+        } else if self.next_bathroom_break < now && !self.in_bathroom_break {
+            match action {
+                ClockJumpAction::OneBreak => now,
+                ClockJumpAction::Reschedule => now + interval,
+            }
+        } else {
+            return false;
+        };
+        self.next_bathroom_break = next;
+        true
+    }
+
+    /// Starts the detection cooldown, or clears it when `seconds` is 0.
+    pub fn start_detection_cooldown(&mut self, seconds: u64, now: DateTime<Utc>) {
+        self.detection_cooldown_until =
+            (seconds > 0).then(|| now + chrono::Duration::seconds(seconds as i64));
+    }
+
+    pub fn in_detection_cooldown(&self, now: DateTime<Utc>) -> bool {
+        self.detection_cooldown_until.is_some_and(|until| now < until)
+    }
+
+    /// Starts a study session of `minutes` from `now`, replacing any running one.
+    pub fn start_study_mode(&mut self, minutes: u64, now: DateTime<Utc>) -> DateTime<Utc> {
+        let until = add_minutes(now, minutes);
+        self.study_until = Some(until);
+        until
+    }
+
+    pub fn in_study_mode(&self, now: DateTime<Utc>) -> bool {
+        self.study_until.is_some_and(|until| now < until)
+    }
+
+    /// The block or running break in force at `now`. A break that is due but has not
+    /// started yet is not a restriction until it is started.
+    pub fn restriction(&self, now: DateTime<Utc>) -> Option<Restriction> {
+        if self.blocked_until.is_some_and(|until| now < until) {
+            Some(Restriction::Blocked)
+        } else if self.in_bathroom_break && self.bathroom_break_until.is_some_and(|until| now < until) {
+            Some(Restriction::BathroomBreak)
+        } else {
+            None
+        }
     }
 
+    pub fn startup_enforcement(&self, interval_hours: u64, min_gap_minutes: u64) -> StartupEnforcement {
+        if self.is_blocked() {
+            StartupEnforcement::Blocked
+        } else if self.is_bathroom_break_time(interval_hours, min_gap_minutes) {
+            StartupEnforcement::BathroomBreak
+        } else if self.in_detection_cooldown(Utc::now()) {
+            StartupEnforcement::Cooldown
+        } else {
+            StartupEnforcement::Normal
+        }
+    }
+
+    /// Blocks for `timeout_minutes` from now, unless an existing block already runs longer.
     pub fn block_browser(&mut self, timeout_minutes: u64) {
-        self.blocked_until = Some(Utc::now() + chrono::Duration::minutes(timeout_minutes as i64));
+        let until = add_minutes(Utc::now(), timeout_minutes);
+        self.blocked_until = Some(self.blocked_until.map_or(until, |current| current.max(until)));
+    }
+
+    /// Blocks for the escalated duration of the current violation count and returns it.
+    pub fn block_browser_escalating(&mut self, base_minutes: u64, multiplier: u64, max_block_minutes: Option<u64>) -> u64 {
+        let minutes = escalated_block_minutes(base_minutes, multiplier, self.violation_count.max(1), max_block_minutes);
+        self.block_browser(minutes);
+        minutes
     }
 
     pub fn start_bathroom_break(&mut self, duration_minutes: u64, interval_hours: u64) {
@@ -64,12 +523,179 @@ impl AppState {
         self.next_bathroom_break = Utc::now() + chrono::Duration::hours(interval_hours as i64);
     }
 
+    /// Like [`start_bathroom_break`](Self::start_bathroom_break), but the next break is
+    /// moved by up to `jitter_minutes` either way so breaks are harder to plan around.
+    pub fn start_bathroom_break_jittered<R: Rng + ?Sized>(
+        &mut self,
+        duration_minutes: u64,
+        interval_hours: u64,
+        jitter_minutes: u64,
+        rng: &mut R,
+    ) {
+        self.start_bathroom_break(duration_minutes, interval_hours);
+        self.next_bathroom_break = Utc::now() + jittered_break_interval(interval_hours, jitter_minutes, rng);
+    }
+
+    /// Starts the acknowledgement window for a detection, unless one is already pending.
+    pub fn start_pending_ack(&mut self, title: &str, pattern: &str, label: Option<&str>, ack_seconds: u64) {
+        if self.pending_ack.is_none() {
+            self.pending_ack = Some(PendingAck {
+                title: title.to_string(),
+                pattern: pattern.to_string(),
+                label: label.map(str::to_string),
+                deadline: Utc::now() + chrono::Duration::seconds(ack_seconds as i64),
+                acknowledged: false,
+            });
+        }
+    }
+
+    /// Adds browser use on local day `today`, to both the day's and the ISO week's
+    /// counter. Each starts from zero when the day or week changed.
+    pub fn add_usage(&mut self, today: NaiveDate, seconds: u64) {
+        if self.usage_day != Some(today) {
+            self.usage_day = Some(today);
+            self.usage_seconds = 0;
+        }
+        self.usage_seconds += seconds;
+
+        let week = today.iso_week();
+        if self.usage_week != Some(week) {
+            self.usage_week = Some(week);
+            self.usage_seconds_week = 0;
+        }
+        self.usage_seconds_week += seconds;
+    }
+
+    /// Whole minutes of browser use recorded for `today`.
+    pub fn usage_minutes(&self, today: NaiveDate) -> u64 {
+        if self.usage_day == Some(today) {
+            self.usage_seconds / 60
+        } else {
+            0
+        }
+    }
+
+    /// Whole minutes of browser use recorded for the ISO week containing `today`.
+    pub fn usage_week_minutes(&self, today: NaiveDate) -> u64 {
+        if self.usage_week == Some(today.iso_week()) {
+            self.usage_seconds_week / 60
+        } else {
+            0
+        }
+    }
+
+    /// The first usage limit used up on `today`, daily before weekly.
+    pub fn usage_limit_reached(&self, today: NaiveDate, daily_max: Option<u64>, weekly_max: Option<u64>) -> Option<UsageLimit> {
+        if let Some(max) = daily_max.filter(|&max| self.usage_minutes(today) >= max) {
+            Some(UsageLimit::Daily(max))
+        } else {
+            weekly_max
+                .filter(|&max| self.usage_week_minutes(today) >= max)
+                .map(UsageLimit::Weekly)
+        }
+    }
+
+    /// Uses up the `allow-once` token, if one was granted. Returns whether the current
+    /// detection should be let through.
+    pub fn take_allow_once(&mut self) -> bool {
+        std::mem::take(&mut self.allow_next_detection)
+    }
+
+    /// Marks the pending detection as acknowledged; `false` if nothing was pending.
+    pub fn acknowledge(&mut self) -> bool {
+        match self.pending_ack.as_mut() {
+            Some(pending) => {
+                pending.acknowledged = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Decides a pending detection at `now`: acknowledged wins, otherwise it times out
+    /// at the deadline. `None` while still waiting or if nothing is pending.
+    pub fn resolve_pending_ack(&self, now: DateTime<Utc>) -> Option<AckOutcome> {
+        let pending = self.pending_ack.as_ref()?;
+        if pending.acknowledged {
+            Some(AckOutcome::Acknowledged)
+        } else if now >= pending.deadline {
+            Some(AckOutcome::TimedOut)
+        } else {
+            None
+        }
+    }
+
+    /// Pushes the end of the current break out by `minutes`, returning the new end.
+    /// Fails if no break is in progress at `now`.
+    pub fn extend_bathroom_break(&mut self, minutes: u64, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        let until = self.bathroom_break_until
+            .filter(|&until| self.in_bathroom_break && until > now)
+            .ok_or_else(|| anyhow!("not on a bathroom break"))?;
+        let extended = add_minutes(until, minutes);
+        self.bathroom_break_until = Some(extended);
+        Ok(extended)
+    }
+
+    /// Postpones the next break by `minutes`, allowed `max_per_day` times per local
+    /// day (`today`). Fails during a break or once the day's snoozes are used up.
+    pub fn snooze_bathroom_break(&mut self, minutes: u64, max_per_day: u32, today: NaiveDate) -> Result<DateTime<Utc>> {
+        if self.in_bathroom_break {
+            return Err(anyhow!("a bathroom break is already in progress"));
+        }
+        if self.snooze_day != Some(today) {
+            self.snooze_day = Some(today);
+            self.snoozes_used = 0;
+        }
+        if self.snoozes_used >= max_per_day {
+            return Err(anyhow!("no snoozes left today (limit {})", max_per_day));
+        }
+        self.snoozes_used += 1;
+        self.next_bathroom_break = add_minutes(self.next_bathroom_break, minutes);
+        Ok(self.next_bathroom_break)
+    }
+
+    /// Adds the whole minutes since the last clean tick to the streak, carrying any
+    /// remainder over. Returns whether the state changed and needs saving.
+    pub fn record_clean_tick(&mut self, now: DateTime<Utc>) -> bool {
+        let Some(at) = self.clean_streak_at else {
+            self.clean_streak_at = Some(now);
+            return true;
+        };
+        let minutes = (now - at).num_minutes();
+        if minutes <= 0 {
+            return false;
+        }
+        self.clean_streak_minutes += minutes as u64;
+        self.clean_streak_at = Some(at + chrono::Duration::minutes(minutes));
+        true
+    }
+
+    /// Starts counting from `now` without touching the streak, so time the daemon was
+    /// not running does not count as clean.
+    pub fn resume_clean_streak(&mut self, now: DateTime<Utc>) {
+        self.clean_streak_at = Some(now);
+    }
+
+    pub fn reset_clean_streak(&mut self, now: DateTime<Utc>) {
+        self.clean_streak_minutes = 0;
+        self.clean_streak_at = Some(now);
+    }
+
+    /// The wallpaper to show when neither blocked nor on a break.
+    pub fn normal_background<'a>(&'a self, backgrounds: &'a BackgroundConfig) -> &'a str {
+        match &self.user_wallpaper {
+            Some(wallpaper) if backgrounds.restore_user_wallpaper => wallpaper,
+            _ => &backgrounds.normal,
+        }
+    }
+
     pub fn end_bathroom_break(&mut self) {
         self.in_bathroom_break = false;
         self.bathroom_break_until = None;
+        self.last_break_end = Some(Utc::now());
     }
 
-    fn default_with_next_break() -> Self {
+    pub(crate) fn default_with_next_break() -> Self {
         AppState {
             blocked_until: None,
             next_bathroom_break: Utc::now() + chrono::Duration::hours(2),
@@ -77,11 +703,31 @@ impl AppState {
             bathroom_break_until: None,
             violation_count: 0,
             violation_window_start: None,
+            pending_ack: None,
+            recent_launches: Vec::new(),
+            ephemeral_profiles: Vec::new(),
+            usage_day: None,
+            usage_seconds: 0,
+            snooze_day: None,
+            snoozes_used: 0,
+            clean_streak_minutes: 0,
+            clean_streak_at: None,
+            allow_next_detection: false,
+            allowed_once_title: None,
+            scan_avg_ms: None,
+            scan_max_ms: None,
+            last_break_end: None,
+            user_wallpaper: None,
+            detection_cooldown_until: None,
+            study_until: None,
+            usage_week: None,
+            usage_seconds_week: 0,
         }
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::field_reassign_with_default)]
 mod tests {
     use super::*;
     use std::io::Write;
@@ -106,6 +752,19 @@ mod tests {
         assert!(state.next_bathroom_break > Utc::now());
     }
 
+    #[test]
+    fn test_save_creates_missing_parent_directories() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let parent = dir.path().join("missing").join("subdir");
+        let path = parent.join("state.json");
+
+        AppState::default().save(&path).unwrap();
+
+        assert!(parent.is_dir());
+        assert!(path.is_file());
+        assert!(AppState::load(&path).is_ok());
+    }
+
     #[test]
     fn test_app_state_save_and_load() {
         let temp_file = NamedTempFile::new().unwrap();
@@ -181,13 +840,85 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_shorter_block_does_not_reduce_longer_block() {
+        let mut state = AppState::default();
+
+        state.block_browser(60);
+        let lockdown = state.blocked_until.unwrap();
+
+        state.block_browser(5);
+        assert_eq!(state.blocked_until.unwrap(), lockdown);
+    }
+
+    #[test]
+    fn test_block_after_expired_block_starts_fresh() {
+        let mut state = AppState::default();
+        state.blocked_until = Some(Utc::now() - chrono::Duration::minutes(30));
+
+        state.block_browser(5);
+
+        let expected_time = Utc::now() + chrono::Duration::minutes(5);
+        let time_diff = (state.blocked_until.unwrap() - expected_time).num_seconds().abs();
+        assert!(time_diff < 2);
+    }
+
+    #[test]
+    fn test_escalated_block_minutes() {
+        assert_eq!(escalated_block_minutes(10, 1, 5, None), 10);
+        assert_eq!(escalated_block_minutes(10, 2, 1, None), 10);
+        assert_eq!(escalated_block_minutes(10, 2, 3, None), 40);
+        assert_eq!(escalated_block_minutes(10, 0, 3, None), 10);
+    }
+
+    #[test]
+    fn test_high_escalation_multiplier_clamps_to_max() {
+        assert_eq!(escalated_block_minutes(10, 100, 3, Some(240)), 240);
+        assert_eq!(escalated_block_minutes(10, u64::MAX, 10, Some(1440)), 1440);
+        assert_eq!(escalated_block_minutes(10, 100, 1, Some(240)), 10);
+    }
+
+    #[test]
+    fn test_huge_block_is_clamped_instead_of_overflowing() {
+        let mut state = AppState::default();
+        state.block_browser(999_999_999_999);
+        let until = state.blocked_until.unwrap();
+        assert!(until > Utc::now() + chrono::Duration::days(99 * 365));
+        assert!(until <= add_minutes(Utc::now(), MAX_DURATION_MINUTES));
+
+        let mut state = AppState { violation_count: 64, ..AppState::default() };
+        let minutes = state.block_browser_escalating(10, u64::MAX, None);
+        assert_eq!(minutes, u64::MAX);
+        assert!(state.is_blocked());
+    }
+
+    #[test]
+    fn test_block_browser_escalating_uses_violation_count() {
+        let mut state = AppState::default();
+        state.violation_count = 3;
+
+        let minutes = state.block_browser_escalating(10, 10, Some(120));
+        assert_eq!(minutes, 120);
+
+        let expected_time = Utc::now() + chrono::Duration::minutes(120);
+        let time_diff = (state.blocked_until.unwrap() - expected_time).num_seconds().abs();
+        assert!(time_diff < 2);
+    }
+
+    #[test]
+    fn test_clamp_block_minutes() {
+        assert_eq!(clamp_block_minutes(500, None), 500);
+        assert_eq!(clamp_block_minutes(500, Some(60)), 60);
+        assert_eq!(clamp_block_minutes(30, Some(60)), 30);
+    }
+
     #[test]
     fn test_is_bathroom_break_time_not_in_break() {
         let mut state = AppState::default();
         state.next_bathroom_break = Utc::now() - chrono::Duration::minutes(1); // Past time
         state.in_bathroom_break = false;
 
-        assert!(state.is_bathroom_break_time(3));
+        assert!(state.is_bathroom_break_time(3, 0));
     }
 
     #[test]
@@ -196,7 +927,7 @@ mod tests {
         state.next_bathroom_break = Utc::now() + chrono::Duration::minutes(10); // Future time
         state.in_bathroom_break = false;
 
-        assert!(!state.is_bathroom_break_time(3));
+        assert!(!state.is_bathroom_break_time(3, 0));
     }
 
     #[test]
@@ -205,7 +936,7 @@ mod tests {
         state.in_bathroom_break = true;
         state.bathroom_break_until = Some(Utc::now() + chrono::Duration::minutes(5));
 
-        assert!(state.is_bathroom_break_time(3));
+        assert!(state.is_bathroom_break_time(3, 0));
     }
 
     #[test]
@@ -214,7 +945,7 @@ mod tests {
         state.in_bathroom_break = true;
         state.bathroom_break_until = Some(Utc::now() - chrono::Duration::minutes(5));
 
-        assert!(!state.is_bathroom_break_time(3));
+        assert!(!state.is_bathroom_break_time(3, 0));
     }
 
     #[test]
@@ -251,6 +982,150 @@ mod tests {
 
         assert!(!state.in_bathroom_break);
         assert!(state.bathroom_break_until.is_none());
+        assert!(state.last_break_end.is_some());
+    }
+
+    #[test]
+    fn test_normal_background_prefers_captured_wallpaper() {
+        let mut backgrounds = crate::config::Config::default().backgrounds;
+        backgrounds.normal = "/bg/normal.jpg".to_string();
+        let mut state = AppState::default();
+        assert_eq!(state.normal_background(&backgrounds), "/bg/normal.jpg");
+
+        state.user_wallpaper = Some("/home/kid/beach.jpg".to_string());
+        assert_eq!(state.normal_background(&backgrounds), "/bg/normal.jpg");
+        backgrounds.restore_user_wallpaper = true;
+        assert_eq!(state.normal_background(&backgrounds), "/home/kid/beach.jpg");
+    }
+
+    #[test]
+    fn test_min_break_gap_suppresses_back_to_back_break() {
+        let now = Utc::now();
+        let mut state = AppState::default();
+        state.last_break_end = Some(now - chrono::Duration::minutes(2));
+        state.next_bathroom_break = now - chrono::Duration::seconds(1);
+
+        assert!(!state.is_bathroom_break_time(3, 30));
+        assert!(state.is_bathroom_break_time(3, 0));
+
+        state.last_break_end = Some(now - chrono::Duration::minutes(31));
+        assert!(state.is_bathroom_break_time(3, 30));
+        assert!(AppState::default().break_gap_elapsed(30, now));
+    }
+
+    #[test]
+    fn test_extend_bathroom_break() {
+        let now = Utc::now();
+        let mut state = AppState::default();
+        state.start_bathroom_break(5, 2);
+        let until = state.bathroom_break_until.unwrap();
+
+        let extended = state.extend_bathroom_break(15, now).unwrap();
+        assert_eq!(extended, until + chrono::Duration::minutes(15));
+        assert_eq!(state.bathroom_break_until, Some(extended));
+        assert!(state.in_bathroom_break);
+
+        let extended = state.extend_bathroom_break(u64::MAX, now).unwrap();
+        assert_eq!(extended, add_minutes(until + chrono::Duration::minutes(15), MAX_DURATION_MINUTES));
+    }
+
+    #[test]
+    fn test_extend_bathroom_break_when_not_on_break() {
+        let now = Utc::now();
+        let mut state = AppState::default();
+        let err = state.extend_bathroom_break(15, now).unwrap_err();
+        assert_eq!(err.to_string(), "not on a bathroom break");
+        assert!(state.bathroom_break_until.is_none());
+
+        // A break whose end has passed but which the daemon has not closed yet.
+        state.in_bathroom_break = true;
+        state.bathroom_break_until = Some(now - chrono::Duration::minutes(1));
+        assert!(state.extend_bathroom_break(15, now).is_err());
+    }
+
+    #[test]
+    fn test_snooze_bathroom_break_moves_next_break() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let mut state = AppState::default_with_next_break();
+        let before = state.next_bathroom_break;
+
+        let next = state.snooze_bathroom_break(15, 2, today).unwrap();
+        assert_eq!(next, before + chrono::Duration::minutes(15));
+        assert_eq!(state.next_bathroom_break, next);
+        assert_eq!(state.snoozes_used, 1);
+
+        let next = state.snooze_bathroom_break(u64::MAX, 2, today).unwrap();
+        assert_eq!(next, add_minutes(before + chrono::Duration::minutes(15), MAX_DURATION_MINUTES));
+    }
+
+    #[test]
+    fn test_snooze_bathroom_break_refused_after_daily_limit() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let mut state = AppState::default_with_next_break();
+        state.snooze_bathroom_break(10, 2, today).unwrap();
+        state.snooze_bathroom_break(10, 2, today).unwrap();
+        let before = state.next_bathroom_break;
+
+        let err = state.snooze_bathroom_break(10, 2, today).unwrap_err();
+        assert_eq!(err.to_string(), "no snoozes left today (limit 2)");
+        assert_eq!(state.next_bathroom_break, before);
+
+        // The count starts over the next day.
+        let tomorrow = today.succ_opt().unwrap();
+        assert!(state.snooze_bathroom_break(10, 2, tomorrow).is_ok());
+        assert_eq!(state.snoozes_used, 1);
+
+        // Disabled entirely, and not allowed during a break.
+        assert!(AppState::default_with_next_break().snooze_bathroom_break(10, 0, today).is_err());
+        state.start_bathroom_break(5, 2);
+        assert!(state.snooze_bathroom_break(10, 5, tomorrow).is_err());
+    }
+
+    #[test]
+    fn test_allow_once_suppresses_exactly_one_detection() {
+        let mut state = AppState::default_with_next_break();
+        assert!(!state.take_allow_once());
+
+        state.allow_next_detection = true;
+        let blocked: Vec<bool> = (0..3).map(|_| !state.take_allow_once()).collect();
+        assert_eq!(blocked, vec![false, true, true]);
+        assert!(!state.allow_next_detection);
+    }
+
+    #[test]
+    fn test_clean_streak_grows_on_clean_ticks_and_resets_on_block() {
+        let start = Utc::now();
+        let at = |seconds: i64| start + chrono::Duration::seconds(seconds);
+        let mut state = AppState::default_with_next_break();
+
+        assert!(state.record_clean_tick(at(0)));
+        assert!(!state.record_clean_tick(at(30)));
+        assert!(state.record_clean_tick(at(90)));
+        assert_eq!(state.clean_streak_minutes, 1);
+        // The 30 seconds left over from the last tick still count.
+        assert!(state.record_clean_tick(at(150)));
+        assert_eq!(state.clean_streak_minutes, 2);
+        assert!(state.record_clean_tick(at(150 + 133 * 60)));
+        assert_eq!(state.clean_streak_minutes, 135);
+        assert_eq!(format_minutes(state.clean_streak_minutes), "2h 15m");
+
+        state.reset_clean_streak(at(9000));
+        assert_eq!(state.clean_streak_minutes, 0);
+        assert!(state.record_clean_tick(at(9060)));
+        assert_eq!(state.clean_streak_minutes, 1);
+
+        // Downtime between daemon runs is not counted.
+        state.resume_clean_streak(at(90_000));
+        assert!(!state.record_clean_tick(at(90_030)));
+        assert_eq!(state.clean_streak_minutes, 1);
+    }
+
+    #[test]
+    fn test_format_minutes() {
+        assert_eq!(format_minutes(0), "0m");
+        assert_eq!(format_minutes(59), "59m");
+        assert_eq!(format_minutes(60), "1h 0m");
+        assert_eq!(format_minutes(135), "2h 15m");
     }
 
     #[test]
@@ -302,4 +1177,365 @@ mod tests {
         assert!(state.in_bathroom_break);
         assert!(state.bathroom_break_until.is_some());
     }
+
+    #[test]
+    fn test_jittered_break_interval_with_fixed_seed() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut seen = std::collections::BTreeSet::new();
+        for _ in 0..200 {
+            let minutes = jittered_break_interval(2, 15, &mut rng).num_minutes();
+            assert!((105..=135).contains(&minutes), "{} outside 2h ± 15m", minutes);
+            seen.insert(minutes);
+        }
+        assert!(seen.len() > 1);
+
+        let first = jittered_break_interval(2, 15, &mut StdRng::seed_from_u64(7));
+        let again = jittered_break_interval(2, 15, &mut StdRng::seed_from_u64(7));
+        assert_eq!(first, again);
+    }
+
+    #[test]
+    fn test_jittered_break_interval_edge_cases() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+
+        assert_eq!(jittered_break_interval(2, 0, &mut rng), chrono::Duration::hours(2));
+        for _ in 0..50 {
+            assert!(jittered_break_interval(0, 30, &mut rng) >= chrono::Duration::minutes(1));
+        }
+    }
+
+    #[test]
+    fn test_start_bathroom_break_jittered() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let mut state = AppState::default();
+
+        let before = Utc::now();
+        state.start_bathroom_break_jittered(10, 1, 20, &mut rng);
+
+        assert!(state.in_bathroom_break);
+        assert!(state.next_bathroom_break >= before + chrono::Duration::minutes(40));
+        assert!(state.next_bathroom_break <= Utc::now() + chrono::Duration::minutes(80));
+    }
+
+    #[test]
+    fn test_pending_ack_acknowledged() {
+        let mut state = AppState::default();
+        assert!(!state.acknowledge());
+        assert_eq!(state.resolve_pending_ack(Utc::now()), None);
+
+        state.start_pending_ack("Bad Page", ".*bad.*", None, 30);
+        assert_eq!(state.resolve_pending_ack(Utc::now()), None);
+
+        assert!(state.acknowledge());
+        assert_eq!(state.resolve_pending_ack(Utc::now()), Some(AckOutcome::Acknowledged));
+        // Acknowledging wins even if the deadline has also passed.
+        let later = Utc::now() + chrono::Duration::seconds(60);
+        assert_eq!(state.resolve_pending_ack(later), Some(AckOutcome::Acknowledged));
+    }
+
+    #[test]
+    fn test_pending_ack_timed_out() {
+        let mut state = AppState::default();
+        state.start_pending_ack("Bad Page", ".*bad.*", None, 30);
+        let deadline = state.pending_ack.as_ref().unwrap().deadline;
+
+        assert_eq!(state.resolve_pending_ack(deadline - chrono::Duration::seconds(1)), None);
+        assert_eq!(state.resolve_pending_ack(deadline), Some(AckOutcome::TimedOut));
+
+        // A second detection does not extend the window.
+        state.start_pending_ack("Other", ".*other.*", None, 300);
+        assert_eq!(state.pending_ack.as_ref().unwrap().deadline, deadline);
+        assert_eq!(state.pending_ack.as_ref().unwrap().title, "Bad Page");
+    }
+
+    #[test]
+    fn test_pending_ack_persists() {
+        let temp_file = NamedTempFile::new().unwrap();
+        let mut state = AppState::default();
+        state.start_pending_ack("Bad Page", ".*bad.*", None, 30);
+        state.acknowledge();
+        state.save(temp_file.path()).unwrap();
+
+        let loaded = AppState::load(temp_file.path()).unwrap();
+        assert_eq!(loaded.pending_ack, state.pending_ack);
+    }
+
+    #[test]
+    fn test_in_warmup() {
+        let started = Utc::now();
+        let at = |secs: i64| started + chrono::Duration::seconds(secs);
+
+        assert!(!in_warmup(started, 0, at(0)));
+        assert!(in_warmup(started, 30, at(0)));
+        assert!(in_warmup(started, 30, at(29)));
+        assert!(!in_warmup(started, 30, at(30)));
+        assert!(!in_warmup(started, 30, at(120)));
+    }
+
+    #[test]
+    fn test_startup_enforcement_blocked_with_browser_running() {
+        let mut state = AppState::default();
+        state.next_bathroom_break = Utc::now() + chrono::Duration::hours(2);
+        state.block_browser(30);
+
+        let enforcement = state.startup_enforcement(2, 0);
+        assert_eq!(enforcement, StartupEnforcement::Blocked);
+        assert!(enforcement.should_kill(true));
+        assert!(!enforcement.should_kill(false));
+    }
+
+    #[test]
+    fn test_startup_enforcement_break_and_normal() {
+        let mut state = AppState::default();
+        state.next_bathroom_break = Utc::now() + chrono::Duration::hours(2);
+        assert_eq!(state.startup_enforcement(2, 0), StartupEnforcement::Normal);
+        assert!(!StartupEnforcement::Normal.should_kill(true));
+
+        state.start_bathroom_break(10, 2);
+        assert_eq!(state.startup_enforcement(2, 0), StartupEnforcement::BathroomBreak);
+        assert!(state.startup_enforcement(2, 0).should_kill(true));
+
+        state.end_bathroom_break();
+        state.blocked_until = Some(Utc::now() - chrono::Duration::minutes(1));
+        assert_eq!(state.startup_enforcement(2, 0), StartupEnforcement::Normal);
+    }
+
+    #[test]
+    fn test_clock_jump_detection() {
+        let minutes = |m| chrono::Duration::minutes(m);
+        let tick = Duration::from_secs(60);
+        assert_eq!(clock_jump(minutes(1), tick, 120), None);
+        assert_eq!(clock_jump(minutes(3), tick, 120), None);
+        // Suspended for an hour: monotonic time stood still.
+        assert_eq!(clock_jump(minutes(61), tick, 120), Some(minutes(60)));
+        assert_eq!(clock_jump(minutes(-9), tick, 120), Some(minutes(-10)));
+        assert_eq!(clock_jump(minutes(61), tick, 0), None);
+    }
+
+    #[test]
+    fn test_settle_clock_jump_caps_overdue_breaks_to_one() {
+        let now = Utc::now();
+        let mut state = AppState::default();
+        state.next_bathroom_break = now - chrono::Duration::hours(7);
+        assert!(state.settle_clock_jump(now, 2, ClockJumpAction::OneBreak));
+        assert_eq!(state.next_bathroom_break, now);
+
+        state.start_bathroom_break(4, 2);
+        assert!(state.next_bathroom_break > now + chrono::Duration::minutes(119));
+        assert!(!state.settle_clock_jump(Utc::now(), 2, ClockJumpAction::OneBreak));
+
+        let mut skipped = AppState::default();
+        skipped.next_bathroom_break = now - chrono::Duration::hours(7);
+        assert!(skipped.settle_clock_jump(now, 2, ClockJumpAction::Reschedule));
+        assert_eq!(skipped.next_bathroom_break, now + chrono::Duration::hours(2));
+
+        let mut backwards = AppState::default();
+        backwards.next_bathroom_break = now + chrono::Duration::hours(30);
+        assert!(backwards.settle_clock_jump(now, 2, ClockJumpAction::OneBreak));
+        assert_eq!(backwards.next_bathroom_break, now + chrono::Duration::hours(2));
+    }
+
+    #[test]
+    fn test_startup_enforcement_skips_blocking_during_detection_cooldown() {
+        let mut state = AppState::default();
+        state.next_bathroom_break = Utc::now() + chrono::Duration::hours(2);
+        state.start_detection_cooldown(60, Utc::now());
+        assert!(state.in_detection_cooldown(Utc::now()));
+
+        let enforcement = state.startup_enforcement(2, 0);
+        assert_eq!(enforcement, StartupEnforcement::Cooldown);
+        assert!(!enforcement.should_kill(true));
+
+        state.detection_cooldown_until = Some(Utc::now() - chrono::Duration::seconds(1));
+        assert_eq!(state.startup_enforcement(2, 0), StartupEnforcement::Normal);
+
+        state.start_detection_cooldown(0, Utc::now());
+        assert_eq!(state.detection_cooldown_until, None);
+    }
+
+    #[test]
+    fn test_restriction_covers_blocks_and_running_breaks() {
+        let now = Utc::now();
+        let mut state = AppState::default_with_next_break();
+        assert_eq!(state.restriction(now), None);
+
+        state.blocked_until = Some(now + chrono::Duration::minutes(5));
+        state.in_bathroom_break = true;
+        state.bathroom_break_until = Some(now + chrono::Duration::minutes(10));
+        assert_eq!(state.restriction(now), Some(Restriction::Blocked));
+        assert_eq!(state.restriction(now + chrono::Duration::minutes(6)), Some(Restriction::BathroomBreak));
+        assert_eq!(state.restriction(now + chrono::Duration::minutes(10)), None);
+    }
+
+    #[test]
+    fn test_study_mode_ends_after_its_duration() {
+        let start = Utc::now();
+        let mut state = AppState::default_with_next_break();
+        assert!(!state.in_study_mode(start));
+
+        let until = state.start_study_mode(30, start);
+        assert_eq!(until, start + chrono::Duration::minutes(30));
+        assert!(state.in_study_mode(start + chrono::Duration::minutes(29)));
+        assert!(!state.in_study_mode(until));
+
+        assert_eq!(state.start_study_mode(u64::MAX, start), add_minutes(start, MAX_DURATION_MINUTES));
+    }
+
+    #[test]
+    fn test_usage_resets_on_new_day() {
+        let monday = NaiveDate::from_ymd_opt(2026, 10, 12).unwrap();
+        let tuesday = monday.succ_opt().unwrap();
+        let mut state = AppState::default();
+
+        state.add_usage(monday, 90);
+        state.add_usage(monday, 60);
+        assert_eq!(state.usage_minutes(monday), 2);
+        assert_eq!(state.usage_minutes(tuesday), 0);
+
+        state.add_usage(tuesday, 30);
+        assert_eq!(state.usage_seconds, 30);
+        assert_eq!(state.usage_minutes(monday), 0);
+    }
+
+    #[test]
+    fn test_weekly_usage_resets_on_new_iso_week() {
+        let sunday = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        let monday = sunday.succ_opt().unwrap();
+        let mut state = AppState::default();
+
+        state.add_usage(sunday - chrono::Duration::days(6), 600);
+        state.add_usage(sunday, 300);
+        assert_eq!(state.usage_week_minutes(sunday), 15);
+        assert_eq!(state.usage_week_minutes(monday), 0);
+
+        state.add_usage(monday, 120);
+        assert_eq!(state.usage_seconds_week, 120);
+        assert_eq!(state.usage_week_minutes(sunday), 0);
+
+        let saved = serde_json::to_string(&state).unwrap();
+        assert!(saved.contains("\"usage_week\":\"2026-W43\""), "{}", saved);
+        let loaded: AppState = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded.usage_week, Some(monday.iso_week()));
+    }
+
+    #[test]
+    fn test_weekly_limit_blocks_once_used_up() {
+        let monday = NaiveDate::from_ymd_opt(2026, 10, 12).unwrap();
+        let mut state = AppState::default();
+        for day in 0..5 {
+            state.add_usage(monday + chrono::Duration::days(day), 60 * 60);
+        }
+        let friday = monday + chrono::Duration::days(4);
+
+        assert_eq!(state.usage_limit_reached(friday, Some(120), Some(300)), Some(UsageLimit::Weekly(300)));
+        assert_eq!(state.usage_limit_reached(friday, Some(60), Some(300)), Some(UsageLimit::Daily(60)));
+        assert_eq!(state.usage_limit_reached(friday, None, Some(301)), None);
+        assert_eq!(state.usage_limit_reached(friday + chrono::Duration::days(3), None, Some(300)), None);
+    }
+
+    #[test]
+    fn test_read_state_locked_missing_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let state = read_state_locked(dir.path().join("state.json")).unwrap();
+        assert!(!state.is_blocked());
+    }
+
+    #[test]
+    fn test_read_state_locked_during_concurrent_writes() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        AppState::default().save(&path).unwrap();
+
+        let writer_path = path.clone();
+        let writer = std::thread::spawn(move || {
+            for n in 0..300 {
+                let state = AppState {
+                    violation_count: n,
+                    recent_launches: vec![Utc::now(); (n % 20) as usize],
+                    ..AppState::default()
+                };
+                state.save(&writer_path).unwrap();
+            }
+        });
+
+        for _ in 0..300 {
+            read_state_locked(&path).unwrap();
+        }
+        writer.join().unwrap();
+        assert_eq!(read_state_locked(&path).unwrap().violation_count, 299);
+    }
+
+    #[test]
+    fn test_concurrent_writers_do_not_collide() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        AppState::default().save(&path).unwrap();
+
+        // Two writers, like the daemon and a subcommand, first saving whole states...
+        let savers: Vec<_> = (0..2).map(|n| {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    AppState { violation_count: n, ..AppState::default() }.save(&path).unwrap();
+                }
+            })
+        }).collect();
+        for saver in savers {
+            saver.join().unwrap();
+        }
+        let start = read_state_locked(&path).unwrap().violation_count;
+        assert!(start < 2);
+
+        // ...then both bumping the count with load-modify-save: no update is lost.
+        let updaters: Vec<_> = (0..2).map(|_| {
+            let store = JsonFileStore::new(&path);
+            std::thread::spawn(move || {
+                for _ in 0..100 {
+                    store.update(&mut |state| {
+                        state.violation_count += 1;
+                        Ok(())
+                    }).unwrap();
+                }
+            })
+        }).collect();
+        for updater in updaters {
+            updater.join().unwrap();
+        }
+        assert_eq!(read_state_locked(&path).unwrap().violation_count, start + 200);
+        let mut files: Vec<_> = fs::read_dir(dir.path()).unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["state.json", "state.json.lock"]);
+    }
+
+    #[test]
+    fn test_update_state_saves_only_on_success() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let store = JsonFileStore::new(dir.path().join("state.json"));
+
+        let count = update_state(&store, |state| {
+            state.violation_count = 3;
+            Ok(state.violation_count)
+        }).unwrap();
+        assert_eq!(count, 3);
+        assert!(update_state(&store, |state| {
+            state.violation_count = 9;
+            Err::<(), _>(anyhow!("refused"))
+        }).is_err());
+        assert_eq!(store.load().unwrap().violation_count, 3);
+    }
+
+    #[test]
+    fn test_read_state_locked_corrupt_file_fails() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("state.json");
+        fs::write(&path, "{ not json").unwrap();
+        assert!(read_state_locked(&path).is_err());
+    }
 }
@@ -0,0 +1,64 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Local, NaiveDateTime, NaiveTime, Utc};
+use chrono_tz::Tz;
+
+/// The zone used for every wall-clock computation (quiet hours, schedules, daily
+/// rollovers). Configured via the top-level `timezone`; `Local` follows the host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Zone {
+    #[default]
+    Local,
+    Named(Tz),
+}
+
+impl Zone {
+    /// Parses an IANA name such as `America/New_York`; `None` means system local time.
+    pub fn parse(name: Option<&str>) -> Result<Self> {
+        match name {
+            None => Ok(Zone::Local),
+            Some(name) => name
+                .parse::<Tz>()
+                .map(Zone::Named)
+                .map_err(|_| anyhow!("unknown timezone '{}'", name)),
+        }
+    }
+
+    pub fn naive_local(&self, instant: DateTime<Utc>) -> NaiveDateTime {
+        match self {
+            Zone::Local => instant.with_timezone(&Local).naive_local(),
+            Zone::Named(tz) => instant.with_timezone(tz).naive_local(),
+        }
+    }
+
+    pub fn time_of(&self, instant: DateTime<Utc>) -> NaiveTime {
+        self.naive_local(instant).time()
+    }
+
+    pub fn now_time(&self) -> NaiveTime {
+        self.time_of(Utc::now())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Zone::parse(None).unwrap(), Zone::Local);
+        assert_eq!(
+            Zone::parse(Some("America/New_York")).unwrap(),
+            Zone::Named(chrono_tz::America::New_York)
+        );
+        assert!(Zone::parse(Some("Mars/Olympus_Mons")).is_err());
+    }
+
+    #[test]
+    fn test_named_zone_ignores_host_tz() {
+        let zone = Zone::parse(Some("Asia/Tokyo")).unwrap();
+        let instant = Utc.with_ymd_and_hms(2026, 3, 1, 15, 30, 0).unwrap();
+        assert_eq!(zone.time_of(instant), NaiveTime::from_hms_opt(0, 30, 0).unwrap());
+        assert_eq!(zone.naive_local(instant).date().to_string(), "2026-03-02");
+    }
+}
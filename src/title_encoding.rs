@@ -0,0 +1,30 @@
+use anyhow::{anyhow, Result};
+use encoding_rs::Encoding;
+
+/// Looks up a `monitoring.legacy_encoding` label such as `latin1` or `gbk`.
+pub fn legacy_encoding(label: &str) -> Result<&'static Encoding> {
+    Encoding::for_label(label.trim().as_bytes())
+        .ok_or_else(|| anyhow!("monitoring.legacy_encoding: unknown encoding '{}'", label))
+}
+
+/// Decodes a legacy `WM_NAME` title with `encoding`, or as lossy UTF-8 without one.
+pub fn decode_legacy_title(bytes: &[u8], encoding: Option<&'static Encoding>) -> String {
+    match encoding {
+        Some(encoding) => encoding.decode_without_bom_handling(bytes).0.into_owned(),
+        None => String::from_utf8_lossy(bytes).into_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_legacy_title() {
+        let latin1 = b"caf\xe9";
+        assert_eq!(decode_legacy_title(latin1, Some(legacy_encoding("latin1").unwrap())), "café");
+        assert_eq!(decode_legacy_title(latin1, None), "caf\u{fffd}");
+        assert_eq!(decode_legacy_title("café".as_bytes(), None), "café");
+        assert!(legacy_encoding("klingon").is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::window_info::WindowInfo;
+use log::{debug, trace};
+use std::collections::{HashMap, VecDeque};
+
+/// Recently observed titles per window, so content that flashed briefly between
+/// checks is still evaluated. Windows that disappear are evicted.
+pub struct TitleHistory {
+    capacity: usize,
+    windows: HashMap<u64, VecDeque<String>>,
+    /// Window ids in the order of the most recent update.
+    order: Vec<u64>,
+}
+
+impl TitleHistory {
+    pub fn new(capacity: usize) -> Self {
+        TitleHistory {
+            capacity: capacity.max(1),
+            windows: HashMap::new(),
+            order: Vec::new(),
+        }
+    }
+
+    /// Records the current title of every window in `windows` and forgets
+    /// windows that are no longer present.
+    pub fn update(&mut self, windows: &[WindowInfo]) {
+        let before = self.windows.len();
+        self.windows
+            .retain(|id, _| windows.iter().any(|w| w.id == *id));
+        if self.windows.len() != before {
+            debug!("TitleHistory: evicted {} closed window(s)", before - self.windows.len());
+        }
+
+        for window in windows {
+            if window.title.is_empty() {
+                continue;
+            }
+            let titles = self.windows.entry(window.id).or_default();
+            if let Some(pos) = titles.iter().position(|t| *t == window.title) {
+                titles.remove(pos);
+            }
+            titles.push_back(window.title.clone());
+            while titles.len() > self.capacity {
+                titles.pop_front();
+            }
+            trace!("TitleHistory: window {} has {} title(s)", window.id, titles.len());
+        }
+
+        self.order = windows
+            .iter()
+            .map(|w| w.id)
+            .filter(|id| self.windows.contains_key(id))
+            .collect();
+        self.order.dedup();
+    }
+
+    /// Every remembered title, windows in the order they were last passed to
+    /// [`update`](Self::update), oldest title first within each window.
+    #[allow(dead_code)]
+    pub fn titles(&self) -> Vec<String> {
+        self.entries().into_iter().map(|(_, title)| title).collect()
+    }
+
+    /// Like [`titles`](Self::titles), paired with the id of the window each title was seen in.
+    pub fn entries(&self) -> Vec<(u64, String)> {
+        self.order
+            .iter()
+            .flat_map(|id| self.windows[id].iter().map(move |title| (*id, title.clone())))
+            .collect()
+    }
+
+    #[allow(dead_code)]
+    pub fn window_titles(&self, id: u64) -> Option<&VecDeque<String>> {
+        self.windows.get(&id)
+    }
+
+    pub fn clear(&mut self) {
+        self.windows.clear();
+        self.order.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::Filter;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    fn window(id: u64, title: &str) -> WindowInfo {
+        WindowInfo {
+            id,
+            pid: Some(100),
+            title: title.to_string(),
+            ..WindowInfo::default()
+        }
+    }
+
+    #[test]
+    fn test_insertion_keeps_recent_titles() {
+        let mut history = TitleHistory::new(3);
+        history.update(&[window(1, "a")]);
+        history.update(&[window(1, "b")]);
+        history.update(&[window(1, "c")]);
+        history.update(&[window(1, "d")]);
+
+        let titles: Vec<&str> = history.window_titles(1).unwrap().iter().map(String::as_str).collect();
+        assert_eq!(titles, vec!["b", "c", "d"]);
+    }
+
+    #[test]
+    fn test_repeated_title_is_not_duplicated() {
+        let mut history = TitleHistory::new(3);
+        history.update(&[window(1, "a")]);
+        history.update(&[window(1, "b")]);
+        history.update(&[window(1, "a")]);
+        history.update(&[window(1, "a")]);
+
+        let titles: Vec<&str> = history.window_titles(1).unwrap().iter().map(String::as_str).collect();
+        assert_eq!(titles, vec!["b", "a"]);
+    }
+
+    #[test]
+    fn test_empty_titles_are_skipped() {
+        let mut history = TitleHistory::new(3);
+        history.update(&[window(1, "")]);
+        assert!(history.titles().is_empty());
+    }
+
+    #[test]
+    fn test_eviction_when_window_closes() {
+        let mut history = TitleHistory::new(3);
+        history.update(&[window(1, "a"), window(2, "b")]);
+        history.update(&[window(2, "c")]);
+
+        assert!(history.window_titles(1).is_none());
+        assert_eq!(history.titles(), vec!["b".to_string(), "c".to_string()]);
+    }
+
+    #[test]
+    fn test_titles_follow_update_order() {
+        let mut history = TitleHistory::new(3);
+        history.update(&[window(1, "a"), window(2, "b")]);
+        history.update(&[window(2, "c"), window(1, "a")]);
+
+        assert_eq!(history.titles(), vec!["b".to_string(), "c".to_string(), "a".to_string()]);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut history = TitleHistory::new(3);
+        history.update(&[window(1, "a")]);
+        history.clear();
+        assert!(history.titles().is_empty());
+    }
+
+    #[test]
+    fn test_matching_across_historical_titles() {
+        let mut bl = NamedTempFile::new().unwrap();
+        bl.write_all(b".*porn.*").unwrap();
+        let wl = NamedTempFile::new().unwrap();
+        let filter = Filter::new(bl.path(), wl.path()).unwrap();
+
+        let mut history = TitleHistory::new(5);
+        history.update(&[window(7, "free porn videos")]);
+        history.update(&[window(7, "YouTube")]);
+
+        // The current title alone is clean, but the flashed title is still remembered.
+        assert!(!filter.check_titles(&["YouTube".to_string()]));
+        let (title, _) = filter.find_blacklisted_title(&history.titles()).unwrap();
+        assert_eq!(title, "free porn videos");
+    }
+}
@@ -0,0 +1,541 @@
+use anyhow::Result;
+
+use crate::config::{DefaultAction, DetectionScope, OnEmpty, PipConfig, UntitledWindows};
+
+/// Reported instead of a pattern when a window is blocked as a suspected picture-in-picture popup.
+pub const PIP_MARKER: &str = "<picture-in-picture>";
+
+/// Reported as both title and pattern when `monitoring.on_empty: block` fires.
+pub const NO_WINDOWS_MARKER: &str = "<no windows>";
+
+/// Reported instead of a pattern when a focused untitled window is blocked.
+pub const UNTITLED_MARKER: &str = "<untitled window>";
+
+/// Window position and size from `XGetGeometry`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Geometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A top-level window as seen by the monitor.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct WindowInfo {
+    /// X11 window id.
+    pub id: u64,
+    /// Owning process from `_NET_WM_PID`, when the window advertises one.
+    pub pid: Option<i32>,
+    pub title: String,
+    /// `_NET_WM_STATE` contains `_NET_WM_STATE_FULLSCREEN`.
+    pub is_fullscreen: bool,
+    pub geometry: Geometry,
+    /// `_NET_WM_STATE` contains `_NET_WM_STATE_ABOVE`.
+    pub always_on_top: bool,
+    /// The class part of `WM_CLASS`, e.g. `firefox` or `Thunar`.
+    pub class: Option<String>,
+    /// Holds the input focus.
+    pub focused: bool,
+    /// `WM_ICON_NAME`, read only when `monitoring.match_properties` asks for it.
+    pub icon_name: Option<String>,
+    /// `WM_WINDOW_ROLE`, read only when `monitoring.match_properties` asks for it.
+    pub role: Option<String>,
+    /// `WM_CLIENT_MACHINE`, the host the client runs on; read only for kiosk mode.
+    pub client_machine: Option<String>,
+}
+
+/// Source of the browser windows currently on screen. Implemented by the X11
+/// monitor; tests substitute a mock.
+pub trait WindowTitles {
+    fn browser_windows(&self, browser_pids: &[i32]) -> anyhow::Result<Vec<WindowInfo>>;
+}
+
+/// Queries every source in `monitoring.backends` order and merges their windows,
+/// keeping the first window seen for each title, marked focused if any source
+/// reported it focused. A failing source is skipped with a warning; the scan only
+/// fails when every source does.
+pub fn union_browser_windows(sources: &[&dyn WindowTitles], browser_pids: &[i32]) -> Result<Vec<WindowInfo>> {
+    let mut windows: Vec<WindowInfo> = Vec::new();
+    let mut last_error = None;
+    let mut any_succeeded = false;
+    for source in sources {
+        match source.browser_windows(browser_pids) {
+            Ok(found) => {
+                any_succeeded = true;
+                for window in found {
+                    match windows.iter_mut().find(|w| w.title == window.title) {
+                        Some(seen) => seen.focused |= window.focused,
+                        None => windows.push(window),
+                    }
+                }
+            }
+            Err(e) if sources.len() > 1 => {
+                log::warn!("Skipping a window backend that failed: {}", e);
+                last_error = Some(e);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    match last_error {
+        Some(e) if !any_succeeded => Err(e),
+        _ => Ok(windows),
+    }
+}
+
+/// The backends listed in `monitoring.backends`, queried together as one source.
+pub struct MergedTitles<'a>(pub Vec<&'a dyn WindowTitles>);
+
+impl WindowTitles for MergedTitles<'_> {
+    fn browser_windows(&self, browser_pids: &[i32]) -> Result<Vec<WindowInfo>> {
+        union_browser_windows(&self.0, browser_pids)
+    }
+}
+
+/// Whether a `_NET_WM_STATE` atom list contains `wanted`.
+pub fn has_state(states: &[u64], wanted: u64) -> bool {
+    wanted != 0 && states.contains(&wanted)
+}
+
+/// Moves fullscreen windows to the front, keeping the relative order otherwise.
+pub fn prioritize_fullscreen(windows: &mut [WindowInfo]) {
+    windows.sort_by_key(|w| !w.is_fullscreen);
+}
+
+/// Whether `title` is currently shown by a fullscreen window.
+pub fn is_fullscreen_title(windows: &[WindowInfo], title: &str) -> bool {
+    windows.iter().any(|w| w.is_fullscreen && w.title == title)
+}
+
+/// Applies `monitoring.detection_scope`: with `focused_only`, only the focused window
+/// is left to check.
+pub fn keep_in_scope(windows: &mut Vec<WindowInfo>, scope: DetectionScope) {
+    if scope == DetectionScope::FocusedOnly {
+        windows.retain(|w| w.focused);
+    }
+}
+
+/// Drops windows whose `WM_CLASS` is listed in `monitoring.ignore_classes` (case-insensitive).
+pub fn remove_ignored_classes(windows: &mut Vec<WindowInfo>, ignore_classes: &[String]) {
+    if ignore_classes.is_empty() {
+        return;
+    }
+    windows.retain(|w| {
+        !w.class
+            .as_deref()
+            .is_some_and(|class| ignore_classes.iter().any(|i| i.eq_ignore_ascii_case(class)))
+    });
+}
+
+/// `security.supervised_users`: drops windows whose process (`_NET_WM_PID`) belongs
+/// to a user not in `supervised`, as reported by `owner`. Windows whose owner cannot
+/// be determined stay. An empty list keeps every window.
+pub fn remove_unsupervised<F: Fn(i32) -> Option<String>>(windows: &mut Vec<WindowInfo>, supervised: &[String], owner: F) {
+    if supervised.is_empty() {
+        return;
+    }
+    windows.retain(|w| match w.pid.and_then(&owner) {
+        Some(user) => supervised.contains(&user),
+        None => true,
+    });
+}
+
+/// `monitoring.exclude_own_windows`: drops windows owned by a helper program the
+/// daemon started itself, such as a block message whose text names the browser.
+pub fn remove_spawned(windows: &mut Vec<WindowInfo>, spawned: &[i32]) {
+    if spawned.is_empty() {
+        return;
+    }
+    windows.retain(|w| !w.pid.is_some_and(|pid| spawned.contains(&pid)));
+}
+
+/// `monitoring.kiosk_mode`: whether `window`'s process should be killed because its
+/// class is neither in `allowed` nor in `safe` (both case-insensitive). Windows of
+/// `protected_pids` (the daemon, its helpers, the browser), of pid 1 or below, and
+/// windows without a pid or `WM_CLASS` are never killed. Any client can set
+/// `_NET_WM_PID`, so the window must also come from `local_host` and `owner_uid` must
+/// report a session user for the pid.
+pub fn kiosk_should_kill<F: Fn(i32) -> Option<u32>>(
+    window: &WindowInfo,
+    allowed: &[String],
+    safe: &[String],
+    protected_pids: &[i32],
+    local_host: &str,
+    owner_uid: F,
+) -> bool {
+    let (Some(pid), Some(class)) = (window.pid, window.class.as_deref()) else {
+        return false;
+    };
+    pid > 1
+        && !protected_pids.contains(&pid)
+        && !allowed.iter().chain(safe).any(|c| c.eq_ignore_ascii_case(class))
+        && window.client_machine.as_deref().is_some_and(|host| host.eq_ignore_ascii_case(local_host))
+        && owner_uid(pid).is_some_and(crate::privileges::is_session_uid)
+}
+
+/// Picture-in-picture popups are small always-on-top windows whose generic
+/// titles slip past the filter; flag those within the configured size limits.
+pub fn is_suspected_pip(window: &WindowInfo, pip: &PipConfig) -> bool {
+    let Geometry { width, height, .. } = window.geometry;
+    pip.enabled
+        && window.always_on_top
+        && !window.is_fullscreen
+        && width > 0
+        && height > 0
+        && width <= pip.max_width
+        && height <= pip.max_height
+}
+
+/// The first window that looks like a picture-in-picture popup.
+pub fn find_pip_window<'a>(windows: &'a [WindowInfo], pip: &PipConfig) -> Option<&'a WindowInfo> {
+    windows.iter().find(|w| is_suspected_pip(w, pip))
+}
+
+/// How a tick proceeds once the browser windows have been collected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmptyDecision {
+    Evaluate,
+    Skip,
+    Block,
+}
+
+/// Applies `monitoring.on_empty`. It only matters when a browser is running but shows
+/// no windows; with no browser running there is nothing to hide and titles are evaluated.
+pub fn empty_decision(windows: &[WindowInfo], browser_running: bool, on_empty: OnEmpty) -> EmptyDecision {
+    if !windows.is_empty() || !browser_running {
+        return EmptyDecision::Evaluate;
+    }
+    match on_empty {
+        OnEmpty::Allow => EmptyDecision::Evaluate,
+        OnEmpty::Ignore => EmptyDecision::Skip,
+        OnEmpty::Block => EmptyDecision::Block,
+    }
+}
+
+/// Applies `monitoring.untitled_windows: suspicious`: in deny mode the focused window
+/// having no title counts as a hit, since a blank title can hide what is on screen.
+/// Untitled windows in the background are left alone.
+pub fn suspicious_untitled(
+    windows: &[WindowInfo],
+    untitled: UntitledWindows,
+    default_action: DefaultAction,
+) -> Option<&WindowInfo> {
+    if untitled != UntitledWindows::Suspicious || default_action != DefaultAction::Deny {
+        return None;
+    }
+    windows.iter().find(|w| w.focused && w.title.trim().is_empty())
+}
+
+/// Applies `monitoring.require_browser_running`: a detection is dropped when the
+/// option is set and no browser process is running, since there is nothing to kill.
+pub fn gate_detection<T>(hit: Option<T>, require_browser_running: bool, browser_running: bool) -> Option<T> {
+    hit.filter(|_| !require_browser_running || browser_running)
+}
+
+/// Chooses which of the root window's `children` (in X stacking order, bottom first)
+/// to inspect when there are more than `max`: the `focused` window, then the rest
+/// from the top of the stack down. Returns the windows and whether the list was
+/// capped. A `max` of 0, or a list that fits, is returned unchanged.
+pub fn limit_windows(children: &[u64], focused: Option<u64>, max: usize) -> (Vec<u64>, bool) {
+    if max == 0 || children.len() <= max {
+        return (children.to_vec(), false);
+    }
+    let focused = focused.filter(|id| children.contains(id));
+    let selected = focused
+        .into_iter()
+        .chain(children.iter().rev().copied().filter(|&id| Some(id) != focused))
+        .take(max)
+        .collect();
+    (selected, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn window(id: u64, title: &str, is_fullscreen: bool) -> WindowInfo {
+        WindowInfo {
+            id,
+            pid: Some(1),
+            title: title.to_string(),
+            is_fullscreen,
+            ..WindowInfo::default()
+        }
+    }
+
+    fn popup(width: u32, height: u32, always_on_top: bool) -> WindowInfo {
+        WindowInfo {
+            title: "Picture-in-Picture".to_string(),
+            geometry: Geometry { x: 1500, y: 800, width, height },
+            always_on_top,
+            ..WindowInfo::default()
+        }
+    }
+
+    fn pip_enabled() -> PipConfig {
+        PipConfig {
+            enabled: true,
+            ..PipConfig::default()
+        }
+    }
+
+    struct FixedTitles(Vec<&'static str>);
+
+    impl WindowTitles for FixedTitles {
+        fn browser_windows(&self, _: &[i32]) -> Result<Vec<WindowInfo>> {
+            Ok(self.0.iter().enumerate().map(|(i, title)| window(i as u64, title, false)).collect())
+        }
+    }
+
+    struct FailingTitles;
+
+    impl WindowTitles for FailingTitles {
+        fn browser_windows(&self, _: &[i32]) -> Result<Vec<WindowInfo>> {
+            Err(anyhow!("Failed to connect to sway IPC"))
+        }
+    }
+
+    #[test]
+    fn test_union_browser_windows_deduplicates_and_skips_failures() {
+        let xlib = FixedTitles(vec!["Cooking tutorial", "News"]);
+        let wayland = FixedTitles(vec!["News", "Free porn"]);
+
+        let windows = union_browser_windows(&[&xlib, &FailingTitles, &wayland], &[]).unwrap();
+        let titles: Vec<&str> = windows.iter().map(|w| w.title.as_str()).collect();
+        assert_eq!(titles, ["Cooking tutorial", "News", "Free porn"]);
+
+        assert!(union_browser_windows(&[&FailingTitles, &FailingTitles], &[]).is_err());
+        assert!(union_browser_windows(&[&FailingTitles], &[]).is_err());
+    }
+
+    #[test]
+    fn test_union_browser_windows_keeps_focus_from_any_backend() {
+        struct FocusedTitle;
+        impl WindowTitles for FocusedTitle {
+            fn browser_windows(&self, _: &[i32]) -> Result<Vec<WindowInfo>> {
+                Ok(vec![WindowInfo { focused: true, ..window(9, "News", false) }])
+            }
+        }
+
+        let windows = union_browser_windows(&[&FixedTitles(vec!["News", "Free porn"]), &FocusedTitle], &[]).unwrap();
+        let focused: Vec<(&str, bool)> = windows.iter().map(|w| (w.title.as_str(), w.focused)).collect();
+        assert_eq!(focused, [("News", true), ("Free porn", false)]);
+    }
+
+    #[test]
+    fn test_remove_ignored_classes() {
+        let with_class = |id, title: &str, class: Option<&str>| WindowInfo {
+            class: class.map(str::to_string),
+            ..window(id, title, false)
+        };
+        let mut windows = vec![
+            with_class(1, "Bad Folder", Some("Thunar")),
+            with_class(2, "Bad Page", Some("firefox")),
+            with_class(3, "Panel", Some("xfce4-panel")),
+            with_class(4, "Untitled", None),
+        ];
+
+        remove_ignored_classes(&mut windows, &["thunar".to_string(), "xfce4-panel".to_string()]);
+
+        let titles: Vec<&str> = windows.iter().map(|w| w.title.as_str()).collect();
+        assert_eq!(titles, vec!["Bad Page", "Untitled"]);
+
+        remove_ignored_classes(&mut windows, &[]);
+        assert_eq!(windows.len(), 2);
+    }
+
+    #[test]
+    fn test_remove_unsupervised() {
+        let owned_by = |id, pid| WindowInfo { pid, ..window(id, "Page", false) };
+        let mut windows = vec![owned_by(1, Some(10)), owned_by(2, Some(20)), owned_by(3, None), owned_by(4, Some(30))];
+        let owner = |pid| match pid {
+            10 => Some("kid".to_string()),
+            20 => Some("parent".to_string()),
+            _ => None,
+        };
+
+        remove_unsupervised(&mut windows, &[], owner);
+        assert_eq!(windows.len(), 4);
+        remove_unsupervised(&mut windows, &["kid".to_string()], owner);
+        let ids: Vec<u64> = windows.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![1, 3, 4]);
+    }
+
+    #[test]
+    fn test_remove_spawned_drops_own_helper_windows() {
+        let owned_by = |id, pid| WindowInfo { pid, ..window(id, "Blocked: firefox was closed", false) };
+        let mut windows = vec![owned_by(1, Some(10)), owned_by(2, Some(4242)), owned_by(3, None)];
+
+        remove_spawned(&mut windows, &[]);
+        assert_eq!(windows.len(), 3);
+        remove_spawned(&mut windows, &[4242, 5000]);
+        let ids: Vec<u64> = windows.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_kiosk_should_kill_only_unlisted_classes() {
+        let app = |pid, class: Option<&str>| WindowInfo {
+            pid,
+            class: class.map(str::to_string),
+            client_machine: Some("kidbox".to_string()),
+            ..window(1, "Some app", false)
+        };
+        let allowed = vec!["Chromium".to_string(), "Pavucontrol".to_string()];
+        let safe = vec!["Xfce4-panel".to_string()];
+        let protected = [100, 200];
+        let kill = |window: &WindowInfo| kiosk_should_kill(window, &allowed, &safe, &protected, "kidbox", |_| Some(1000));
+
+        assert!(kill(&app(Some(4242), Some("Steam"))));
+        assert!(!kill(&app(Some(4242), Some("chromium"))));
+        assert!(!kill(&app(Some(4242), Some("Xfce4-panel"))));
+        assert!(!kill(&app(Some(200), Some("Zenity"))));
+        assert!(!kill(&app(Some(1), Some("Steam"))));
+        assert!(!kill(&app(None, Some("Steam"))));
+        assert!(!kill(&app(Some(4242), None)));
+    }
+
+    #[test]
+    fn test_kiosk_should_kill_only_local_session_processes() {
+        let steam = |machine: Option<&str>| WindowInfo {
+            pid: Some(4242),
+            class: Some("Steam".to_string()),
+            client_machine: machine.map(str::to_string),
+            ..window(1, "Steam", false)
+        };
+        let kill = |window: &WindowInfo, uid: Option<u32>| kiosk_should_kill(window, &[], &[], &[], "kidbox", |_| uid);
+
+        assert!(kill(&steam(Some("KIDBOX")), Some(1000)));
+        assert!(!kill(&steam(Some("remote-host")), Some(1000)), "an ssh -X client's pid is not ours");
+        assert!(!kill(&steam(None), Some(1000)));
+        assert!(!kill(&steam(Some("kidbox")), Some(0)), "root processes are never killed");
+        assert!(!kill(&steam(Some("kidbox")), Some(104)), "nor system users");
+        assert!(!kill(&steam(Some("kidbox")), Some(65534)));
+        assert!(!kill(&steam(Some("kidbox")), None));
+    }
+
+    #[test]
+    fn test_pip_heuristic() {
+        let pip = pip_enabled();
+
+        assert!(is_suspected_pip(&popup(400, 225, true), &pip));
+        assert!(is_suspected_pip(&popup(pip.max_width, pip.max_height, true), &pip));
+        assert!(!is_suspected_pip(&popup(400, 225, false), &pip));
+        assert!(!is_suspected_pip(&popup(1280, 720, true), &pip));
+        assert!(!is_suspected_pip(&popup(0, 0, true), &pip));
+
+        let mut fullscreen = popup(400, 225, true);
+        fullscreen.is_fullscreen = true;
+        assert!(!is_suspected_pip(&fullscreen, &pip));
+    }
+
+    #[test]
+    fn test_pip_heuristic_disabled() {
+        assert!(!is_suspected_pip(&popup(400, 225, true), &PipConfig::default()));
+    }
+
+    #[test]
+    fn test_find_pip_window() {
+        let windows = vec![window(1, "Main", false), popup(320, 180, true)];
+        assert_eq!(find_pip_window(&windows, &pip_enabled()).unwrap().title, "Picture-in-Picture");
+        assert!(find_pip_window(&windows[..1], &pip_enabled()).is_none());
+    }
+
+    #[test]
+    fn test_has_state_finds_fullscreen_atom() {
+        let (above, fullscreen, hidden) = (301, 305, 310);
+        assert!(has_state(&[above, fullscreen, hidden], fullscreen));
+        assert!(!has_state(&[above, hidden], fullscreen));
+        assert!(!has_state(&[], fullscreen));
+    }
+
+    #[test]
+    fn test_has_state_ignores_unresolved_atom() {
+        assert!(!has_state(&[0, 1], 0));
+    }
+
+    #[test]
+    fn test_prioritize_fullscreen() {
+        let mut windows = vec![
+            window(1, "a", false),
+            window(2, "b", true),
+            window(3, "c", false),
+            window(4, "d", true),
+        ];
+        prioritize_fullscreen(&mut windows);
+
+        let ids: Vec<u64> = windows.iter().map(|w| w.id).collect();
+        assert_eq!(ids, vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn test_is_fullscreen_title() {
+        let windows = vec![window(1, "video", true), window(2, "docs", false)];
+        assert!(is_fullscreen_title(&windows, "video"));
+        assert!(!is_fullscreen_title(&windows, "docs"));
+        assert!(!is_fullscreen_title(&windows, "missing"));
+    }
+
+    #[test]
+    fn test_suspicious_untitled_focused_window() {
+        let focused_blank = WindowInfo { focused: true, ..window(7, "", false) };
+        let windows = vec![window(1, "Cooking tutorial", false), focused_blank.clone()];
+
+        let hit = suspicious_untitled(&windows, UntitledWindows::Suspicious, DefaultAction::Deny);
+        assert_eq!(hit, Some(&focused_blank));
+        assert_eq!(suspicious_untitled(&windows, UntitledWindows::Ignore, DefaultAction::Deny), None);
+        assert_eq!(suspicious_untitled(&windows, UntitledWindows::Suspicious, DefaultAction::Allow), None);
+
+        // An untitled window that does not have focus is not suspicious.
+        let background = vec![window(7, "  ", false), WindowInfo { focused: true, ..window(1, "Docs", false) }];
+        assert_eq!(suspicious_untitled(&background, UntitledWindows::Suspicious, DefaultAction::Deny), None);
+    }
+
+    #[test]
+    fn test_empty_decision_for_each_option() {
+        assert_eq!(empty_decision(&[], true, OnEmpty::Allow), EmptyDecision::Evaluate);
+        assert_eq!(empty_decision(&[], true, OnEmpty::Ignore), EmptyDecision::Skip);
+        assert_eq!(empty_decision(&[], true, OnEmpty::Block), EmptyDecision::Block);
+    }
+
+    #[test]
+    fn test_empty_decision_ignores_option_when_not_empty_or_no_browser() {
+        let windows = [window(1, "Cooking", false)];
+        for on_empty in [OnEmpty::Allow, OnEmpty::Ignore, OnEmpty::Block] {
+            assert_eq!(empty_decision(&windows, true, on_empty), EmptyDecision::Evaluate);
+            assert_eq!(empty_decision(&[], false, on_empty), EmptyDecision::Evaluate);
+        }
+    }
+
+    #[test]
+    fn test_gate_detection_requires_running_browser() {
+        let hit = Some(("free porn".to_string(), ".*porn.*".to_string()));
+        assert_eq!(gate_detection(hit.clone(), true, false), None);
+        assert_eq!(gate_detection(hit.clone(), true, true), hit);
+        assert_eq!(gate_detection(hit.clone(), false, false), hit);
+        assert_eq!(gate_detection(None::<String>, true, true), None);
+    }
+
+    #[test]
+    fn test_limit_windows_stops_at_cap() {
+        let children: Vec<u64> = (1..=5000).collect();
+
+        let (selected, capped) = limit_windows(&children, Some(42), 100);
+        assert!(capped);
+        assert_eq!(selected.len(), 100);
+        assert_eq!(selected[0], 42);
+        assert_eq!(&selected[1..4], [5000, 4999, 4998]);
+
+        // A focus outside the children is ignored.
+        let (selected, _) = limit_windows(&children, Some(99_999), 3);
+        assert_eq!(selected, vec![5000, 4999, 4998]);
+    }
+
+    #[test]
+    fn test_limit_windows_within_cap_is_unchanged() {
+        let children = vec![3, 1, 2];
+        assert_eq!(limit_windows(&children, Some(2), 3), (children.clone(), false));
+        assert_eq!(limit_windows(&children, Some(2), 0), (children, false));
+    }
+}
@@ -1,26 +1,244 @@
 use anyhow::{anyhow, Result};
-use log::{debug, info, trace};
-use std::ffi::CStr;
+use log::{debug, info, trace, warn};
+use std::ffi::{CStr, CString};
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+use encoding_rs::Encoding;
 use x11::xlib::*;
 
+use crate::command::set_child_xauthority;
+use crate::title_encoding::decode_legacy_title;
+use crate::window_info::{has_state, limit_windows, Geometry, WindowInfo, WindowTitles};
+use crate::xauthority::{read_cookie, MIT_MAGIC_COOKIE};
+
 pub struct WindowMonitor {
     display: *mut Display,
+    /// Most top-level windows inspected per call; 0 means no limit.
+    max_windows: usize,
+    /// Report browser windows whose title is empty (`monitoring.untitled_windows`).
+    keep_untitled: bool,
+    /// Look up the focused window on every scan (`monitoring.detection_scope`).
+    track_focus: bool,
+    /// Encoding of legacy `WM_NAME` titles; lossy UTF-8 when unset.
+    legacy_encoding: Option<&'static Encoding>,
+    /// Read `WM_ICON_NAME` and `WM_WINDOW_ROLE` of browser windows.
+    read_icon_name: bool,
+    read_role: bool,
+}
+
+/// Display assumed when neither the config nor `$DISPLAY` names one, as under systemd.
+pub const DEFAULT_DISPLAY: &str = ":0";
+
+/// The X server to connect to and the Xauthority file that grants access to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayTarget {
+    pub display: String,
+    pub xauthority: Option<PathBuf>,
+}
+
+impl DisplayTarget {
+    /// Works out the display and Xauthority from the configured values, then the
+    /// environment, then the defaults: display `:0` and `~/.Xauthority` if it exists.
+    pub fn resolve(
+        configured_display: Option<&str>,
+        configured_xauthority: Option<&str>,
+        env_display: Option<&str>,
+        env_xauthority: Option<&str>,
+        home: Option<&Path>,
+    ) -> Self {
+        let non_empty = |value: Option<&str>| value.filter(|v| !v.is_empty()).map(str::to_string);
+        let display = non_empty(configured_display)
+            .or_else(|| non_empty(env_display))
+            .unwrap_or_else(|| DEFAULT_DISPLAY.to_string());
+        let xauthority = non_empty(configured_xauthority)
+            .or_else(|| non_empty(env_xauthority))
+            .map(PathBuf::from)
+            .or_else(|| home.map(|h| h.join(".Xauthority")).filter(|p| p.is_file()));
+        DisplayTarget { display, xauthority }
+    }
+
+    /// [`resolve`](Self::resolve) against this process's `DISPLAY`, `XAUTHORITY` and `HOME`.
+    pub fn discover(configured_display: Option<&str>, configured_xauthority: Option<&str>) -> Self {
+        let var = |name| std::env::var(name).ok();
+        let home = var("HOME").map(PathBuf::from);
+        let target = Self::resolve(
+            configured_display,
+            configured_xauthority,
+            var("DISPLAY").as_deref(),
+            var("XAUTHORITY").as_deref(),
+            home.as_deref(),
+        );
+        debug!("Resolved X display '{}' with Xauthority {:?}", target.display, target.xauthority);
+        target
+    }
+
+    /// Hands the display's cookie from the resolved Xauthority file to Xlib for the
+    /// connections opened after this, and the file to the X clients the daemon
+    /// starts. The process environment is left alone.
+    pub fn authorize(&self) {
+        let Some(path) = &self.xauthority else { return };
+        set_child_xauthority(Some(path));
+        match read_cookie(path, &self.display) {
+            Ok(Some(cookie)) => {
+                info!("Using Xauthority '{}'", path.display());
+                set_authorization(&cookie);
+            }
+            Ok(None) => warn!("Xauthority '{}' has no {} for display '{}'; using Xlib's default lookup",
+                path.display(), MIT_MAGIC_COOKIE, self.display),
+            Err(e) => warn!("Cannot read Xauthority '{}': {}", path.display(), e),
+        }
+    }
+}
+
+/// Makes Xlib send `cookie` as [`MIT_MAGIC_COOKIE`] on every connection opened after
+/// this call. Xlib keeps its own copy.
+fn set_authorization(cookie: &[u8]) {
+    let mut name = MIT_MAGIC_COOKIE.as_bytes().to_vec();
+    let mut data = cookie.to_vec();
+    unsafe {
+        XSetAuthorization(name.as_mut_ptr().cast(), name.len() as i32, data.as_mut_ptr().cast(), data.len() as i32);
+    }
+}
+
+/// Pause between attempts while waiting for the display to become available.
+const DISPLAY_RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Calls `open` until it succeeds or `timeout` has passed, returning the last error.
+/// A zero timeout makes exactly one attempt.
+fn retry_until<T>(timeout: Duration, interval: Duration, mut open: impl FnMut() -> Result<T>) -> Result<T> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match open() {
+            Ok(value) => return Ok(value),
+            Err(e) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Err(e);
+                }
+                debug!("{} — retrying", e);
+                thread::sleep(interval.min(deadline - now));
+            }
+        }
+    }
 }
 
 impl WindowMonitor {
+    /// Opens `$DISPLAY`. When it is unset, as is common under systemd, falls back to
+    /// [`DEFAULT_DISPLAY`] and the user's `~/.Xauthority` (see [`DisplayTarget`]).
+    #[allow(dead_code)]
     pub fn new() -> Result<Self> {
-        info!("Opening X11 display");
+        if std::env::var_os("DISPLAY").is_some_and(|d| !d.is_empty()) {
+            info!("Opening X11 display");
+            unsafe {
+                let display = XOpenDisplay(ptr::null());
+                if display.is_null() {
+                    return Err(anyhow!("Failed to open X11 display"));
+                }
+                info!("X11 display opened successfully");
+                return Ok(WindowMonitor {
+                    display,
+                    max_windows: 0,
+                    keep_untitled: false,
+                    track_focus: false,
+                    legacy_encoding: None,
+                    read_icon_name: false,
+                    read_role: false,
+                });
+            }
+        }
+        Self::new_for_target(&DisplayTarget::discover(None, None), Duration::ZERO)
+    }
+
+    /// Limits [`get_browser_windows`](Self::get_browser_windows) to `max_windows`
+    /// top-level windows, preferring the focused one; 0 means no limit.
+    pub fn with_max_windows(mut self, max_windows: usize) -> Self {
+        self.max_windows = max_windows;
+        self
+    }
+
+    /// Makes [`get_browser_windows`](Self::get_browser_windows) include browser windows
+    /// with an empty title instead of skipping them.
+    pub fn with_untitled_windows(mut self, keep_untitled: bool) -> Self {
+        self.keep_untitled = keep_untitled;
+        self
+    }
+
+    /// Marks the focused window in every [`get_browser_windows`](Self::get_browser_windows)
+    /// result, not only when untitled windows are kept or the list is capped.
+    pub fn with_focus_tracking(mut self, track_focus: bool) -> Self {
+        self.track_focus = track_focus;
+        self
+    }
+
+    /// Decodes `WM_NAME` with `encoding` when a window has no `_NET_WM_NAME`.
+    pub fn with_legacy_encoding(mut self, encoding: Option<&'static Encoding>) -> Self {
+        self.legacy_encoding = encoding;
+        self
+    }
+
+    /// Also reads `WM_ICON_NAME` and/or `WM_WINDOW_ROLE` into each [`WindowInfo`].
+    pub fn with_extra_properties(mut self, icon_name: bool, role: bool) -> Self {
+        self.read_icon_name = icon_name;
+        self.read_role = role;
+        self
+    }
+
+    /// The focused window, or `None` when focus is on the root or nowhere.
+    fn focused_window(&self) -> Option<Window> {
+        unsafe {
+            let root = XDefaultRootWindow(self.display);
+            let mut window: Window = 0;
+            let mut revert_to: i32 = 0;
+            XGetInputFocus(self.display, &mut window, &mut revert_to);
+            (window != 0 && window != root).then_some(window)
+        }
+    }
+
+    /// Authorizes with the target's Xauthority and opens its display, retrying for up
+    /// to `timeout` like [`new_with_timeout`](Self::new_with_timeout).
+    pub fn new_for_target(target: &DisplayTarget, timeout: Duration) -> Result<Self> {
+        target.authorize();
+        Self::new_with_display_timeout(&target.display, timeout)
+    }
+
+    /// Opens a specific display (e.g. `:1` for an Xvfb/VNC server) instead of `$DISPLAY`.
+    pub fn new_with_display(display_name: &str) -> Result<Self> {
+        info!("Opening X11 display '{}'", display_name);
+        let name = CString::new(display_name)
+            .map_err(|_| anyhow!("Failed to open X11 display '{}': name contains NUL", display_name))?;
         unsafe {
-            let display = XOpenDisplay(ptr::null());
+            let display = XOpenDisplay(name.as_ptr());
             if display.is_null() {
-                return Err(anyhow!("Failed to open X11 display"));
+                return Err(anyhow!("Failed to open X11 display '{}'", display_name));
             }
-            info!("X11 display opened successfully");
-            Ok(WindowMonitor { display })
+            info!("X11 display '{}' opened successfully", display_name);
+            Ok(WindowMonitor {
+                display,
+                max_windows: 0,
+                keep_untitled: false,
+                track_focus: false,
+                legacy_encoding: None,
+                read_icon_name: false,
+                read_role: false,
+            })
         }
     }
 
+    /// Like [`new`](Self::new), but keeps retrying for up to `timeout` while X is
+    /// unavailable (e.g. during a session transition). `Duration::ZERO` tries once.
+    #[allow(dead_code)]
+    pub fn new_with_timeout(timeout: Duration) -> Result<Self> {
+        retry_until(timeout, DISPLAY_RETRY_INTERVAL, Self::new)
+    }
+
+    /// [`new_with_display`](Self::new_with_display) with the same retry behaviour as
+    /// [`new_with_timeout`](Self::new_with_timeout).
+    pub fn new_with_display_timeout(display_name: &str, timeout: Duration) -> Result<Self> {
+        retry_until(timeout, DISPLAY_RETRY_INTERVAL, || Self::new_with_display(display_name))
+    }
+
     #[allow(dead_code)]
     pub fn get_active_window_title(&self) -> Result<String> {
         debug!("get_active_window_title: querying input focus");
@@ -42,8 +260,27 @@ impl WindowMonitor {
         }
     }
 
+    /// Gives keyboard focus to the root window.
+    pub fn focus_root(&self) -> Result<()> {
+        unsafe {
+            let root = XDefaultRootWindow(self.display);
+            XSetInputFocus(self.display, root, RevertToPointerRoot, CurrentTime);
+            XFlush(self.display);
+        }
+        Ok(())
+    }
+
+    #[allow(dead_code)]
     pub fn get_browser_window_titles(&self, browser_pids: &[i32]) -> Result<Vec<String>> {
-        debug!("get_browser_window_titles: checking against pids {:?}", browser_pids);
+        Ok(self
+            .get_browser_windows(browser_pids)?
+            .into_iter()
+            .map(|w| w.title)
+            .collect())
+    }
+
+    pub fn get_browser_windows(&self, browser_pids: &[i32]) -> Result<Vec<WindowInfo>> {
+        debug!("get_browser_windows: checking against pids {:?}", browser_pids);
         unsafe {
             let root = XDefaultRootWindow(self.display);
             let mut children: *mut Window = ptr::null_mut();
@@ -66,20 +303,51 @@ impl WindowMonitor {
 
             debug!("XQueryTree returned {} child window(s)", nchildren);
 
-            let mut titles = Vec::new();
+            let all: Vec<Window> = if children.is_null() {
+                Vec::new()
+            } else {
+                std::slice::from_raw_parts(children, nchildren as usize).to_vec()
+            };
+            if !children.is_null() {
+                XFree(children as *mut _);
+            }
+            let focused = if self.keep_untitled || self.track_focus || (self.max_windows > 0 && all.len() > self.max_windows) {
+                self.focused_window()
+            } else {
+                None
+            };
+            let (candidates, capped) = limit_windows(&all, focused, self.max_windows);
+            if capped {
+                warn!("{} top-level window(s); checking only {} (monitoring.max_windows)",
+                    all.len(), candidates.len());
+            }
 
-            for i in 0..nchildren {
-                let window = *children.offset(i as isize);
+            let mut windows = Vec::new();
+
+            for (i, &window) in candidates.iter().enumerate() {
                 if let Some(pid) = self.get_window_pid(window) {
                     if browser_pids.contains(&pid) {
                         if let Ok(title) = self.get_window_title(window) {
-                            if !title.is_empty() {
+                            if !title.is_empty() || self.keep_untitled {
                                 if log::log_enabled!(log::Level::Trace) {
                                     trace!("Browser window title (pid {}): '{}'", pid, title);
                                 } else {
                                     debug!("Browser window title: '{}'", title);
                                 }
-                                titles.push(title);
+                                let states = self.get_window_states(window);
+                                windows.push(WindowInfo {
+                                    id: window,
+                                    pid: Some(pid),
+                                    title,
+                                    is_fullscreen: has_state(&states, self.intern_atom(c"_NET_WM_STATE_FULLSCREEN")),
+                                    geometry: self.get_window_geometry(window),
+                                    always_on_top: has_state(&states, self.intern_atom(c"_NET_WM_STATE_ABOVE")),
+                                    class: self.get_window_class(window),
+                                    focused: focused == Some(window),
+                                    icon_name: self.read_icon_name.then(|| self.get_icon_name(window)).flatten(),
+                                    role: self.read_role.then(|| self.get_window_role(window)).flatten(),
+                                    client_machine: None,
+                                });
                             }
                         }
                     } else if log::log_enabled!(log::Level::Trace) {
@@ -92,13 +360,50 @@ impl WindowMonitor {
                 }
             }
 
-            info!("get_browser_window_titles: {} non-empty title(s) found", titles.len());
+            info!("get_browser_windows: {} window(s) found", windows.len());
+
+            Ok(windows)
+        }
+    }
+
+    /// Every top-level window that names its process, whatever program owns it,
+    /// with its title and class (`monitoring.kiosk_mode`).
+    pub fn get_all_windows(&self) -> Result<Vec<WindowInfo>> {
+        unsafe {
+            let root = XDefaultRootWindow(self.display);
+            let mut children: *mut Window = ptr::null_mut();
+            let mut nchildren: u32 = 0;
+            let mut parent: Window = 0;
+            let mut root_return: Window = 0;
 
+            if XQueryTree(self.display, root, &mut root_return, &mut parent, &mut children, &mut nchildren) == 0 {
+                return Err(anyhow!("Failed to query window tree"));
+            }
+            let all: Vec<Window> = if children.is_null() {
+                Vec::new()
+            } else {
+                std::slice::from_raw_parts(children, nchildren as usize).to_vec()
+            };
             if !children.is_null() {
                 XFree(children as *mut _);
             }
 
-            Ok(titles)
+            let windows: Vec<WindowInfo> = all
+                .into_iter()
+                .filter_map(|window| {
+                    let pid = self.get_window_pid(window)?;
+                    Some(WindowInfo {
+                        id: window,
+                        pid: Some(pid),
+                        title: self.get_window_title(window).unwrap_or_default(),
+                        class: self.get_window_class(window),
+                        client_machine: self.get_text_property(window, c"WM_CLIENT_MACHINE"),
+                        ..WindowInfo::default()
+                    })
+                })
+                .collect();
+            debug!("get_all_windows: {} window(s) with a pid", windows.len());
+            Ok(windows)
         }
     }
 
@@ -149,11 +454,164 @@ impl WindowMonitor {
         }
     }
 
+    fn intern_atom(&self, name: &CStr) -> Atom {
+        unsafe { XInternAtom(self.display, name.as_ptr(), 1) }
+    }
+
+    /// The class half of `WM_CLASS`.
+    fn get_window_class(&self, window: Window) -> Option<String> {
+        unsafe {
+            let mut hint = XClassHint {
+                res_name: ptr::null_mut(),
+                res_class: ptr::null_mut(),
+            };
+            if XGetClassHint(self.display, window, &mut hint) == 0 {
+                return None;
+            }
+            let class = (!hint.res_class.is_null())
+                .then(|| CStr::from_ptr(hint.res_class).to_string_lossy().into_owned());
+            if !hint.res_name.is_null() {
+                XFree(hint.res_name as *mut _);
+            }
+            if !hint.res_class.is_null() {
+                XFree(hint.res_class as *mut _);
+            }
+            class
+        }
+    }
+
+    /// `WM_ICON_NAME`, decoded like a legacy title.
+    fn get_icon_name(&self, window: Window) -> Option<String> {
+        unsafe {
+            let mut name: *mut i8 = ptr::null_mut();
+            if XGetIconName(self.display, window, &mut name) == 0 || name.is_null() {
+                return None;
+            }
+            let icon_name = decode_legacy_title(CStr::from_ptr(name).to_bytes(), self.legacy_encoding);
+            XFree(name as *mut _);
+            (!icon_name.is_empty()).then_some(icon_name)
+        }
+    }
+
+    /// `WM_WINDOW_ROLE`, e.g. `browser` or `pop-up` for Chromium windows.
+    fn get_window_role(&self, window: Window) -> Option<String> {
+        self.get_text_property(window, c"WM_WINDOW_ROLE")
+    }
+
+    /// An 8-bit text property such as `WM_WINDOW_ROLE` or `WM_CLIENT_MACHINE`.
+    fn get_text_property(&self, window: Window, name: &CStr) -> Option<String> {
+        unsafe {
+            let atom = self.intern_atom(name);
+            if atom == 0 {
+                return None;
+            }
+
+            let mut actual_type: Atom = 0;
+            let mut actual_format: i32 = 0;
+            let mut nitems: u64 = 0;
+            let mut bytes_after: u64 = 0;
+            let mut prop: *mut u8 = ptr::null_mut();
+
+            let result = XGetWindowProperty(
+                self.display,
+                window,
+                atom,
+                0,
+                256,
+                0,
+                AnyPropertyType as u64,
+                &mut actual_type,
+                &mut actual_format,
+                &mut nitems,
+                &mut bytes_after,
+                &mut prop as *mut *mut u8,
+            );
+
+            if result != 0 || prop.is_null() || nitems == 0 || actual_format != 8 {
+                if !prop.is_null() {
+                    XFree(prop as *mut _);
+                }
+                return None;
+            }
+
+            let value = String::from_utf8_lossy(std::slice::from_raw_parts(prop, nitems as usize)).into_owned();
+            XFree(prop as *mut _);
+            (!value.is_empty()).then_some(value)
+        }
+    }
+
+    /// Position and size via `XGetGeometry`; all zero if the query fails.
+    fn get_window_geometry(&self, window: Window) -> Geometry {
+        unsafe {
+            let mut root: Window = 0;
+            let (mut x, mut y) = (0, 0);
+            let (mut width, mut height, mut border, mut depth) = (0, 0, 0, 0);
+            let status = XGetGeometry(
+                self.display,
+                window,
+                &mut root,
+                &mut x,
+                &mut y,
+                &mut width,
+                &mut height,
+                &mut border,
+                &mut depth,
+            );
+            if status == 0 {
+                return Geometry::default();
+            }
+            Geometry { x, y, width, height }
+        }
+    }
+
+    /// Reads the `_NET_WM_STATE` atom list (fullscreen, above, hidden, ...).
+    fn get_window_states(&self, window: Window) -> Vec<Atom> {
+        unsafe {
+            let net_wm_state = self.intern_atom(c"_NET_WM_STATE");
+            if net_wm_state == 0 {
+                return Vec::new();
+            }
+
+            let mut actual_type: Atom = 0;
+            let mut actual_format: i32 = 0;
+            let mut nitems: u64 = 0;
+            let mut bytes_after: u64 = 0;
+            let mut prop: *mut u8 = ptr::null_mut();
+
+            let result = XGetWindowProperty(
+                self.display,
+                window,
+                net_wm_state,
+                0,
+                64,
+                0,
+                XA_ATOM,
+                &mut actual_type,
+                &mut actual_format,
+                &mut nitems,
+                &mut bytes_after,
+                &mut prop as *mut *mut u8,
+            );
+
+            if result != 0 || prop.is_null() || nitems == 0 || actual_format != 32 {
+                if !prop.is_null() {
+                    XFree(prop as *mut _);
+                }
+                return Vec::new();
+            }
+
+            // Format-32 properties are returned as an array of C longs.
+            let states = std::slice::from_raw_parts(prop as *const Atom, nitems as usize).to_vec();
+            XFree(prop as *mut _);
+            states
+        }
+    }
+
     fn get_window_pid(&self, window: Window) -> Option<i32> {
         unsafe {
             let net_wm_pid = XInternAtom(
                 self.display,
-                b"_NET_WM_PID\0".as_ptr() as *const i8,
+                c"_NET_WM_PID".as_ptr(),
                 0,
             );
 
@@ -201,12 +659,12 @@ impl WindowMonitor {
             // XFetchName reads WM_NAME (legacy), which Chrome does not set.
             let net_wm_name = XInternAtom(
                 self.display,
-                b"_NET_WM_NAME\0".as_ptr() as *const i8,
+                c"_NET_WM_NAME".as_ptr(),
                 0,
             );
             let utf8_string = XInternAtom(
                 self.display,
-                b"UTF8_STRING\0".as_ptr() as *const i8,
+                c"UTF8_STRING".as_ptr(),
                 0,
             );
 
@@ -252,8 +710,7 @@ impl WindowMonitor {
                 return Ok(String::new());
             }
 
-            let c_str = CStr::from_ptr(name);
-            let title = c_str.to_string_lossy().into_owned();
+            let title = decode_legacy_title(CStr::from_ptr(name).to_bytes(), self.legacy_encoding);
 
             XFree(name as *mut _);
 
@@ -262,6 +719,12 @@ impl WindowMonitor {
     }
 }
 
+impl WindowTitles for WindowMonitor {
+    fn browser_windows(&self, browser_pids: &[i32]) -> Result<Vec<WindowInfo>> {
+        self.get_browser_windows(browser_pids)
+    }
+}
+
 impl Drop for WindowMonitor {
     fn drop(&mut self) {
         unsafe {
@@ -274,3 +737,103 @@ impl Drop for WindowMonitor {
 
 unsafe impl Send for WindowMonitor {}
 unsafe impl Sync for WindowMonitor {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_with_display_invalid_name() {
+        let err = WindowMonitor::new_with_display("invalid-host-ivh.invalid:4242").err().unwrap();
+        assert!(err.to_string().starts_with("Failed to open X11 display"), "{}", err);
+    }
+
+    #[test]
+    fn test_doctor_reports_invalid_display_unreachable_within_timeout() {
+        let timeout = Duration::from_millis(300);
+        let started = Instant::now();
+        let result = crate::doctor::check_display(|| {
+            let monitor = WindowMonitor::new_with_display_timeout("invalid-host-ivh.invalid:4242", timeout)?;
+            Ok(monitor.get_all_windows()?.len())
+        });
+
+        assert!(!result.passed && result.hard, "{}", result.line());
+        assert!(result.detail.starts_with("unreachable: Failed to open X11 display"), "{}", result.detail);
+        assert!(started.elapsed() < timeout + Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_new_with_display_nul_in_name() {
+        let err = WindowMonitor::new_with_display(":0\0junk").err().unwrap();
+        assert!(err.to_string().starts_with("Failed to open X11 display"), "{}", err);
+    }
+
+    #[test]
+    fn test_resolve_display_target() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let home = dir.path();
+        let resolve = |display, xauth, env_display, env_xauth| {
+            DisplayTarget::resolve(display, xauth, env_display, env_xauth, Some(home))
+        };
+
+        // Nothing configured, nothing in the environment, no ~/.Xauthority.
+        assert_eq!(resolve(None, None, None, None), DisplayTarget {
+            display: ":0".to_string(),
+            xauthority: None,
+        });
+        assert_eq!(resolve(None, None, Some(""), Some("")).display, ":0");
+
+        // The environment is used when nothing is configured.
+        assert_eq!(resolve(None, None, Some(":2"), Some("/run/user/1000/xauth")), DisplayTarget {
+            display: ":2".to_string(),
+            xauthority: Some(PathBuf::from("/run/user/1000/xauth")),
+        });
+
+        // Configured values win over the environment.
+        assert_eq!(resolve(Some(":1"), Some("/etc/xauth"), Some(":2"), Some("/run/xauth")), DisplayTarget {
+            display: ":1".to_string(),
+            xauthority: Some(PathBuf::from("/etc/xauth")),
+        });
+
+        // ~/.Xauthority is picked up once it exists.
+        std::fs::write(home.join(".Xauthority"), b"").unwrap();
+        assert_eq!(resolve(None, None, None, None).xauthority, Some(home.join(".Xauthority")));
+        assert_eq!(DisplayTarget::resolve(None, None, None, None, None).xauthority, None);
+    }
+
+    #[test]
+    fn test_new_with_zero_timeout_fails_promptly() {
+        let started = Instant::now();
+        let err = WindowMonitor::new_with_display_timeout("invalid-host-ivh.invalid:4242", Duration::ZERO).err().unwrap();
+        assert!(err.to_string().starts_with("Failed to open X11 display"), "{}", err);
+        assert!(started.elapsed() < DISPLAY_RETRY_INTERVAL * 4, "{:?}", started.elapsed());
+    }
+
+    #[test]
+    fn test_retry_until_succeeds_after_failures() {
+        let mut attempts = 0;
+        let value = retry_until(Duration::from_secs(5), Duration::from_millis(1), || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(anyhow!("not yet"))
+            } else {
+                Ok(attempts)
+            }
+        })
+        .unwrap();
+        assert_eq!(value, 3);
+    }
+
+    #[test]
+    fn test_retry_until_gives_up_at_timeout() {
+        let mut attempts = 0;
+        let err = retry_until(Duration::from_millis(20), Duration::from_millis(5), || -> Result<()> {
+            attempts += 1;
+            Err(anyhow!("attempt {}", attempts))
+        })
+        .err()
+        .unwrap();
+        assert!(attempts > 1);
+        assert_eq!(err.to_string(), format!("attempt {}", attempts));
+    }
+}
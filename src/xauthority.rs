@@ -0,0 +1,133 @@
+use anyhow::{anyhow, Result};
+use std::fs;
+use std::path::Path;
+
+/// The authorization protocol Xlib uses by default.
+pub const MIT_MAGIC_COOKIE: &str = "MIT-MAGIC-COOKIE-1";
+
+/// Address families of Xauthority entries that can be matched without resolving
+/// network addresses: a host name, and any host.
+const FAMILY_LOCAL: u16 = 256;
+const FAMILY_WILD: u16 = 65535;
+
+/// One record of an Xauthority file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XauthEntry {
+    pub family: u16,
+    pub address: Vec<u8>,
+    /// Display number, empty for every display of the host.
+    pub number: String,
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Parses the records of an Xauthority file: a family, then address, display
+/// number, protocol name and data, each a big-endian `u16` length and bytes.
+pub fn parse_entries(mut bytes: &[u8]) -> Result<Vec<XauthEntry>> {
+    fn take_u16(bytes: &mut &[u8]) -> Result<u16> {
+        let (head, rest) = bytes.split_first_chunk::<2>().ok_or_else(|| anyhow!("truncated Xauthority entry"))?;
+        *bytes = rest;
+        Ok(u16::from_be_bytes(*head))
+    }
+    fn take_field(bytes: &mut &[u8]) -> Result<Vec<u8>> {
+        let len = take_u16(bytes)? as usize;
+        if bytes.len() < len {
+            return Err(anyhow!("truncated Xauthority entry"));
+        }
+        let (field, rest) = bytes.split_at(len);
+        *bytes = rest;
+        Ok(field.to_vec())
+    }
+
+    let mut entries = Vec::new();
+    while !bytes.is_empty() {
+        entries.push(XauthEntry {
+            family: take_u16(&mut bytes)?,
+            address: take_field(&mut bytes)?,
+            number: String::from_utf8_lossy(&take_field(&mut bytes)?).into_owned(),
+            name: String::from_utf8_lossy(&take_field(&mut bytes)?).into_owned(),
+            data: take_field(&mut bytes)?,
+        });
+    }
+    Ok(entries)
+}
+
+/// Splits a display name such as `:0`, `unix:1` or `host:2.0` into its host and
+/// display number.
+fn split_display(display: &str) -> Option<(&str, &str)> {
+    let (host, rest) = display.rsplit_once(':')?;
+    let number = rest.split('.').next().filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))?;
+    Some((host, number))
+}
+
+/// The [`MIT_MAGIC_COOKIE`] for `display` among `entries`, as seen from the host
+/// `hostname`. Entries for a named host or any host are considered; ones that
+/// only give a network address are not.
+pub fn find_cookie(entries: &[XauthEntry], display: &str, hostname: &str) -> Option<Vec<u8>> {
+    let (host, number) = split_display(display)?;
+    let host = if host.is_empty() || host == "unix" { hostname } else { host };
+    entries
+        .iter()
+        .find(|entry| {
+            entry.name == MIT_MAGIC_COOKIE
+                && (entry.number.is_empty() || entry.number == number)
+                && (entry.family == FAMILY_WILD
+                    || (entry.family == FAMILY_LOCAL && entry.address.eq_ignore_ascii_case(host.as_bytes())))
+        })
+        .map(|entry| entry.data.clone())
+}
+
+/// Reads `path` and finds the cookie for `display` on this host.
+pub fn read_cookie(path: &Path, display: &str) -> Result<Option<Vec<u8>>> {
+    let entries = parse_entries(&fs::read(path)?)?;
+    let hostname = nix::unistd::gethostname()?.to_string_lossy().into_owned();
+    Ok(find_cookie(&entries, display, &hostname))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(family: u16, address: &str, number: &str, name: &str, data: &[u8]) -> Vec<u8> {
+        let mut bytes = family.to_be_bytes().to_vec();
+        for field in [address.as_bytes(), number.as_bytes(), name.as_bytes(), data] {
+            bytes.extend((field.len() as u16).to_be_bytes());
+            bytes.extend(field);
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_parse_entries() {
+        let mut file = entry(FAMILY_LOCAL, "desk", "0", MIT_MAGIC_COOKIE, &[1, 2, 3]);
+        file.extend(entry(FAMILY_WILD, "", "", "XDM-AUTHORIZATION-1", &[9]));
+
+        let entries = parse_entries(&file).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0], XauthEntry {
+            family: FAMILY_LOCAL,
+            address: b"desk".to_vec(),
+            number: "0".to_string(),
+            name: MIT_MAGIC_COOKIE.to_string(),
+            data: vec![1, 2, 3],
+        });
+        assert!(parse_entries(&file[..file.len() - 1]).is_err());
+        assert!(parse_entries(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_find_cookie_for_the_display() {
+        let mut file = entry(FAMILY_LOCAL, "other", "0", MIT_MAGIC_COOKIE, &[1]);
+        file.extend(entry(FAMILY_LOCAL, "desk", "1", MIT_MAGIC_COOKIE, &[2]));
+        file.extend(entry(FAMILY_LOCAL, "Desk", "0", MIT_MAGIC_COOKIE, &[3]));
+        file.extend(entry(FAMILY_WILD, "", "", MIT_MAGIC_COOKIE, &[4]));
+        let entries = parse_entries(&file).unwrap();
+
+        assert_eq!(find_cookie(&entries, ":0", "desk"), Some(vec![3]));
+        assert_eq!(find_cookie(&entries, "unix:1.0", "desk"), Some(vec![2]));
+        assert_eq!(find_cookie(&entries, "other:0", "desk"), Some(vec![1]));
+        assert_eq!(find_cookie(&entries, ":5", "desk"), Some(vec![4]), "a wildcard entry covers any display");
+        assert_eq!(find_cookie(&entries[..3], ":5", "desk"), None);
+        assert_eq!(find_cookie(&entries, "no-display-number", "desk"), None);
+    }
+}
@@ -2,13 +2,16 @@
 use inappropriate_video_handler::background::BackgroundManager;
 use inappropriate_video_handler::browser::BrowserManager;
 use inappropriate_video_handler::config::{
-    BackgroundConfig, BrowserConfig, Config, FileConfig, MonitoringConfig, TimeoutConfig,
+    AuditConfig, BackgroundConfig, BrowserConfig, CaptureConfig, ClockJumpAction, Config, DetectionAction, DetectionScope, ErrorAction, FileConfig, FilterConfig, MatchProperty,
+    ManagementConfig, MonitoringConfig, NotificationConfig, OnEmpty, PipConfig, PrivacyConfig, ProfileConfig, SecurityConfig,
+    StateBackend, TimeoutConfig, TitleBackend, UntitledWindows,
 };
 use inappropriate_video_handler::filter::Filter;
 use inappropriate_video_handler::state::AppState;
 
 use chrono::{Duration, Utc};
 use serial_test::serial;
+use std::collections::BTreeMap;
 use std::fs;
 use std::io::Write;
 use tempfile::{NamedTempFile, TempDir};
@@ -19,9 +22,54 @@ fn create_test_config() -> Config {
             executable: "echo".to_string(),
             url: "https://test.com".to_string(),
             process_name: "test-process".to_string(),
+            max_launches_per_minute: 0,
+            launch_wrapper: None,
+            launch_wrapper_args: Vec::new(),
+            launch_args: Vec::new(),
+            auto_relaunch: false,
+            relaunch_delay_seconds: 0,
+            quiet: false,
+            ephemeral_profile: false,
         },
         monitoring: MonitoringConfig {
             check_frequency_seconds: 1,
+            title_history_size: 5,
+            display: None,
+            xauthority: None,
+            display_timeout_seconds: 0,
+            diagnostic_display_timeout_seconds: 3,
+            fullscreen_priority: false,
+            enforce_on_startup: true,
+            kill_while_restricted: true,
+            exclude_own_windows: true,
+            kiosk_mode: false,
+            allowed_classes: Vec::new(),
+            kiosk_safe_classes: Vec::new(),
+            focus_after_kill: None,
+            startup_delay_seconds: 0,
+            detect_pip: PipConfig::default(),
+            capture_on_block: CaptureConfig::default(),
+            ignore_classes: Vec::new(),
+            ack_seconds: 0,
+            whitelist_recheck_seconds: None,
+            title_debounce_scans: 0,
+            on_detection: vec![DetectionAction::Kill],
+            lock_command: vec!["loginctl".to_string(), "lock-session".to_string()],
+            on_empty: OnEmpty::Allow,
+            untitled_windows: UntitledWindows::Ignore,
+            detection_scope: DetectionScope::AnyWindow,
+            match_properties: vec![MatchProperty::Title],
+            backends: vec![TitleBackend::Xlib],
+            legacy_encoding: None,
+            require_browser_running: false,
+            max_consecutive_errors: 0,
+            on_repeated_errors: ErrorAction::Reconnect,
+            clock_jump_threshold_seconds: 120,
+            on_clock_jump: ClockJumpAction::OneBreak,
+            max_windows: 0,
+            block_evasion: false,
+            evasion_patterns: Vec::new(),
+            inspect_history: None,
         },
         timeouts: TimeoutConfig {
             blacklist_timeout_minutes: 5,
@@ -30,11 +78,27 @@ fn create_test_config() -> Config {
             grace_retries: 3,
             hard_lock_minutes: 40,
             retry_reset_minutes: 20,
+            escalation_multiplier: 1,
+            max_block_minutes: None,
+            break_jitter_minutes: 0,
+            min_break_gap_minutes: 0,
+            daily_limit_minutes: None,
+            weekly_limit_minutes: None,
+            max_snoozes_per_day: 0,
+            detection_cooldown_seconds: 0,
         },
         backgrounds: BackgroundConfig {
             normal: "/tmp/test_normal.jpg".to_string(),
             blocked: "/tmp/test_blocked.jpg".to_string(),
             bathroom_break: "/tmp/test_break.jpg".to_string(),
+            warning: None,
+            block_message: None,
+            block_sequence: Vec::new(),
+            reassert: false,
+            restore_user_wallpaper: false,
+            user_wallpaper: None,
+            countdown: false,
+            blocked_by_label: BTreeMap::new(),
         },
         files: FileConfig {
             blacklist: "test_blacklist.txt".to_string(),
@@ -42,7 +106,24 @@ fn create_test_config() -> Config {
             state_file: "/tmp/ivh_test/state.json".to_string(),
             log_file: "/tmp/ivh_test/ivh.log".to_string(),
             titles_file: "/tmp/ivh_test/window-titles.txt".to_string(),
+            state_backend: StateBackend::Json,
+            study_whitelist: None,
         },
+        filter: FilterConfig::default(),
+        audit: AuditConfig {
+            enabled: false,
+            file: "/tmp/ivh_test/audit.log".to_string(),
+            max_size_mb: 1,
+            keep: 1,
+            compress: false,
+        },
+        notifications: NotificationConfig::default(),
+        privacy: PrivacyConfig::default(),
+        security: SecurityConfig::default(),
+        management: ManagementConfig::default(),
+        hooks: BTreeMap::new(),
+        timezone: None,
+        profiles: BTreeMap::new(),
     }
 }
 
@@ -248,7 +329,7 @@ fn test_timeout_expiration_logic() {
 
     state.start_bathroom_break(0, 1);
     std::thread::sleep(std::time::Duration::from_millis(10));
-    assert!(!state.is_bathroom_break_time(1));
+    assert!(!state.is_bathroom_break_time(1, 0));
 }
 
 #[test]
@@ -342,3 +423,31 @@ fn test_time_calculations() {
         .abs();
     assert!(time_diff < 5);
 }
+
+#[test]
+fn test_profiles_use_separate_state_files() {
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut base = create_test_config();
+    base.files.state_file = temp_dir.path().join("state.json").to_string_lossy().into_owned();
+    base.profiles.insert("alice".to_string(), ProfileConfig::default());
+    base.profiles.insert("bob".to_string(), ProfileConfig::default());
+    let yaml_content = serde_yaml::to_string(&base).unwrap();
+
+    let mut alice = serde_yaml::from_str::<Config>(&yaml_content).unwrap();
+    alice.apply_profile("alice").unwrap();
+    let mut bob = serde_yaml::from_str::<Config>(&yaml_content).unwrap();
+    bob.apply_profile("bob").unwrap();
+
+    assert_ne!(alice.files.state_file, bob.files.state_file);
+
+    let mut alice_state = AppState::default();
+    alice_state.block_browser(10);
+    alice_state.save(&alice.files.state_file).unwrap();
+
+    let bob_state = AppState::default();
+    bob_state.save(&bob.files.state_file).unwrap();
+
+    assert!(AppState::load(&alice.files.state_file).unwrap().is_blocked());
+    assert!(!AppState::load(&bob.files.state_file).unwrap().is_blocked());
+}